@@ -0,0 +1,51 @@
+// Runs the aggregator inside another tokio application: the application serves the
+// aggregator with its own gRPC server and consumes the merged book in process.
+// cargo run --example embed -- <symbol> [depth]
+use orderbook::aggregator::Aggregator;
+use orderbook::config::ServerOptions;
+use orderbook::orderbook_proto::orderbook_aggregator_client::OrderbookAggregatorClient;
+use orderbook::orderbook_proto::SummaryRequest;
+use std::time::Duration;
+use tonic::transport::Server;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let symbol = args.get(1).map(String::as_str).unwrap_or("btcusdt");
+    let depth = args.get(2).and_then(|d| d.parse().ok()).unwrap_or(5);
+
+    let mut options = ServerOptions::new(symbol, depth);
+    options.addr = "127.0.0.1:50052".parse()?;
+    let aggregator = Aggregator::connect(options).await?;
+    let addr = aggregator.addr();
+
+    // The application's own services can be added to the same server
+    let server = tokio::spawn(
+        Server::builder()
+            .add_service(aggregator.into_service())
+            .serve(addr),
+    );
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut client = OrderbookAggregatorClient::connect(format!("http://{}", addr)).await?;
+    let request = SummaryRequest {
+        fields: vec!["spread".to_string(), "bbo".to_string()],
+        ..Default::default()
+    };
+    let mut stream = client.book_summary(request).await?.into_inner();
+
+    for _ in 0..10 {
+        match stream.message().await? {
+            Some(summary) => println!(
+                "spread: {} best bid: {:?} best ask: {:?}",
+                summary.spread,
+                summary.bids.first().map(|level| level.price),
+                summary.asks.first().map(|level| level.price)
+            ),
+            None => break,
+        }
+    }
+
+    server.abort();
+    Ok(())
+}
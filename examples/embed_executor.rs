@@ -0,0 +1,21 @@
+// Runs the aggregator from an application without a tokio runtime, here a plain futures
+// executor: the aggregator spawns its tasks and the gRPC server on a runtime of its own
+// (runtime::default_runtime), or on the one of ServerOptions::runtime when given.
+// cargo run --example embed_executor -- <symbol> [depth], then
+// cargo run --bin orderbook-client -- --server http://127.0.0.1:50053
+use futures::executor::block_on;
+use orderbook::aggregator::Aggregator;
+use orderbook::config::ServerOptions;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let symbol = args.get(1).map(String::as_str).unwrap_or("btcusdt");
+    let depth = args.get(2).and_then(|d| d.parse().ok()).unwrap_or(5);
+
+    let mut options = ServerOptions::new(symbol, depth);
+    options.addr = "127.0.0.1:50053".parse()?;
+    block_on(async {
+        let aggregator = Aggregator::connect(options).await?;
+        aggregator.serve().await
+    })
+}
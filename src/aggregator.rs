@@ -0,0 +1,2803 @@
+use crate::audit::{AuditGuard, AuditLog};
+use crate::basis::{compute_basis, mid_price};
+use crate::bbo_attribution::BboAttribution;
+use crate::bitstamp_pool::BitstampPool;
+use crate::book::{dedup_levels, OrderBook};
+use crate::cadence::{CadenceTracker, MIN_MERGE_INTERVAL};
+use crate::clock::{Clock, SystemClock};
+use crate::compaction::CompactionPolicy;
+use crate::compression::message_text;
+use crate::config::{
+    check_symbol_settings, symbol_pipeline, MergeCadence, ServerOptions, SymbolPipeline,
+    SymbolSettings,
+};
+use crate::connectors::bitstamp::{
+    bitstamp_book_channel, bitstamp_channel, bitstamp_message_channel,
+};
+use crate::connectors::{
+    apply_connector_message_async, connect_connector_async, process_message,
+    unsubscribe_connector_async, AsyncSocket, BinanceConnector, BitstampConnector, ErrorAction,
+    ExchangeConnector, ExchangeError, VenueOverride, ERROR_BACKOFF,
+};
+use crate::csv_export::serve_csv;
+use crate::depeg::{needs_depeg_guard, quote_asset, DepegEvent, DepegGuard};
+use crate::deviation::{DeviationEvent, DeviationMonitor};
+use crate::doctor::configured_venues;
+use crate::emission::EmissionPolicy;
+use crate::fair_value::{weighted_mid, FairValueModel};
+use crate::feed_status::{
+    feed_status, is_empty_book, stale_venues, unavailable_venues, FeedMonitor,
+};
+use crate::fx::FixedRate;
+use crate::grpc::convert::{
+    encode_decimal_prices, orderbook_to_split_book, orderbook_to_summary,
+    reference_improvement_to_proto, summary_checksum, venue_to_summary_venue,
+};
+use crate::history::SummaryHistory;
+use crate::idle::{IdleTracker, IdleTransition};
+use crate::index_price::IndexFormula;
+use crate::keepalive::{
+    is_control_frame, keepalive_frame, Keepalive, KeepaliveAction, KEEPALIVE_INTERVAL,
+    KEEPALIVE_TIMEOUT,
+};
+use crate::latency::{DelayLine, LatencyEstimator};
+use crate::merge::merge_orderbooks;
+use crate::metering::UsageMeter;
+use crate::metrics::{serve_metrics, MetricsRegistry};
+use crate::number::decimal_to_f64;
+use crate::orderbook_proto;
+use crate::profiling::serve_profiles;
+use crate::projection::SummaryFields;
+use crate::reconnect::{ReconnectBackoff, ReconnectLimiter, RECONNECT_BASE, RECONNECT_MAX};
+use crate::recording::Recorder;
+use crate::reference::reference_improvement;
+use crate::registry::find_connector;
+use crate::render::print_orderbook;
+use crate::resolver::endpoint_resolver;
+use crate::retention::{apply_retention, FileUsage, RetentionPolicy};
+use crate::runtime::default_runtime;
+use crate::scripting::ScriptHook;
+use crate::sink::{RedisSink, SinkFanOut, SummarySink, WebhookSink};
+use crate::symbols::check_venue_symbol;
+use crate::tape::TapeTrade;
+use crate::tenant::{Tenant, TenantRegistry, UpdateThrottle, API_KEY_HEADER};
+use crate::touch_filter::trim_beyond_touch;
+use crate::toxicity::ToxicityMeter;
+use crate::walls::{WallDetector, WallEvent};
+
+use futures::stream::StreamExt;
+use futures::{Future, SinkExt};
+use orderbook_proto::orderbook_aggregator_server::OrderbookAggregatorServer;
+use orderbook_proto::{
+    Alert, Basis, BasisRequest, FeedStatus, IndexConstituent, IndexPrice, Summary, VenueSymbol,
+};
+use prost::Message;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::spawn;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::{broadcast, Notify};
+use tokio::task::spawn_blocking;
+use tonic::{transport::Server, Request, Status};
+use tungstenite::Message as WebSocketMessage;
+
+// Clients are known by their tenant namespace, anonymous without tenants
+pub(crate) fn client_name(tenant: &Option<Arc<Tenant>>) -> &str {
+    tenant
+        .as_ref()
+        .map_or("anonymous", |tenant| tenant.config.namespace.as_str())
+}
+
+fn new_alert(clock: &dyn Clock, kind: &str, exchange: &str, message: String) -> Alert {
+    Alert {
+        kind: kind.to_string(),
+        exchange: exchange.to_string(),
+        message,
+        timestamp: clock.now_millis(),
+    }
+}
+
+// While the stablecoin is depegged the bitstamp book is quoted in a different
+// asset, so it is kept out of the merged book until the peg holds again, and sent
+// apart in Summary.split_books
+fn mergeable_orderbook(
+    orderbook: &OrderBook,
+    depeg_guard: &Option<Arc<Mutex<DepegGuard>>>,
+) -> OrderBook {
+    match depeg_guard {
+        Some(depeg_guard) if !depeg_guard.lock().unwrap().is_equivalent() => OrderBook::new(),
+        _ => orderbook.clone(),
+    }
+}
+
+// Sends the updates of one subscription, keeping the max update rate and the
+// metrics of the client's tenant, and metering what was sent
+pub(crate) struct ClientSender<T> {
+    sender: Mutex<Sender<Result<T, ()>>>,
+    pub(crate) tenant: Option<Arc<Tenant>>,
+    throttle: Mutex<UpdateThrottle>,
+    symbol: String,
+    usage_meter: Arc<UsageMeter>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<T: Message> ClientSender<T> {
+    pub(crate) fn new(
+        sender: Sender<Result<T, ()>>,
+        tenant: Option<Arc<Tenant>>,
+        symbol: String,
+        usage_meter: Arc<UsageMeter>,
+        clock: Arc<dyn Clock>,
+    ) -> ClientSender<T> {
+        let interval = tenant
+            .as_ref()
+            .and_then(|tenant| tenant.min_update_interval());
+        ClientSender {
+            sender: Mutex::new(sender),
+            tenant,
+            throttle: Mutex::new(UpdateThrottle::new(interval)),
+            symbol,
+            usage_meter,
+            clock,
+        }
+    }
+
+    // True when the update has to be dropped to keep the tenant's max update rate
+    fn throttled(&self) -> bool {
+        let throttled = !self.throttle.lock().unwrap().allow(self.clock.now());
+        if let (true, Some(tenant)) = (throttled, &self.tenant) {
+            tenant
+                .metrics
+                .updates_throttled
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        throttled
+    }
+
+    fn is_closed(&self) -> bool {
+        self.sender.lock().unwrap().is_closed()
+    }
+
+    // Completes once the client went away
+    async fn closed(&self) {
+        let sender = self.sender.lock().unwrap().clone();
+        sender.closed().await
+    }
+
+    fn send(&self, update: T) -> Result<(), TrySendError<Result<T, ()>>> {
+        let bytes = update.encoded_len();
+        self.sender.lock().unwrap().try_send(Ok(update))?;
+        self.usage_meter
+            .record(client_name(&self.tenant), &self.symbol, bytes);
+        if let Some(tenant) = &self.tenant {
+            tenant.metrics.updates_sent.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+// Merged book of the feed, with a summary of every field the clients select from
+#[derive(Debug)]
+pub(crate) struct FeedUpdate {
+    // After the script hook, at the depth of the symbol's pipeline
+    orderbook: OrderBook,
+    // Before the decimal encoding and without a subscription id
+    summary: Summary,
+    // The venues' books left out of the merge for their quote, with the quote asset
+    split_books: Vec<(String, String, OrderBook)>,
+    // Sent whatever the emission policy and the update rate of the clients, e.g. a new
+    // feed status
+    forced: bool,
+}
+
+// Summaries a slow client can fall behind the feed by before it skips to the latest
+const FEED_CAPACITY: usize = 100;
+
+// State shared by the reader loops of the symbol's feed. The venues are read and merged
+// once for all the BookSummary streams, every merged book is published on the
+// broadcast channel the streams subscribe to, and the feed stops once the last one
+// went away. With sinks or idle_shutdown the feed is held from the startup on, see
+// hold_feed
+struct BookFeed {
+    sender: broadcast::Sender<Arc<FeedUpdate>>,
+    // The depth of the symbol's pipeline, the streams send fewer levels for tenants
+    // with a lower max depth
+    depth: u32,
+    // Latest book of each venue, by connector name, and the venues whose latest book
+    // only refreshed the best bid and ask
+    orderbooks: Mutex<HashMap<String, OrderBook>>,
+    bbo_only_venues: Mutex<HashSet<String>>,
+    // Venues whose latest book has an empty side, not merged until it is back
+    empty_book_venues: Mutex<HashSet<String>>,
+    // As-of time of the latest update of each venue
+    venue_timestamps: Mutex<HashMap<String, u64>>,
+    // The venues read in merge order, and the ones whose socket ended
+    venues: Vec<String>,
+    ended_venues: Mutex<HashSet<String>>,
+    started_at: u64,
+    // Status of the latest summary, a change is sent even without book updates
+    last_status: Mutex<FeedStatus>,
+    // Set when the symbol's books are conflated or merged on fixed ticks, aligned to
+    // wall-clock ticks
+    align_interval: Option<Duration>,
+    // Set when the symbol's books are merged on the slowest venue's cadence
+    slowest_cadence: Option<Mutex<CadenceTracker>>,
+    // Fed with every merged book
+    toxicity_meter: Mutex<ToxicityMeter>,
+    // Books held back by latency compensation, with the bbo_only flag and updated_by,
+    // and the wake up of the task releasing them
+    delayed_books: Mutex<DelayLine<(OrderBook, bool, String)>>,
+    delayed_books_added: Notify,
+    // Every field, but the analytics turned off for the symbol
+    fields: SummaryFields,
+    service: OrderbookAggregatorService,
+}
+
+// A BookSummary stream reading the feed, with the client's depth, fields, alignment
+// and emission policy
+pub(crate) struct ClientSubscription {
+    // Summary.subscription_id, unique in the server run
+    pub(crate) id: u64,
+    pub(crate) sender: ClientSender<Summary>,
+    // The symbol's depth, or less if the client's tenant has a lower max depth
+    pub(crate) depth: u32,
+    pub(crate) fields: SummaryFields,
+    // Set when the client asked for books aligned to wall-clock ticks longer than the
+    // symbol's own
+    pub(crate) align_interval: Option<Duration>,
+    pub(crate) emission_policy: Mutex<EmissionPolicy>,
+    // Latest update of the feed, sent at the next aligned tick or on a RequestSnapshot
+    pub(crate) latest: Mutex<Option<Arc<FeedUpdate>>>,
+}
+
+fn venue_refs(venues: &[(String, OrderBook)]) -> Vec<(&str, &OrderBook)> {
+    venues
+        .iter()
+        .map(|(exchange, orderbook)| (exchange.as_str(), orderbook))
+        .collect()
+}
+
+impl BookFeed {
+    // True once every stream reading the feed went away
+    fn is_closed(&self) -> bool {
+        self.sender.receiver_count() == 0
+    }
+
+    fn set_orderbook(&self, exchange: &str, orderbook: OrderBook, bbo_only: bool) {
+        self.check_empty_book(exchange, &orderbook);
+        self.orderbooks
+            .lock()
+            .unwrap()
+            .insert(exchange.to_string(), orderbook);
+        let mut bbo_only_venues = self.bbo_only_venues.lock().unwrap();
+        if bbo_only {
+            bbo_only_venues.insert(exchange.to_string());
+        } else {
+            bbo_only_venues.remove(exchange);
+        }
+    }
+
+    // Keeps the venues whose new book has an empty side out of the merge, alerting once
+    // across the feeds of the server run when a venue's book turns empty or comes back
+    fn check_empty_book(&self, exchange: &str, orderbook: &OrderBook) {
+        let mut empty_book_venues = self.empty_book_venues.lock().unwrap();
+        if is_empty_book(orderbook) {
+            empty_book_venues.insert(exchange.to_string());
+        } else {
+            empty_book_venues.remove(exchange);
+        }
+        drop(empty_book_venues);
+        let mut feed_monitor = self.service.feed_monitor.lock().unwrap();
+        if !feed_monitor.on_book(exchange, orderbook) {
+            return;
+        }
+        let message = if feed_monitor.has_empty_book(exchange) {
+            "book without bids or asks, left out of the merged book".to_string()
+        } else {
+            "book back to normal".to_string()
+        };
+        drop(feed_monitor);
+        eprintln!("The {} {}", exchange, message);
+        // Sending only fails when nobody is subscribed to alerts
+        let _ = self.service.alert_sender.send(new_alert(
+            self.service.clock.as_ref(),
+            "empty_book",
+            exchange,
+            message,
+        ));
+    }
+
+    // Called by the reader loops with every message, before it is applied
+    fn observe_latency(&self, exchange: &str, connector: &dyn ExchangeConnector, message: &str) {
+        if self.service.latency_compensation.is_none() {
+            return;
+        }
+        if let Some(event_time) = connector.event_time(message) {
+            let mut latency_estimator = self.service.latency_estimator.lock().unwrap();
+            let received_at = self.service.clock.now_millis();
+            latency_estimator.observe(exchange, event_time, received_at);
+        }
+    }
+
+    // Called by the reader loops with a new book of the venue. With latency compensation
+    // the books of the faster venues are held back by their latency relative to the
+    // slowest venue, so the merged book is a view of the venues at about the same time
+    fn receive_orderbook(&self, exchange: &str, orderbook: OrderBook, bbo_only: bool, by: &str) {
+        let orderbook = self.dedup_orderbook(exchange, orderbook);
+        let max_skew = match self.service.latency_compensation {
+            Some(max_skew) => max_skew.as_millis() as u64,
+            None => {
+                self.set_orderbook(exchange, orderbook, bbo_only);
+                self.on_venue_update(exchange, by);
+                return;
+            }
+        };
+        let latency_estimator = self.service.latency_estimator.lock().unwrap();
+        let delay = latency_estimator.compensation_delay(exchange, max_skew);
+        drop(latency_estimator);
+        // Books without a delay still queue behind the ones held back before them
+        self.delayed_books.lock().unwrap().push(
+            exchange,
+            self.service.clock.now_millis() + delay,
+            (orderbook, bbo_only, by.to_string()),
+        );
+        self.release_delayed_books();
+        self.delayed_books_added.notify_one();
+    }
+
+    // A venue's ladder never repeats a price, the repeated levels are collapsed and
+    // counted for GetFeedStatus
+    fn dedup_orderbook(&self, exchange: &str, mut orderbook: OrderBook) -> OrderBook {
+        let duplicates = dedup_levels(&mut orderbook.bids) + dedup_levels(&mut orderbook.asks);
+        if duplicates > 0 {
+            eprintln!("{} sent {} duplicate price levels", exchange, duplicates);
+            self.service
+                .feed_monitor
+                .lock()
+                .unwrap()
+                .on_duplicate_levels(exchange, duplicates as u64);
+        }
+        orderbook
+    }
+
+    fn release_delayed_books(&self) {
+        let now = self.service.clock.now_millis();
+        let due = self.delayed_books.lock().unwrap().pop_due(now);
+        for (exchange, (orderbook, bbo_only, updated_by)) in due {
+            self.set_orderbook(&exchange, orderbook, bbo_only);
+            self.on_venue_update(&exchange, &updated_by);
+        }
+    }
+
+    // The latest book of each venue in merge order, without the empty ones
+    fn mergeable_venues(&self) -> Vec<(String, OrderBook)> {
+        let orderbooks = self.orderbooks.lock().unwrap();
+        let empty_book_venues = self.empty_book_venues.lock().unwrap();
+        self.venues
+            .iter()
+            .filter(|name| !empty_book_venues.contains(*name))
+            .filter_map(|name| {
+                let orderbook = orderbooks.get(name)?;
+                // The USD book is only merged while USDT holds its peg
+                let orderbook = if name == "bitstamp" {
+                    mergeable_orderbook(orderbook, &self.service.depeg_guard)
+                } else {
+                    orderbook.clone()
+                };
+                Some((name.clone(), orderbook))
+            })
+            .collect()
+    }
+
+    // The bitstamp book while it isn't merged for the depeg, at the pipeline's depth
+    fn split_books(&self) -> Vec<(String, String, OrderBook)> {
+        let equivalent = match &self.service.depeg_guard {
+            Some(depeg_guard) => depeg_guard.lock().unwrap().is_equivalent(),
+            None => true,
+        };
+        if equivalent || self.empty_book_venues.lock().unwrap().contains("bitstamp") {
+            return Vec::new();
+        }
+        let mut orderbook = match self.orderbooks.lock().unwrap().get("bitstamp") {
+            Some(orderbook) => orderbook.clone(),
+            None => return Vec::new(),
+        };
+        orderbook.bids.truncate(self.depth as usize);
+        orderbook.asks.truncate(self.depth as usize);
+        let quote = quote_asset(&self.service.bitstamp_symbol).unwrap_or_default();
+        vec![("bitstamp".to_string(), quote.to_string(), orderbook)]
+    }
+
+    // Called by the reader loops once the venue's book is updated
+    fn on_venue_update(&self, exchange: &str, updated_by: &str) {
+        self.service.sequence.fetch_add(1, Ordering::Relaxed);
+        let now = self.service.clock.now_millis();
+        self.service
+            .feed_monitor
+            .lock()
+            .unwrap()
+            .on_update(exchange, now);
+        self.venue_timestamps
+            .lock()
+            .unwrap()
+            .insert(exchange.to_string(), now);
+        self.ended_venues.lock().unwrap().remove(exchange);
+        self.service.bbo_attribution.lock().unwrap().update(
+            &venue_refs(&self.mergeable_venues()),
+            exchange,
+            now,
+        );
+        match &self.slowest_cadence {
+            Some(cadence) => cadence.lock().unwrap().on_update(exchange, now),
+            None if self.align_interval.is_none() => self.send_summary(updated_by, false),
+            None => {}
+        }
+    }
+
+    // The interval of the aligned merges, the longer of the requested alignment and the
+    // slowest venue's cadence
+    fn merge_interval(&self) -> Duration {
+        let slowest = self.slowest_cadence.as_ref().map(|cadence| {
+            let slowest_interval = cadence.lock().unwrap().slowest_interval();
+            slowest_interval.unwrap_or(MIN_MERGE_INTERVAL)
+        });
+        self.align_interval
+            .max(slowest)
+            .unwrap_or(MIN_MERGE_INTERVAL)
+    }
+
+    // Called by the reader loops when they stop reading the venue's socket
+    fn on_venue_down(&self, exchange: &str) {
+        eprintln!("The {} feed ended", exchange);
+        self.ended_venues
+            .lock()
+            .unwrap()
+            .insert(exchange.to_string());
+        self.check_feed_status();
+    }
+
+    // Called by the reader loops on an error event of the venue's exchange, returns what
+    // they do about it
+    fn on_venue_error(&self, exchange: &str, error: ExchangeError) -> ErrorAction {
+        eprintln!("{} sent an error: {}, {:?}", exchange, error, error.action);
+        // Sending only fails when nobody is subscribed to alerts
+        let _ = self.service.alert_sender.send(new_alert(
+            self.service.clock.as_ref(),
+            "exchange_error",
+            exchange,
+            error.to_string(),
+        ));
+        self.service.feed_monitor.lock().unwrap().on_error(
+            exchange,
+            error,
+            self.service.clock.now_millis(),
+        )
+    }
+
+    // Called by the reader loops on a sequence gap of the venue's incremental feed, before
+    // they reset the connector and resubscribe. Its book is left out of the merge
+    // instead of being served stale until it is rebuilt from a new snapshot
+    fn on_venue_resync(&self, exchange: &str) {
+        eprintln!("{} stream out of sync, rebuilding its book", exchange);
+        let _ = self.service.alert_sender.send(new_alert(
+            self.service.clock.as_ref(),
+            "sequence_gap",
+            exchange,
+            "Sequence gap, rebuilding the book from a new snapshot".to_string(),
+        ));
+        self.service
+            .feed_monitor
+            .lock()
+            .unwrap()
+            .on_resync(exchange);
+        self.orderbooks.lock().unwrap().remove(exchange);
+    }
+
+    fn stale_venues(&self) -> Vec<String> {
+        stale_venues(
+            &self.venues,
+            &self.venue_timestamps.lock().unwrap(),
+            &self.ended_venues.lock().unwrap(),
+            self.started_at,
+            self.service.clock.now_millis(),
+            self.service.stale_after.as_millis() as u64,
+        )
+    }
+
+    fn empty_book_venues(&self) -> Vec<String> {
+        let empty_book_venues = self.empty_book_venues.lock().unwrap();
+        self.venues
+            .iter()
+            .filter(|venue| empty_book_venues.contains(*venue))
+            .cloned()
+            .collect()
+    }
+
+    // Sends the last known book with the new status when venues go stale or come
+    // back, so the clients don't show the last price as live while nothing updates
+    fn check_feed_status(&self) {
+        let unavailable = unavailable_venues(&self.stale_venues(), &self.empty_book_venues());
+        let status = feed_status(self.venues.len(), unavailable);
+        if status != *self.last_status.lock().unwrap() && !self.is_closed() {
+            self.send_summary("feed status", true);
+        }
+    }
+
+    // Alerts when a venue's mid deviates from the fair value of the venues that are
+    // not quarantined, or of all venues if they all are
+    fn check_deviations(
+        &self,
+        deviation_monitor: &Mutex<DeviationMonitor>,
+        venues: &[(String, OrderBook)],
+    ) {
+        let mut deviation_monitor = deviation_monitor.lock().unwrap();
+        let trusted_venues: Vec<(String, OrderBook)> = venues
+            .iter()
+            .filter(|(exchange, _)| !deviation_monitor.is_quarantined(exchange))
+            .cloned()
+            .collect();
+        let fair_value_model = &self.service.fair_value_model;
+        let fair_value = match fair_value_model
+            .fair_value(&venue_refs(&trusted_venues))
+            .or_else(|| fair_value_model.fair_value(&venue_refs(venues)))
+        {
+            Some(fair_value) => fair_value,
+            None => return,
+        };
+
+        let now = self.service.clock.now();
+        for (exchange, orderbook) in venues {
+            let mid = match mid_price(orderbook) {
+                Some(mid) => mid,
+                None => continue,
+            };
+            let alert = match deviation_monitor.update(exchange, mid, fair_value, now) {
+                Some(DeviationEvent::Deviating {
+                    exchange,
+                    deviation_bps,
+                }) => new_alert(
+                    self.service.clock.as_ref(),
+                    "venue_deviation",
+                    &exchange,
+                    format!(
+                        "{} mid {} deviates {:.2} bps from the fair value {}{}",
+                        exchange,
+                        mid,
+                        deviation_bps,
+                        fair_value,
+                        if deviation_monitor.quarantine {
+                            ", quarantined from the merged book"
+                        } else {
+                            ""
+                        }
+                    ),
+                ),
+                Some(DeviationEvent::Recovered {
+                    exchange,
+                    deviation_bps,
+                }) => new_alert(
+                    self.service.clock.as_ref(),
+                    "venue_recovered",
+                    &exchange,
+                    format!(
+                        "{} mid {} is back within {:.2} bps of the fair value {}",
+                        exchange, mid, deviation_bps, fair_value
+                    ),
+                ),
+                None => continue,
+            };
+            // Sending only fails when nobody is subscribed to alerts
+            let _ = self.service.alert_sender.send(alert);
+        }
+    }
+
+    // Alerts when a wall appears on or is removed from the book of a venue
+    fn check_walls(&self, wall_detector: &Mutex<WallDetector>, venues: &[(String, OrderBook)]) {
+        let mut wall_detector = wall_detector.lock().unwrap();
+        let now = self.service.clock.now();
+        for (exchange, orderbook) in venues {
+            for event in wall_detector.update(exchange, orderbook, now) {
+                let alert = match event {
+                    WallEvent::Appeared {
+                        exchange,
+                        side,
+                        price,
+                        amount,
+                        average_amount,
+                    } => new_alert(
+                        self.service.clock.as_ref(),
+                        "wall_appeared",
+                        &exchange,
+                        format!(
+                            "{} {} wall of {} at {}, {:.1}x the average level of {:.4}",
+                            exchange,
+                            side,
+                            amount,
+                            price,
+                            decimal_to_f64(amount) / average_amount,
+                            average_amount
+                        ),
+                    ),
+                    WallEvent::Removed {
+                        exchange,
+                        side,
+                        price,
+                        amount,
+                        lifetime,
+                    } => new_alert(
+                        self.service.clock.as_ref(),
+                        "wall_removed",
+                        &exchange,
+                        format!(
+                            "{} {} wall of {} at {} removed after {:.1} s",
+                            exchange,
+                            side,
+                            amount,
+                            price,
+                            lifetime.as_secs_f64()
+                        ),
+                    ),
+                };
+                let _ = self.service.alert_sender.send(alert);
+            }
+        }
+    }
+
+    // How much better the merged book is than the reference venue's own book, also
+    // exported as gauges for Prometheus
+    fn reference_improvement(
+        &self,
+        merged_orderbook: &OrderBook,
+    ) -> Option<orderbook_proto::ReferenceImprovement> {
+        let venue = self.service.reference_venue.as_deref()?;
+        let band_bps = self.service.reference_band_bps;
+        let improvement = {
+            let orderbooks = self.orderbooks.lock().unwrap();
+            reference_improvement(merged_orderbook, orderbooks.get(venue)?, band_bps)?
+        };
+        let labels = [
+            ("symbol", self.service.symbol.as_str()),
+            ("reference", venue),
+        ];
+        for (name, value) in [
+            ("bid_improvement_bps", improvement.bid_improvement_bps),
+            ("ask_improvement_bps", improvement.ask_improvement_bps),
+            ("extra_bid_depth", improvement.extra_bid_depth),
+            ("extra_ask_depth", improvement.extra_ask_depth),
+        ] {
+            let name = format!("orderbook_reference_{}", name);
+            self.service.metrics.set_gauge(&name, &labels, value);
+        }
+        Some(reference_improvement_to_proto(
+            venue,
+            band_bps,
+            &improvement,
+        ))
+    }
+
+    // Merges the latest books of all exchanges, runs the analytics and the script hook
+    // and publishes the summary to the streams, which send it to their client when its
+    // emission policy triggers, or whatever the policy when forced
+    fn send_summary(&self, updated_by: &str, forced: bool) {
+        let depth = self.depth as usize;
+        let mut venues = self.mergeable_venues();
+
+        if let Some(deviation_monitor) = &self.service.deviation_monitor {
+            self.check_deviations(deviation_monitor, &venues);
+            let deviation_monitor = deviation_monitor.lock().unwrap();
+            venues.retain(|(exchange, _)| !deviation_monitor.is_quarantined(exchange));
+        }
+        if let Some(wall_detector) = &self.service.wall_detector {
+            self.check_walls(wall_detector, &venues);
+        }
+
+        // The merged book is trimmed after every merge, so merging the books one by one
+        // keeps the top levels of all of them
+        let mut merged_orderbook = OrderBook::new();
+        for (exchange, orderbook) in &venues {
+            merged_orderbook = match self.service.max_touch_distance_bps.get(exchange) {
+                Some(max_distance_bps) => merge_orderbooks(
+                    &merged_orderbook,
+                    &trim_beyond_touch(orderbook, *max_distance_bps),
+                    depth,
+                ),
+                None => merge_orderbooks(&merged_orderbook, orderbook, depth),
+            };
+        }
+        // The fair value blends the venues' own books, not only their levels in the merged book
+        let (fair_value, index_prices) = if self.fields.fair_value {
+            let venue_refs = venue_refs(&venues);
+            let index_prices = self
+                .service
+                .indexes
+                .iter()
+                .filter_map(|(name, formula)| {
+                    Some((name.clone(), formula.compute(&venue_refs)?.price))
+                })
+                .collect();
+            (
+                self.service.fair_value_model.fair_value(&venue_refs),
+                index_prices,
+            )
+        } else {
+            (None, HashMap::new())
+        };
+
+        let toxicity = if self.fields.toxicity {
+            self.toxicity_meter
+                .lock()
+                .unwrap()
+                .update(&merged_orderbook)
+        } else {
+            None
+        };
+        let reference = self.reference_improvement(&merged_orderbook);
+
+        let mut signals = HashMap::new();
+        if let Some(script_hook) = &self.service.script_hook {
+            match script_hook.on_update(&merged_orderbook) {
+                Ok(output) => {
+                    for (kind, message) in output.alerts {
+                        // Sending only fails when nobody is subscribed to alerts
+                        let _ = self.service.alert_sender.send(new_alert(
+                            self.service.clock.as_ref(),
+                            &kind,
+                            "script",
+                            message,
+                        ));
+                    }
+                    if let Some(orderbook) = output.orderbook {
+                        merged_orderbook = orderbook;
+                    }
+                    signals = output.signals;
+                }
+                Err(err) => eprintln!("Error in script hook: {}", err),
+            }
+        }
+
+        println!("Orderbook updated by {}:", updated_by);
+        print_orderbook(&merged_orderbook);
+        let mut summary = orderbook_to_summary(&merged_orderbook, &self.fields);
+        if self.fields.signals {
+            summary.signals = signals;
+        }
+        if self.fields.fair_value {
+            summary.weighted_mid = weighted_mid(&merged_orderbook);
+            summary.fair_value = fair_value;
+            summary.index_prices = index_prices;
+        }
+        summary.toxicity = toxicity;
+        if self.fields.reference {
+            summary.reference_improvement = reference;
+        }
+        summary.timestamp = self.service.clock.now_millis();
+        summary.sequence = self.service.sequence.load(Ordering::Relaxed);
+        summary.symbol = self.service.symbol.clone();
+        summary.venue_timestamps = self.venue_timestamps.lock().unwrap().clone();
+        summary.stale_venues = self.stale_venues();
+        summary.bbo_only_venues = {
+            let bbo_only_venues = self.bbo_only_venues.lock().unwrap();
+            self.venues
+                .iter()
+                .filter(|venue| bbo_only_venues.contains(*venue))
+                .cloned()
+                .collect()
+        };
+        summary.empty_book_venues = self.empty_book_venues();
+        let split_books = self.split_books();
+        summary.split_books = split_books
+            .iter()
+            .map(|(exchange, quote, orderbook)| {
+                orderbook_to_split_book(exchange, quote, orderbook, &self.fields)
+            })
+            .collect();
+        let unavailable = unavailable_venues(&summary.stale_venues, &summary.empty_book_venues);
+        let status = feed_status(self.venues.len(), unavailable);
+        summary.set_status(status);
+        *self.last_status.lock().unwrap() = status;
+        summary.checksum = summary_checksum(&summary);
+
+        // The sinks get every field whatever the clients subscribed to, once per book
+        if !self.service.sinks.is_empty() {
+            self.service.sinks.send(summary.clone());
+        }
+        // Sending only fails once every stream went away, the feed is stopping
+        let _ = self.sender.send(Arc::new(FeedUpdate {
+            orderbook: merged_orderbook,
+            summary,
+            split_books,
+            forced,
+        }));
+    }
+}
+
+impl ClientSubscription {
+    // Called with every update of the feed. Aligned streams send the latest one at their
+    // next tick, forced updates are sent right away
+    fn receive(&self, update: Arc<FeedUpdate>) {
+        *self.latest.lock().unwrap() = Some(Arc::clone(&update));
+        if update.forced {
+            self.send_update(&update, false, false);
+        } else if self.align_interval.is_none() {
+            self.send_merged_summary(&update);
+        }
+    }
+
+    // Sends the merged book to the client when the tenant's max update rate allows it
+    // and the emission policy triggers
+    fn send_merged_summary(&self, update: &FeedUpdate) {
+        if self.sender.throttled() {
+            return;
+        }
+        self.send_update(update, true, false);
+    }
+
+    // The latest book of the feed the client asked for with RequestSnapshot, false
+    // before the first one
+    pub(crate) fn send_snapshot(&self) -> bool {
+        let latest = self.latest.lock().unwrap().clone();
+        match latest {
+            Some(update) => {
+                self.send_update(&update, false, true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The feed's summary with the client's depth and fields, without the emission policy
+    // unless apply_emission_policy is set
+    fn send_update(&self, update: &FeedUpdate, apply_emission_policy: bool, snapshot: bool) {
+        let depth = self.depth as usize;
+        let mut orderbook = update.orderbook.clone();
+        orderbook.bids.truncate(depth);
+        orderbook.asks.truncate(depth);
+        if apply_emission_policy && !self.emission_policy.lock().unwrap().should_emit(&orderbook) {
+            return;
+        }
+
+        let feed_summary = &update.summary;
+        let mut summary = orderbook_to_summary(&orderbook, &self.fields);
+        if self.fields.signals {
+            summary.signals = feed_summary.signals.clone();
+        }
+        if self.fields.fair_value {
+            summary.weighted_mid = feed_summary.weighted_mid;
+            summary.fair_value = feed_summary.fair_value;
+            summary.index_prices = feed_summary.index_prices.clone();
+        }
+        if self.fields.toxicity {
+            summary.toxicity = feed_summary.toxicity;
+        }
+        if self.fields.reference {
+            summary.reference_improvement = feed_summary.reference_improvement.clone();
+        }
+        summary.timestamp = feed_summary.timestamp;
+        summary.sequence = feed_summary.sequence;
+        summary.symbol = feed_summary.symbol.clone();
+        summary.subscription_id = self.id;
+        summary.snapshot = snapshot;
+        summary.venue_timestamps = feed_summary.venue_timestamps.clone();
+        summary.stale_venues = feed_summary.stale_venues.clone();
+        summary.bbo_only_venues = feed_summary.bbo_only_venues.clone();
+        summary.empty_book_venues = feed_summary.empty_book_venues.clone();
+        summary.split_books = update
+            .split_books
+            .iter()
+            .map(|(exchange, quote, orderbook)| {
+                let mut orderbook = orderbook.clone();
+                orderbook.bids.truncate(depth);
+                orderbook.asks.truncate(depth);
+                orderbook_to_split_book(exchange, quote, &orderbook, &self.fields)
+            })
+            .collect();
+        summary.status = feed_summary.status;
+        summary.checksum = summary_checksum(&summary);
+        if self.fields.decimal_prices {
+            encode_decimal_prices(&mut summary);
+        }
+        // A client not reading fast enough misses the summary, like a lagging stream
+        let _ = self.sender.send(summary);
+    }
+}
+
+// Updates the depeg guard with the USDT/USD mid and alerts when the equivalence changes
+fn process_usdt_message(
+    message_text: &str,
+    depeg_guard: &Mutex<DepegGuard>,
+    alert_sender: &broadcast::Sender<Alert>,
+    clock: &dyn Clock,
+) {
+    let rate =
+        process_message(message_text, "bitstamp", 1).and_then(|orderbook| mid_price(&orderbook));
+    let event = match rate {
+        Some(rate) => depeg_guard.lock().unwrap().update_rate(rate),
+        None => return,
+    };
+
+    let alert = match event {
+        Some(DepegEvent::Depegged {
+            rate,
+            deviation_bps,
+        }) => new_alert(
+            clock,
+            "depeg",
+            "bitstamp",
+            format!(
+                "USDT/USD at {} ({:.1} bps off peg), splitting USD and USDT books",
+                rate, deviation_bps
+            ),
+        ),
+        Some(DepegEvent::Repegged {
+            rate,
+            deviation_bps,
+        }) => new_alert(
+            clock,
+            "repeg",
+            "bitstamp",
+            format!(
+                "USDT/USD at {} ({:.1} bps off peg), merging USD and USDT books",
+                rate, deviation_bps
+            ),
+        ),
+        None => return,
+    };
+    eprintln!("Alert: {}", alert.message);
+    // Sending only fails when nobody is subscribed to alerts
+    let _ = alert_sender.send(alert);
+}
+
+// The interval of the symbol's aligned merges, its conflation interval or the ticks of
+// its merge cadence, None when it is merged on every update or on the slowest venue's
+// cadence
+pub(crate) fn pipeline_align_interval(pipeline: &SymbolPipeline) -> Option<Duration> {
+    match pipeline.merge_cadence {
+        Some(MergeCadence::Tick(tick)) => pipeline.conflation.max(Some(tick)),
+        _ => pipeline.conflation,
+    }
+}
+
+impl BookFeed {
+    fn new(
+        sender: broadcast::Sender<Arc<FeedUpdate>>,
+        pipeline: &SymbolPipeline,
+        service: OrderbookAggregatorService,
+    ) -> BookFeed {
+        let slowest_cadence = (pipeline.merge_cadence == Some(MergeCadence::Slowest))
+            .then(|| Mutex::new(CadenceTracker::new()));
+        // Analytics turned off for the symbol aren't computed
+        let fields = SummaryFields {
+            fair_value: pipeline.fair_value,
+            toxicity: pipeline.toxicity,
+            ..SummaryFields::all()
+        };
+        BookFeed {
+            sender,
+            depth: pipeline.depth,
+            orderbooks: Mutex::new(HashMap::new()),
+            bbo_only_venues: Mutex::new(HashSet::new()),
+            empty_book_venues: Mutex::new(HashSet::new()),
+            venue_timestamps: Mutex::new(HashMap::new()),
+            venues: service.venues.clone(),
+            ended_venues: Mutex::new(HashSet::new()),
+            started_at: service.clock.now_millis(),
+            last_status: Mutex::new(FeedStatus::Live),
+            align_interval: pipeline_align_interval(pipeline),
+            slowest_cadence,
+            toxicity_meter: Mutex::new(service.toxicity_meter.clone()),
+            delayed_books: Mutex::new(DelayLine::new()),
+            delayed_books_added: Notify::new(),
+            fields,
+            service,
+        }
+    }
+}
+
+// Reads every venue of the symbol into the feed until the last receiver of the feed
+// went away
+async fn process_socket_messages(feed: Arc<BookFeed>) -> Result<(), Box<dyn std::error::Error>> {
+    let service = feed.service.clone();
+
+    // Aligned merges are published at the wall-clock ticks, e.g. at every multiple of
+    // 250 ms, so the books of several servers line up in time. On the slowest venue's
+    // cadence the interval follows its update rate
+    let aligned = feed.align_interval.is_some() || feed.slowest_cadence.is_some();
+    let align_task = aligned.then(|| {
+        let feed = Arc::clone(&feed);
+        spawn(async move {
+            loop {
+                let interval_ms = (feed.merge_interval().as_millis() as u64).max(1);
+                let now = feed.service.clock.now_millis();
+                let next_tick = interval_ms - now % interval_ms;
+                tokio::time::sleep(Duration::from_millis(next_tick)).await;
+                if feed.is_closed() {
+                    break;
+                }
+                // Nothing to align before the first update
+                if !feed.venue_timestamps.lock().unwrap().is_empty() {
+                    feed.send_summary("aligned tick", false);
+                }
+            }
+        })
+    });
+
+    // Releases the books held back by latency compensation once they are due
+    let release_task = service.latency_compensation.map(|_| {
+        let feed = Arc::clone(&feed);
+        spawn(async move {
+            loop {
+                let next_release = feed.delayed_books.lock().unwrap().next_release();
+                let now = feed.service.clock.now_millis();
+                let wait = next_release.map_or(Duration::from_secs(1), |release_at| {
+                    Duration::from_millis(release_at.saturating_sub(now))
+                });
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = feed.delayed_books_added.notified() => {}
+                }
+                if feed.is_closed() {
+                    break;
+                }
+                feed.release_delayed_books();
+            }
+        })
+    });
+
+    // Feeds that stall without closing their socket are only noticed by the clock
+    let status_task = spawn({
+        let feed = Arc::clone(&feed);
+        let period =
+            (service.stale_after / 2).clamp(Duration::from_millis(100), Duration::from_secs(1));
+        async move {
+            loop {
+                tokio::time::sleep(period).await;
+                if feed.is_closed() {
+                    break;
+                }
+                feed.check_feed_status();
+            }
+        }
+    });
+
+    // Every venue is read through its connector: bitstamp on the sockets of its pool,
+    // each of which can carry the book channel and the USDT/USD channel, the others on
+    // a socket of their own, binance and the ones enabled with --connector, e.g.
+    // connectors from other crates. The sockets connected at startup go to the first
+    // feed, the ones started after the previous feed stopped connect their own
+    let mut reader_tasks = Vec::new();
+    if let Some(bitstamp_pool) = &service.bitstamp_pool {
+        for index in 0..bitstamp_pool.socket_count() {
+            let source = SocketSource::BitstampPool(Arc::clone(bitstamp_pool), index);
+            let socket = bitstamp_pool.take_socket(index);
+            reader_tasks.push(spawn(read_venue(
+                Arc::clone(&feed),
+                "bitstamp".to_string(),
+                source,
+                socket,
+            )));
+        }
+    }
+    for (name, startup_socket) in &service.venue_sockets {
+        let socket = startup_socket.lock().unwrap().take();
+        reader_tasks.push(spawn(read_venue(
+            Arc::clone(&feed),
+            name.clone(),
+            SocketSource::Connector,
+            socket,
+        )));
+    }
+
+    // Await all tasks to complete
+    for reader_task in reader_tasks {
+        reader_task.await?;
+    }
+    if let Some(align_task) = align_task {
+        align_task.abort();
+    }
+    if let Some(release_task) = release_task {
+        release_task.abort();
+    }
+    status_task.abort();
+
+    Ok(())
+}
+
+// Reads one venue of the feed until the last stream reading it went away, unless an
+// error event of its exchange disabled the venue
+async fn read_venue(
+    feed: Arc<BookFeed>,
+    venue: String,
+    source: SocketSource,
+    socket: Option<AsyncSocket>,
+) {
+    let connector = match feed.service.new_connector(&venue, false, feed.depth) {
+        Some(connector) => connector,
+        None => return,
+    };
+    if feed
+        .service
+        .feed_monitor
+        .lock()
+        .unwrap()
+        .is_disabled(&venue)
+    {
+        feed.on_venue_down(&venue);
+        return;
+    }
+    run_connector(
+        &venue,
+        connector,
+        source,
+        socket,
+        &feed.service.symbol,
+        feed.depth,
+        feed.service.new_backoff(),
+        feed.as_ref(),
+        feed.sender.closed(),
+    )
+    .await
+}
+
+// Sends the updates of the feed to one BookSummary stream until its client went away
+pub(crate) async fn forward_summaries(
+    subscription: Arc<ClientSubscription>,
+    mut feed_receiver: broadcast::Receiver<Arc<FeedUpdate>>,
+    service: OrderbookAggregatorService,
+) {
+    // Streams aligned on a longer interval than the symbol's send the latest update of
+    // the feed at their own wall-clock ticks
+    let align_task = subscription.align_interval.map(|interval| {
+        let subscription = Arc::clone(&subscription);
+        let clock = Arc::clone(&service.clock);
+        spawn(async move {
+            let interval_ms = (interval.as_millis() as u64).max(1);
+            loop {
+                let next_tick = interval_ms - clock.now_millis() % interval_ms;
+                tokio::time::sleep(Duration::from_millis(next_tick)).await;
+                if subscription.sender.is_closed() {
+                    break;
+                }
+                // Nothing to align before the first update
+                let latest = subscription.latest.lock().unwrap().clone();
+                if let Some(update) = latest {
+                    subscription.send_merged_summary(&update);
+                }
+            }
+        })
+    });
+
+    loop {
+        let update = tokio::select! {
+            update = feed_receiver.recv() => update,
+            _ = subscription.sender.closed() => break,
+        };
+        match update {
+            Ok(update) => subscription.receive(update),
+            // The books are complete, a stream that fell behind only needs the latest
+            Err(RecvError::Lagged(skipped)) => {
+                eprintln!(
+                    "Subscription {} fell behind the feed, skipped {} summaries",
+                    subscription.id, skipped
+                );
+            }
+            // The feed stopped as its last stream went away while this one started, or
+            // after an error, the stream starts a new one
+            Err(RecvError::Closed) => feed_receiver = service.subscribe_feed(),
+        }
+    }
+
+    if let Some(align_task) = align_task {
+        align_task.abort();
+    }
+    service
+        .subscriptions
+        .lock()
+        .unwrap()
+        .remove(&subscription.id);
+}
+
+// Where a reader's sockets come from: its connector, or for bitstamp the socket of its
+// pool, which carries the venue's channels with others
+enum SocketSource {
+    Connector,
+    BitstampPool(Arc<BitstampPool>, usize),
+}
+
+impl SocketSource {
+    // A new socket subscribed to the venue, the connector's book restarts from a new
+    // snapshot. The connector is handed back, see connect_connector_async
+    async fn connect(
+        &self,
+        mut connector: Box<dyn ExchangeConnector>,
+        symbol: &str,
+    ) -> (
+        Box<dyn ExchangeConnector>,
+        Result<AsyncSocket, Box<dyn Error>>,
+    ) {
+        connector.reset();
+        match self {
+            SocketSource::Connector => connect_connector_async(connector, symbol).await,
+            SocketSource::BitstampPool(bitstamp_pool, index) => {
+                (connector, bitstamp_pool.resubscribe(*index).await)
+            }
+        }
+    }
+
+    // Sending the unsubscription is best effort, the socket is dropped anyway. A socket
+    // of the pool is closed, its other channels go with it
+    async fn close(
+        &self,
+        connector: &mut dyn ExchangeConnector,
+        mut socket: AsyncSocket,
+        symbol: &str,
+    ) {
+        match self {
+            SocketSource::Connector => {
+                let _ = unsubscribe_connector_async(connector, &mut socket, symbol).await;
+            }
+            SocketSource::BitstampPool(..) => {
+                let _ = socket.close(None).await;
+            }
+        }
+    }
+}
+
+// Where a reader puts its venue's books and reports its events: the symbol's feed, or
+// the book of a BasisStream or IndexPriceStream leg
+trait BookTarget: Send + Sync {
+    // False once the books aren't wanted anymore, the reader stops
+    fn receive(&self, venue: &str, connector: &dyn ExchangeConnector, orderbook: OrderBook)
+        -> bool;
+
+    // The messages of the socket's other channels, true when the message was one of them
+    fn receive_other(&self, _venue: &str, _message_text: &str) -> bool {
+        false
+    }
+
+    // Every message of the venue, before it is applied
+    fn observe(&self, _venue: &str, _connector: &dyn ExchangeConnector, _message_text: &str) {}
+
+    fn receive_trade(&self, _trade: TapeTrade) {}
+
+    // Returns what the reader does about the error
+    fn on_error(&self, venue: &str, error: ExchangeError) -> ErrorAction {
+        eprintln!("{} sent an error: {}, {:?}", venue, error, error.action);
+        error.action
+    }
+
+    // Before the reader resubscribes after a sequence gap
+    fn on_resync(&self, venue: &str) {
+        eprintln!("{} stream out of sync, resubscribing", venue);
+    }
+
+    // The reader stopped after an error it didn't resubscribe from
+    fn on_down(&self, _venue: &str) {}
+}
+
+impl BookTarget for BookFeed {
+    fn receive(
+        &self,
+        venue: &str,
+        connector: &dyn ExchangeConnector,
+        orderbook: OrderBook,
+    ) -> bool {
+        self.receive_orderbook(venue, orderbook, connector.bbo_only(), connector.name());
+        true
+    }
+
+    // The sockets of the bitstamp pool also carry the USDT/USD rate of the depeg guard
+    fn receive_other(&self, venue: &str, message_text: &str) -> bool {
+        let depeg_guard = match &self.service.depeg_guard {
+            Some(depeg_guard) if venue == "bitstamp" => depeg_guard,
+            _ => return false,
+        };
+        if bitstamp_message_channel(message_text) != Some(bitstamp_channel("usdtusd")) {
+            return false;
+        }
+        process_usdt_message(
+            message_text,
+            depeg_guard,
+            &self.service.alert_sender,
+            self.service.clock.as_ref(),
+        );
+        true
+    }
+
+    fn observe(&self, venue: &str, connector: &dyn ExchangeConnector, message_text: &str) {
+        self.observe_latency(venue, connector, message_text);
+    }
+
+    fn receive_trade(&self, trade: TapeTrade) {
+        let _ = self.service.trade_sender.send(trade);
+    }
+
+    fn on_error(&self, venue: &str, error: ExchangeError) -> ErrorAction {
+        self.on_venue_error(venue, error)
+    }
+
+    fn on_resync(&self, venue: &str) {
+        self.on_venue_resync(venue);
+    }
+
+    fn on_down(&self, venue: &str) {
+        self.on_venue_down(venue);
+    }
+}
+
+// The latest book of a BasisStream or IndexPriceStream leg, on_update sends the
+// subscription's quote and returns false once its client went away
+struct LegBook<F> {
+    orderbook: Arc<Mutex<OrderBook>>,
+    on_update: F,
+}
+
+impl<F: Fn() -> bool + Send + Sync> BookTarget for LegBook<F> {
+    fn receive(
+        &self,
+        _venue: &str,
+        _connector: &dyn ExchangeConnector,
+        orderbook: OrderBook,
+    ) -> bool {
+        *self.orderbook.lock().unwrap() = orderbook;
+        (self.on_update)()
+    }
+}
+
+// Resubscribes after an error event of the venue's exchange, after ERROR_BACKOFF when it
+// was rate limited. None when the error disabled the venue or resubscribing failed
+async fn resubscribe_after_error(
+    source: &SocketSource,
+    venue: &str,
+    connector: Box<dyn ExchangeConnector>,
+    symbol: &str,
+    action: ErrorAction,
+) -> (Box<dyn ExchangeConnector>, Option<AsyncSocket>) {
+    match action {
+        ErrorAction::Disable => return (connector, None),
+        ErrorAction::BackOff => tokio::time::sleep(ERROR_BACKOFF).await,
+        ErrorAction::Resubscribe => {}
+    }
+    let (connector, connected) = source.connect(connector, symbol).await;
+    match connected {
+        Ok(socket) => (connector, Some(socket)),
+        Err(err) => {
+            eprintln!("Resubscribing {} failed: {}", venue, err);
+            (connector, None)
+        }
+    }
+}
+
+// Connects the venue again with the backoff until it succeeds, None once the client went
+// away in the meantime
+async fn reconnect(
+    source: &SocketSource,
+    venue: &str,
+    mut connector: Box<dyn ExchangeConnector>,
+    symbol: &str,
+    backoff: &mut ReconnectBackoff,
+    client_closed: impl Future<Output = ()>,
+) -> (Box<dyn ExchangeConnector>, Option<AsyncSocket>) {
+    tokio::pin!(client_closed);
+    loop {
+        if !backoff.wait(venue, &mut client_closed).await {
+            return (connector, None);
+        }
+        let (returned, connected) = source.connect(connector, symbol).await;
+        connector = returned;
+        match connected {
+            Ok(socket) => return (connector, Some(socket)),
+            Err(err) => eprintln!("Reconnecting {} failed: {}", venue, err),
+        }
+    }
+}
+
+// Reads a venue's socket into the target until the client went away or the target stops
+// taking books, for every reader of the server: the venues of the symbol's feed and the
+// legs of the basis and index subscriptions. Without a socket the reader connects its
+// own, a dropped or silent socket is reconnected with the backoff, and after a sequence
+// gap or an error event of the exchange the venue is resubscribed
+#[allow(clippy::too_many_arguments)]
+async fn run_connector(
+    venue: &str,
+    mut connector: Box<dyn ExchangeConnector>,
+    source: SocketSource,
+    socket: Option<AsyncSocket>,
+    symbol: &str,
+    depth: u32,
+    mut backoff: ReconnectBackoff,
+    target: &impl BookTarget,
+    client_closed: impl Future<Output = ()>,
+) {
+    tokio::pin!(client_closed);
+    let socket = match socket {
+        Some(socket) => Some(socket),
+        None => {
+            let connected = reconnect(
+                &source,
+                venue,
+                connector,
+                symbol,
+                &mut backoff,
+                &mut client_closed,
+            );
+            let (returned, socket) = connected.await;
+            connector = returned;
+            socket
+        }
+    };
+    let mut socket = match socket {
+        Some(socket) => socket,
+        None => return,
+    };
+    let mut keepalive = Keepalive::new(KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT);
+    loop {
+        let message = tokio::select! {
+            message = socket.next() => message,
+            action = keepalive.tick() => match action {
+                KeepaliveAction::Send => {
+                    let frame = keepalive_frame(connector.as_ref());
+                    // A socket failing to send it fails the next read
+                    let _ = socket.send(frame).await;
+                    continue;
+                }
+                KeepaliveAction::Reconnect => {
+                    eprintln!("{} went silent, reconnecting", venue);
+                    None
+                }
+            },
+            _ = &mut client_closed => break,
+        };
+        let message = match message {
+            Some(Ok(message)) => message,
+            // Dropped by the exchange, the books resume once reconnected
+            _ => {
+                let reconnected = reconnect(
+                    &source,
+                    venue,
+                    connector,
+                    symbol,
+                    &mut backoff,
+                    &mut client_closed,
+                );
+                let (returned, reconnected) = reconnected.await;
+                connector = returned;
+                match reconnected {
+                    Some(new_socket) => {
+                        socket = new_socket;
+                        keepalive.reset(Instant::now());
+                    }
+                    None => return,
+                }
+                continue;
+            }
+        };
+        backoff.reset();
+        keepalive.received(Instant::now());
+        if is_control_frame(&message) {
+            continue;
+        }
+        let message_text = &message_text(&message);
+        if let Some(reply) = connector.heartbeat_reply(message_text) {
+            // A socket failing to send it fails the next read
+            let _ = socket.send(WebSocketMessage::Text(reply)).await;
+            continue;
+        }
+        if let Some(error) = connector.parse_error(message_text) {
+            let action = target.on_error(venue, error);
+            let resubscribed = resubscribe_after_error(&source, venue, connector, symbol, action);
+            let (returned, resubscribed) = resubscribed.await;
+            connector = returned;
+            match resubscribed {
+                Some(new_socket) => {
+                    socket = new_socket;
+                    keepalive.reset(Instant::now());
+                }
+                None => {
+                    target.on_down(venue);
+                    return;
+                }
+            }
+            continue;
+        }
+        if target.receive_other(venue, message_text) {
+            continue;
+        }
+        target.observe(venue, connector.as_ref(), message_text);
+        let applied =
+            apply_connector_message_async(connector.as_mut(), message_text, depth as usize).await;
+        if let Some(new_orderbook) = applied {
+            if !target.receive(venue, connector.as_ref(), new_orderbook) {
+                break;
+            }
+        } else if connector.needs_resync() {
+            // Reconnecting resets the connector, which refetches its snapshot
+            target.on_resync(venue);
+            let reconnected = reconnect(
+                &source,
+                venue,
+                connector,
+                symbol,
+                &mut backoff,
+                &mut client_closed,
+            );
+            let (returned, reconnected) = reconnected.await;
+            connector = returned;
+            match reconnected {
+                Some(new_socket) => {
+                    socket = new_socket;
+                    keepalive.reset(Instant::now());
+                }
+                None => return,
+            }
+            continue;
+        }
+        for trade in connector.take_trades() {
+            target.receive_trade(trade);
+        }
+    }
+    source.close(connector.as_mut(), socket, symbol).await;
+}
+
+// Keeps the latest spot and perp books and sends the basis whenever either side updates
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn process_basis_messages(
+    sender: Arc<ClientSender<Basis>>,
+    request: BasisRequest,
+    depth: u32,
+    reconnect_limiter: Arc<ReconnectLimiter>,
+    spot_connector: Box<dyn ExchangeConnector>,
+    spot_socket: AsyncSocket,
+    perp_connector: Box<dyn ExchangeConnector>,
+    perp_socket: AsyncSocket,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let spot_orderbook = Arc::new(Mutex::new(OrderBook::new()));
+    let perp_orderbook = Arc::new(Mutex::new(OrderBook::new()));
+
+    let send_basis = {
+        let sender = Arc::clone(&sender);
+        let spot_orderbook = Arc::clone(&spot_orderbook);
+        let perp_orderbook = Arc::clone(&perp_orderbook);
+        let request = request.clone();
+        move || -> bool {
+            if sender.throttled() {
+                return true;
+            }
+            let perp_orderbook = perp_orderbook.lock().unwrap();
+            let quote = compute_basis(&spot_orderbook.lock().unwrap(), &perp_orderbook);
+            match quote {
+                Some(quote) => {
+                    let basis = Basis {
+                        spot_exchange: request.spot_exchange.clone(),
+                        perp_exchange: request.perp_exchange.clone(),
+                        spot_mid: quote.spot_mid,
+                        perp_mid: quote.perp_mid,
+                        basis: quote.basis,
+                        basis_bps: quote.basis_bps,
+                        annualized_basis_bps: quote.annualized_basis_bps,
+                        perp_venue: perp_orderbook.venues.first().map(venue_to_summary_venue),
+                    };
+                    sender.send(basis).is_ok()
+                }
+                // Keep reading until both books have a top of book
+                None => true,
+            }
+        }
+    };
+
+    let spot_task = spawn({
+        let sender = Arc::clone(&sender);
+        let spot_orderbook = Arc::clone(&spot_orderbook);
+        let spot_exchange = request.spot_exchange.clone();
+        let spot_symbol = request.spot_symbol.clone();
+        let send_basis = send_basis.clone();
+        let backoff = ReconnectBackoff::new(RECONNECT_BASE, RECONNECT_MAX)
+            .with_limiter(Arc::clone(&reconnect_limiter));
+        async move {
+            let spot_book = LegBook {
+                orderbook: spot_orderbook,
+                on_update: send_basis,
+            };
+            run_connector(
+                &spot_exchange,
+                spot_connector,
+                SocketSource::Connector,
+                Some(spot_socket),
+                &spot_symbol,
+                depth,
+                backoff,
+                &spot_book,
+                sender.closed(),
+            )
+            .await
+        }
+    });
+
+    let perp_task = spawn({
+        let perp_orderbook = Arc::clone(&perp_orderbook);
+        let perp_exchange = request.perp_exchange.clone();
+        let perp_symbol = request.perp_symbol.clone();
+        let backoff =
+            ReconnectBackoff::new(RECONNECT_BASE, RECONNECT_MAX).with_limiter(reconnect_limiter);
+        async move {
+            let perp_book = LegBook {
+                orderbook: perp_orderbook,
+                on_update: send_basis,
+            };
+            run_connector(
+                &perp_exchange,
+                perp_connector,
+                SocketSource::Connector,
+                Some(perp_socket),
+                &perp_symbol,
+                depth,
+                backoff,
+                &perp_book,
+                sender.closed(),
+            )
+            .await
+        }
+    });
+
+    // Await both tasks to complete
+    spot_task.await?;
+    perp_task.await?;
+
+    Ok(())
+}
+
+// A venue of an index subscription, as (exchange, symbol, connector, socket)
+type IndexVenue = (String, String, Box<dyn ExchangeConnector>, AsyncSocket);
+
+// Keeps the latest book of every venue of the index and sends the index whenever one
+// of them updates
+pub(crate) async fn process_index_messages(
+    sender: Arc<ClientSender<IndexPrice>>,
+    name: String,
+    formula: IndexFormula,
+    depth: u32,
+    venues: Vec<IndexVenue>,
+    reconnect_limiter: Arc<ReconnectLimiter>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let orderbooks: Vec<(String, Arc<Mutex<OrderBook>>)> = venues
+        .iter()
+        .map(|(exchange, ..)| (exchange.clone(), Arc::new(Mutex::new(OrderBook::new()))))
+        .collect();
+
+    let send_index = {
+        let sender = Arc::clone(&sender);
+        let orderbooks = orderbooks.clone();
+        move || -> bool {
+            if sender.throttled() {
+                return true;
+            }
+            let latest: Vec<(String, OrderBook)> = orderbooks
+                .iter()
+                .map(|(exchange, orderbook)| (exchange.clone(), orderbook.lock().unwrap().clone()))
+                .collect();
+            match formula.compute(&venue_refs(&latest)) {
+                Some(index) => {
+                    let index_price = IndexPrice {
+                        name: name.clone(),
+                        price: index.price,
+                        constituents: index
+                            .constituents
+                            .into_iter()
+                            .map(|constituent| IndexConstituent {
+                                exchange: constituent.exchange,
+                                mid: constituent.mid,
+                                weight: constituent.weight,
+                            })
+                            .collect(),
+                        excluded: index.excluded,
+                        timestamp: sender.clock.now_millis(),
+                    };
+                    sender.send(index_price).is_ok()
+                }
+                // Keep reading until enough venues have a top of book
+                None => true,
+            }
+        }
+    };
+
+    let tasks: Vec<_> = venues
+        .into_iter()
+        .zip(orderbooks)
+        .map(|((exchange, symbol, connector, socket), (_, orderbook))| {
+            let send_index = send_index.clone();
+            let sender = Arc::clone(&sender);
+            let backoff = ReconnectBackoff::new(RECONNECT_BASE, RECONNECT_MAX)
+                .with_limiter(Arc::clone(&reconnect_limiter));
+            spawn(async move {
+                let venue_book = LegBook {
+                    orderbook,
+                    on_update: send_index,
+                };
+                run_connector(
+                    &exchange,
+                    connector,
+                    SocketSource::Connector,
+                    Some(socket),
+                    &symbol,
+                    depth,
+                    backoff,
+                    &venue_book,
+                    sender.closed(),
+                )
+                .await
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await?;
+    }
+
+    Ok(())
+}
+
+// Which of binance's streams the binance venue reads, see ServerOptions
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BinanceStreams {
+    pub(crate) combined: bool,
+    pub(crate) futures: bool,
+    pub(crate) us: bool,
+}
+
+impl BinanceStreams {
+    // Binance.US lists no futures, the futures are read from the global endpoint
+    fn connector(&self, depth: u32) -> BinanceConnector {
+        let connector = match (self.futures, self.combined) {
+            (true, _) => return BinanceConnector::futures(depth),
+            (false, true) => BinanceConnector::combined(depth),
+            (false, false) => BinanceConnector::new(depth),
+        };
+        if self.us {
+            connector.us()
+        } else {
+            connector
+        }
+    }
+}
+
+// What the connectors of the server are configured with, see ServerOptions
+#[derive(Clone, Default)]
+pub(crate) struct ConnectorSettings {
+    pub(crate) compaction: Option<CompactionPolicy>,
+    pub(crate) binance_streams: BinanceStreams,
+    pub(crate) fx_rates: HashMap<String, f64>,
+    pub(crate) tape: bool,
+    pub(crate) endpoints: HashMap<String, String>,
+    pub(crate) update_speeds: HashMap<String, Duration>,
+    pub(crate) venue_params: HashMap<String, Vec<(String, String)>>,
+    pub(crate) venue_symbols: HashMap<String, String>,
+    pub(crate) bitstamp_diff_book: bool,
+}
+
+impl ConnectorSettings {
+    // The channel bitstamp's book is read from, diff_order_book with --bitstamp-diff-book
+    pub(crate) fn bitstamp_book_channel(&self, symbol: &str) -> String {
+        bitstamp_book_channel(symbol, self.bitstamp_diff_book)
+    }
+
+    // The venue parameters the connectors don't take, checked before connecting so that
+    // a typo fails the startup instead of being ignored
+    pub(crate) fn check_venue_params(&self, depth: u32) -> Result<(), String> {
+        let mut names: Vec<&String> = self.venue_params.keys().collect();
+        names.sort();
+        for name in names {
+            let mut connector = base_connector(name, false, depth, self)
+                .ok_or_else(|| format!("Unknown connector {} in --venue-param", name))?;
+            for (key, value) in &self.venue_params[name] {
+                connector
+                    .set_subscription_param(key, value)
+                    .map_err(|err| {
+                        format!("Invalid --venue-param {}.{}={}: {}", name, key, value, err)
+                    })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn base_connector(
+    name: &str,
+    perp: bool,
+    depth: u32,
+    settings: &ConnectorSettings,
+) -> Option<Box<dyn ExchangeConnector>> {
+    if name == "binance" && !perp {
+        Some(Box::new(settings.binance_streams.connector(depth)))
+    } else if name == "bitstamp" && !perp && settings.bitstamp_diff_book {
+        Some(Box::new(BitstampConnector::diff()))
+    } else {
+        find_connector(name, perp, depth)
+    }
+}
+
+// Registered connector with the server's compaction policy, FX rates and trade tape,
+// and binance on the streams the server is configured with. The endpoints, update
+// speeds, venue parameters and symbols are those of the merged venues, the perpetual
+// legs keep theirs
+pub(crate) fn configured_connector(
+    name: &str,
+    perp: bool,
+    depth: u32,
+    settings: &ConnectorSettings,
+) -> Option<Box<dyn ExchangeConnector>> {
+    let mut connector = base_connector(name, perp, depth, settings)?;
+    if let Some(compaction) = settings.compaction {
+        connector.set_compaction(compaction);
+    }
+    for (currency, rate) in &settings.fx_rates {
+        connector.set_fx_rate_source(currency, Arc::new(FixedRate(*rate)));
+    }
+    if settings.tape {
+        connector.enable_trades();
+    }
+    if perp {
+        return Some(connector);
+    }
+    if let Some(speed) = settings.update_speeds.get(name) {
+        connector.set_update_speed(*speed);
+    }
+    for (key, value) in settings.venue_params.get(name).into_iter().flatten() {
+        // Rejected parameters fail the startup, see check_venue_params
+        let _ = connector.set_subscription_param(key, value);
+    }
+    let (url, symbol) = (
+        settings.endpoints.get(name),
+        settings.venue_symbols.get(name),
+    );
+    if url.is_none() && symbol.is_none() {
+        return Some(connector);
+    }
+    let mut connector = VenueOverride::new(connector);
+    if let Some(url) = url {
+        connector = connector.with_url(url);
+    }
+    if let Some(symbol) = symbol {
+        connector = connector.with_symbol(symbol);
+    }
+    Some(Box::new(connector))
+}
+
+#[derive(Clone)]
+pub struct OrderbookAggregatorService {
+    pub(crate) symbol: String,
+    // depth is required to trim the messages from websocket
+    depth: u32,
+    pub(crate) bitstamp_symbol: String,
+    // Bitstamp limits the channels per socket and also carries the USDT/USD rate,
+    // so it is read through its pool instead of a socket of its own
+    bitstamp_pool: Option<Arc<BitstampPool>>,
+    // Connected at startup so we don't have to connect everytime, each taken by the
+    // first subscription reading the venue
+    venue_sockets: Vec<StartupSocket>,
+    // Every venue merged, in merge order
+    pub(crate) venues: Vec<String>,
+    // Only set when the binance and bitstamp books are quoted in USDT and USD
+    depeg_guard: Option<Arc<Mutex<DepegGuard>>>,
+    script_hook: Option<Arc<ScriptHook>>,
+    pub(crate) alert_sender: broadcast::Sender<Alert>,
+    // The trades of the venues' trade channels with --tape, printed as one tape by a
+    // task of their own rather than by the readers
+    trade_sender: broadcast::Sender<TapeTrade>,
+    // Without tenants every client is served, otherwise clients need the API key of a
+    // tenant
+    tenants: Option<Arc<TenantRegistry>>,
+    pub(crate) audit_log: Option<Arc<AuditLog>>,
+    pub(crate) usage_meter: Arc<UsageMeter>,
+    connector_settings: Arc<ConnectorSettings>,
+    fair_value_model: FairValueModel,
+    deviation_monitor: Option<Arc<Mutex<DeviationMonitor>>>,
+    wall_detector: Option<Arc<Mutex<WallDetector>>>,
+    // Outputs of the merged summaries besides the client streams, e.g. the recording
+    sinks: Arc<SinkFanOut>,
+    // The latest summaries of the symbol with --summary-history, also one of the sinks
+    pub(crate) summary_history: Option<Arc<SummaryHistory>>,
+    // Cloned by every subscription, with the server's bucket size
+    toxicity_meter: ToxicityMeter,
+    pub(crate) stale_after: Duration,
+    pub(crate) indexes: Arc<BTreeMap<String, IndexFormula>>,
+    // Venue book updates read since the server started, see Summary.sequence
+    sequence: Arc<AtomicU64>,
+    pub(crate) feed_monitor: Arc<Mutex<FeedMonitor>>,
+    pub(crate) started_at: u64,
+    max_touch_distance_bps: Arc<HashMap<String, f64>>,
+    // Shared by the reconnect backoffs of every socket, see new_backoff
+    pub(crate) reconnect_limiter: Arc<ReconnectLimiter>,
+    // Depth, conflation and analytics of the symbols of the config file
+    pub(crate) symbol_settings: Arc<BTreeMap<String, SymbolSettings>>,
+    // The BookSummary streams by Summary.subscription_id, for RequestSnapshot
+    pub(crate) subscriptions: Arc<Mutex<HashMap<u64, Weak<ClientSubscription>>>>,
+    pub(crate) next_subscription_id: Arc<AtomicU64>,
+    // The feed the BookSummary streams read, while any does
+    book_feed: Arc<Mutex<Weak<BookFeed>>>,
+    // Consolidated best bid and offer of the venues read by the subscriptions
+    pub(crate) bbo_attribution: Arc<Mutex<BboAttribution>>,
+    // Max delay of the faster venues' books when latency compensation is on, and the
+    // latency of each venue it is based on
+    pub(crate) latency_compensation: Option<Duration>,
+    pub(crate) latency_estimator: Arc<Mutex<LatencyEstimator>>,
+    // Venue the merged book is compared to, see reference::reference_improvement
+    reference_venue: Option<String>,
+    reference_band_bps: f64,
+    metrics: Arc<MetricsRegistry>,
+    // Every task of the subscriptions is spawned on it, see ServerOptions::runtime
+    pub(crate) runtime: Handle,
+    // Time of the service, the system clock unless connected with connect_with_clock
+    pub(crate) clock: Arc<dyn Clock>,
+}
+
+impl OrderbookAggregatorService {
+    pub(crate) fn pipeline(&self, symbol: &str) -> SymbolPipeline {
+        symbol_pipeline(&self.symbol_settings, symbol, self.depth)
+    }
+
+    // A receiver of the symbol's feed, starting the feed for the first stream
+    pub(crate) fn subscribe_feed(&self) -> broadcast::Receiver<Arc<FeedUpdate>> {
+        let mut book_feed = self.book_feed.lock().unwrap();
+        // A feed without receivers is stopping, the stream starts a new one
+        if let Some(feed) = book_feed.upgrade().filter(|feed| !feed.is_closed()) {
+            return feed.sender.subscribe();
+        }
+        let (sender, receiver) = broadcast::channel(FEED_CAPACITY);
+        let feed = Arc::new(BookFeed::new(
+            sender,
+            &self.pipeline(&self.symbol),
+            self.clone(),
+        ));
+        *book_feed = Arc::downgrade(&feed);
+        self.runtime.spawn(async move {
+            if let Err(err) = process_socket_messages(feed).await {
+                eprintln!("Error in the book feed: {}", err);
+            }
+        });
+        receiver
+    }
+
+    // The backoff of a socket, its attempts also capped by the server's reconnect caps
+    fn new_backoff(&self) -> ReconnectBackoff {
+        ReconnectBackoff::new(RECONNECT_BASE, RECONNECT_MAX)
+            .with_limiter(Arc::clone(&self.reconnect_limiter))
+    }
+
+    pub(crate) fn new_connector(
+        &self,
+        name: &str,
+        perp: bool,
+        depth: u32,
+    ) -> Option<Box<dyn ExchangeConnector>> {
+        configured_connector(name, perp, depth, &self.connector_settings)
+    }
+
+    // The venues merged for the server's symbol, with the symbol as each lists it
+    pub(crate) fn venue_symbols(&self, depth: u32) -> Vec<VenueSymbol> {
+        let feed_monitor = self.feed_monitor.lock().unwrap();
+        self.venues
+            .iter()
+            .map(|exchange| {
+                let symbol = if exchange == "bitstamp" {
+                    &self.bitstamp_symbol
+                } else {
+                    &self.symbol
+                };
+                let exchange_symbol = self
+                    .new_connector(exchange, false, depth)
+                    .map(|connector| connector.normalize_symbol(symbol))
+                    .unwrap_or_else(|| symbol.clone());
+                VenueSymbol {
+                    exchange: exchange.clone(),
+                    exchange_symbol,
+                    disabled: feed_monitor.is_disabled(exchange),
+                }
+            })
+            .collect()
+    }
+
+    // Returns the tenant of the request's API key, which must be allowed to subscribe
+    // to all the symbols. None when the server runs without tenants
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn authorize<T>(
+        &self,
+        request: &Request<T>,
+        symbols: &[&str],
+    ) -> Result<Option<Arc<Tenant>>, Status> {
+        let tenants = match &self.tenants {
+            Some(tenants) => tenants,
+            None => return Ok(None),
+        };
+        let api_key = request
+            .metadata()
+            .get(API_KEY_HEADER)
+            .and_then(|api_key| api_key.to_str().ok());
+        let tenant = tenants
+            .authenticate(api_key)
+            .ok_or_else(|| Status::unauthenticated("Missing or unknown API key"))?;
+
+        if let Some(symbol) = symbols.iter().find(|symbol| !tenant.allows_symbol(symbol)) {
+            tenant
+                .metrics
+                .rejected_subscriptions
+                .fetch_add(1, Ordering::Relaxed);
+            return Err(Status::permission_denied(format!(
+                "Namespace {} is not allowed to subscribe to {}",
+                tenant.config.namespace, symbol
+            )));
+        }
+        tenant.metrics.subscriptions.fetch_add(1, Ordering::Relaxed);
+        Ok(Some(tenant))
+    }
+
+    // Records the subscription in the audit log, if any. The guard has to live as long
+    // as the response stream so the unsubscription is recorded when the client leaves
+    pub(crate) fn audit<T>(
+        &self,
+        request: &Request<T>,
+        rpc: &str,
+        tenant: &Option<Arc<Tenant>>,
+        params: String,
+    ) -> Option<AuditGuard> {
+        let client = client_name(tenant);
+        let ip = request
+            .remote_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
+        self.audit_log
+            .as_ref()
+            .map(|audit_log| audit_log.subscribe(rpc, client, params, ip))
+    }
+}
+
+// A venue's socket connected at startup, see OrderbookAggregatorService::venue_sockets
+type StartupSocket = (String, Arc<Mutex<Option<AsyncSocket>>>);
+
+// Checks the instrument lists of the venues publishing one for their symbols, with
+// --check-symbols, a venue not listing its symbol fails the server's startup
+fn check_listed_symbols(
+    venues: &[(String, String)],
+    depth: u32,
+    settings: &ConnectorSettings,
+) -> Result<(), String> {
+    let problems: Vec<String> = venues
+        .iter()
+        .filter_map(|(name, symbol)| {
+            let connector = configured_connector(name, false, depth, settings)?;
+            check_venue_symbol(connector.as_ref(), symbol)?.err()
+        })
+        .collect();
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("; "))
+    }
+}
+
+// Connects the bitstamp pool and the sockets of the other venues, a venue failing to
+// connect fails the server's startup
+async fn connect_startup_sockets(
+    bitstamp_url: String,
+    bitstamp_channels: Vec<String>,
+    bitstamp_channels_per_socket: usize,
+    socket_venues: Vec<String>,
+    symbol: String,
+    depth: u32,
+    settings: ConnectorSettings,
+) -> Result<(BitstampPool, Vec<StartupSocket>), String> {
+    let bitstamp_pool = BitstampPool::connect(
+        &bitstamp_url,
+        &bitstamp_channels,
+        bitstamp_channels_per_socket,
+    )
+    .await
+    .map_err(|err| err.to_string())?;
+    let mut venue_sockets = Vec::new();
+    for name in socket_venues {
+        let connector = configured_connector(&name, false, depth, &settings)
+            .ok_or_else(|| format!("Unknown connector: {}", name))?;
+        let (_, socket) = connect_connector_async(connector, &symbol).await;
+        let socket = socket.map_err(|err| err.to_string())?;
+        venue_sockets.push((name, Arc::new(Mutex::new(Some(socket)))));
+    }
+    Ok((bitstamp_pool, venue_sockets))
+}
+
+// Holds the symbol's feed from the startup on, so the recording, the webhook and Redis
+// sinks and the summary history get its books whether or not a BookSummary stream is
+// open. A feed stopped after an error is started again. With idle_shutdown the feed is
+// let go once the symbol had no subscriber for that long, its readers close their
+// exchange connections with the last stream, and it is held again from the next
+// subscription on
+async fn hold_feed(
+    service: OrderbookAggregatorService,
+    feed_receiver: broadcast::Receiver<Arc<FeedUpdate>>,
+    idle_shutdown: Option<Duration>,
+) {
+    let mut feed_receiver = Some(feed_receiver);
+    let mut tracker = idle_shutdown.map(IdleTracker::new);
+    let period = match idle_shutdown {
+        Some(idle_shutdown) => {
+            (idle_shutdown / 4).clamp(Duration::from_millis(100), Duration::from_secs(10))
+        }
+        None => RECONNECT_BASE,
+    };
+    loop {
+        tokio::time::sleep(period).await;
+        if let (Some(tracker), Some(idle_shutdown)) = (tracker.as_mut(), idle_shutdown) {
+            let in_use = !service.subscriptions.lock().unwrap().is_empty();
+            match tracker.update(in_use, service.clock.now_millis()) {
+                Some(IdleTransition::Suspend) => {
+                    println!(
+                        "No subscriber of {} for {} s, closing its exchange connections",
+                        service.symbol,
+                        idle_shutdown.as_secs()
+                    );
+                    feed_receiver = None;
+                }
+                Some(IdleTransition::Resume) => {
+                    feed_receiver = Some(service.subscribe_feed());
+                }
+                None => {}
+            }
+        }
+        if let Some(receiver) = feed_receiver.as_mut() {
+            loop {
+                match receiver.try_recv() {
+                    Ok(_) | Err(TryRecvError::Lagged(_)) => {}
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Closed) => {
+                        *receiver = service.subscribe_feed();
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The aggregator connected to the exchanges. Applications embedding it can add
+// into_service to their own gRPC server instead of calling serve
+pub struct Aggregator {
+    service: OrderbookAggregatorService,
+    addr: SocketAddr,
+}
+
+impl Aggregator {
+    pub async fn connect(options: ServerOptions) -> Result<Aggregator, Box<dyn Error>> {
+        Aggregator::connect_with_clock(options, Arc::new(SystemClock)).await
+    }
+
+    // Same as connect, with the time of the staleness checks, timers, analytics windows
+    // and timestamps taken from the clock, e.g. a SimulatedClock in tests and replays
+    pub async fn connect_with_clock(
+        options: ServerOptions,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Aggregator, Box<dyn Error>> {
+        let runtime = options.runtime.clone().unwrap_or_else(default_runtime);
+        let depth = options.pipeline(&options.symbol).depth;
+        endpoint_resolver().set_refresh_interval(options.dns_refresh_interval);
+        let connector_settings = options.connector_settings();
+        connector_settings.check_venue_params(depth)?;
+        check_symbol_settings(&options.symbol_settings, &options.symbol)?;
+        if options.check_symbols {
+            let venues = configured_venues(&options);
+            let settings = connector_settings.clone();
+            runtime
+                .spawn_blocking(move || check_listed_symbols(&venues, depth, &settings))
+                .await??;
+        }
+        let mut bitstamp_channels =
+            vec![connector_settings.bitstamp_book_channel(&options.bitstamp_symbol)];
+        let depeg_guard = if needs_depeg_guard(&options.symbol, &options.bitstamp_symbol) {
+            bitstamp_channels.push(bitstamp_channel("usdtusd"));
+            Some(Arc::new(Mutex::new(DepegGuard::new(
+                options.depeg_threshold_bps,
+            ))))
+        } else {
+            None
+        };
+        // The sockets are connected on the runtime whatever executor polls this
+        let (bitstamp_pool, venue_sockets) = runtime
+            .spawn(connect_startup_sockets(
+                options.bitstamp_url().to_string(),
+                bitstamp_channels,
+                options.bitstamp_channels_per_socket,
+                options.socket_venues(),
+                options.symbol.clone(),
+                depth,
+                connector_settings.clone(),
+            ))
+            .await??;
+        // The tasks of the setup below are spawned on the runtime as well, there is no
+        // await in between
+        let _runtime_context = runtime.enter();
+
+        let venues = options.venues();
+        let script_hook = match options
+            .scripts
+            .iter()
+            .find(|(symbol, _)| *symbol == options.symbol)
+        {
+            Some((_, path)) => Some(Arc::new(ScriptHook::from_file(path)?)),
+            None => None,
+        };
+        let (alert_sender, _) = broadcast::channel(100);
+        let (trade_sender, _) = broadcast::channel(FEED_CAPACITY);
+        if options.tape {
+            let mut trades = trade_sender.subscribe();
+            spawn(async move {
+                loop {
+                    match trades.recv().await {
+                        Ok(trade) => println!("Trade: {}", trade),
+                        Err(RecvError::Lagged(skipped)) => {
+                            eprintln!("The tape fell behind, {} trades skipped", skipped)
+                        }
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+        }
+
+        let tenants = match &options.tenants_file {
+            Some(path) => Some(Arc::new(TenantRegistry::from_file(path)?)),
+            None => None,
+        };
+        let metrics = Arc::new(MetricsRegistry::default());
+        if let Some(tenants) = tenants.clone() {
+            // Every tenant's metrics are reported under its own namespace label
+            metrics.add_collector(move |metrics| {
+                for tenant in tenants.tenants() {
+                    tenant.record_metrics(metrics);
+                }
+            });
+        }
+
+        let audit_log = match &options.audit_log_file {
+            Some(path) => Some(Arc::new(AuditLog::open(path)?)),
+            None => None,
+        };
+
+        let recorder = match &options.record_file {
+            Some(path) => Some(Arc::new(Recorder::open(path)?)),
+            None => None,
+        };
+
+        let mut sinks: Vec<Arc<dyn SummarySink>> = Vec::new();
+        if let Some(recorder) = &recorder {
+            sinks.push(recorder.clone());
+        }
+        if let Some(url) = &options.webhook_url {
+            sinks.push(Arc::new(
+                WebhookSink::new(url)?.with_format(options.webhook_json_format),
+            ));
+        }
+        if let Some(url) = &options.redis_url {
+            sinks.push(Arc::new(
+                RedisSink::new(url)?.with_format(options.redis_json_format),
+            ));
+        }
+        let summary_history = options
+            .summary_history
+            .map(|capacity| Arc::new(SummaryHistory::new(capacity)));
+        if let Some(history) = &summary_history {
+            sinks.push(history.clone());
+        }
+        sinks.extend(options.sinks.iter().cloned());
+        let sinks = Arc::new(SinkFanOut::new(sinks));
+        if !sinks.is_empty() {
+            let sinks = Arc::clone(&sinks);
+            metrics.add_collector(move |metrics| sinks.record_metrics(metrics));
+        }
+
+        if let Some(addr) = options.metrics_addr {
+            let metrics = Arc::clone(&metrics);
+            spawn(async move {
+                if let Err(err) = serve_metrics(addr, metrics).await {
+                    eprintln!("Failed to serve metrics on {}: {}", addr, err);
+                }
+            });
+        }
+        if let Some(addr) = options.profile_addr {
+            spawn(async move {
+                if let Err(err) = serve_profiles(addr).await {
+                    eprintln!("Failed to serve profiles on {}: {}", addr, err);
+                }
+            });
+        }
+
+        let usage_meter = Arc::new(UsageMeter::new());
+        if let Some(path) = options.usage_export_file.clone() {
+            let usage_meter = Arc::clone(&usage_meter);
+            let mut interval = tokio::time::interval(options.usage_export_interval);
+            spawn(async move {
+                // The first tick completes immediately, there is nothing to export yet
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    // Blocking, and held back while the retention cleanup rewrites the file
+                    let export = {
+                        let (usage_meter, path) = (Arc::clone(&usage_meter), path.clone());
+                        spawn_blocking(move || {
+                            usage_meter.export(&path).map_err(|err| err.to_string())
+                        })
+                    };
+                    match export.await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(err)) => eprintln!("Failed to export usage to {}: {}", path, err),
+                        Err(_) => break,
+                    }
+                }
+            });
+        }
+
+        let retained_files: Vec<String> = [
+            &options.record_file,
+            &options.audit_log_file,
+            &options.usage_export_file,
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+        if options.retention.is_enabled() && !retained_files.is_empty() {
+            let retention = options.retention;
+            let recorder = recorder.clone();
+            let audit_log = audit_log.clone();
+            let usage_meter = Arc::clone(&usage_meter);
+            let metrics = Arc::clone(&metrics);
+            let mut interval = tokio::time::interval(options.retention_interval);
+            spawn(async move {
+                loop {
+                    interval.tick().await;
+                    let retained_files = retained_files.clone();
+                    let recorder = recorder.clone();
+                    let audit_log = audit_log.clone();
+                    let usage_meter = Arc::clone(&usage_meter);
+                    let usages = spawn_blocking(move || {
+                        clean_up_files(
+                            &retained_files,
+                            &retention,
+                            recorder.as_deref(),
+                            audit_log.as_deref(),
+                            &usage_meter,
+                        )
+                        .map_err(|err| err.to_string())
+                    })
+                    .await;
+                    match usages {
+                        Ok(Ok(usages)) => {
+                            for usage in usages {
+                                usage.record_metrics(&metrics);
+                            }
+                        }
+                        Ok(Err(err)) => eprintln!("Retention cleanup failed: {}", err),
+                        Err(_) => break,
+                    }
+                }
+            });
+        }
+
+        let deviation_monitor = options.deviation_threshold_bps.map(|threshold_bps| {
+            Arc::new(Mutex::new(DeviationMonitor::new(
+                threshold_bps,
+                options.deviation_sustain,
+                options.quarantine_deviating_venues,
+            )))
+        });
+
+        let wall_detector = options
+            .wall_multiple
+            .map(|multiple| Arc::new(Mutex::new(WallDetector::new(multiple, options.wall_window))));
+        let reconnect_limiter = {
+            let alert_sender = alert_sender.clone();
+            let clock = Arc::clone(&clock);
+            Arc::new(ReconnectLimiter::new(
+                options.max_reconnects_per_minute,
+                options.max_venue_reconnects_per_minute.clone(),
+                move |venue, cap| {
+                    let message = format!("Reconnects of {} held back by the {}", venue, cap);
+                    let _ = alert_sender.send(new_alert(
+                        clock.as_ref(),
+                        "reconnect_cap",
+                        venue,
+                        message,
+                    ));
+                },
+            ))
+        };
+
+        let service = OrderbookAggregatorService {
+            symbol: options.symbol,
+            depth: options.depth,
+            bitstamp_symbol: options.bitstamp_symbol,
+            bitstamp_pool: Some(Arc::new(bitstamp_pool)),
+            venue_sockets,
+            venues,
+            depeg_guard,
+            script_hook,
+            alert_sender,
+            trade_sender,
+            tenants,
+            audit_log,
+            usage_meter,
+            connector_settings: Arc::new(connector_settings),
+            fair_value_model: options.fair_value_model,
+            deviation_monitor,
+            wall_detector,
+            sinks,
+            summary_history,
+            toxicity_meter: ToxicityMeter::new(
+                options.toxicity_bucket_volume,
+                options.toxicity_buckets,
+            ),
+            stale_after: options.stale_after,
+            indexes: Arc::new(options.indexes),
+            sequence: Arc::new(AtomicU64::new(0)),
+            feed_monitor: Arc::new(Mutex::new(FeedMonitor::default())),
+            started_at: clock.now_millis(),
+            max_touch_distance_bps: Arc::new(options.max_touch_distance_bps),
+            reconnect_limiter,
+            symbol_settings: Arc::new(options.symbol_settings),
+            subscriptions: Arc::new(Mutex::new(HashMap::new())),
+            next_subscription_id: Arc::new(AtomicU64::new(1)),
+            book_feed: Arc::new(Mutex::new(Weak::new())),
+            bbo_attribution: Arc::new(Mutex::new(BboAttribution::new(
+                options.stale_after.as_millis() as u64,
+            ))),
+            latency_compensation: options.latency_compensation,
+            latency_estimator: Arc::new(Mutex::new(LatencyEstimator::new())),
+            reference_venue: options.reference_venue,
+            reference_band_bps: options.reference_band_bps,
+            metrics,
+            runtime,
+            clock,
+        };
+
+        if !service.sinks.is_empty() || options.idle_shutdown.is_some() {
+            let feed_receiver = service.subscribe_feed();
+            spawn(hold_feed(
+                service.clone(),
+                feed_receiver,
+                options.idle_shutdown,
+            ));
+        }
+
+        if let Some(addr) = options.csv_addr {
+            let service = service.clone();
+            spawn(async move {
+                if let Err(err) = serve_csv(addr, service).await {
+                    eprintln!("Failed to serve CSV on {}: {}", addr, err);
+                }
+            });
+        }
+
+        Ok(Aggregator {
+            service,
+            addr: options.addr,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn into_service(self) -> OrderbookAggregatorServer<OrderbookAggregatorService> {
+        OrderbookAggregatorServer::new(self.service)
+    }
+
+    // Serves the gRPC service on the configured address until the server stops. The
+    // server runs on the aggregator's runtime, the returned future can be awaited from
+    // any executor
+    pub async fn serve(self) -> Result<(), Box<dyn Error>> {
+        let addr = self.addr;
+        println!("gRPC server listening on {}", addr);
+        let runtime = self.service.runtime.clone();
+        let server = Server::builder()
+            .add_service(self.into_service())
+            .serve(addr);
+        runtime.spawn(server).await??;
+
+        Ok(())
+    }
+}
+
+// Applies the retention policy with the writes of the recorder, audit log and usage
+// export paused, and has the first two reopen their file when the cleanup renamed a
+// rewritten one over it
+fn clean_up_files(
+    files: &[String],
+    retention: &RetentionPolicy,
+    recorder: Option<&Recorder>,
+    audit_log: Option<&AuditLog>,
+    usage_meter: &UsageMeter,
+) -> Result<Vec<FileUsage>, Box<dyn Error>> {
+    let mut recorder_file = recorder.map(|recorder| recorder.pause_writes());
+    let mut audit_log_file = audit_log.map(|audit_log| audit_log.pause_writes());
+    let _usage_export = usage_meter.pause_exports();
+    // File ages are wall-clock whatever the server's clock
+    let usages = apply_retention(files, retention, SystemClock.now_millis())?;
+    let rewritten = |path: &str| {
+        usages
+            .iter()
+            .any(|usage| usage.removed_records > 0 && usage.path == path)
+    };
+    if let (Some(recorder), Some(file)) = (recorder, recorder_file.as_mut()) {
+        if rewritten(recorder.path()) {
+            recorder.reopen(file)?;
+        }
+    }
+    if let (Some(audit_log), Some(file)) = (audit_log, audit_log_file.as_mut()) {
+        if rewritten(audit_log.path()) {
+            audit_log.reopen(file)?;
+        }
+    }
+    Ok(usages)
+}
+
+// Connects to the exchanges and serves the gRPC service until the server stops
+pub async fn run_server(options: ServerOptions) -> Result<(), Box<dyn Error>> {
+    Aggregator::connect(options).await?.serve().await
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimulatedClock;
+    use crate::orderbook_proto::orderbook_aggregator_server::OrderbookAggregator;
+    use crate::orderbook_proto::{SummariesSinceRequest, SummaryRequest};
+    use std::net::TcpListener;
+    use std::thread;
+    use tokio::sync::mpsc::{channel, Receiver};
+    use tokio::time::timeout;
+    use tungstenite::accept;
+
+    // WebSocket exchange on localhost acknowledging every connection, then sending it the
+    // frame every 20 ms until the client goes away
+    fn streaming_exchange(ack: &'static str, frame: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                thread::spawn(move || {
+                    let mut socket = accept(stream).unwrap();
+                    let _ = socket.read_message();
+                    let _ = socket.write_message(WebSocketMessage::Text(ack.to_string()));
+                    while socket
+                        .write_message(WebSocketMessage::Text(frame.to_string()))
+                        .is_ok()
+                    {
+                        thread::sleep(Duration::from_millis(20));
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    // The options of a btcusdt server reading binance and bitstamp from local exchanges
+    fn test_options() -> ServerOptions {
+        let binance = streaming_exchange(
+            r#"{"result":null,"id":1}"#,
+            r#"{"lastUpdateId":1,"bids":[["10.0","1.0"],["9.0","2.0"]],"asks":[["11.0","0.8"],["12.0","1.0"]]}"#,
+        );
+        let bitstamp = streaming_exchange(
+            r#"{"event":"bts:subscription_succeeded","channel":"detail_order_book_btcusdt","data":{}}"#,
+            r#"{"event":"data","channel":"detail_order_book_btcusdt","data":{"bids":[["9.5","1.0","1"]],"asks":[["11.5","0.7","3"]]}}"#,
+        );
+        let mut options = ServerOptions::new("btcusdt", 5);
+        options.endpoints.insert("binance".to_string(), binance);
+        options.endpoints.insert("bitstamp".to_string(), bitstamp);
+        options
+    }
+
+    async fn test_service() -> OrderbookAggregatorService {
+        Aggregator::connect(test_options()).await.unwrap().service
+    }
+
+    // A BookSummary stream of the service with its own depth and fields, and the
+    // receiver of its client
+    fn client_subscription(
+        service: &OrderbookAggregatorService,
+        depth: u32,
+        fields: SummaryFields,
+    ) -> (Arc<ClientSubscription>, Receiver<Result<Summary, ()>>) {
+        let (sender, receiver) = channel(100);
+        let subscription = Arc::new(ClientSubscription {
+            id: service.next_subscription_id.fetch_add(1, Ordering::Relaxed),
+            sender: ClientSender::new(
+                sender,
+                None,
+                service.symbol.clone(),
+                Arc::clone(&service.usage_meter),
+                Arc::clone(&service.clock),
+            ),
+            depth,
+            fields,
+            align_interval: None,
+            emission_policy: Mutex::new(
+                EmissionPolicy::from_request(&SummaryRequest::default()).unwrap(),
+            ),
+            latest: Mutex::new(None),
+        });
+        (subscription, receiver)
+    }
+
+    async fn next_summary(receiver: &mut Receiver<Result<Summary, ()>>) -> Summary {
+        timeout(Duration::from_secs(10), receiver.recv())
+            .await
+            .expect("No summary in 10 s")
+            .unwrap()
+            .unwrap()
+    }
+
+    fn feed_update(sequence: u64) -> Arc<FeedUpdate> {
+        Arc::new(FeedUpdate {
+            orderbook: OrderBook::default(),
+            summary: Summary {
+                sequence,
+                ..Default::default()
+            },
+            split_books: Vec::new(),
+            forced: false,
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shared_feed() {
+        let service = test_service().await;
+        let (first, mut first_receiver) = client_subscription(&service, 1, SummaryFields::all());
+        let bids_only = SummaryFields::from_mask(&["bids".to_string()]).unwrap();
+        let (second, mut second_receiver) = client_subscription(&service, 2, bids_only);
+        spawn(forward_summaries(
+            Arc::clone(&first),
+            service.subscribe_feed(),
+            service.clone(),
+        ));
+        spawn(forward_summaries(
+            Arc::clone(&second),
+            service.subscribe_feed(),
+            service.clone(),
+        ));
+
+        // One feed for both streams
+        let feed = service.book_feed.lock().unwrap().upgrade().unwrap();
+        assert_eq!(feed.sender.receiver_count(), 2);
+
+        // The same merged book, at the depth and with the fields of each stream, once
+        // both venues sent theirs
+        let mut second_summary = next_summary(&mut second_receiver).await;
+        while second_summary.bids.len() < 2 {
+            second_summary = next_summary(&mut second_receiver).await;
+        }
+        let mut first_summary = next_summary(&mut first_receiver).await;
+        while first_summary.sequence < second_summary.sequence {
+            first_summary = next_summary(&mut first_receiver).await;
+        }
+        assert_eq!(first_summary.sequence, second_summary.sequence);
+        assert_eq!(first_summary.timestamp, second_summary.timestamp);
+        assert_eq!(first_summary.subscription_id, first.id);
+        assert_eq!(second_summary.subscription_id, second.id);
+        assert_eq!((first_summary.bids.len(), first_summary.asks.len()), (1, 1));
+        assert_eq!(
+            (second_summary.bids.len(), second_summary.asks.len()),
+            (2, 0)
+        );
+        assert_eq!(first_summary.bids[0].price, second_summary.bids[0].price);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_lagging_stream() {
+        let service = test_service().await;
+        let (subscription, mut receiver) = client_subscription(&service, 5, SummaryFields::all());
+        let (sender, feed_receiver) = broadcast::channel(2);
+        for sequence in 1..=5 {
+            sender.send(feed_update(sequence)).unwrap();
+        }
+        spawn(forward_summaries(
+            subscription,
+            feed_receiver,
+            service.clone(),
+        ));
+
+        // The summaries the stream fell behind by are skipped, it goes on from the latest
+        assert_eq!(next_summary(&mut receiver).await.sequence, 4);
+        assert_eq!(next_summary(&mut receiver).await.sequence, 5);
+        sender.send(feed_update(6)).unwrap();
+        assert_eq!(next_summary(&mut receiver).await.sequence, 6);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_feed_restarts() {
+        let service = test_service().await;
+        let feed_receiver = service.subscribe_feed();
+        let stopped_feed = service.book_feed.lock().unwrap().upgrade().unwrap();
+
+        // Once the last stream went away the next one starts a new feed
+        drop(feed_receiver);
+        assert!(stopped_feed.is_closed());
+        let feed_receiver = service.subscribe_feed();
+        let feed = service.book_feed.lock().unwrap().upgrade().unwrap();
+        assert!(!Arc::ptr_eq(&feed, &stopped_feed));
+
+        // A stream subscribed to a feed stopping meanwhile reads the current one
+        let (subscription, mut receiver) = client_subscription(&service, 5, SummaryFields::all());
+        let (sender, closed_receiver) = broadcast::channel(1);
+        drop(sender);
+        spawn(forward_summaries(
+            subscription,
+            closed_receiver,
+            service.clone(),
+        ));
+        let summary = next_summary(&mut receiver).await;
+        assert!(!summary.bids.is_empty());
+        assert_eq!(feed.sender.receiver_count(), 2);
+        drop(feed_receiver);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_history_without_stream() {
+        let mut options = test_options();
+        options.summary_history = Some(10);
+        let service = Aggregator::connect(options).await.unwrap().service;
+
+        // The history is fed from the startup on, polling needs no open stream
+        let mut sequence = 0;
+        for _ in 0..100 {
+            let request = Request::new(SummariesSinceRequest {
+                sequence,
+                ..Default::default()
+            });
+            let since = service.get_summaries_since(request).await.unwrap();
+            sequence = since.get_ref().latest_sequence;
+            if sequence > 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(sequence > 1, "No summary kept in 5 s");
+        assert!(service.subscriptions.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_idle_shutdown() {
+        let clock = Arc::new(SimulatedClock::new(0));
+        let mut options = test_options();
+        options.idle_shutdown = Some(Duration::from_millis(400));
+        let aggregator = Aggregator::connect_with_clock(options, clock.clone());
+        let service = aggregator.await.unwrap().service;
+        let book_feed = || service.book_feed.lock().unwrap().upgrade();
+        assert!(book_feed().is_some());
+
+        for _ in 0..2 {
+            // Let go once the symbol had no subscriber for 400 ms, its readers stop with it
+            for _ in 0..100 {
+                if book_feed().is_none() {
+                    break;
+                }
+                clock.advance(Duration::from_millis(200));
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            assert!(book_feed().is_none(), "Feed not suspended in 5 s");
+
+            // The next subscription starts it again
+            let request = Request::new(SummaryRequest::default());
+            let mut summaries = service.book_summary(request).await.unwrap().into_inner();
+            let summary = timeout(Duration::from_secs(10), summaries.next()).await;
+            assert!(summary.expect("No summary in 10 s").unwrap().is_ok());
+            tokio::time::sleep(Duration::from_millis(300)).await;
+
+            // And it is held again once the stream went away
+            drop(summaries);
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            assert!(service.subscriptions.lock().unwrap().is_empty());
+            assert!(!book_feed().unwrap().is_closed());
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// One line of the audit log. Every subscription writes a subscribe record and,
+// once the client is gone, an unsubscribe record with the same id and the duration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub subscription_id: u64,
+    pub event: String,
+    pub rpc: String,
+    // Tenant namespace of the client, anonymous without tenants
+    pub client: String,
+    pub params: String,
+    pub ip: String,
+    pub timestamp: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+// Filters of an audit log query, empty strings match everything
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub client: String,
+    pub rpc: String,
+    pub since: u64,
+    // Keeps the most recent records, 0 returns all of them
+    pub limit: usize,
+}
+
+impl AuditFilter {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        (self.client.is_empty() || record.client == self.client)
+            && (self.rpc.is_empty() || record.rpc == self.rpc)
+            && record.timestamp >= self.since
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// Append only JSON lines file, so the log survives restarts and can be shipped as is
+pub struct AuditLog {
+    path: String,
+    file: Mutex<File>,
+    next_id: AtomicU64,
+}
+
+impl AuditLog {
+    pub fn open(path: &str) -> Result<AuditLog, Box<dyn Error>> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        // A crash mid-append leaves a truncated last line, the next records start on a
+        // line of their own
+        if file.seek(SeekFrom::End(0))? > 0 {
+            let mut last = [0; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                writeln!(file)?;
+            }
+        }
+        let audit_log = AuditLog {
+            path: path.to_string(),
+            file: Mutex::new(file),
+            next_id: AtomicU64::new(0),
+        };
+
+        // Subscription ids keep increasing over restarts
+        let last_id = audit_log
+            .query(&AuditFilter::default())?
+            .iter()
+            .map(|record| record.subscription_id)
+            .max();
+        audit_log
+            .next_id
+            .store(last_id.map_or(1, |id| id + 1), Ordering::Relaxed);
+
+        Ok(audit_log)
+    }
+
+    // Holds back the writes while the retention cleanup rewrites the file
+    pub(crate) fn pause_writes(&self) -> MutexGuard<'_, File> {
+        self.file.lock().unwrap()
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+
+    // Called with the writes paused once the retention cleanup renamed the rewritten log
+    // over the file held open
+    pub(crate) fn reopen(&self, file: &mut File) -> Result<(), Box<dyn Error>> {
+        *file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+
+    fn write(&self, record: &AuditRecord) {
+        let result = serde_json::to_string(record)
+            .map_err(|err| err.to_string())
+            .and_then(|line| {
+                writeln!(self.file.lock().unwrap(), "{}", line).map_err(|err| err.to_string())
+            });
+        // A failing audit log must not stop the market data
+        if let Err(err) = result {
+            eprintln!("Failed to write audit record to {}: {}", self.path, err);
+        }
+    }
+
+    // Records the subscription, the unsubscription is recorded when the guard is dropped
+    pub fn subscribe(
+        self: &Arc<Self>,
+        rpc: &str,
+        client: &str,
+        params: String,
+        ip: String,
+    ) -> AuditGuard {
+        let record = AuditRecord {
+            subscription_id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            event: "subscribe".to_string(),
+            rpc: rpc.to_string(),
+            client: client.to_string(),
+            params,
+            ip,
+            timestamp: now_millis(),
+            duration_ms: None,
+        };
+        self.write(&record);
+
+        AuditGuard {
+            audit_log: Arc::clone(self),
+            record,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn query(&self, filter: &AuditFilter) -> Result<Vec<AuditRecord>, Box<dyn Error>> {
+        let mut records = Vec::new();
+        let mut malformed = 0;
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            // e.g. the truncated line of a crash mid-append, the other records still count
+            let record: AuditRecord = match serde_json::from_str(&line) {
+                Ok(record) => record,
+                Err(_) => {
+                    malformed += 1;
+                    continue;
+                }
+            };
+            if filter.matches(&record) {
+                records.push(record);
+            }
+        }
+        if malformed > 0 {
+            eprintln!(
+                "Skipped {} malformed lines of the audit log {}",
+                malformed, self.path
+            );
+        }
+
+        if filter.limit > 0 && records.len() > filter.limit {
+            records.drain(..records.len() - filter.limit);
+        }
+        Ok(records)
+    }
+}
+
+// Lives as long as the subscription's response stream
+pub struct AuditGuard {
+    audit_log: Arc<AuditLog>,
+    record: AuditRecord,
+    started: Instant,
+}
+
+impl Drop for AuditGuard {
+    fn drop(&mut self) {
+        let record = AuditRecord {
+            event: "unsubscribe".to_string(),
+            timestamp: now_millis(),
+            duration_ms: Some(self.started.elapsed().as_millis() as u64),
+            ..self.record.clone()
+        };
+        self.audit_log.write(&record);
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_log() {
+        let path = std::env::temp_dir().join(format!("orderbook-audit-{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let audit_log = Arc::new(AuditLog::open(path).unwrap());
+        let guard = audit_log.subscribe(
+            "BookSummary",
+            "research",
+            "symbol=btcusdt".to_string(),
+            "127.0.0.1:5000".to_string(),
+        );
+        drop(guard);
+        let risk_guard = audit_log.subscribe(
+            "Alerts",
+            "risk",
+            String::new(),
+            "127.0.0.1:5001".to_string(),
+        );
+
+        let records = audit_log.query(&AuditFilter::default()).unwrap();
+        assert_eq!(records.len(), 3);
+
+        let research = AuditFilter {
+            client: "research".to_string(),
+            ..AuditFilter::default()
+        };
+        let records = audit_log.query(&research).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].event, "subscribe");
+        assert_eq!(records[1].event, "unsubscribe");
+        assert_eq!(records[0].subscription_id, records[1].subscription_id);
+        assert_eq!(records[1].params, "symbol=btcusdt");
+        assert!(records[0].duration_ms.is_none());
+        assert!(records[1].duration_ms.is_some());
+
+        let latest = AuditFilter {
+            limit: 1,
+            ..AuditFilter::default()
+        };
+        assert_eq!(audit_log.query(&latest).unwrap()[0].client, "risk");
+        drop(risk_guard);
+
+        // Ids continue after reopening the log
+        let reopened = Arc::new(AuditLog::open(path).unwrap());
+        let guard = reopened.subscribe("Alerts", "risk", String::new(), String::new());
+        assert_eq!(guard.record.subscription_id, 3);
+
+        drop(guard);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_audit_log_malformed_lines() {
+        let path = std::env::temp_dir().join(format!(
+            "orderbook-audit-malformed-{}.log",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let record = r#"{"subscription_id":4,"event":"subscribe","rpc":"Alerts","client":"risk","params":"","ip":"","timestamp":1}"#;
+        // The server crashed while appending the second record
+        std::fs::write(path, format!("{}\n{{\"subscription_id\":5,\"ev", record)).unwrap();
+
+        let audit_log = Arc::new(AuditLog::open(path).unwrap());
+        let guard = audit_log.subscribe("Alerts", "risk", String::new(), String::new());
+        assert_eq!(guard.record.subscription_id, 5);
+        drop(guard);
+
+        let records = audit_log.query(&AuditFilter::default()).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].subscription_id, 4);
+        assert_eq!(records[2].event, "unsubscribe");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+use crate::book::OrderBook;
+use crate::number::decimal_to_f64;
+
+// Bybit and Binance perpetuals settle funding every 8 hours, the basis is
+// annualized as if it converges once per funding interval
+const FUNDING_INTERVALS_PER_YEAR: f64 = 365.0 * 24.0 / 8.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasisQuote {
+    pub spot_mid: f64,
+    pub perp_mid: f64,
+    pub basis: f64,
+    pub basis_bps: f64,
+    pub annualized_basis_bps: f64,
+}
+
+pub fn mid_price(orderbook: &OrderBook) -> Option<f64> {
+    match (orderbook.bids.first(), orderbook.asks.first()) {
+        (Some(best_bid), Some(best_ask)) => {
+            Some(decimal_to_f64(best_bid.price + best_ask.price) / 2.0)
+        }
+        _ => None,
+    }
+}
+
+// Basis is perp mid - spot mid, returns None until both books have a top of book
+pub fn compute_basis(spot_orderbook: &OrderBook, perp_orderbook: &OrderBook) -> Option<BasisQuote> {
+    let spot_mid = mid_price(spot_orderbook)?;
+    let perp_mid = mid_price(perp_orderbook)?;
+    if spot_mid <= 0.0 {
+        return None;
+    }
+
+    let basis = perp_mid - spot_mid;
+    let basis_bps = basis / spot_mid * 10_000.0;
+
+    Some(BasisQuote {
+        spot_mid,
+        perp_mid,
+        basis,
+        basis_bps,
+        annualized_basis_bps: basis_bps * FUNDING_INTERVALS_PER_YEAR,
+    })
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PriceAmountLevel;
+    use crate::number::decimal_from_f64;
+    use rust_decimal_macros::dec;
+
+    fn orderbook(bid: f64, ask: f64) -> OrderBook {
+        OrderBook {
+            bids: vec![PriceAmountLevel {
+                exchange: "exchange1".to_string(),
+                price: decimal_from_f64(bid).unwrap(),
+                amount: dec!(1.0),
+            }],
+            asks: vec![PriceAmountLevel {
+                exchange: "exchange1".to_string(),
+                price: decimal_from_f64(ask).unwrap(),
+                amount: dec!(1.0),
+            }],
+            spread: bid - ask,
+            venues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_mid_price() {
+        assert_eq!(mid_price(&orderbook(99.0, 101.0)), Some(100.0));
+        assert_eq!(mid_price(&OrderBook::new()), None);
+    }
+
+    #[test]
+    fn test_compute_basis() {
+        let spot = orderbook(99.0, 101.0);
+        let perp = orderbook(100.0, 102.0);
+
+        let basis = compute_basis(&spot, &perp).unwrap();
+
+        assert_eq!(basis.basis, 1.0);
+        assert_eq!(basis.basis_bps, 100.0);
+        assert_eq!(basis.annualized_basis_bps, 100.0 * 1095.0);
+
+        assert!(compute_basis(&spot, &OrderBook::new()).is_none());
+    }
+}
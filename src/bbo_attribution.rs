@@ -0,0 +1,235 @@
+use crate::book::OrderBook;
+use crate::number::decimal_to_f64;
+use std::collections::BTreeMap;
+
+// One side of the consolidated quote: the best price across the venues, the venues
+// quoting it, the venue that set it and since when (ms since epoch)
+#[derive(Debug, Clone, PartialEq)]
+pub struct InsideQuote {
+    pub price: f64,
+    pub venues: Vec<String>,
+    pub set_by: String,
+    pub since: u64,
+}
+
+// Time a venue spent at the inside and how often it set a new best price
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VenueAttribution {
+    pub bid_ms: u64,
+    pub ask_ms: u64,
+    pub bid_sets: u64,
+    pub ask_sets: u64,
+}
+
+// Tracks the consolidated best bid and offer (NBBO-style) of the venues' own books and
+// attributes the time at the inside to the venues quoting it. Venues tied at the best
+// price are all credited, so the shares of a side can add up to more than 100%. Time
+// between updates longer than max_gap_ms, e.g. while nobody is subscribed and no venue
+// is read, isn't attributed
+#[derive(Debug, Clone)]
+pub struct BboAttribution {
+    max_gap_ms: u64,
+    last_update: Option<u64>,
+    tracked_ms: u64,
+    bid: Option<InsideQuote>,
+    ask: Option<InsideQuote>,
+    venues: BTreeMap<String, VenueAttribution>,
+}
+
+// The inside of one side, given each venue's best price, keeping the setter while the
+// price holds. A new price is set by the updated venue when it quotes it
+fn next_inside(
+    previous: Option<InsideQuote>,
+    best_prices: &[(&str, f64)],
+    is_better: impl Fn(f64, f64) -> bool,
+    updated_by: &str,
+    now: u64,
+) -> Option<InsideQuote> {
+    let price = best_prices
+        .iter()
+        .map(|(_, price)| *price)
+        .reduce(|best, price| if is_better(price, best) { price } else { best })?;
+    let venues: Vec<String> = best_prices
+        .iter()
+        .filter(|(_, venue_price)| *venue_price == price)
+        .map(|(exchange, _)| exchange.to_string())
+        .collect();
+    match previous {
+        Some(previous) if previous.price == price => Some(InsideQuote { venues, ..previous }),
+        _ => {
+            let set_by = venues
+                .iter()
+                .find(|exchange| *exchange == updated_by)
+                .unwrap_or(&venues[0])
+                .clone();
+            Some(InsideQuote {
+                price,
+                venues,
+                set_by,
+                since: now,
+            })
+        }
+    }
+}
+
+impl BboAttribution {
+    pub fn new(max_gap_ms: u64) -> BboAttribution {
+        BboAttribution {
+            max_gap_ms,
+            last_update: None,
+            tracked_ms: 0,
+            bid: None,
+            ask: None,
+            venues: BTreeMap::new(),
+        }
+    }
+
+    // Credits the time since the last update to the venues at the inside
+    fn credit(&mut self, now: u64) {
+        let elapsed = match self.last_update {
+            Some(last_update) => now.saturating_sub(last_update),
+            None => 0,
+        };
+        if elapsed == 0 || elapsed > self.max_gap_ms {
+            return;
+        }
+        self.tracked_ms += elapsed;
+        for exchange in self.bid.iter().flat_map(|bid| &bid.venues) {
+            self.venues.entry(exchange.clone()).or_default().bid_ms += elapsed;
+        }
+        for exchange in self.ask.iter().flat_map(|ask| &ask.venues) {
+            self.venues.entry(exchange.clone()).or_default().ask_ms += elapsed;
+        }
+    }
+
+    // Called with the latest book of every venue after updated_by's book changed
+    pub fn update(&mut self, venues: &[(&str, &OrderBook)], updated_by: &str, now: u64) {
+        self.credit(now);
+        self.last_update = Some(now);
+
+        let best_bids: Vec<(&str, f64)> = venues
+            .iter()
+            .filter_map(|(exchange, orderbook)| {
+                Some((*exchange, decimal_to_f64(orderbook.bids.first()?.price)))
+            })
+            .collect();
+        let best_asks: Vec<(&str, f64)> = venues
+            .iter()
+            .filter_map(|(exchange, orderbook)| {
+                Some((*exchange, decimal_to_f64(orderbook.asks.first()?.price)))
+            })
+            .collect();
+        let previous_bid = self.bid.as_ref().map(|bid| bid.price);
+        let previous_ask = self.ask.as_ref().map(|ask| ask.price);
+        self.bid = next_inside(self.bid.take(), &best_bids, |a, b| a > b, updated_by, now);
+        self.ask = next_inside(self.ask.take(), &best_asks, |a, b| a < b, updated_by, now);
+
+        if let Some(bid) = self
+            .bid
+            .as_ref()
+            .filter(|bid| Some(bid.price) != previous_bid)
+        {
+            self.venues.entry(bid.set_by.clone()).or_default().bid_sets += 1;
+        }
+        if let Some(ask) = self
+            .ask
+            .as_ref()
+            .filter(|ask| Some(ask.price) != previous_ask)
+        {
+            self.venues.entry(ask.set_by.clone()).or_default().ask_sets += 1;
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<&InsideQuote> {
+        self.bid.as_ref()
+    }
+
+    pub fn best_ask(&self) -> Option<&InsideQuote> {
+        self.ask.as_ref()
+    }
+
+    // The time tracked and each venue's attribution, including the time since the last
+    // update up to now
+    pub fn report(&self, now: u64) -> (u64, BTreeMap<String, VenueAttribution>) {
+        let mut attribution = self.clone();
+        attribution.credit(now);
+        (attribution.tracked_ms, attribution.venues)
+    }
+}
+
+// Share of the tracked time, in percent
+pub fn time_share(venue_ms: u64, tracked_ms: u64) -> f64 {
+    if tracked_ms == 0 {
+        0.0
+    } else {
+        venue_ms as f64 / tracked_ms as f64 * 100.0
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PriceAmountLevel;
+    use crate::number::decimal_from_f64;
+    use rust_decimal_macros::dec;
+
+    fn book(bid: f64, ask: f64) -> OrderBook {
+        let level = |price: f64| PriceAmountLevel {
+            exchange: String::new(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: dec!(1.0),
+        };
+        OrderBook {
+            bids: vec![level(bid)],
+            asks: vec![level(ask)],
+            spread: bid - ask,
+            venues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_bbo_attribution() {
+        let mut attribution = BboAttribution::new(10_000);
+        let binance = book(100.0, 101.0);
+        let kraken = book(99.5, 101.0);
+        attribution.update(&[("binance", &binance)], "binance", 1_000);
+        attribution.update(
+            &[("binance", &binance), ("kraken", &kraken)],
+            "kraken",
+            2_000,
+        );
+        // kraken improves the bid for 3 s
+        let kraken = book(100.5, 101.0);
+        attribution.update(
+            &[("binance", &binance), ("kraken", &kraken)],
+            "kraken",
+            4_000,
+        );
+        assert_eq!(attribution.best_bid().unwrap().set_by, "kraken");
+        assert_eq!(attribution.best_bid().unwrap().since, 4_000);
+        assert_eq!(attribution.best_ask().unwrap().set_by, "binance");
+        assert_eq!(
+            attribution.best_ask().unwrap().venues,
+            vec!["binance", "kraken"]
+        );
+        // Not attributed, longer than the max gap
+        attribution.update(
+            &[("binance", &binance), ("kraken", &kraken)],
+            "binance",
+            60_000,
+        );
+
+        let (tracked_ms, venues) = attribution.report(61_000);
+        assert_eq!(tracked_ms, 4_000);
+        assert_eq!(venues["binance"].bid_ms, 3_000);
+        assert_eq!(venues["kraken"].bid_ms, 1_000);
+        assert_eq!(venues["binance"].ask_ms, 4_000);
+        assert_eq!(venues["kraken"].ask_ms, 3_000);
+        assert_eq!(
+            (venues["binance"].bid_sets, venues["kraken"].bid_sets),
+            (1, 1)
+        );
+        assert_eq!(time_share(venues["kraken"].ask_ms, tracked_ms), 75.0);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::connection_manager::connection_manager;
+use crate::connectors::bitstamp::bitstamp_connect_channels;
+use crate::connectors::AsyncSocket;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Mutex;
+
+// Splits the channels into groups of at most max_channels_per_socket, one group per socket
+pub fn plan_channels(channels: &[String], max_channels_per_socket: usize) -> Vec<Vec<String>> {
+    channels
+        .chunks(max_channels_per_socket.max(1))
+        .map(|group| group.to_vec())
+        .collect()
+}
+
+// Maps every channel to the index of the socket it is subscribed on
+pub fn channel_owners(groups: &[Vec<String>]) -> HashMap<String, usize> {
+    groups
+        .iter()
+        .enumerate()
+        .flat_map(|(index, group)| group.iter().map(move |channel| (channel.clone(), index)))
+        .collect()
+}
+
+// Bitstamp limits the channels per connection, so the subscriptions are spread over
+// several sockets and the owner of every channel is kept for resubscription. The
+// sockets connected up front are each read by the first task taking it, the later
+// tasks connect their own
+pub struct BitstampPool {
+    url: String,
+    sockets: Vec<Mutex<Option<AsyncSocket>>>,
+    owners: HashMap<String, usize>,
+}
+
+impl BitstampPool {
+    pub async fn connect(
+        url: &str,
+        channels: &[String],
+        max_channels_per_socket: usize,
+    ) -> Result<BitstampPool, Box<dyn Error>> {
+        let max_channels_per_socket =
+            connection_manager().streams_per_connection(url, max_channels_per_socket);
+        let groups = plan_channels(channels, max_channels_per_socket);
+
+        let mut sockets = Vec::new();
+        for group in &groups {
+            let socket = bitstamp_connect_channels(url, group).await?;
+            sockets.push(Mutex::new(Some(socket)));
+        }
+
+        Ok(BitstampPool {
+            url: url.to_string(),
+            sockets,
+            owners: channel_owners(&groups),
+        })
+    }
+
+    pub fn socket_count(&self) -> usize {
+        self.sockets.len()
+    }
+
+    // The socket connected up front, None once a task took it
+    pub fn take_socket(&self, index: usize) -> Option<AsyncSocket> {
+        self.sockets[index].lock().unwrap().take()
+    }
+
+    pub fn channels_of(&self, index: usize) -> Vec<String> {
+        let mut channels: Vec<String> = self
+            .owners
+            .iter()
+            .filter(|(_, owner)| **owner == index)
+            .map(|(channel, _)| channel.clone())
+            .collect();
+        channels.sort();
+        channels
+    }
+
+    // A new socket subscribed to the same channels as the socket of the index
+    pub async fn resubscribe(&self, index: usize) -> Result<AsyncSocket, Box<dyn Error>> {
+        bitstamp_connect_channels(&self.url, &self.channels_of(index)).await
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_channels() {
+        let channels: Vec<String> = ["btcusd", "ethusd", "solusd", "xrpusd", "usdtusd"]
+            .iter()
+            .map(|symbol| format!("detail_order_book_{}", symbol))
+            .collect();
+
+        let groups = plan_channels(&channels, 2);
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0], channels[..2].to_vec());
+        assert_eq!(groups[2], vec!["detail_order_book_usdtusd".to_string()]);
+
+        // A limit of zero still puts one channel on every socket
+        assert_eq!(plan_channels(&channels, 0).len(), 5);
+    }
+
+    #[test]
+    fn test_channel_owners() {
+        let groups = vec![
+            vec!["detail_order_book_btcusd".to_string()],
+            vec![
+                "detail_order_book_ethusd".to_string(),
+                "detail_order_book_usdtusd".to_string(),
+            ],
+        ];
+
+        let owners = channel_owners(&groups);
+
+        assert_eq!(owners.len(), 3);
+        assert_eq!(owners["detail_order_book_btcusd"], 0);
+        assert_eq!(owners["detail_order_book_usdtusd"], 1);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::book::{OrderBook, OrderBookSide, PriceAmountLevel};
+use crate::compaction::Compactor;
+use crate::number::decimal_to_f64;
+
+// Book maintained from a snapshot and the changed levels, for the exchanges that only
+// send the levels that changed after their snapshot
+#[derive(Debug, Clone)]
+pub(crate) struct LocalBook {
+    pub bids: OrderBookSide,
+    pub asks: OrderBookSide,
+    pub compactor: Compactor,
+}
+
+impl Default for LocalBook {
+    fn default() -> LocalBook {
+        LocalBook {
+            bids: OrderBookSide::bids(),
+            asks: OrderBookSide::asks(),
+            compactor: Compactor::default(),
+        }
+    }
+}
+
+impl LocalBook {
+    pub fn new() -> LocalBook {
+        LocalBook::default()
+    }
+
+    pub fn replace(&mut self, bids: Vec<PriceAmountLevel>, asks: Vec<PriceAmountLevel>) {
+        self.bids.replace(bids);
+        self.asks.replace(asks);
+    }
+
+    pub fn apply_updates(
+        &mut self,
+        bids: Vec<PriceAmountLevel>,
+        asks: Vec<PriceAmountLevel>,
+        exchange: &str,
+    ) {
+        self.bids.apply_updates(bids);
+        self.asks.apply_updates(asks);
+        if self.compactor.on_update(&mut self.bids, &mut self.asks) {
+            let stats = self.compactor.stats();
+            println!(
+                "Compacted {} book: {} bids and {} asks pruned in {} compactions",
+                exchange, stats.pruned_bids, stats.pruned_asks, stats.compactions
+            );
+        }
+    }
+
+    // Drops the levels beyond the depth, for exchanges that maintain the book only to
+    // the subscribed depth and don't remove the levels falling out of it
+    pub fn truncate(&mut self, depth: usize) {
+        self.bids.truncate(depth);
+        self.asks.truncate(depth);
+    }
+
+    // Empties the book, the compactor is kept so its stats cover the whole run
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+    }
+
+    pub fn orderbook(&self, depth: usize) -> OrderBook {
+        let bids = self.bids.top(depth);
+        let asks = self.asks.top(depth);
+        let spread = match (bids.first(), asks.first()) {
+            (Some(first_bid), Some(first_ask)) => decimal_to_f64(first_bid.price - first_ask.price),
+            _ => 0.0,
+        };
+        OrderBook {
+            bids,
+            asks,
+            spread,
+            venues: Vec::new(),
+        }
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number::decimal_from_f64;
+
+    #[test]
+    fn test_local_book() {
+        let level = |price: f64, amount: f64| PriceAmountLevel {
+            exchange: "kraken".to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: decimal_from_f64(amount).unwrap(),
+        };
+        let mut book = LocalBook::new();
+        book.replace(
+            vec![level(10.0, 1.0), level(9.0, 2.0)],
+            vec![level(11.0, 1.0), level(12.0, 3.0)],
+        );
+        book.apply_updates(
+            vec![level(10.0, 0.0), level(9.5, 1.5)],
+            vec![level(10.5, 0.2)],
+            "kraken",
+        );
+        book.truncate(2);
+
+        let orderbook = book.orderbook(10);
+        let prices = |levels: &[PriceAmountLevel]| {
+            levels
+                .iter()
+                .map(|level| decimal_to_f64(level.price))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(prices(&orderbook.bids), vec![9.5, 9.0]);
+        assert_eq!(prices(&orderbook.asks), vec![10.5, 11.0]);
+        assert_eq!(orderbook.spread, -1.0);
+    }
+}
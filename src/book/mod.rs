@@ -0,0 +1,101 @@
+// The book model shared by the connectors, the merge and the gRPC service, and the
+// local book of the exchanges sending the changed levels
+mod local;
+mod side;
+
+use crate::number::decimal_number;
+pub(crate) use local::LocalBook;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+pub use side::OrderBookSide;
+use std::collections::HashMap;
+
+// Prices and amounts are kept as the decimals the exchanges sent, so 0.00000001 stays
+// exact through the merge, they are only turned into doubles for the analytics and
+// the summaries (number::decimal_to_f64)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PriceAmountLevel {
+    pub exchange: String,
+    #[serde(with = "decimal_number")]
+    pub price: Decimal,
+    #[serde(with = "decimal_number")]
+    pub amount: Decimal,
+}
+
+// Latest derivative data of a venue, only set for perpetual connectors
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VenueMetadata {
+    pub exchange: String,
+    pub mark_price: Option<f64>,
+    pub funding_rate: Option<f64>,
+    pub next_funding_time: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct OrderBook {
+    pub bids: Vec<PriceAmountLevel>,
+    pub asks: Vec<PriceAmountLevel>,
+    pub spread: f64,
+    pub venues: Vec<VenueMetadata>,
+}
+
+impl OrderBook {
+    pub fn new() -> OrderBook {
+        OrderBook {
+            bids: Vec::new(),
+            asks: Vec::new(),
+            spread: 0.0,
+            venues: Vec::new(),
+        }
+    }
+}
+
+// Collapses the levels of one venue repeating a price, as Bitstamp sent during resets,
+// into one level at the place of the first with the amount of the latest. Returns the
+// number of levels dropped
+pub(crate) fn dedup_levels(levels: &mut Vec<PriceAmountLevel>) -> usize {
+    let received = levels.len();
+    let mut positions: HashMap<Decimal, usize> = HashMap::new();
+    let mut deduped: Vec<PriceAmountLevel> = Vec::with_capacity(levels.len());
+    for level in levels.drain(..) {
+        match positions.get(&level.price) {
+            Some(&position) => deduped[position].amount = level.amount,
+            None => {
+                positions.insert(level.price, deduped.len());
+                deduped.push(level);
+            }
+        }
+    }
+    *levels = deduped;
+    received - levels.len()
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_levels() {
+        let level = |price: &str, amount: &str| PriceAmountLevel {
+            exchange: "bitstamp".to_string(),
+            price: price.parse().unwrap(),
+            amount: amount.parse().unwrap(),
+        };
+        // 10 and 10.0 are the same price
+        let mut levels = vec![level("10", "1"), level("9.5", "2"), level("10.0", "0.4")];
+        assert_eq!(dedup_levels(&mut levels), 1);
+        let levels: Vec<(String, String)> = levels
+            .iter()
+            .map(|l| (l.price.to_string(), l.amount.to_string()))
+            .collect();
+        assert_eq!(
+            levels,
+            vec![("10".into(), "0.4".into()), ("9.5".into(), "2".into())]
+        );
+
+        let mut levels = vec![level("10", "1"), level("9.5", "2")];
+        assert_eq!(dedup_levels(&mut levels), 0);
+        assert_eq!(levels.len(), 2);
+    }
+}
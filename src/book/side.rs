@@ -0,0 +1,160 @@
+use crate::book::PriceAmountLevel;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+// One side of a book maintained from level updates, keyed by price so an update is an
+// O(log n) insert or remove and the best levels are read without sorting the side
+#[derive(Debug, Clone)]
+pub struct OrderBookSide {
+    levels: BTreeMap<Decimal, PriceAmountLevel>,
+    // Asks are best at the lowest price, bids at the highest
+    ascending: bool,
+}
+
+impl OrderBookSide {
+    pub fn bids() -> OrderBookSide {
+        OrderBookSide {
+            levels: BTreeMap::new(),
+            ascending: false,
+        }
+    }
+
+    pub fn asks() -> OrderBookSide {
+        OrderBookSide {
+            levels: BTreeMap::new(),
+            ascending: true,
+        }
+    }
+
+    // Replaces the side with a snapshot's levels, the last one of a repeated price wins
+    pub fn replace(&mut self, levels: Vec<PriceAmountLevel>) {
+        self.levels.clear();
+        for level in levels {
+            self.levels.insert(level.price, level);
+        }
+    }
+
+    // An amount of zero removes the level at that price
+    pub fn apply(&mut self, update: PriceAmountLevel) {
+        if update.amount.is_zero() {
+            self.levels.remove(&update.price);
+        } else {
+            self.levels.insert(update.price, update);
+        }
+    }
+
+    pub fn apply_updates(&mut self, updates: Vec<PriceAmountLevel>) {
+        for update in updates {
+            self.apply(update);
+        }
+    }
+
+    // The levels best first
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &PriceAmountLevel> + '_> {
+        if self.ascending {
+            Box::new(self.levels.values())
+        } else {
+            Box::new(self.levels.values().rev())
+        }
+    }
+
+    pub fn best(&self) -> Option<&PriceAmountLevel> {
+        if self.ascending {
+            self.levels.values().next()
+        } else {
+            self.levels.values().next_back()
+        }
+    }
+
+    pub fn worst(&self) -> Option<&PriceAmountLevel> {
+        if self.ascending {
+            self.levels.values().next_back()
+        } else {
+            self.levels.values().next()
+        }
+    }
+
+    // The best depth levels, best first
+    pub fn top(&self, depth: usize) -> Vec<PriceAmountLevel> {
+        self.iter().take(depth).cloned().collect()
+    }
+
+    // Drops the levels beyond the depth
+    pub fn truncate(&mut self, depth: usize) {
+        while self.levels.len() > depth {
+            if self.ascending {
+                self.levels.pop_last();
+            } else {
+                self.levels.pop_first();
+            }
+        }
+    }
+
+    // Keeps the levels passing the predicate, returns how many were removed
+    pub fn retain(&mut self, mut keep: impl FnMut(&PriceAmountLevel) -> bool) -> usize {
+        let before = self.levels.len();
+        self.levels.retain(|_, level| keep(level));
+        before - self.levels.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.levels.clear();
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number::{decimal_from_f64, decimal_to_f64};
+    use rust_decimal_macros::dec;
+
+    fn level(price: f64, amount: f64) -> PriceAmountLevel {
+        PriceAmountLevel {
+            exchange: "bybit".to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: decimal_from_f64(amount).unwrap(),
+        }
+    }
+
+    fn prices(levels: &[PriceAmountLevel]) -> Vec<f64> {
+        levels
+            .iter()
+            .map(|level| decimal_to_f64(level.price))
+            .collect()
+    }
+
+    #[test]
+    fn test_order_book_side() {
+        let mut bids = OrderBookSide::bids();
+        bids.replace(vec![level(9.0, 2.0), level(10.0, 1.0), level(8.0, 1.0)]);
+        bids.apply_updates(vec![level(10.0, 0.0), level(9.5, 1.5), level(9.0, 3.0)]);
+        assert_eq!(prices(&bids.top(10)), vec![9.5, 9.0, 8.0]);
+        assert_eq!(bids.best().unwrap().price, dec!(9.5));
+        assert_eq!(bids.top(2)[1].amount, dec!(3));
+        // Removing a level that isn't there is a no-op
+        bids.apply(level(7.0, 0.0));
+        assert_eq!(bids.len(), 3);
+
+        let mut asks = OrderBookSide::asks();
+        asks.replace(vec![level(12.0, 1.0), level(11.0, 1.0), level(13.0, 1.0)]);
+        assert_eq!(prices(&asks.top(2)), vec![11.0, 12.0]);
+
+        // The worst levels go
+        bids.truncate(2);
+        asks.truncate(2);
+        assert_eq!(prices(&bids.top(10)), vec![9.5, 9.0]);
+        assert_eq!(prices(&asks.top(10)), vec![11.0, 12.0]);
+
+        assert_eq!(asks.retain(|level| level.price < dec!(12)), 1);
+        assert_eq!(prices(&asks.top(10)), vec![11.0]);
+    }
+}
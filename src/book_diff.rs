@@ -0,0 +1,316 @@
+use crate::book::PriceAmountLevel;
+use crate::recording::{read_recording, RecordedBook};
+use rust_decimal::Decimal;
+use std::error::Error;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelChange {
+    Appeared,
+    Disappeared,
+    Resized,
+    Unchanged,
+}
+
+impl LevelChange {
+    fn marker(&self) -> &'static str {
+        match self {
+            LevelChange::Appeared => "+",
+            LevelChange::Disappeared => "-",
+            LevelChange::Resized => "~",
+            LevelChange::Unchanged => " ",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            LevelChange::Appeared => "appeared",
+            LevelChange::Disappeared => "disappeared",
+            LevelChange::Resized => "resized",
+            LevelChange::Unchanged => "",
+        }
+    }
+
+    // ANSI color of the terminal line, and the background of the HTML row
+    fn ansi_color(&self) -> &'static str {
+        match self {
+            LevelChange::Appeared => "\x1b[32m",
+            LevelChange::Disappeared => "\x1b[31m",
+            LevelChange::Resized => "\x1b[33m",
+            LevelChange::Unchanged => "",
+        }
+    }
+
+    fn html_color(&self) -> &'static str {
+        match self {
+            LevelChange::Appeared => "#d4f7d4",
+            LevelChange::Disappeared => "#f7d4d4",
+            LevelChange::Resized => "#f7f0c8",
+            LevelChange::Unchanged => "#ffffff",
+        }
+    }
+}
+
+// A level of an exchange in either book, with its amount at both moments, 0 where the
+// level isn't in the book
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelDiff {
+    pub exchange: String,
+    pub price: Decimal,
+    pub before: Decimal,
+    pub after: Decimal,
+    pub change: LevelChange,
+}
+
+// The levels of two recorded books, best first on both sides
+#[derive(Debug, Clone, Default)]
+pub struct BookDiff {
+    pub at: u64,
+    pub vs: u64,
+    pub bids: Vec<LevelDiff>,
+    pub asks: Vec<LevelDiff>,
+}
+
+// Levels are matched by exchange and price, the merged book can hold the same price for
+// several exchanges
+fn diff_levels(
+    before: &[PriceAmountLevel],
+    after: &[PriceAmountLevel],
+    descending: bool,
+) -> Vec<LevelDiff> {
+    let find = |levels: &[PriceAmountLevel], level: &PriceAmountLevel| {
+        levels
+            .iter()
+            .find(|other| other.exchange == level.exchange && other.price == level.price)
+            .map(|other| other.amount)
+    };
+    let mut diffs: Vec<LevelDiff> = before
+        .iter()
+        .map(|level| {
+            let after_amount = find(after, level);
+            LevelDiff {
+                exchange: level.exchange.clone(),
+                price: level.price,
+                before: level.amount,
+                after: after_amount.unwrap_or(Decimal::ZERO),
+                change: match after_amount {
+                    None => LevelChange::Disappeared,
+                    Some(amount) if amount != level.amount => LevelChange::Resized,
+                    Some(_) => LevelChange::Unchanged,
+                },
+            }
+        })
+        .collect();
+    diffs.extend(
+        after
+            .iter()
+            .filter(|level| find(before, level).is_none())
+            .map(|level| LevelDiff {
+                exchange: level.exchange.clone(),
+                price: level.price,
+                before: Decimal::ZERO,
+                after: level.amount,
+                change: LevelChange::Appeared,
+            }),
+    );
+    diffs.sort_by(|a, b| {
+        let order = a.price.cmp(&b.price);
+        if descending {
+            order.reverse()
+        } else {
+            order
+        }
+    });
+    diffs
+}
+
+// The latest book recorded at or before the timestamp
+pub fn book_at(books: &[RecordedBook], timestamp: u64) -> Option<&RecordedBook> {
+    books
+        .iter()
+        .filter(|book| book.timestamp <= timestamp)
+        .max_by_key(|book| book.timestamp)
+}
+
+pub fn diff_books(before: &RecordedBook, after: &RecordedBook) -> BookDiff {
+    BookDiff {
+        at: before.timestamp,
+        vs: after.timestamp,
+        bids: diff_levels(&before.orderbook.bids, &after.orderbook.bids, true),
+        asks: diff_levels(&before.orderbook.asks, &after.orderbook.asks, false),
+    }
+}
+
+impl BookDiff {
+    // Volume added (positive) or withdrawn (negative) on each side, as (bids, asks)
+    pub fn net_change(&self) -> (Decimal, Decimal) {
+        let net = |levels: &[LevelDiff]| levels.iter().map(|l| l.after - l.before).sum();
+        (net(&self.bids), net(&self.asks))
+    }
+
+    // Asks from the highest down to the bids, like a ladder, with ANSI colors if set
+    pub fn to_terminal(&self, color: bool) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "Book at {} vs {} (ms since epoch)", self.at, self.vs);
+        let _ = writeln!(
+            text,
+            "  {:<6} {:<12} {:<14} {:<14} {:<14}",
+            "Side", "Exchange", "Price", "Before", "After"
+        );
+        let asks = self.asks.iter().rev().map(|level| ("ask", level));
+        let bids = self.bids.iter().map(|level| ("bid", level));
+        for (side, level) in asks.chain(bids) {
+            let (start, end) = match (color, level.change) {
+                (true, change) if change != LevelChange::Unchanged => {
+                    (change.ansi_color(), "\x1b[0m")
+                }
+                _ => ("", ""),
+            };
+            let _ = writeln!(
+                text,
+                "{}{} {:<6} {:<12} {:<14} {:<14} {:<14} {}{}",
+                start,
+                level.change.marker(),
+                side,
+                level.exchange,
+                level.price,
+                level.before,
+                level.after,
+                level.change.label(),
+                end
+            );
+        }
+        let (bids, asks) = self.net_change();
+        let _ = writeln!(text, "Net change: bids {:+}, asks {:+}", bids, asks);
+        text
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Orderbook diff</title>\n\
+             <style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}\
+             td,th{border:1px solid #ccc;padding:4px 8px;text-align:right}</style>\n</head>\n<body>\n",
+        );
+        let _ = writeln!(html, "<h1>Orderbook diff</h1>");
+        let _ = writeln!(
+            html,
+            "<p>Book at {} vs {} (ms since epoch)</p>",
+            self.at, self.vs
+        );
+        let _ = writeln!(html, "<table>");
+        let _ = writeln!(
+            html,
+            "<tr><th>Side</th><th>Exchange</th><th>Price</th><th>Before</th><th>After</th><th>Change</th></tr>"
+        );
+        let asks = self.asks.iter().rev().map(|level| ("ask", level));
+        let bids = self.bids.iter().map(|level| ("bid", level));
+        for (side, level) in asks.chain(bids) {
+            let _ = writeln!(
+                html,
+                "<tr style=\"background:{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                level.change.html_color(),
+                side,
+                level.exchange,
+                level.price,
+                level.before,
+                level.after,
+                level.change.label()
+            );
+        }
+        let (bids, asks) = self.net_change();
+        let _ = writeln!(
+            html,
+            "</table>\n<p>Net change: bids {:+}, asks {:+}</p>\n</body>\n</html>",
+            bids, asks
+        );
+        html
+    }
+}
+
+// Diffs the books of a recording at two moments, each the latest book recorded at or
+// before the moment, and writes the diff as HTML if out is set
+pub fn generate_diff(
+    file: &str,
+    at: u64,
+    vs: u64,
+    out: Option<&str>,
+) -> Result<BookDiff, Box<dyn Error>> {
+    let books = read_recording(file)?;
+    let before = book_at(&books, at).ok_or_else(|| format!("No book recorded by {}", at))?;
+    let after = book_at(&books, vs).ok_or_else(|| format!("No book recorded by {}", vs))?;
+    let diff = diff_books(before, after);
+    if let Some(out) = out {
+        std::fs::write(out, diff.to_html())?;
+    }
+    Ok(diff)
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::OrderBook;
+    use crate::number::decimal_from_f64;
+    use rust_decimal_macros::dec;
+
+    fn level(exchange: &str, price: f64, amount: f64) -> PriceAmountLevel {
+        PriceAmountLevel {
+            exchange: exchange.to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: decimal_from_f64(amount).unwrap(),
+        }
+    }
+
+    fn recorded(timestamp: u64, bids: Vec<PriceAmountLevel>) -> RecordedBook {
+        RecordedBook {
+            timestamp,
+            orderbook: OrderBook {
+                bids,
+                asks: vec![level("binance", 101.0, 1.0)],
+                spread: 0.0,
+                venues: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_diff_books() {
+        let books = [
+            recorded(
+                1_000,
+                vec![level("binance", 100.0, 2.0), level("bitstamp", 100.0, 1.0)],
+            ),
+            recorded(
+                2_000,
+                vec![level("binance", 100.0, 0.5), level("binance", 99.0, 3.0)],
+            ),
+        ];
+        assert!(book_at(&books, 999).is_none());
+        let diff = diff_books(
+            book_at(&books, 1_500).unwrap(),
+            book_at(&books, 2_000).unwrap(),
+        );
+
+        let changes: Vec<(&str, Decimal, LevelChange)> = diff
+            .bids
+            .iter()
+            .map(|l| (l.exchange.as_str(), l.price, l.change))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("binance", dec!(100), LevelChange::Resized),
+                ("bitstamp", dec!(100), LevelChange::Disappeared),
+                ("binance", dec!(99), LevelChange::Appeared),
+            ]
+        );
+        assert_eq!(diff.asks[0].change, LevelChange::Unchanged);
+        assert_eq!(diff.net_change(), (dec!(0.5), dec!(0)));
+
+        let text = diff.to_terminal(false);
+        assert!(text.contains("- bid    bitstamp"));
+        assert!(!text.contains('\x1b'));
+        assert!(diff.to_terminal(true).contains("\x1b[31m"));
+        assert!(diff.to_html().contains("<td>disappeared</td>"));
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Bounds of the slowest venue's cadence, so a burst of every venue doesn't merge on
+// every update and a stalled venue doesn't hold the merges of the others back
+pub const MIN_MERGE_INTERVAL: Duration = Duration::from_millis(50);
+pub const MAX_MERGE_INTERVAL: Duration = Duration::from_secs(5);
+// Weight of the latest interval between two updates of a venue
+const SMOOTHING: f64 = 0.2;
+
+// The update rate of each venue of a subscription, for merges on the slowest venue's
+// cadence (MergeCadence::Slowest)
+#[derive(Debug, Default)]
+pub struct CadenceTracker {
+    // The latest update of each venue and its smoothed interval between updates, in ms
+    venues: HashMap<String, (u64, Option<f64>)>,
+}
+
+impl CadenceTracker {
+    pub fn new() -> CadenceTracker {
+        CadenceTracker::default()
+    }
+
+    pub fn on_update(&mut self, exchange: &str, now: u64) {
+        let max_interval = MAX_MERGE_INTERVAL.as_millis() as f64;
+        match self.venues.get_mut(exchange) {
+            Some((last_update, interval)) => {
+                // A reconnect's gap counts as a slow update, not as the venue's cadence
+                let latest = (now.saturating_sub(*last_update) as f64).min(max_interval);
+                *interval = Some(interval.map_or(latest, |interval| {
+                    interval + SMOOTHING * (latest - interval)
+                }));
+                *last_update = now;
+            }
+            None => {
+                self.venues.insert(exchange.to_string(), (now, None));
+            }
+        }
+    }
+
+    // The interval of the slowest venue, None until a venue updated twice
+    pub fn slowest_interval(&self) -> Option<Duration> {
+        let slowest = self
+            .venues
+            .values()
+            .filter_map(|(_, interval)| *interval)
+            .reduce(f64::max)?;
+        let slowest = Duration::from_millis(slowest.round() as u64);
+        Some(slowest.clamp(MIN_MERGE_INTERVAL, MAX_MERGE_INTERVAL))
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slowest_interval() {
+        let mut tracker = CadenceTracker::new();
+        tracker.on_update("binance", 0);
+        tracker.on_update("bitstamp", 0);
+        assert_eq!(tracker.slowest_interval(), None);
+
+        // binance every 100 ms, bitstamp every second
+        for tick in 1..=10 {
+            tracker.on_update("binance", tick * 100);
+        }
+        assert_eq!(tracker.slowest_interval(), Some(Duration::from_millis(100)));
+        tracker.on_update("bitstamp", 1_000);
+        assert_eq!(tracker.slowest_interval(), Some(Duration::from_secs(1)));
+
+        // Smoothed, one faster update doesn't halve it
+        tracker.on_update("bitstamp", 1_500);
+        assert_eq!(tracker.slowest_interval(), Some(Duration::from_millis(900)));
+
+        // A stalled venue is capped
+        tracker.on_update("bitstamp", 60_000);
+        assert!(tracker.slowest_interval().unwrap() <= MAX_MERGE_INTERVAL);
+    }
+}
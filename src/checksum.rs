@@ -0,0 +1,46 @@
+use crc32fast::Hasher;
+
+// Levels of each side covered by the checksum
+pub const CHECKSUM_DEPTH: usize = 10;
+
+// CRC32 of the top CHECKSUM_DEPTH (price, amount) pairs of the bids, then of the asks,
+// over the little endian IEEE 754 bytes, so any client can compute it from the decoded
+// levels without depending on how floats are formatted
+pub fn book_checksum(
+    bids: impl IntoIterator<Item = (f64, f64)>,
+    asks: impl IntoIterator<Item = (f64, f64)>,
+) -> u32 {
+    let mut hasher = Hasher::new();
+    let bids = bids.into_iter().take(CHECKSUM_DEPTH);
+    let asks = asks.into_iter().take(CHECKSUM_DEPTH);
+    for (price, amount) in bids.chain(asks) {
+        hasher.update(&price.to_le_bytes());
+        hasher.update(&amount.to_le_bytes());
+    }
+    hasher.finalize()
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_book_checksum() {
+        let bids = [(100.0, 1.0), (99.5, 2.0)];
+        let asks = [(100.5, 1.5)];
+        let checksum = book_checksum(bids, asks);
+
+        assert_eq!(book_checksum(bids, asks), checksum);
+        assert_ne!(book_checksum([(100.0, 1.0), (99.5, 2.5)], asks), checksum);
+        // The sides aren't interchangeable
+        assert_ne!(book_checksum(asks, bids), checksum);
+        assert_eq!(book_checksum([], []), 0);
+
+        // Levels beyond the checksum depth don't change it
+        let deep_bids: Vec<(f64, f64)> = (0..20).map(|i| (100.0 - i as f64, 1.0)).collect();
+        let mut changed = deep_bids.clone();
+        changed[CHECKSUM_DEPTH].1 = 5.0;
+        assert_eq!(book_checksum(deep_bids, asks), book_checksum(changed, asks));
+    }
+}
@@ -1,22 +1,210 @@
 pub mod orderbook {
     tonic::include_proto!("orderbook");
 }
+use ::orderbook::book::PriceAmountLevel;
+use ::orderbook::client_cache::{CachedBook, ClientCache};
+use ::orderbook::config::preset_from_args;
+use ::orderbook::failover::ServerEndpoints;
+use ::orderbook::grouping::{group_levels, next_grouping_step};
+use ::orderbook::grpc::check_summary_schema;
+use ::orderbook::number::{decimal_from_f64, decimal_to_f64};
+use ::orderbook::sla::{parse_duration, SlaConditions, SlaMonitor};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
 use orderbook::orderbook_aggregator_client::OrderbookAggregatorClient;
-use orderbook::{Empty, Summary};
-use tonic::Request;
+use orderbook::{
+    AuditQuery, Basis, BasisRequest, EmissionTrigger, Empty, FeedStatus, IndexPrice, IndexRequest,
+    Level, ListSymbolsRequest, PriceEncoding, SnapshotRequest, SummariesSinceRequest, Summary,
+    SummaryRequest, UsageQuery,
+};
+use std::io::{stdout, Write};
+use std::time::{Duration, Instant};
+use tokio::spawn;
+use tokio::sync::mpsc::{self, Receiver};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Streaming};
+
+// Catches levels corrupted in transport or decoding
+fn verify_checksum(summary: &Summary) -> bool {
+    let checksum = ::orderbook::checksum::book_checksum(
+        summary.bids.iter().map(|level| (level.price, level.amount)),
+        summary.asks.iter().map(|level| (level.price, level.amount)),
+    );
+    checksum == summary.checksum
+}
+
+// Parses the decimal strings of a summary sent with PRICE_ENCODING_STRING back into the
+// doubles, a server not knowing the encoding leaves them empty and sends the doubles
+fn decode_decimal_prices(mut summary: Summary) -> Summary {
+    let split_levels = summary
+        .split_books
+        .iter_mut()
+        .flat_map(|book| book.bids.iter_mut().chain(book.asks.iter_mut()));
+    for level in summary
+        .bids
+        .iter_mut()
+        .chain(summary.asks.iter_mut())
+        .chain(split_levels)
+    {
+        if let (Ok(price), Ok(amount)) = (level.price_decimal.parse(), level.amount_decimal.parse())
+        {
+            level.price = price;
+            level.amount = amount;
+        }
+    }
+    if let Ok(spread) = summary.spread_decimal.parse() {
+        summary.spread = spread;
+    }
+    summary
+}
+
+// None while every venue is updating
+fn feed_status_line(summary: &Summary) -> Option<String> {
+    let status = match summary.status() {
+        FeedStatus::Live => return None,
+        FeedStatus::Degraded => "DEGRADED",
+        FeedStatus::Down => "DOWN, the book below is stale",
+    };
+    let mut line = format!(
+        "Feed status: {} (stale: {})",
+        status,
+        summary.stale_venues.join(", ")
+    );
+    if !summary.empty_book_venues.is_empty() {
+        line.push_str(&format!(
+            " (empty book: {})",
+            summary.empty_book_venues.join(", ")
+        ));
+    }
+    Some(line)
+}
 
 fn print_summary(summary: &Summary) {
+    if !verify_checksum(summary) {
+        eprintln!("Checksum mismatch, the levels below may be corrupted");
+    }
+    if let Some(status) = feed_status_line(summary) {
+        println!("{}", status);
+    }
+    if !summary.bbo_only_venues.is_empty() {
+        println!(
+            "Only the best bid and ask are fresh for {}",
+            summary.bbo_only_venues.join(", ")
+        );
+    }
     println!("Spread: {:#?}", summary.spread);
+    if let (Some(weighted_mid), Some(fair_value)) = (summary.weighted_mid, summary.fair_value) {
+        println!("Weighted mid: {} Fair value: {}", weighted_mid, fair_value);
+    }
+    for (name, price) in &summary.index_prices {
+        println!("Index {}: {}", name, price);
+    }
+    if let Some(toxicity) = summary.toxicity {
+        println!("Toxicity: {:.3}", toxicity);
+    }
+    if let Some(reference) = &summary.reference_improvement {
+        println!(
+            "vs {}: bid +{:.2} bps ask +{:.2} bps, depth within {} bps +{} bid +{} ask",
+            reference.venue,
+            reference.bid_improvement_bps,
+            reference.ask_improvement_bps,
+            reference.band_bps,
+            reference.extra_bid_depth,
+            reference.extra_ask_depth
+        );
+    }
+    for venue in &summary.venues {
+        println!(
+            "{} mark price: {:?} funding rate: {:?} next funding time: {:?}",
+            venue.exchange, venue.mark_price, venue.funding_rate, venue.next_funding_time
+        );
+    }
+    for (name, value) in &summary.signals {
+        println!("Signal {}: {}", name, value);
+    }
+    for (exchange, timestamp) in &summary.venue_timestamps {
+        println!(
+            "{} as of {} ({} ms before the book)",
+            exchange,
+            timestamp,
+            summary.timestamp.saturating_sub(*timestamp)
+        );
+    }
+    for line in ladder_lines(&summary.bids, &summary.asks) {
+        println!("{}", line);
+    }
+    for book in &summary.split_books {
+        println!(
+            "{} in {}, not merged with the book above:",
+            book.exchange,
+            book.quote.to_uppercase()
+        );
+        for line in ladder_lines(&book.bids, &book.asks) {
+            println!("{}", line);
+        }
+    }
+
+    println!();
+}
+
+fn to_cached_levels(levels: &[Level]) -> Vec<(String, f64, f64)> {
+    levels
+        .iter()
+        .map(|level| (level.exchange.clone(), level.price, level.amount))
+        .collect()
+}
+
+fn from_cached_levels(levels: &[(String, f64, f64)]) -> Vec<Level> {
+    levels
+        .iter()
+        .map(|(exchange, price, amount)| Level {
+            exchange: exchange.clone(),
+            price: *price,
+            amount: *amount,
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn cached_book(summary: &Summary) -> CachedBook {
+    CachedBook {
+        sequence: summary.sequence,
+        timestamp: summary.timestamp,
+        spread: summary.spread,
+        bids: to_cached_levels(&summary.bids),
+        asks: to_cached_levels(&summary.asks),
+    }
+}
+
+fn print_stale_book(symbol: &str, book: &CachedBook) {
     println!(
+        "Last known book of {} as of {} (stale, from the cache)",
+        symbol, book.timestamp
+    );
+    println!("Spread: {:#?}", book.spread);
+    let lines = ladder_lines(
+        &from_cached_levels(&book.bids),
+        &from_cached_levels(&book.asks),
+    );
+    for line in lines {
+        println!("{}", line);
+    }
+    println!();
+}
+
+fn ladder_lines(bids: &[Level], asks: &[Level]) -> Vec<String> {
+    let mut lines = vec![format!(
         "{:<6} {:<12} {:<16} {:<12} | {:<12} {:<16} {:<12}",
         "Depth", "BidExchange", "BidVolume", "BidPrice", "AskPrice", "AskVolume", "AskExchange"
-    );
+    )];
 
-    let max_levels = summary.bids.len().max(summary.asks.len());
+    let max_levels = bids.len().max(asks.len());
 
     for i in 0..max_levels {
-        let bid = summary.bids.get(i);
-        let ask = summary.asks.get(i);
+        let bid = bids.get(i);
+        let ask = asks.get(i);
 
         let bid_exchange = bid.map(|b| b.exchange.clone()).unwrap_or("".to_string());
         let bid_amount = bid.map(|b| b.amount.to_string()).unwrap_or("".to_string());
@@ -26,7 +214,7 @@ fn print_summary(summary: &Summary) {
         let ask_amount = ask.map(|a| a.amount.to_string()).unwrap_or("".to_string());
         let ask_exchange = ask.map(|a| a.exchange.clone()).unwrap_or("".to_string());
 
-        println!(
+        lines.push(format!(
             "{:<6} {:<12} {:<16} {:<12} | {:<12} {:<16} {:<12}",
             format!("[{}]", i + 1),
             bid_exchange,
@@ -35,24 +223,795 @@ fn print_summary(summary: &Summary) {
             ask_price,
             ask_amount,
             ask_exchange
+        ));
+    }
+    lines
+}
+
+// Merged ladder grouped into price buckets of step on the client, so the grouping can
+// change without resubscribing
+fn grouped_levels(levels: &[Level], step: f64, is_bid: bool) -> Vec<Level> {
+    let levels: Vec<PriceAmountLevel> = levels
+        .iter()
+        .filter_map(|level| {
+            Some(PriceAmountLevel {
+                exchange: level.exchange.clone(),
+                price: decimal_from_f64(level.price)?,
+                amount: decimal_from_f64(level.amount)?,
+            })
+        })
+        .collect();
+    group_levels(&levels, step, is_bid)
+        .into_iter()
+        .map(|level| Level {
+            exchange: level.exchange,
+            price: decimal_to_f64(level.price),
+            amount: decimal_to_f64(level.amount),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn render_tui(summary: &Summary, step: f64) -> std::io::Result<()> {
+    let mut stdout = stdout();
+    queue!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
+    let grouping = if step > 0.0 {
+        step.to_string()
+    } else {
+        "none".to_string()
+    };
+    // Raw mode doesn't translate \n, every line returns the cursor itself
+    write!(
+        stdout,
+        "Grouping: {}   [+/-] coarser/finer  [0] ungrouped  [q] quit\r\n",
+        grouping
+    )?;
+    if let Some(status) = feed_status_line(summary) {
+        write!(stdout, "{}\r\n", status)?;
+    }
+    write!(stdout, "Spread: {}\r\n", summary.spread)?;
+    let bids = grouped_levels(&summary.bids, step, true);
+    let asks = grouped_levels(&summary.asks, step, false);
+    for line in ladder_lines(&bids, &asks) {
+        write!(stdout, "{}\r\n", line)?;
+    }
+    stdout.flush()
+}
+
+// Redraws the latest summary on every update and key press, the keys only change the
+// grouping of the ladder on the client
+async fn tui_loop(mut summaries: Receiver<Summary>) -> Result<(), Box<dyn std::error::Error>> {
+    // crossterm's reads block, they are forwarded from their own thread
+    let (key_sender, mut keys) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if let Event::Key(key) = event {
+                if key.kind == KeyEventKind::Press && key_sender.send(key).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut step = 0.0;
+    let mut latest: Option<Summary> = None;
+    loop {
+        tokio::select! {
+            summary = summaries.recv() => match summary {
+                Some(summary) => latest = Some(summary),
+                None => return Ok(()),
+            },
+            Some(key) = keys.recv() => match key.code {
+                KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Right => {
+                    step = next_grouping_step(step, true)
+                }
+                KeyCode::Char('-') | KeyCode::Left => step = next_grouping_step(step, false),
+                KeyCode::Char('0') => step = 0.0,
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                // Raw mode turns ctrl-c into a key press
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(())
+                }
+                _ => continue,
+            },
+        }
+        if let Some(summary) = &latest {
+            render_tui(summary, step)?;
+        }
+    }
+}
+
+async fn run_tui(summaries: Receiver<Summary>) -> Result<(), Box<dyn std::error::Error>> {
+    terminal::enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen, Hide)?;
+    let result = tui_loop(summaries).await;
+    // The terminal is restored even if the stream failed
+    execute!(stdout(), LeaveAlternateScreen, Show)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+// Failover settings of the summary stream
+struct FailoverOptions {
+    // A server without a summary for this long is considered down
+    liveness_timeout: Duration,
+    // How often a preferred server is probed while failed over
+    fail_back_interval: Duration,
+}
+
+async fn connect_endpoint(
+    addr: &str,
+    timeout: Duration,
+) -> Result<OrderbookAggregatorClient<Channel>, tonic::transport::Error> {
+    let channel = Endpoint::from_shared(addr.to_string())?
+        .connect_timeout(timeout)
+        .connect()
+        .await?;
+    Ok(OrderbookAggregatorClient::new(channel))
+}
+
+// Connects to the first server that accepts the connection, for the one-shot modes
+async fn connect_any(
+    endpoints: &ServerEndpoints,
+    timeout: Duration,
+) -> Result<OrderbookAggregatorClient<Channel>, Box<dyn std::error::Error>> {
+    let mut last_err: Box<dyn std::error::Error> = "No server address".into();
+    for addr in endpoints.addrs() {
+        match connect_endpoint(addr, timeout).await {
+            Ok(client) => return Ok(client),
+            Err(err) => {
+                eprintln!("Failed to connect to {}: {}", addr, err);
+                last_err = err.into();
+            }
+        }
+    }
+    Err(last_err)
+}
+
+// The health check of a server: it is connected and sends a summary within the
+// liveness timeout, of a schema the client reads. Returns the stream and its first
+// summary
+async fn open_summary_stream(
+    addr: &str,
+    request: &SummaryRequest,
+    api_key: &Option<String>,
+    liveness_timeout: Duration,
+) -> Result<(Streaming<Summary>, Summary), String> {
+    let mut client = connect_endpoint(addr, liveness_timeout)
+        .await
+        .map_err(|err| err.to_string())?;
+    let opened = async {
+        let mut stream = client
+            .book_summary(new_request(request.clone(), api_key))
+            .await?
+            .into_inner();
+        Ok::<_, tonic::Status>((stream.message().await?, stream))
+    };
+    match tokio::time::timeout(liveness_timeout, opened).await {
+        Ok(Ok((Some(summary), stream))) => {
+            check_summary_schema(summary.schema_version)?;
+            Ok((stream, decode_decimal_prices(summary)))
+        }
+        Ok(Ok((None, _))) => Err("the stream ended".to_string()),
+        Ok(Err(status)) => Err(status.to_string()),
+        Err(_) => Err(format!(
+            "no summary within {} s",
+            liveness_timeout.as_secs()
+        )),
+    }
+}
+
+// Asks the server for a fresh summary on the stream, the summary failing its checksum
+// is replaced by the next one without waiting for the emission trigger
+async fn request_snapshot(
+    addr: &str,
+    subscription_id: u64,
+    api_key: &Option<String>,
+    timeout: Duration,
+) -> Result<(), String> {
+    let mut client = connect_endpoint(addr, timeout)
+        .await
+        .map_err(|err| err.to_string())?;
+    client
+        .request_snapshot(new_request(SnapshotRequest { subscription_id }, api_key))
+        .await
+        .map_err(|status| status.to_string())?;
+    Ok(())
+}
+
+// Streams the summaries of the current server into summaries, failing over to the next
+// server when the stream errors, ends or goes quiet, and failing back to a preferred
+// server once it passes the health check again
+async fn stream_with_failover(
+    mut endpoints: ServerEndpoints,
+    request: SummaryRequest,
+    api_key: Option<String>,
+    options: FailoverOptions,
+    summaries: mpsc::Sender<Summary>,
+) {
+    loop {
+        let index = endpoints.next_endpoint(Instant::now());
+        tokio::time::sleep(endpoints.retry_delay(index, Instant::now())).await;
+        let mut addr = endpoints.addr(index).to_string();
+        let (mut stream, first_summary) =
+            match open_summary_stream(&addr, &request, &api_key, options.liveness_timeout).await {
+                Ok(opened) => opened,
+                Err(err) => {
+                    eprintln!("Server {} is unhealthy: {}", addr, err);
+                    endpoints.record_failure(index, Instant::now());
+                    continue;
+                }
+            };
+        endpoints.record_success(index);
+        eprintln!("Streaming summaries from {}", addr);
+        if summaries.send(first_summary).await.is_err() {
+            return;
+        }
+
+        let mut last_summary = Instant::now();
+        let mut fail_back_check = tokio::time::interval_at(
+            tokio::time::Instant::now() + options.fail_back_interval,
+            options.fail_back_interval,
         );
+        loop {
+            let deadline = tokio::time::Instant::from_std(last_summary + options.liveness_timeout);
+            tokio::select! {
+                message = tokio::time::timeout_at(deadline, stream.message()) => {
+                    let summary = match message {
+                        Ok(Ok(Some(summary))) => decode_decimal_prices(summary),
+                        Ok(Ok(None)) => {
+                            eprintln!("Server {} ended the stream", addr);
+                            break;
+                        }
+                        Ok(Err(status)) => {
+                            eprintln!("Server {} failed: {}", addr, status);
+                            break;
+                        }
+                        Err(_) => {
+                            eprintln!(
+                                "No summary from {} for {} s",
+                                addr,
+                                options.liveness_timeout.as_secs()
+                            );
+                            break;
+                        }
+                    };
+                    last_summary = Instant::now();
+                    // A corrupted snapshot isn't requested again, the next update replaces it
+                    if !verify_checksum(&summary) && !summary.snapshot {
+                        let subscription_id = summary.subscription_id;
+                        let timeout = options.liveness_timeout;
+                        if let Err(err) =
+                            request_snapshot(&addr, subscription_id, &api_key, timeout).await
+                        {
+                            eprintln!("Snapshot request to {} failed: {}", addr, err);
+                        }
+                    }
+                    if summaries.send(summary).await.is_err() {
+                        return;
+                    }
+                }
+                _ = fail_back_check.tick() => {
+                    let Some(preferred) = endpoints.fail_back_candidate(Instant::now()) else {
+                        continue;
+                    };
+                    let preferred_addr = endpoints.addr(preferred).to_string();
+                    match open_summary_stream(
+                        &preferred_addr,
+                        &request,
+                        &api_key,
+                        options.liveness_timeout,
+                    )
+                    .await
+                    {
+                        Ok((preferred_stream, summary)) => {
+                            eprintln!("Failing back from {} to {}", addr, preferred_addr);
+                            endpoints.record_success(preferred);
+                            stream = preferred_stream;
+                            addr = preferred_addr;
+                            last_summary = Instant::now();
+                            if summaries.send(summary).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(_) => endpoints.record_failure(preferred, Instant::now()),
+                    }
+                }
+            }
+        }
+        endpoints.record_failure(endpoints.current(), Instant::now());
     }
+}
 
-    println!();
+fn print_basis(basis: &Basis) {
+    println!(
+        "{} mid: {} | {} mid: {} | basis: {} ({:.2} bps, {:.2} bps annualized)",
+        basis.spot_exchange,
+        basis.spot_mid,
+        basis.perp_exchange,
+        basis.perp_mid,
+        basis.basis,
+        basis.basis_bps,
+        basis.annualized_basis_bps
+    );
+    if let Some(venue) = &basis.perp_venue {
+        println!(
+            "{} mark price: {:?} funding rate: {:?} next funding time: {:?}",
+            venue.exchange, venue.mark_price, venue.funding_rate, venue.next_funding_time
+        );
+    }
+}
+
+fn print_index_price(index_price: &IndexPrice) {
+    let constituents: Vec<String> = index_price
+        .constituents
+        .iter()
+        .map(|constituent| {
+            format!(
+                "{} {} ({:.1}%)",
+                constituent.exchange,
+                constituent.mid,
+                constituent.weight * 100.0
+            )
+        })
+        .collect();
+    println!(
+        "Index {}: {} | {}",
+        index_price.name,
+        index_price.price,
+        constituents.join(", ")
+    );
+    if !index_price.excluded.is_empty() {
+        println!("Excluded: {}", index_price.excluded.join(", "));
+    }
+}
+
+fn flag_secs(args: &[String], flag: &str) -> Option<u64> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|index| args.get(index + 1))
+        .and_then(|secs| secs.parse().ok())
+}
+
+// Attaches the API key given with --api-key <key>, required when the server has tenants
+fn new_request<T>(message: T, api_key: &Option<String>) -> Request<T> {
+    let mut request = Request::new(message);
+    if let Some(api_key) = api_key.as_ref().and_then(|api_key| api_key.parse().ok()) {
+        request.metadata_mut().insert("x-api-key", api_key);
+    }
+    request
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let addr = "http://localhost:50051";
+    let args: Vec<String> = std::env::args().collect();
+    // --preset <name> [--config <path>] completes the command line with a preset
+    let args = match preset_from_args(&args)? {
+        Some(preset) => preset.client_args(&args),
+        None => args,
+    };
+    // --server <addr>... lists the servers of a redundant deployment, in order of
+    // preference, e.g. --server http://a:50051 --server http://b:50051
+    let mut addrs: Vec<String> = args
+        .iter()
+        .enumerate()
+        .filter(|(_, arg)| *arg == "--server")
+        .filter_map(|(index, _)| args.get(index + 1).cloned())
+        .collect();
+    if addrs.is_empty() {
+        addrs.push("http://localhost:50051".to_string());
+    }
+    let endpoints = ServerEndpoints::new(addrs).unwrap();
+    let failover = FailoverOptions {
+        liveness_timeout: Duration::from_secs(
+            flag_secs(&args, "--liveness-timeout-secs").unwrap_or(10),
+        ),
+        fail_back_interval: Duration::from_secs(flag_secs(&args, "--fail-back-secs").unwrap_or(30)),
+    };
+
+    // basis mode: orderbook-client basis <spot_exchange> <spot_symbol> <perp_exchange> <perp_symbol>
+    let api_key = args
+        .iter()
+        .position(|arg| arg == "--api-key")
+        .and_then(|index| args.get(index + 1))
+        .cloned();
+    if args.get(1).map(String::as_str) == Some("basis") {
+        if args.len() < 6 {
+            println!(
+                "Usage: cargo run --bin orderbook-client -- basis <spot_exchange> <spot_symbol> <perp_exchange> <perp_symbol>"
+            );
+            return Ok(());
+        }
+        let mut client = connect_any(&endpoints, failover.liveness_timeout).await?;
 
-    let mut client = OrderbookAggregatorClient::connect(addr).await?;
+        let request = new_request(
+            BasisRequest {
+                spot_exchange: args[2].clone(),
+                spot_symbol: args[3].clone(),
+                perp_exchange: args[4].clone(),
+                perp_symbol: args[5].clone(),
+            },
+            &api_key,
+        );
+        let mut stream = client.basis_stream(request).await?.into_inner();
 
-    let request = Request::new(Empty {});
-    let mut stream = client.book_summary(request).await?.into_inner();
+        while let Some(basis) = stream.message().await? {
+            print_basis(&basis);
+        }
+
+        return Ok(());
+    }
+
+    // index mode: orderbook-client index <name>, streams an index of the server's config
+    if args.get(1).map(String::as_str) == Some("index") {
+        let Some(name) = args.get(2).filter(|arg| !arg.starts_with("--")) else {
+            println!("Usage: cargo run --bin orderbook-client -- index <name>");
+            return Ok(());
+        };
+        let mut client = connect_any(&endpoints, failover.liveness_timeout).await?;
+        let request = new_request(IndexRequest { name: name.clone() }, &api_key);
+        let mut stream = client.index_price_stream(request).await?.into_inner();
+
+        while let Some(index_price) = stream.message().await? {
+            print_index_price(&index_price);
+        }
+
+        return Ok(());
+    }
+
+    // audit mode: orderbook-client audit [client], lists the subscriptions of a client
+    if args.get(1).map(String::as_str) == Some("audit") {
+        let mut client = connect_any(&endpoints, failover.liveness_timeout).await?;
+        let client_filter = args
+            .get(2)
+            .filter(|arg| !arg.starts_with("--"))
+            .cloned()
+            .unwrap_or_default();
+        let query = AuditQuery {
+            client: client_filter,
+            ..Default::default()
+        };
+        let records = client
+            .query_audit_log(new_request(query, &api_key))
+            .await?
+            .into_inner();
+
+        for record in records.records {
+            println!(
+                "[{}] #{} {} {} {} from {} ({}) duration: {:?} ms",
+                record.timestamp,
+                record.subscription_id,
+                record.event,
+                record.rpc,
+                record.client,
+                record.ip,
+                record.params,
+                record.duration_ms
+            );
+        }
+
+        return Ok(());
+    }
+
+    // usage mode: orderbook-client usage [client], messages and bytes sent per symbol
+    if args.get(1).map(String::as_str) == Some("usage") {
+        let mut client = connect_any(&endpoints, failover.liveness_timeout).await?;
+        let client_filter = args
+            .get(2)
+            .filter(|arg| !arg.starts_with("--"))
+            .cloned()
+            .unwrap_or_default();
+        let report = client
+            .get_usage_report(new_request(
+                UsageQuery {
+                    client: client_filter,
+                },
+                &api_key,
+            ))
+            .await?
+            .into_inner();
+
+        println!("Usage from {} to {}:", report.since, report.timestamp);
+        for usage in report.usage {
+            println!(
+                "{:<16} {:<24} {:>12} messages {:>16} bytes",
+                usage.client, usage.symbol, usage.messages, usage.bytes
+            );
+        }
+
+        return Ok(());
+    }
+
+    // feeds mode: orderbook-client feeds
+    if args.get(1).map(String::as_str) == Some("feeds") {
+        let mut client = connect_any(&endpoints, failover.liveness_timeout).await?;
+        let report = client
+            .get_feed_status(new_request(Empty {}, &api_key))
+            .await?
+            .into_inner();
+
+        println!("Feed status: {:?}", report.status());
+        for venue in report.venues {
+            let state = if venue.disabled {
+                "disabled"
+            } else if venue.stale {
+                "stale"
+            } else if venue.empty_book {
+                "empty"
+            } else {
+                "live"
+            };
+            println!(
+                "{:<12} {:<8} last update {}",
+                venue.exchange, state, venue.last_update
+            );
+            if venue.latency_ms != 0.0 {
+                println!(
+                    "    latency {:.1} ms, delayed by {} ms",
+                    venue.latency_ms, venue.compensation_delay_ms
+                );
+            }
+            if venue.duplicate_levels != 0 {
+                println!(
+                    "    {} duplicate price levels collapsed",
+                    venue.duplicate_levels
+                );
+            }
+            if venue.resyncs != 0 {
+                println!("    {} sequence gaps resynced", venue.resyncs);
+            }
+            if let Some(error) = venue.last_error {
+                println!(
+                    "    last error at {}: {} (code {}), {:?}",
+                    error.timestamp,
+                    error.message,
+                    if error.code.is_empty() {
+                        "none"
+                    } else {
+                        &error.code
+                    },
+                    error.action()
+                );
+            }
+        }
+
+        return Ok(());
+    }
+
+    // bbo mode: orderbook-client bbo, time each venue spent at the best bid and offer
+    if args.get(1).map(String::as_str) == Some("bbo") {
+        let mut client = connect_any(&endpoints, failover.liveness_timeout).await?;
+        let report = client
+            .get_bbo_attribution(new_request(Empty {}, &api_key))
+            .await?
+            .into_inner();
+
+        for (side, quote) in [
+            ("Best bid", &report.best_bid),
+            ("Best ask", &report.best_ask),
+        ] {
+            if let Some(quote) = quote {
+                println!(
+                    "{}: {} at {:?}, set by {} at {}",
+                    side, quote.price, quote.venues, quote.set_by, quote.since
+                );
+            }
+        }
+        println!("Tracked for {} ms:", report.tracked_ms);
+        for venue in report.venues {
+            println!(
+                "{:<12} bid {:>6.2}% ({} sets) ask {:>6.2}% ({} sets)",
+                venue.exchange,
+                venue.bid_time_pct,
+                venue.bid_sets,
+                venue.ask_time_pct,
+                venue.ask_sets
+            );
+        }
+
+        return Ok(());
+    }
+
+    // symbols mode: orderbook-client symbols, the symbols the server serves and the
+    // venues merged for them
+    if args.get(1).map(String::as_str) == Some("symbols") {
+        let mut client = connect_any(&endpoints, failover.liveness_timeout).await?;
+        let request = ListSymbolsRequest {
+            include_venues: true,
+        };
+        let list = client
+            .list_symbols(new_request(request, &api_key))
+            .await?
+            .into_inner();
+
+        for symbol in list.symbols {
+            let served = if symbol.book_summary {
+                "book summary"
+            } else {
+                "basis legs only"
+            };
+            println!("{:<12} depth {:<4} {}", symbol.symbol, symbol.depth, served);
+            for venue in symbol.venues {
+                let disabled = if venue.disabled { " (disabled)" } else { "" };
+                println!(
+                    "    {:<12} {}{}",
+                    venue.exchange, venue.exchange_symbol, disabled
+                );
+            }
+        }
+
+        return Ok(());
+    }
+
+    // since mode: orderbook-client since [sequence], the summaries the server kept after
+    // the sequence, for a client that missed them
+    if args.get(1).map(String::as_str) == Some("since") {
+        let mut client = connect_any(&endpoints, failover.liveness_timeout).await?;
+        let request = SummariesSinceRequest {
+            sequence: args
+                .get(2)
+                .and_then(|sequence| sequence.parse().ok())
+                .unwrap_or(0),
+            symbol: String::new(),
+        };
+        let since = client
+            .get_summaries_since(new_request(request, &api_key))
+            .await?
+            .into_inner();
+
+        if since.truncated {
+            println!("Some summaries after the sequence were already dropped");
+        }
+        for summary in since.summaries {
+            println!(
+                "{:<10} {} spread {} bid {:?} ask {:?}",
+                summary.sequence,
+                summary.timestamp,
+                summary.spread,
+                summary.bids.first().map(|level| level.price),
+                summary.asks.first().map(|level| level.price)
+            );
+        }
+        println!("Latest sequence: {}", since.latest_sequence);
+
+        return Ok(());
+    }
+
+    // alerts mode: orderbook-client alerts
+    if args.get(1).map(String::as_str) == Some("alerts") {
+        let mut client = connect_any(&endpoints, failover.liveness_timeout).await?;
+        let mut stream = client
+            .alerts(new_request(Empty {}, &api_key))
+            .await?
+            .into_inner();
+
+        while let Some(alert) = stream.message().await? {
+            println!(
+                "[{}] {} {}: {}",
+                alert.timestamp, alert.kind, alert.exchange, alert.message
+            );
+        }
+
+        return Ok(());
+    }
+
+    // orderbook-client [--fields spread,bbo] only receives the selected summary fields
+    let fields = match args.iter().position(|arg| arg == "--fields") {
+        Some(index) => args
+            .get(index + 1)
+            .map(|fields| fields.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    // --align-ms <ms> receives books aligned to wall-clock ticks instead of every update
+    let align_interval_ms = args
+        .iter()
+        .position(|arg| arg == "--align-ms")
+        .and_then(|index| args.get(index + 1))
+        .and_then(|ms| ms.parse().ok())
+        .unwrap_or(0);
+    // --trigger bbo|spread:<bps>|timer only sends the books changing the best bid and
+    // ask, moving the spread by <bps>, or every --align-ms tick
+    let trigger = args
+        .iter()
+        .position(|arg| arg == "--trigger")
+        .and_then(|index| args.get(index + 1))
+        .map_or("any", String::as_str);
+    let (trigger, spread_change_bps) = match trigger.split_once(':') {
+        Some(("spread", bps)) => (EmissionTrigger::SpreadChange, bps.parse().unwrap_or(0.0)),
+        _ if trigger == "bbo" => (EmissionTrigger::BboChange, 0.0),
+        _ if trigger == "timer" => (EmissionTrigger::Timer, 0.0),
+        _ => (EmissionTrigger::AnyChange, 0.0),
+    };
+    // --string-prices receives the prices and amounts as decimal strings, without the
+    // float rounding of the doubles
+    let price_encoding = if args.iter().any(|arg| arg == "--string-prices") {
+        PriceEncoding::String
+    } else {
+        PriceEncoding::Double
+    };
+    let request = SummaryRequest {
+        fields,
+        align_interval_ms,
+        trigger: trigger as i32,
+        spread_change_bps,
+        price_encoding: price_encoding as i32,
+    };
+    // The one-shot modes above use the first server accepting the connection, the
+    // summary stream fails over across the servers
+    let (sender, mut summaries) = mpsc::channel(16);
+    spawn(stream_with_failover(
+        endpoints, request, api_key, failover, sender,
+    ));
+
+    // orderbook-client tui shows the ladder full screen, with keys to group its prices
+    if args.get(1).map(String::as_str) == Some("tui") {
+        return run_tui(summaries).await;
+    }
+
+    // orderbook-client monitor --max-spread-bps 5 --min-depth 10 --duration 60s checks the
+    // books of the window and exits with 1 on a violation, 2 when no book arrived
+    if args.get(1).map(String::as_str) == Some("monitor") {
+        let flag_f64 = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|index| args.get(index + 1))
+                .and_then(|value| value.parse().ok())
+        };
+        let conditions = SlaConditions {
+            max_spread_bps: flag_f64("--max-spread-bps"),
+            min_depth: flag_f64("--min-depth"),
+        };
+        let window = match args.iter().position(|arg| arg == "--duration") {
+            Some(index) => args
+                .get(index + 1)
+                .and_then(|duration| parse_duration(duration))
+                .ok_or("--duration needs a duration like 60s")?,
+            None => Duration::from_secs(60),
+        };
+        let mut monitor = SlaMonitor::new(conditions);
+        let deadline = tokio::time::Instant::now() + window;
+        while let Ok(Some(summary)) = tokio::time::timeout_at(deadline, summaries.recv()).await {
+            monitor.observe(
+                summary.bids.iter().map(|level| (level.price, level.amount)),
+                summary.asks.iter().map(|level| (level.price, level.amount)),
+            );
+        }
+        println!("{}", monitor.verdict());
+        std::process::exit(monitor.verdict().exit_code());
+    }
+
+    // --cache <path> shows the last known books right away and keeps them on disk
+    let mut cache = match args.iter().position(|arg| arg == "--cache") {
+        Some(index) => {
+            let path = args.get(index + 1).ok_or("--cache needs a path")?;
+            Some(ClientCache::open(path)?)
+        }
+        None => None,
+    };
+    if let Some(cache) = &cache {
+        for (symbol, book) in cache.stale_books() {
+            print_stale_book(symbol, book);
+        }
+    }
 
-    while let Some(summary) = stream.message().await? {
+    while let Some(summary) = summaries.recv().await {
         println!("Orderbook received: ");
         print_summary(&summary);
+        if let Some(cache) = &mut cache {
+            let gap = cache.update(&summary.symbol, cached_book(&summary), Instant::now())?;
+            match gap.map(|gap| (gap.missed_updates, gap.elapsed_ms)) {
+                Some((Some(missed_updates), elapsed_ms)) => println!(
+                    "Caught up on {}: {} updates missed over {} ms",
+                    summary.symbol, missed_updates, elapsed_ms
+                ),
+                Some((None, elapsed_ms)) => println!(
+                    "Caught up on {} after {} ms, the server restarted in between",
+                    summary.symbol, elapsed_ms
+                ),
+                None => {}
+            }
+        }
     }
 
     Ok(())
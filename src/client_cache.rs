@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// The cache is written at most this often, the last book before a crash may be lost
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// Last book of a symbol received by the client, with the server's sequence and
+// timestamp of its summary
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CachedBook {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub spread: f64,
+    // (exchange, price, amount)
+    pub bids: Vec<(String, f64, f64)>,
+    pub asks: Vec<(String, f64, f64)>,
+}
+
+// What the client missed between the cached book and the first live one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    // None when the server restarted in between, its sequence started over
+    pub missed_updates: Option<u64>,
+    pub elapsed_ms: u64,
+}
+
+pub fn gap(cached: &CachedBook, live: &CachedBook) -> Gap {
+    Gap {
+        missed_updates: live
+            .sequence
+            .checked_sub(cached.sequence)
+            .map(|updates| updates.saturating_sub(1)),
+        elapsed_ms: live.timestamp.saturating_sub(cached.timestamp),
+    }
+}
+
+// On-disk cache of the last book of every symbol, as JSON, so a restarted client shows
+// the last known state right away (stale until live data resumes) and reports how
+// much it missed
+#[derive(Debug)]
+pub struct ClientCache {
+    path: PathBuf,
+    books: BTreeMap<String, CachedBook>,
+    // Symbols with a live book since the cache was opened
+    live: HashSet<String>,
+    last_flush: Option<Instant>,
+}
+
+impl ClientCache {
+    // A missing file is an empty cache
+    pub fn open(path: &str) -> Result<ClientCache, Box<dyn Error>> {
+        let books = if Path::new(path).exists() {
+            let contents = fs::read_to_string(path)
+                .map_err(|err| format!("Cannot read cache {}: {}", path, err))?;
+            serde_json::from_str(&contents)
+                .map_err(|err| format!("Invalid cache {}: {}", path, err))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(ClientCache {
+            path: PathBuf::from(path),
+            books,
+            live: HashSet::new(),
+            last_flush: None,
+        })
+    }
+
+    // The cached books of the symbols without a live book yet
+    pub fn stale_books(&self) -> impl Iterator<Item = (&String, &CachedBook)> {
+        self.books
+            .iter()
+            .filter(|(symbol, _)| !self.live.contains(*symbol))
+    }
+
+    // Caches a live book, returns the gap to the cached book on the first live book
+    // of the symbol
+    pub fn update(
+        &mut self,
+        symbol: &str,
+        book: CachedBook,
+        now: Instant,
+    ) -> Result<Option<Gap>, Box<dyn Error>> {
+        let first_live = self.live.insert(symbol.to_string());
+        let gap = self
+            .books
+            .get(symbol)
+            .filter(|_| first_live)
+            .map(|cached| gap(cached, &book));
+        self.books.insert(symbol.to_string(), book);
+        if self
+            .last_flush
+            .is_none_or(|last_flush| now.duration_since(last_flush) >= FLUSH_INTERVAL)
+        {
+            self.flush()?;
+            self.last_flush = Some(now);
+        }
+        Ok(gap)
+    }
+
+    // Written to a temporary file first, so a crash doesn't leave half a cache
+    pub fn flush(&self) -> Result<(), Box<dyn Error>> {
+        let temporary_path = self.path.with_extension("tmp");
+        fs::write(&temporary_path, serde_json::to_string(&self.books)?)?;
+        fs::rename(&temporary_path, &self.path)?;
+        Ok(())
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(sequence: u64, timestamp: u64) -> CachedBook {
+        CachedBook {
+            sequence,
+            timestamp,
+            spread: -1.0,
+            bids: vec![("binance".to_string(), 100.0, 1.0)],
+            asks: vec![("bitstamp".to_string(), 101.0, 2.0)],
+        }
+    }
+
+    #[test]
+    fn test_client_cache() {
+        let path = std::env::temp_dir().join(format!("client_cache_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let now = Instant::now();
+
+        let mut cache = ClientCache::open(path).unwrap();
+        assert_eq!(cache.stale_books().count(), 0);
+        assert_eq!(cache.update("btcusdt", book(10, 1_000), now).unwrap(), None);
+        drop(cache);
+
+        // Restarted client
+        let mut cache = ClientCache::open(path).unwrap();
+        let stale: Vec<_> = cache.stale_books().collect();
+        assert_eq!(stale, vec![(&"btcusdt".to_string(), &book(10, 1_000))]);
+        let caught_up = cache.update("btcusdt", book(15, 3_500), now).unwrap();
+        assert_eq!(
+            caught_up,
+            Some(Gap {
+                missed_updates: Some(4),
+                elapsed_ms: 2_500
+            })
+        );
+        assert_eq!(cache.stale_books().count(), 0);
+        assert_eq!(cache.update("btcusdt", book(16, 3_600), now).unwrap(), None);
+
+        // The server restarted
+        assert_eq!(gap(&book(15, 3_500), &book(2, 4_000)).missed_updates, None);
+        fs::remove_file(path).unwrap();
+    }
+}
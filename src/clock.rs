@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Source of the time of the server's time based logic: the staleness of the feeds, the
+// aligned ticks and the latency compensation, the analytics windows and the timestamps
+// of the summaries, alerts and recordings. Tests and replays use a SimulatedClock, so
+// they run the same way every time and without sleeps
+pub trait Clock: Send + Sync {
+    // Wall-clock time, in ms since epoch
+    fn now_millis(&self) -> u64;
+
+    // Monotonic time, for the intervals
+    fn now(&self) -> Instant;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// Time that only moves when it is advanced or set, from the wall-clock time it starts
+// at. It never goes back, like the system clock's monotonic time
+#[derive(Debug)]
+pub struct SimulatedClock {
+    origin: Instant,
+    start_millis: u64,
+    elapsed_ms: AtomicU64,
+}
+
+impl SimulatedClock {
+    pub fn new(start_millis: u64) -> SimulatedClock {
+        SimulatedClock {
+            origin: Instant::now(),
+            start_millis,
+            elapsed_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed_ms
+            .fetch_add(duration.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    // Moves the clock to a wall-clock time, e.g. the timestamp of a recorded book. Times
+    // before the current one leave the clock as it is
+    pub fn set_millis(&self, millis: u64) {
+        self.elapsed_ms
+            .fetch_max(millis.saturating_sub(self.start_millis), Ordering::Relaxed);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now_millis(&self) -> u64 {
+        self.start_millis + self.elapsed_ms.load(Ordering::Relaxed)
+    }
+
+    fn now(&self) -> Instant {
+        self.origin + Duration::from_millis(self.elapsed_ms.load(Ordering::Relaxed))
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulated_clock() {
+        let clock = SimulatedClock::new(1_000);
+        let start = clock.now();
+        assert_eq!(clock.now_millis(), 1_000);
+
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(clock.now_millis(), 1_250);
+        assert_eq!(clock.now() - start, Duration::from_millis(250));
+
+        clock.set_millis(2_000);
+        assert_eq!(clock.now_millis(), 2_000);
+        // Never goes back
+        clock.set_millis(1_500);
+        assert_eq!(clock.now_millis(), 2_000);
+        assert_eq!(clock.now() - start, Duration::from_millis(1_000));
+    }
+}
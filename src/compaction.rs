@@ -0,0 +1,165 @@
+use crate::book::OrderBookSide;
+use crate::number::decimal_to_f64;
+use rust_decimal::Decimal;
+
+// Diff maintained books keep every level the exchange ever sent, so levels far from mid
+// accumulate over a long run. Compaction prunes them back to max_distance_bps, but only
+// once a level drifted beyond max_distance_bps + hysteresis_bps, so levels around the
+// boundary aren't pruned and re-added on every small move of the mid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompactionPolicy {
+    pub max_distance_bps: f64,
+    pub hysteresis_bps: f64,
+    // Compaction is checked every that many applied updates
+    pub interval_updates: u64,
+}
+
+impl CompactionPolicy {
+    pub fn new(max_distance_bps: f64) -> CompactionPolicy {
+        CompactionPolicy {
+            max_distance_bps,
+            hysteresis_bps: max_distance_bps / 10.0,
+            interval_updates: 100,
+        }
+    }
+}
+
+// Pruned levels are counted, so depth metrics can still account for them
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CompactionStats {
+    pub compactions: u64,
+    pub pruned_bids: u64,
+    pub pruned_asks: u64,
+}
+
+fn distance_bps(price: Decimal, mid: f64) -> f64 {
+    (decimal_to_f64(price) - mid).abs() / mid * 10_000.0
+}
+
+fn mid(bids: &OrderBookSide, asks: &OrderBookSide) -> Option<f64> {
+    Some(decimal_to_f64(bids.best()?.price + asks.best()?.price) / 2.0)
+}
+
+fn prune_levels(levels: &mut OrderBookSide, mid: f64, max_distance_bps: f64) -> u64 {
+    levels.retain(|level| distance_bps(level.price, mid) <= max_distance_bps) as u64
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Compactor {
+    policy: Option<CompactionPolicy>,
+    updates: u64,
+    stats: CompactionStats,
+}
+
+impl Compactor {
+    pub fn new(policy: Option<CompactionPolicy>) -> Compactor {
+        Compactor {
+            policy,
+            ..Compactor::default()
+        }
+    }
+
+    pub fn stats(&self) -> CompactionStats {
+        self.stats
+    }
+
+    // Called after every applied update, returns true if levels were pruned
+    pub fn on_update(&mut self, bids: &mut OrderBookSide, asks: &mut OrderBookSide) -> bool {
+        let policy = match self.policy {
+            Some(policy) => policy,
+            None => return false,
+        };
+        self.updates += 1;
+        if self.updates < policy.interval_updates.max(1) {
+            return false;
+        }
+        self.updates = 0;
+
+        let mid = match mid(bids, asks) {
+            Some(mid) if mid > 0.0 => mid,
+            _ => return false,
+        };
+        // The sides are sorted, the levels farthest from mid are at their ends
+        let outside_band = [bids.best(), bids.worst(), asks.best(), asks.worst()]
+            .into_iter()
+            .flatten()
+            .any(|level| {
+                distance_bps(level.price, mid) > policy.max_distance_bps + policy.hysteresis_bps
+            });
+        if !outside_band {
+            return false;
+        }
+
+        self.stats.compactions += 1;
+        self.stats.pruned_bids += prune_levels(bids, mid, policy.max_distance_bps);
+        self.stats.pruned_asks += prune_levels(asks, mid, policy.max_distance_bps);
+        true
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PriceAmountLevel;
+    use crate::number::decimal_from_f64;
+    use rust_decimal_macros::dec;
+
+    fn level(price: f64) -> PriceAmountLevel {
+        PriceAmountLevel {
+            exchange: "bybit".to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: dec!(1.0),
+        }
+    }
+
+    fn levels(mut side: OrderBookSide, prices: &[f64]) -> OrderBookSide {
+        side.replace(prices.iter().map(|price| level(*price)).collect());
+        side
+    }
+
+    #[test]
+    fn test_compactor() {
+        // 100 bps max distance, pruned once a level is more than 120 bps away from mid
+        let mut compactor = Compactor::new(Some(CompactionPolicy {
+            max_distance_bps: 100.0,
+            hysteresis_bps: 20.0,
+            interval_updates: 2,
+        }));
+        // mid is 100, levels at 98.9 and 101.1 are 110 bps away, within the band
+        let mut bids = levels(OrderBookSide::bids(), &[99.9, 99.5, 98.9]);
+        let mut asks = levels(OrderBookSide::asks(), &[100.1, 101.1]);
+
+        assert!(!compactor.on_update(&mut bids, &mut asks));
+        assert!(!compactor.on_update(&mut bids, &mut asks));
+        assert_eq!(bids.len(), 3);
+
+        // A level 150 bps away pushes the book outside the band, everything beyond
+        // 100 bps is pruned
+        asks.apply(level(101.5));
+        assert!(!compactor.on_update(&mut bids, &mut asks));
+        assert!(compactor.on_update(&mut bids, &mut asks));
+        assert_eq!(bids.len(), 2);
+        assert_eq!(asks.len(), 1);
+        assert_eq!(
+            compactor.stats(),
+            CompactionStats {
+                compactions: 1,
+                pruned_bids: 1,
+                pruned_asks: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_compactor_disabled() {
+        let mut compactor = Compactor::new(None);
+        let mut bids = levels(OrderBookSide::bids(), &[99.0, 10.0]);
+        let mut asks = levels(OrderBookSide::asks(), &[101.0]);
+
+        for _ in 0..1000 {
+            assert!(!compactor.on_update(&mut bids, &mut asks));
+        }
+        assert_eq!(bids.len(), 2);
+    }
+}
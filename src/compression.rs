@@ -0,0 +1,73 @@
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use std::borrow::Cow;
+use std::io::Read;
+use tungstenite::Message;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// First byte of a zlib stream with the deflate method, the second byte is a checksum
+fn is_zlib(payload: &[u8]) -> bool {
+    payload.len() >= 2
+        && payload[0] & 0x0f == 8
+        && (u16::from(payload[0]) << 8 | u16::from(payload[1])) % 31 == 0
+}
+
+// Payloads compressed at the application layer, as gzip, zlib or raw deflate.
+// Uncompressed UTF-8 payloads are returned as they are
+pub(crate) fn decompress(payload: &[u8]) -> Option<String> {
+    let mut text = String::new();
+    let result = if payload.starts_with(&GZIP_MAGIC) {
+        GzDecoder::new(payload).read_to_string(&mut text)
+    } else if is_zlib(payload) {
+        ZlibDecoder::new(payload).read_to_string(&mut text)
+    } else if let Ok(payload) = std::str::from_utf8(payload) {
+        return Some(payload.to_string());
+    } else {
+        DeflateDecoder::new(payload).read_to_string(&mut text)
+    };
+    result.ok().map(|_| text)
+}
+
+// Text of an exchange message, some exchanges send their JSON compressed in binary frames
+pub(crate) fn message_text(message: &Message) -> Cow<'_, str> {
+    match message {
+        Message::Text(text) => Cow::Borrowed(text),
+        Message::Binary(payload) => Cow::Owned(decompress(payload).unwrap_or_default()),
+        _ => Cow::Borrowed(""),
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::Write;
+
+    const MESSAGE: &str = r#"{"bids":[["100.0","1.5"]],"asks":[["101.0","2.0"]]}"#;
+
+    #[test]
+    fn test_message_text() {
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(MESSAGE.as_bytes()).unwrap();
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(MESSAGE.as_bytes()).unwrap();
+        let mut deflate = DeflateEncoder::new(Vec::new(), Compression::default());
+        deflate.write_all(MESSAGE.as_bytes()).unwrap();
+
+        for payload in [
+            gzip.finish().unwrap(),
+            zlib.finish().unwrap(),
+            deflate.finish().unwrap(),
+        ] {
+            assert_eq!(message_text(&Message::Binary(payload)), MESSAGE);
+        }
+        assert_eq!(message_text(&Message::Text(MESSAGE.to_string())), MESSAGE);
+        assert_eq!(
+            message_text(&Message::Binary(MESSAGE.as_bytes().to_vec())),
+            MESSAGE
+        );
+        assert_eq!(message_text(&Message::Ping(Vec::new())), "");
+    }
+}
@@ -0,0 +1,503 @@
+use crate::index_price::IndexFormula;
+use crate::sla::parse_duration;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+mod server;
+pub use server::{ServerOptions, USAGE};
+
+// Read when --config isn't given
+pub const DEFAULT_CONFIG_FILE: &str = "orderbook.toml";
+
+// A named setup of the server and the client, e.g.
+// majors = { symbols = ["btcusdt", "ethusdt"], depth = 20, exchanges = ["binance", "bybit"] }
+// The server uses symbols, depth, exchanges, binance_us, endpoints, update_speeds_ms and
+// venue_params, the client servers, fields and align_ms
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    #[serde(default)]
+    pub symbols: Vec<String>,
+    pub depth: Option<u32>,
+    #[serde(default)]
+    pub exchanges: Vec<String>,
+    // Reads binance from Binance.US, like --binance-us
+    #[serde(default)]
+    pub binance_us: bool,
+    // WebSocket endpoints and update speeds of the exchanges, like --endpoint and
+    // --update-speed-ms
+    #[serde(default)]
+    pub endpoints: BTreeMap<String, String>,
+    #[serde(default)]
+    pub update_speeds_ms: BTreeMap<String, u32>,
+    // Venue specific subscription parameters, like --venue-param, e.g.
+    // venue_params = { kraken = { depth = 100 } }
+    #[serde(default)]
+    pub venue_params: BTreeMap<String, BTreeMap<String, toml::Value>>,
+    #[serde(default)]
+    pub servers: Vec<String>,
+    #[serde(default)]
+    pub fields: Vec<String>,
+    pub align_ms: Option<u32>,
+}
+
+// Analytics of the summaries that can be turned off for a symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Analytics {
+    FairValue,
+    Toxicity,
+}
+
+// When the venues' books of a symbol are merged, instead of on every update. During a
+// burst a 100 ms venue updates 10 times as often as a 1 s one, the merged book would
+// thrash with the fast venue's view. "slowest" merges on the cadence of the slowest
+// venue, a duration like "250ms" on fixed ticks
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum MergeCadence {
+    Slowest,
+    Tick(Duration),
+}
+
+impl TryFrom<String> for MergeCadence {
+    type Error = String;
+
+    fn try_from(cadence: String) -> Result<MergeCadence, String> {
+        if cadence == "slowest" {
+            return Ok(MergeCadence::Slowest);
+        }
+        match parse_duration(&cadence) {
+            Some(tick) if !tick.is_zero() => Ok(MergeCadence::Tick(tick)),
+            _ => Err(format!(
+                "Invalid merge cadence {}, expected slowest or a duration like 250ms",
+                cadence
+            )),
+        }
+    }
+}
+
+// Settings of the pipeline of one symbol, what isn't set is the server's, e.g.
+// [symbols.btcusdt]
+// depth = 50
+// conflation_ms = 100
+// merge_cadence = "slowest"
+// [symbols.dogeusdt]
+// depth = 10
+// conflation_ms = 1000
+// analytics = ["fair_value"]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolSettings {
+    pub depth: Option<u32>,
+    // Summaries are sent at most once per interval, with the latest book of each venue
+    pub conflation_ms: Option<u32>,
+    // Every analytics when not set
+    pub analytics: Option<Vec<Analytics>>,
+    // Merged on every update when not set
+    pub merge_cadence: Option<MergeCadence>,
+}
+
+// What the pipeline of a symbol runs with, its settings completed with the server's
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolPipeline {
+    pub depth: u32,
+    pub conflation: Option<Duration>,
+    pub merge_cadence: Option<MergeCadence>,
+    pub fair_value: bool,
+    pub toxicity: bool,
+}
+
+// The pipeline of the symbol, with the server's depth and every analytics for a symbol
+// without settings
+pub fn symbol_pipeline(
+    settings: &BTreeMap<String, SymbolSettings>,
+    symbol: &str,
+    default_depth: u32,
+) -> SymbolPipeline {
+    let settings = settings.get(symbol).cloned().unwrap_or_default();
+    let enabled = |analytics: Analytics| {
+        settings
+            .analytics
+            .as_ref()
+            .is_none_or(|enabled| enabled.contains(&analytics))
+    };
+    SymbolPipeline {
+        depth: settings.depth.unwrap_or(default_depth),
+        conflation: settings
+            .conflation_ms
+            .filter(|conflation_ms| *conflation_ms > 0)
+            .map(|conflation_ms| Duration::from_millis(conflation_ms.into())),
+        merge_cadence: settings.merge_cadence,
+        fair_value: enabled(Analytics::FairValue),
+        toxicity: enabled(Analytics::Toxicity),
+    }
+}
+
+// The settings of the symbols the server doesn't serve, whose only pipelines are the
+// BasisStream legs taking their depth. A conflation interval, analytics or merge cadence
+// set for one of them wouldn't apply, so it fails the startup instead of being ignored
+pub fn check_symbol_settings(
+    settings: &BTreeMap<String, SymbolSettings>,
+    served_symbol: &str,
+) -> Result<(), String> {
+    for (symbol, settings) in settings {
+        if symbol == served_symbol {
+            continue;
+        }
+        let mut ignored = Vec::new();
+        if settings.conflation_ms.is_some() {
+            ignored.push("conflation_ms");
+        }
+        if settings.analytics.is_some() {
+            ignored.push("analytics");
+        }
+        if settings.merge_cadence.is_some() {
+            ignored.push("merge_cadence");
+        }
+        if !ignored.is_empty() {
+            return Err(format!(
+                "{} of [symbols.{}] don't apply, the server only serves {}, other symbols \
+                 only take a depth for their BasisStream legs",
+                ignored.join(", "),
+                symbol,
+                served_symbol
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Config {
+    // The deployment of the server when no preset is named, e.g.
+    // [server]
+    // symbols = ["btcusdt"]
+    // depth = 20
+    // exchanges = ["binance", "bitstamp", "okx"]
+    // update_speeds_ms = { binance = 1000 }
+    // [server.endpoints]
+    // okx = "wss://wsaws.okx.com:8443/ws/v5/public"
+    pub server: Option<Preset>,
+    #[serde(default)]
+    pub presets: BTreeMap<String, Preset>,
+    #[serde(default)]
+    pub indexes: BTreeMap<String, IndexFormula>,
+    #[serde(default)]
+    pub symbols: BTreeMap<String, SymbolSettings>,
+}
+
+pub fn load_config(path: &str) -> Result<Config, Box<dyn Error>> {
+    let contents =
+        fs::read_to_string(path).map_err(|err| format!("Cannot read config {}: {}", path, err))?;
+    toml::from_str(&contents).map_err(|err| format!("Invalid config {}: {}", path, err).into())
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|index| args.get(index + 1))
+}
+
+fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|arg| arg == flag)
+}
+
+// The preset named by --preset <name>, from --config <path> or DEFAULT_CONFIG_FILE
+pub fn preset_from_args(args: &[String]) -> Result<Option<Preset>, Box<dyn Error>> {
+    let name = match flag_value(args, "--preset") {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+    let path = flag_value(args, "--config").map_or(DEFAULT_CONFIG_FILE, String::as_str);
+    let mut config = load_config(path)?;
+    match config.presets.remove(name) {
+        Some(preset) => Ok(Some(preset)),
+        None => Err(format!(
+            "Unknown preset {} in {}, expected one of {}",
+            name,
+            path,
+            config
+                .presets
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into()),
+    }
+}
+
+// The preset the server runs with, the one of --preset or else the [server] section of
+// the config file, so the whole deployment can be described there instead of on the
+// command line
+pub fn server_preset_from_args(args: &[String]) -> Result<Option<Preset>, Box<dyn Error>> {
+    match preset_from_args(args)? {
+        Some(preset) => Ok(Some(preset)),
+        None => Ok(config_from_args(args)?.server),
+    }
+}
+
+// The config of --config <path> or DEFAULT_CONFIG_FILE. Without --config a missing
+// default file is an empty config
+fn config_from_args(args: &[String]) -> Result<Config, Box<dyn Error>> {
+    match flag_value(args, "--config") {
+        Some(path) => load_config(path),
+        None if Path::new(DEFAULT_CONFIG_FILE).exists() => load_config(DEFAULT_CONFIG_FILE),
+        None => Ok(Config::default()),
+    }
+}
+
+// The index formulas of the config file
+pub fn indexes_from_args(
+    args: &[String],
+) -> Result<BTreeMap<String, IndexFormula>, Box<dyn Error>> {
+    Ok(config_from_args(args)?.indexes)
+}
+
+// The settings of the symbols' pipelines of the config file
+pub fn symbols_from_args(
+    args: &[String],
+) -> Result<BTreeMap<String, SymbolSettings>, Box<dyn Error>> {
+    Ok(config_from_args(args)?.symbols)
+}
+
+impl Preset {
+    // The server command line <symbol> [depth] [options] completed with the preset.
+    // What the command line gives takes precedence, a server serves a single symbol,
+    // the first of the preset unless one is given. binance and bitstamp are always
+    // merged, the other exchanges are added as connectors
+    pub fn server_args(&self, args: &[String]) -> Vec<String> {
+        let positional = args
+            .iter()
+            .skip(1)
+            .take_while(|arg| !arg.starts_with("--"))
+            .count();
+        let mut expanded: Vec<String> = args.iter().take(1).cloned().collect();
+        if positional >= 1 {
+            expanded.push(args[1].clone());
+        } else {
+            expanded.extend(self.symbols.first().cloned());
+        }
+        if positional >= 2 {
+            expanded.push(args[2].clone());
+        } else if let Some(depth) = self.depth {
+            expanded.push(depth.to_string());
+        }
+        expanded.extend(args.iter().skip(1 + positional.min(2)).cloned());
+
+        if !has_flag(args, "--connector") {
+            for exchange in &self.exchanges {
+                if exchange != "binance" && exchange != "bitstamp" {
+                    expanded.push("--connector".to_string());
+                    expanded.push(exchange.clone());
+                }
+            }
+        }
+        if self.binance_us && !has_flag(args, "--binance-us") {
+            expanded.push("--binance-us".to_string());
+        }
+        if !has_flag(args, "--endpoint") {
+            for (exchange, url) in &self.endpoints {
+                expanded.push("--endpoint".to_string());
+                expanded.push(format!("{}={}", exchange, url));
+            }
+        }
+        if !has_flag(args, "--update-speed-ms") {
+            for (exchange, speed_ms) in &self.update_speeds_ms {
+                expanded.push("--update-speed-ms".to_string());
+                expanded.push(format!("{}={}", exchange, speed_ms));
+            }
+        }
+        if !has_flag(args, "--venue-param") {
+            for (exchange, params) in &self.venue_params {
+                for (key, value) in params {
+                    // Strings without their quotes, numbers as written
+                    let value = match value {
+                        toml::Value::String(value) => value.clone(),
+                        value => value.to_string(),
+                    };
+                    expanded.push("--venue-param".to_string());
+                    expanded.push(format!("{}.{}={}", exchange, key, value));
+                }
+            }
+        }
+        expanded
+    }
+
+    // The client command line completed with the preset's servers, fields and
+    // alignment, the ones given on the command line take precedence
+    pub fn client_args(&self, args: &[String]) -> Vec<String> {
+        let mut expanded = args.to_vec();
+        if !has_flag(args, "--server") {
+            for server in &self.servers {
+                expanded.push("--server".to_string());
+                expanded.push(server.clone());
+            }
+        }
+        if !has_flag(args, "--fields") && !self.fields.is_empty() {
+            expanded.push("--fields".to_string());
+            expanded.push(self.fields.join(","));
+        }
+        if let (false, Some(align_ms)) = (has_flag(args, "--align-ms"), self.align_ms) {
+            expanded.push("--align-ms".to_string());
+            expanded.push(align_ms.to_string());
+        }
+        expanded
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_presets() {
+        let config: Config = toml::from_str(
+            r#"
+            [presets]
+            majors = { symbols = ["btcusdt", "ethusdt"], depth = 20, exchanges = ["binance", "bybit"] }
+            desk = { servers = ["http://a:50051", "http://b:50051"], fields = ["spread", "bbo"] }
+            us = { symbols = ["btcusd"], binance_us = true }
+            "#,
+        )
+        .unwrap();
+        let majors = &config.presets["majors"];
+
+        assert_eq!(
+            majors.server_args(&args("server --preset majors")),
+            args("server btcusdt 20 --preset majors --connector bybit")
+        );
+        // The command line takes precedence
+        assert_eq!(
+            majors.server_args(&args("server ethusdt 5 --connector kraken")),
+            args("server ethusdt 5 --connector kraken")
+        );
+        assert_eq!(
+            majors.server_args(&args("doctor ethusdt")),
+            args("doctor ethusdt 20 --connector bybit")
+        );
+        assert_eq!(
+            config.presets["us"].server_args(&args("server --preset us")),
+            args("server btcusd --preset us --binance-us")
+        );
+
+        let desk = &config.presets["desk"];
+        assert_eq!(
+            desk.client_args(&args("client tui --align-ms 250")),
+            args(
+                "client tui --align-ms 250 --server http://a:50051 --server http://b:50051 \
+                 --fields spread,bbo"
+            )
+        );
+
+        assert!(toml::from_str::<Config>("[presets]\nmajors = { symbol = \"btcusdt\" }").is_err());
+    }
+
+    #[test]
+    fn test_server_section() {
+        let config: Config = toml::from_str(
+            r#"
+            [server]
+            symbols = ["ethusdt"]
+            depth = 20
+            exchanges = ["binance", "bitstamp", "okx"]
+            update_speeds_ms = { binance = 1000 }
+            venue_params = { okx = { channel = "books50-l2-tbt" }, kraken = { depth = 100 } }
+
+            [server.endpoints]
+            okx = "wss://wsaws.okx.com:8443/ws/v5/public"
+            "#,
+        )
+        .unwrap();
+        let server = config.server.unwrap();
+
+        assert_eq!(
+            server.server_args(&args("server")),
+            args(
+                "server ethusdt 20 --connector okx \
+                 --endpoint okx=wss://wsaws.okx.com:8443/ws/v5/public \
+                 --update-speed-ms binance=1000 --venue-param kraken.depth=100 \
+                 --venue-param okx.channel=books50-l2-tbt"
+            )
+        );
+        assert_eq!(
+            server.server_args(&args("server btcusdt --update-speed-ms binance=100")),
+            args(
+                "server btcusdt 20 --update-speed-ms binance=100 --connector okx \
+                 --endpoint okx=wss://wsaws.okx.com:8443/ws/v5/public \
+                 --venue-param kraken.depth=100 --venue-param okx.channel=books50-l2-tbt"
+            )
+        );
+    }
+
+    #[test]
+    fn test_symbol_pipelines() {
+        let config: Config = toml::from_str(
+            r#"
+            [symbols.btcusdt]
+            depth = 50
+            conflation_ms = 100
+            merge_cadence = "slowest"
+
+            [symbols.dogeusdt]
+            depth = 10
+            conflation_ms = 1000
+            analytics = ["fair_value"]
+            merge_cadence = "500ms"
+            "#,
+        )
+        .unwrap();
+
+        let btc = symbol_pipeline(&config.symbols, "btcusdt", 20);
+        assert_eq!(btc.depth, 50);
+        assert_eq!(btc.conflation, Some(Duration::from_millis(100)));
+        assert!(btc.fair_value && btc.toxicity);
+        assert_eq!(btc.merge_cadence, Some(MergeCadence::Slowest));
+        let doge = symbol_pipeline(&config.symbols, "dogeusdt", 20);
+        assert_eq!(doge.depth, 10);
+        assert_eq!(doge.conflation, Some(Duration::from_secs(1)));
+        assert!(doge.fair_value && !doge.toxicity);
+        assert_eq!(
+            doge.merge_cadence,
+            Some(MergeCadence::Tick(Duration::from_millis(500)))
+        );
+        // The server's settings for the other symbols
+        let eth = symbol_pipeline(&config.symbols, "ethusdt", 20);
+        assert_eq!((eth.depth, eth.conflation), (20, None));
+        assert_eq!(eth.merge_cadence, None);
+
+        assert!(toml::from_str::<Config>("[symbols.btcusdt]\nanalytics = [\"vpin\"]").is_err());
+        assert!(toml::from_str::<Config>("[symbols.btcusdt]\nmerge_cadence = \"fast\"").is_err());
+    }
+
+    #[test]
+    fn test_check_symbol_settings() {
+        let config: Config = toml::from_str(
+            r#"
+            [symbols.btcusdt]
+            depth = 50
+            conflation_ms = 100
+            analytics = ["fair_value"]
+
+            [symbols.ethusdt]
+            depth = 10
+            "#,
+        )
+        .unwrap();
+        assert!(check_symbol_settings(&config.symbols, "btcusdt").is_ok());
+
+        // Only the served symbol's pipeline takes more than a depth
+        let err = check_symbol_settings(&config.symbols, "ethusdt").unwrap_err();
+        assert!(err.starts_with("conflation_ms, analytics of [symbols.btcusdt] don't apply"));
+    }
+}
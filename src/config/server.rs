@@ -0,0 +1,504 @@
+use super::{symbol_pipeline, SymbolPipeline, SymbolSettings};
+use crate::aggregator::{BinanceStreams, ConnectorSettings};
+use crate::compaction::CompactionPolicy;
+use crate::connectors::bitstamp::BITSTAMP_URL;
+use crate::fair_value::FairValueModel;
+use crate::index_price::IndexFormula;
+use crate::retention::RetentionPolicy;
+use crate::sink::{JsonFormat, SummarySink};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+
+// The options of the aggregator server, and their command line
+
+// Returns the value following a flag, e.g. --bitstamp-symbol btcusd
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    super::flag_value(args, flag).cloned()
+}
+
+// Returns the values of a flag that can be repeated, e.g. --connector a --connector b
+fn flag_values(args: &[String], flag: &str) -> Vec<String> {
+    args.windows(2)
+        .filter(|pair| pair[0] == flag)
+        .map(|pair| pair[1].clone())
+        .collect()
+}
+
+// Everything the aggregator server is started with, see ServerOptions::new for the
+// defaults and ServerOptions::from_args for the command line
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    pub symbol: String,
+    pub depth: u32,
+    // bitstamp can be quoted in USD while binance is quoted in USDT, e.g. btcusdt and btcusd
+    pub bitstamp_symbol: String,
+    pub depeg_threshold_bps: f64,
+    pub bitstamp_channels_per_socket: usize,
+    // Additional registered connectors merged into the book, e.g. out of tree connectors
+    pub connectors: Vec<String>,
+    // Rhai scripts run on every merged update, as (symbol, path)
+    pub scripts: Vec<(String, String)>,
+    // JSON file of the tenants, see tenant::TenantRegistry
+    pub tenants_file: Option<String>,
+    // JSON lines file every subscription is recorded in
+    pub audit_log_file: Option<String>,
+    // JSON lines file the merged books are recorded in, for offline reports
+    pub record_file: Option<String>,
+    // JSON lines file the usage report is appended to every usage_export_interval
+    pub usage_export_file: Option<String>,
+    pub usage_export_interval: Duration,
+    // Cleanup of the recording, audit log and usage export files, off by default
+    pub retention: RetentionPolicy,
+    pub retention_interval: Duration,
+    // Prunes the far levels of diff maintained books, off by default
+    pub compaction: Option<CompactionPolicy>,
+    // Reads binance's depth, bookTicker and trade streams on one socket
+    pub binance_combined_stream: bool,
+    // Reads the USD-M futures book as the binance venue instead of the spot one
+    pub binance_futures: bool,
+    // Reads binance from Binance.US (stream.binance.us) instead of the global endpoint,
+    // for US users
+    pub binance_us: bool,
+    // Maintains bitstamp's book from its diff_order_book channel and a REST snapshot
+    // instead of reading the full detail_order_book on every update
+    pub bitstamp_diff_book: bool,
+    // Rates of the local currencies of venues like Upbit in the served quote, by
+    // currency, e.g. KRW per USDT. Upbit falls back to its own KRW-USDT market
+    pub fx_rates: HashMap<String, f64>,
+    // Reads the trades of the venues with a trade channel (binance's combined stream,
+    // Coinbase and Kraken) next to their books and prints them as one tape
+    pub tape: bool,
+    // WebSocket endpoints replacing the exchanges' own, by venue, e.g. a regional or a
+    // test one, and the update speeds of the venues streaming at several speeds
+    pub endpoints: HashMap<String, String>,
+    pub update_speeds: HashMap<String, Duration>,
+    // Venue specific parameters of the subscriptions, by venue, e.g. the depth of
+    // Kraken's book channel, each connector takes its own keys
+    pub venue_params: HashMap<String, Vec<(String, String)>>,
+    // The symbols of the venues not listing the server's symbol under the name their
+    // connector derives from it, by venue, e.g. a renamed asset. bitstamp's is
+    // bitstamp_symbol
+    pub venue_symbols: HashMap<String, String>,
+    // Checks every merged venue lists its symbol in its instrument list before
+    // connecting, off by default as it calls the venues' REST APIs
+    pub check_symbols: bool,
+    // Blend of the venues in Summary.fair_value
+    pub fair_value_model: FairValueModel,
+    // Every merged summary is POSTed as JSON to this url
+    pub webhook_url: Option<String>,
+    // Every merged summary is published as JSON on this redis://host:port/channel
+    pub redis_url: Option<String>,
+    // Field names of the JSON of each sink, the legacy compact book for older consumers
+    pub webhook_json_format: JsonFormat,
+    pub redis_json_format: JsonFormat,
+    // Sinks of the embedding application, e.g. a Kafka producer, driven next to the
+    // recording, webhook and Redis sinks
+    pub sinks: Vec<Arc<dyn SummarySink>>,
+    // Count of the latest summaries kept in memory for GetSummariesSince, off by default
+    pub summary_history: Option<usize>,
+    // Alerts when a venue's mid deviates from the fair value for the sustain period,
+    // and optionally quarantines the venue, off by default
+    pub deviation_threshold_bps: Option<f64>,
+    pub deviation_sustain: Duration,
+    pub quarantine_deviating_venues: bool,
+    // Alerts when a level of a venue rests at least this many times the venue's average
+    // level size over the window, and when it is removed, off by default
+    pub wall_multiple: Option<f64>,
+    pub wall_window: Duration,
+    // Exchange hostnames are resolved again after this interval
+    pub dns_refresh_interval: Duration,
+    // Volume of a bucket of the flow toxicity and the number of buckets averaged
+    pub toxicity_bucket_volume: f64,
+    pub toxicity_buckets: usize,
+    // A venue without an update for this long is reported stale in Summary.status
+    pub stale_after: Duration,
+    // Without a subscriber for this long, the symbol's feed and its exchange connections
+    // are suspended until the next subscription, off by default
+    pub idle_shutdown: Option<Duration>,
+    // Index formulas of the config file, by name, see config::indexes_from_args
+    pub indexes: BTreeMap<String, IndexFormula>,
+    // Levels of these venues further than this from their own best price are left out
+    // of the merged book, see touch_filter::trim_beyond_touch
+    pub max_touch_distance_bps: HashMap<String, f64>,
+    // Caps on the reconnect attempts a minute, of all the venues together and of each
+    // venue, see reconnect::ReconnectLimiter. Uncapped by default
+    pub max_reconnects_per_minute: Option<u32>,
+    pub max_venue_reconnects_per_minute: HashMap<String, u32>,
+    // Depth, conflation and analytics per symbol, see config::symbols_from_args. The
+    // served symbol's depth takes precedence over depth
+    pub symbol_settings: BTreeMap<String, SymbolSettings>,
+    // Holds back the books of the faster venues by up to this, off by default, see
+    // latency::LatencyEstimator
+    pub latency_compensation: Option<Duration>,
+    // Venue whose own book the merged book is compared to in Summary.reference_improvement,
+    // with the depth counted within reference_band_bps of its mid, off by default
+    pub reference_venue: Option<String>,
+    pub reference_band_bps: f64,
+    // Serves the gauges of the server in the Prometheus format, off by default
+    pub metrics_addr: Option<SocketAddr>,
+    // Serves CPU profiles of the running server as flamegraphs, off by default. Builds
+    // without the profiling feature answer with an error
+    pub profile_addr: Option<SocketAddr>,
+    // Serves the merged book as CSV over HTTP for spreadsheets, off by default
+    pub csv_addr: Option<SocketAddr>,
+    // The tokio runtime the server's tasks are spawned on, runtime::default_runtime when
+    // not set. Applications on another executor pass the handle of a runtime of theirs
+    pub runtime: Option<Handle>,
+    pub addr: SocketAddr,
+}
+
+impl ServerOptions {
+    // The connectors merged into the book, binance, bitstamp and the ones enabled with
+    // --connector, in merge order
+    pub fn venues(&self) -> Vec<String> {
+        let mut venues = vec!["binance".to_string(), "bitstamp".to_string()];
+        venues.extend(self.connectors.iter().cloned());
+        venues
+    }
+
+    pub(crate) fn binance_streams(&self) -> BinanceStreams {
+        BinanceStreams {
+            combined: self.binance_combined_stream,
+            futures: self.binance_futures,
+            us: self.binance_us,
+        }
+    }
+
+    pub(crate) fn connector_settings(&self) -> ConnectorSettings {
+        ConnectorSettings {
+            compaction: self.compaction,
+            binance_streams: self.binance_streams(),
+            fx_rates: self.fx_rates.clone(),
+            tape: self.tape,
+            endpoints: self.endpoints.clone(),
+            update_speeds: self.update_speeds.clone(),
+            venue_params: self.venue_params.clone(),
+            venue_symbols: self.venue_symbols.clone(),
+            bitstamp_diff_book: self.bitstamp_diff_book,
+        }
+    }
+
+    // Bitstamp's endpoint, the one of --endpoint bitstamp=<url> if given
+    pub(crate) fn bitstamp_url(&self) -> &str {
+        self.endpoints
+            .get("bitstamp")
+            .map_or(BITSTAMP_URL, String::as_str)
+    }
+
+    // The venues read from a socket of their own, all but the bitstamp pool
+    pub(crate) fn socket_venues(&self) -> Vec<String> {
+        let mut venues = self.venues();
+        venues.retain(|name| name != "bitstamp");
+        venues
+    }
+
+    // The settings of the symbol's pipeline, the server's ones for a symbol without
+    // settings
+    pub fn pipeline(&self, symbol: &str) -> SymbolPipeline {
+        symbol_pipeline(&self.symbol_settings, symbol, self.depth)
+    }
+
+    pub fn new(symbol: &str, depth: u32) -> ServerOptions {
+        ServerOptions {
+            symbol: symbol.to_string(),
+            depth,
+            bitstamp_symbol: symbol.to_string(),
+            depeg_threshold_bps: 50.0,
+            bitstamp_channels_per_socket: 10,
+            connectors: Vec::new(),
+            scripts: Vec::new(),
+            tenants_file: None,
+            audit_log_file: None,
+            record_file: None,
+            retention: RetentionPolicy::default(),
+            retention_interval: Duration::from_secs(300),
+            usage_export_file: None,
+            usage_export_interval: Duration::from_secs(300),
+            compaction: None,
+            binance_combined_stream: false,
+            binance_futures: false,
+            binance_us: false,
+            bitstamp_diff_book: false,
+            fx_rates: HashMap::new(),
+            tape: false,
+            endpoints: HashMap::new(),
+            update_speeds: HashMap::new(),
+            venue_params: HashMap::new(),
+            venue_symbols: HashMap::new(),
+            check_symbols: false,
+            fair_value_model: FairValueModel::new(),
+            webhook_url: None,
+            redis_url: None,
+            webhook_json_format: JsonFormat::Canonical,
+            redis_json_format: JsonFormat::Canonical,
+            sinks: Vec::new(),
+            summary_history: None,
+            deviation_threshold_bps: None,
+            deviation_sustain: Duration::from_secs(5),
+            quarantine_deviating_venues: false,
+            wall_multiple: None,
+            wall_window: Duration::from_secs(60),
+            dns_refresh_interval: Duration::from_secs(60),
+            toxicity_bucket_volume: 10.0,
+            toxicity_buckets: 50,
+            stale_after: Duration::from_secs(10),
+            idle_shutdown: None,
+            indexes: BTreeMap::new(),
+            max_touch_distance_bps: HashMap::new(),
+            max_reconnects_per_minute: None,
+            max_venue_reconnects_per_minute: HashMap::new(),
+            symbol_settings: BTreeMap::new(),
+            latency_compensation: None,
+            reference_venue: None,
+            reference_band_bps: 10.0,
+            metrics_addr: None,
+            profile_addr: None,
+            csv_addr: None,
+            runtime: None,
+            addr: "0.0.0.0:50051".parse().unwrap(),
+        }
+    }
+
+    // Parses <symbol> [depth] [--flag value]..., returns None if the symbol is missing
+    pub fn from_args(args: &[String]) -> Option<ServerOptions> {
+        let symbol = args.get(1).filter(|symbol| !symbol.starts_with("--"))?;
+        let depth = args.get(2).and_then(|d| d.parse().ok()).unwrap_or(10);
+        let defaults = ServerOptions::new(symbol, depth);
+        // --venue-symbol <exchange>=<symbol>
+        let venue_symbols: HashMap<String, String> = flag_values(args, "--venue-symbol")
+            .iter()
+            .filter_map(|symbol| symbol.split_once('='))
+            .map(|(exchange, symbol)| (exchange.to_string(), symbol.to_string()))
+            .collect();
+        let bitstamp_symbol = flag_value(args, "--bitstamp-symbol")
+            .or_else(|| venue_symbols.get("bitstamp").cloned())
+            .unwrap_or(defaults.bitstamp_symbol);
+        let check_symbols = args.iter().any(|arg| arg == "--check-symbols");
+        let depeg_threshold_bps = flag_value(args, "--depeg-threshold-bps")
+            .and_then(|bps| bps.parse().ok())
+            .unwrap_or(defaults.depeg_threshold_bps);
+        let bitstamp_channels_per_socket = flag_value(args, "--bitstamp-channels-per-socket")
+            .and_then(|channels| channels.parse().ok())
+            .unwrap_or(defaults.bitstamp_channels_per_socket);
+        let connectors = flag_values(args, "--connector");
+        // --script <symbol>=<path>, only the script of the served symbol is used
+        let scripts = flag_values(args, "--script")
+            .iter()
+            .filter_map(|script| script.split_once('='))
+            .map(|(symbol, path)| (symbol.to_string(), path.to_string()))
+            .collect();
+        let tenants_file = flag_value(args, "--tenants");
+        let audit_log_file = flag_value(args, "--audit-log");
+        let record_file = flag_value(args, "--record");
+        let usage_export_file = flag_value(args, "--usage-export");
+        let usage_export_interval = flag_value(args, "--usage-export-interval-secs")
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(defaults.usage_export_interval);
+        let compaction = flag_value(args, "--compaction-distance-bps")
+            .and_then(|bps| bps.parse().ok())
+            .map(|bps| {
+                let mut policy = CompactionPolicy::new(bps);
+                if let Some(hysteresis_bps) =
+                    flag_value(args, "--compaction-hysteresis-bps").and_then(|bps| bps.parse().ok())
+                {
+                    policy.hysteresis_bps = hysteresis_bps;
+                }
+                policy
+            });
+        // --fair-value-weighting equal ignores the top of book volume of the venues
+        let fair_value_model = FairValueModel {
+            volume_weighted: flag_value(args, "--fair-value-weighting").as_deref() != Some("equal"),
+            // --venue-weight <exchange>=<weight>
+            venue_weights: flag_values(args, "--venue-weight")
+                .iter()
+                .filter_map(|weight| weight.split_once('='))
+                .filter_map(|(exchange, weight)| Some((exchange.to_string(), weight.parse().ok()?)))
+                .collect(),
+        };
+        let webhook_url = flag_value(args, "--webhook-url");
+        let redis_url = flag_value(args, "--redis-url");
+        // --webhook-json-format legacy, canonical otherwise
+        let json_format = |flag| {
+            flag_value(args, flag)
+                .and_then(|name| JsonFormat::from_name(&name))
+                .unwrap_or_default()
+        };
+        let webhook_json_format = json_format("--webhook-json-format");
+        let redis_json_format = json_format("--redis-json-format");
+        let summary_history = flag_value(args, "--summary-history")
+            .and_then(|count| count.parse().ok())
+            .filter(|count| *count > 0);
+        let deviation_threshold_bps =
+            flag_value(args, "--deviation-threshold-bps").and_then(|bps| bps.parse().ok());
+        let deviation_sustain = flag_value(args, "--deviation-sustain-ms")
+            .and_then(|ms| ms.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.deviation_sustain);
+        let quarantine_deviating_venues = args
+            .iter()
+            .any(|arg| arg == "--quarantine-deviating-venues");
+        let wall_multiple = flag_value(args, "--wall-multiple")
+            .and_then(|multiple| multiple.parse().ok())
+            .filter(|multiple: &f64| *multiple > 1.0);
+        let wall_window = flag_value(args, "--wall-window-secs")
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(defaults.wall_window);
+        let binance_combined_stream = args.iter().any(|arg| arg == "--binance-combined-stream");
+        let binance_futures = args.iter().any(|arg| arg == "--binance-futures");
+        let binance_us = args.iter().any(|arg| arg == "--binance-us");
+        let bitstamp_diff_book = args.iter().any(|arg| arg == "--bitstamp-diff-book");
+        let tape = args.iter().any(|arg| arg == "--tape");
+        // --fx-rate <currency>=<rate>, e.g. krw=1380
+        let fx_rates = flag_values(args, "--fx-rate")
+            .iter()
+            .filter_map(|rate| rate.split_once('='))
+            .filter_map(|(currency, rate)| Some((currency.to_uppercase(), rate.parse().ok()?)))
+            .collect();
+        let retention = RetentionPolicy {
+            max_age: flag_value(args, "--retention-max-age-hours")
+                .and_then(|hours| hours.parse::<u64>().ok())
+                .map(|hours| Duration::from_secs(hours * 3600)),
+            max_total_bytes: flag_value(args, "--retention-max-mb")
+                .and_then(|mb| mb.parse::<u64>().ok())
+                .map(|mb| mb * 1024 * 1024),
+        };
+        let retention_interval = flag_value(args, "--retention-interval-secs")
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(defaults.retention_interval);
+        let dns_refresh_interval = flag_value(args, "--dns-refresh-secs")
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(defaults.dns_refresh_interval);
+        let toxicity_bucket_volume = flag_value(args, "--toxicity-bucket-volume")
+            .and_then(|volume| volume.parse().ok())
+            .unwrap_or(defaults.toxicity_bucket_volume);
+        let toxicity_buckets = flag_value(args, "--toxicity-buckets")
+            .and_then(|buckets| buckets.parse().ok())
+            .unwrap_or(defaults.toxicity_buckets);
+        let stale_after = flag_value(args, "--stale-after-ms")
+            .and_then(|ms| ms.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.stale_after);
+        let idle_shutdown = flag_value(args, "--idle-shutdown-secs")
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs);
+        let latency_compensation = flag_value(args, "--latency-compensation-max-skew-ms")
+            .and_then(|ms| ms.parse().ok())
+            .map(Duration::from_millis);
+        let reference_venue = flag_value(args, "--reference-venue");
+        let reference_band_bps = flag_value(args, "--reference-band-bps")
+            .and_then(|bps| bps.parse().ok())
+            .unwrap_or(defaults.reference_band_bps);
+        let metrics_addr = flag_value(args, "--metrics-addr").and_then(|addr| addr.parse().ok());
+        let profile_addr = flag_value(args, "--profile-addr").and_then(|addr| addr.parse().ok());
+        let csv_addr = flag_value(args, "--csv-addr").and_then(|addr| addr.parse().ok());
+        // --endpoint <exchange>=<url> and --update-speed-ms <exchange>=<ms>
+        let endpoints = flag_values(args, "--endpoint")
+            .iter()
+            .filter_map(|endpoint| endpoint.split_once('='))
+            .map(|(exchange, url)| (exchange.to_string(), url.to_string()))
+            .collect();
+        let update_speeds = flag_values(args, "--update-speed-ms")
+            .iter()
+            .filter_map(|speed| speed.split_once('='))
+            .filter_map(|(exchange, ms)| {
+                Some((
+                    exchange.to_string(),
+                    Duration::from_millis(ms.parse().ok()?),
+                ))
+            })
+            .collect();
+        // --venue-param <exchange>.<key>=<value>, in the order given
+        let mut venue_params: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        for param in flag_values(args, "--venue-param") {
+            let Some((name_key, value)) = param.split_once('=') else {
+                continue;
+            };
+            let Some((name, key)) = name_key.split_once('.') else {
+                continue;
+            };
+            venue_params
+                .entry(name.to_string())
+                .or_default()
+                .push((key.to_string(), value.to_string()));
+        }
+        // --max-touch-distance-bps <exchange>=<bps>
+        let max_touch_distance_bps = flag_values(args, "--max-touch-distance-bps")
+            .iter()
+            .filter_map(|limit| limit.split_once('='))
+            .filter_map(|(exchange, bps)| Some((exchange.to_string(), bps.parse().ok()?)))
+            .collect();
+        let max_reconnects_per_minute = flag_value(args, "--max-reconnects-per-minute")
+            .and_then(|cap| cap.parse().ok())
+            .filter(|cap| *cap > 0);
+        // --max-venue-reconnects-per-minute <exchange>=<n>
+        let max_venue_reconnects_per_minute =
+            flag_values(args, "--max-venue-reconnects-per-minute")
+                .iter()
+                .filter_map(|cap| cap.split_once('='))
+                .filter_map(|(exchange, cap)| Some((exchange.to_string(), cap.parse().ok()?)))
+                .filter(|(_, cap)| *cap > 0)
+                .collect();
+
+        Some(ServerOptions {
+            bitstamp_symbol,
+            depeg_threshold_bps,
+            bitstamp_channels_per_socket,
+            connectors,
+            scripts,
+            tenants_file,
+            audit_log_file,
+            record_file,
+            usage_export_file,
+            usage_export_interval,
+            retention,
+            retention_interval,
+            compaction,
+            binance_combined_stream,
+            binance_futures,
+            binance_us,
+            bitstamp_diff_book,
+            fx_rates,
+            tape,
+            endpoints,
+            update_speeds,
+            venue_params,
+            venue_symbols,
+            check_symbols,
+            fair_value_model,
+            webhook_url,
+            redis_url,
+            webhook_json_format,
+            redis_json_format,
+            summary_history,
+            deviation_threshold_bps,
+            deviation_sustain,
+            quarantine_deviating_venues,
+            wall_multiple,
+            wall_window,
+            dns_refresh_interval,
+            toxicity_bucket_volume,
+            toxicity_buckets,
+            stale_after,
+            idle_shutdown,
+            max_touch_distance_bps,
+            max_reconnects_per_minute,
+            max_venue_reconnects_per_minute,
+            latency_compensation,
+            reference_venue,
+            reference_band_bps,
+            metrics_addr,
+            profile_addr,
+            csv_addr,
+            ..defaults
+        })
+    }
+}
+
+pub const USAGE: &str = "Usage: cargo run -- <symbol> [depth] [--bitstamp-symbol <symbol>] [--depeg-threshold-bps <bps>] [--bitstamp-channels-per-socket <channels>] [--connector <name>]... [--script <symbol>=<path>]... [--tenants <path>] [--audit-log <path>] [--record <path>] [--usage-export <path>] [--usage-export-interval-secs <secs>] [--retention-max-age-hours <hours>] [--retention-max-mb <mb>] [--retention-interval-secs <secs>] [--compaction-distance-bps <bps>] [--compaction-hysteresis-bps <bps>] [--binance-combined-stream] [--binance-futures] [--binance-us] [--bitstamp-diff-book] [--fx-rate <currency>=<rate>]... [--tape] [--endpoint <exchange>=<url>]... [--update-speed-ms <exchange>=<ms>]... [--venue-param <exchange>.<key>=<value>]... [--venue-symbol <exchange>=<symbol>]... [--check-symbols] [--webhook-url <url>] [--redis-url <redis://host:port/channel>] [--webhook-json-format canonical|legacy] [--redis-json-format canonical|legacy] [--summary-history <count>] [--fair-value-weighting volume|equal] [--venue-weight <exchange>=<weight>]... [--deviation-threshold-bps <bps>] [--deviation-sustain-ms <ms>] [--quarantine-deviating-venues] [--wall-multiple <multiple>] [--wall-window-secs <secs>] [--dns-refresh-secs <secs>] [--toxicity-bucket-volume <volume>] [--toxicity-buckets <buckets>] [--stale-after-ms <ms>] [--idle-shutdown-secs <secs>] [--max-touch-distance-bps <exchange>=<bps>]... [--max-reconnects-per-minute <n>] [--max-venue-reconnects-per-minute <exchange>=<n>]... [--latency-compensation-max-skew-ms <ms>] [--reference-venue <exchange>] [--reference-band-bps <bps>] [--metrics-addr <addr>] [--profile-addr <addr>] [--csv-addr <addr>] [--preset <name>] [--config <path>] [--dry-run]";
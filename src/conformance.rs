@@ -0,0 +1,133 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::connectors::{
+    apply_connector_message, connect_connector, unsubscribe_connector, ExchangeConnector,
+};
+use crate::mock_exchange::{MockExchange, MockSession};
+use crate::number::decimal_to_f64;
+use tungstenite::client::AutoStream;
+use tungstenite::{Message, WebSocket};
+
+type Levels = (Vec<(f64, f64)>, Vec<(f64, f64)>);
+
+// Exchange specific frames every connector provides to the conformance suite. The
+// updates follow the snapshot with the book expected after each of them: the changed
+// levels for connectors that maintain a local book, a later snapshot replacing the
+// book for the others. gap is only set for connectors that can detect lost updates
+pub struct ConformanceFixture {
+    pub symbol: &'static str,
+    pub ack: String,
+    pub snapshot: String,
+    pub expected_snapshot: Levels,
+    pub updates: Vec<(String, Levels)>,
+    pub gap: Option<String>,
+}
+
+fn levels(orderbook: &OrderBook) -> Levels {
+    let doubles = |levels: &[PriceAmountLevel]| {
+        levels
+            .iter()
+            .map(|level| (decimal_to_f64(level.price), decimal_to_f64(level.amount)))
+            .collect()
+    };
+    (doubles(&orderbook.bids), doubles(&orderbook.asks))
+}
+
+fn next_message(socket: &mut WebSocket<AutoStream>) -> String {
+    match socket
+        .read_message()
+        .expect("Mock exchange closed the stream")
+    {
+        Message::Text(message_text) => message_text,
+        message => panic!("Unexpected message from mock exchange: {:?}", message),
+    }
+}
+
+// Runs every connector through the same scripted sessions: a rejected subscription,
+// a session with snapshot, updates and gap that is closed by the exchange, and a
+// reconnected session that is unsubscribed by the client
+pub fn run_conformance(
+    make_connector: impl Fn(&str) -> Box<dyn ExchangeConnector>,
+    fixture: ConformanceFixture,
+) {
+    let symbol = fixture.symbol;
+    assert!(
+        !fixture.updates.is_empty(),
+        "The fixture has no update after the snapshot"
+    );
+    let mut frames = vec![fixture.snapshot.clone()];
+    frames.extend(fixture.updates.iter().map(|(update, _)| update.clone()));
+    if let Some(gap) = &fixture.gap {
+        frames.push(gap.clone());
+    }
+
+    let exchange = MockExchange::start(vec![
+        MockSession {
+            ack: r#"{"error": "invalid subscription"}"#.to_string(),
+            frames: Vec::new(),
+            close: true,
+        },
+        MockSession {
+            ack: fixture.ack.clone(),
+            frames,
+            close: true,
+        },
+        MockSession {
+            ack: fixture.ack.clone(),
+            frames: vec![fixture.snapshot.clone()],
+            close: false,
+        },
+    ]);
+    let mut connector = make_connector(&exchange.url);
+
+    // Subscribe ack handling
+    assert!(connect_connector(connector.as_ref(), symbol).is_err());
+    let mut socket = connect_connector(connector.as_ref(), symbol).unwrap();
+
+    // Snapshot correctness
+    let orderbook = apply_connector_message(connector.as_mut(), &next_message(&mut socket), 10)
+        .expect("Snapshot was not applied");
+    assert_eq!(levels(&orderbook), fixture.expected_snapshot);
+
+    // Deltas and replacing snapshots
+    for (_, expected_update) in &fixture.updates {
+        let orderbook = apply_connector_message(connector.as_mut(), &next_message(&mut socket), 10)
+            .expect("Update was not applied");
+        assert_eq!(&levels(&orderbook), expected_update);
+        assert!(!connector.needs_resync());
+    }
+
+    // Gap handling
+    if fixture.gap.is_some() {
+        assert!(
+            apply_connector_message(connector.as_mut(), &next_message(&mut socket), 10).is_none()
+        );
+        assert!(connector.needs_resync());
+    }
+
+    // Reconnect after the exchange closed the stream
+    while let Ok(message) = socket.read_message() {
+        assert!(message.is_close(), "Unexpected message: {:?}", message);
+    }
+    connector.reset();
+    assert!(!connector.needs_resync());
+    let mut socket = connect_connector(connector.as_ref(), symbol).unwrap();
+    let orderbook = apply_connector_message(connector.as_mut(), &next_message(&mut socket), 10)
+        .expect("Snapshot was not applied after reconnect");
+    assert_eq!(levels(&orderbook), fixture.expected_snapshot);
+
+    // Unsubscribe
+    unsubscribe_connector(connector.as_ref(), &mut socket, symbol).unwrap();
+    drop(socket);
+
+    // The connectors subscribe the symbol as the exchange lists it
+    let symbol = connector.normalize_symbol(symbol);
+    let subscribe_messages = connector.subscribe_messages(&symbol);
+    let expected_messages = [
+        subscribe_messages.clone(),
+        subscribe_messages.clone(),
+        subscribe_messages,
+        connector.unsubscribe_messages(&symbol),
+    ]
+    .concat();
+    assert_eq!(exchange.finish(), expected_messages);
+}
@@ -0,0 +1,243 @@
+use crate::connectors::AsyncSocket;
+use crate::resolver::endpoint_resolver;
+use native_tls::TlsConnector;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::task::spawn_blocking;
+use tungstenite::client::AutoStream;
+use tungstenite::handshake::client::Response;
+use tungstenite::WebSocket;
+use url::Url;
+
+// Connection limits of a venue, from its API documentation where it has one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct VenueLimits {
+    // Connections opened at the same time
+    pub max_concurrent_connects: usize,
+    // New connections allowed per window, per IP
+    pub max_connects: usize,
+    pub window: Duration,
+    // Streams or channels a single connection can be subscribed to
+    pub max_streams_per_connection: usize,
+}
+
+const DEFAULT_LIMITS: VenueLimits = VenueLimits {
+    max_concurrent_connects: 5,
+    max_connects: 300,
+    window: Duration::from_secs(300),
+    max_streams_per_connection: usize::MAX,
+};
+
+pub(crate) fn venue_limits(host: &str) -> VenueLimits {
+    match host {
+        // 300 connections per 5 minutes per IP, 1024 streams per connection, the same
+        // for the USD-M futures streams
+        "stream.binance.com" | "stream.binance.us" | "fstream.binance.com" => VenueLimits {
+            max_streams_per_connection: 1024,
+            ..DEFAULT_LIMITS
+        },
+        // 500 connections per 5 minutes per IP
+        "stream.bybit.com" => VenueLimits {
+            max_connects: 500,
+            ..DEFAULT_LIMITS
+        },
+        _ => DEFAULT_LIMITS,
+    }
+}
+
+#[derive(Debug, Default)]
+struct VenueState {
+    connecting: usize,
+    recent_connects: VecDeque<Instant>,
+}
+
+// How long a new connection has to wait for the venue's limits, None if it can connect
+fn connect_delay(state: &mut VenueState, limits: &VenueLimits, now: Instant) -> Option<Duration> {
+    while let Some(oldest) = state.recent_connects.front() {
+        if now.duration_since(*oldest) < limits.window {
+            break;
+        }
+        state.recent_connects.pop_front();
+    }
+    if state.recent_connects.len() >= limits.max_connects {
+        let oldest = state.recent_connects[0];
+        return Some(limits.window.saturating_sub(now.duration_since(oldest)));
+    }
+    if state.connecting >= limits.max_concurrent_connects {
+        // Woken up when a connection attempt ends
+        return Some(Duration::MAX);
+    }
+    None
+}
+
+// Opens every exchange connection of the process. The addresses come from the shared
+// resolver, the TLS context is built once and reused, and the connections to a venue
+// are paced to its documented limits, so many symbols and channels don't get the
+// server's IP banned
+pub(crate) struct ConnectionManager {
+    venues: Mutex<HashMap<String, VenueState>>,
+    connect_ended: Condvar,
+    tls_connector: OnceLock<Result<TlsConnector, String>>,
+}
+
+struct ConnectPermit<'a> {
+    manager: &'a ConnectionManager,
+    host: String,
+}
+
+impl Drop for ConnectPermit<'_> {
+    fn drop(&mut self) {
+        if let Some(state) = self.manager.venues().get_mut(&self.host) {
+            state.connecting -= 1;
+        }
+        self.manager.connect_ended.notify_all();
+    }
+}
+
+impl ConnectionManager {
+    fn new() -> ConnectionManager {
+        ConnectionManager {
+            venues: Mutex::new(HashMap::new()),
+            connect_ended: Condvar::new(),
+            tls_connector: OnceLock::new(),
+        }
+    }
+
+    fn venues(&self) -> MutexGuard<'_, HashMap<String, VenueState>> {
+        self.venues.lock().unwrap()
+    }
+
+    // The root certificates are loaded once, the connections share the TLS context
+    pub(crate) fn tls_connector(&self) -> Result<TlsConnector, Box<dyn Error>> {
+        self.tls_connector
+            .get_or_init(|| TlsConnector::new().map_err(|err| err.to_string()))
+            .clone()
+            .map_err(|err| err.into())
+    }
+
+    // Blocks until the venue's limits allow one more connection
+    fn acquire(&self, host: &str) -> ConnectPermit<'_> {
+        let limits = venue_limits(host);
+        let mut venues = self.venues();
+        loop {
+            let state = venues.entry(host.to_string()).or_default();
+            let now = Instant::now();
+            match connect_delay(state, &limits, now) {
+                None => {
+                    state.connecting += 1;
+                    state.recent_connects.push_back(now);
+                    break;
+                }
+                Some(Duration::MAX) => venues = self.connect_ended.wait(venues).unwrap(),
+                Some(delay) => {
+                    eprintln!(
+                        "Connection limit of {} reached, waiting {} ms",
+                        host,
+                        delay.as_millis()
+                    );
+                    venues = self.connect_ended.wait_timeout(venues, delay).unwrap().0;
+                }
+            }
+        }
+        ConnectPermit {
+            manager: self,
+            host: host.to_string(),
+        }
+    }
+
+    pub(crate) fn connect(
+        &self,
+        url: &Url,
+    ) -> Result<(WebSocket<AutoStream>, Response), Box<dyn Error>> {
+        let host = url.host_str().ok_or("No host name in the url")?;
+        let _permit = self.acquire(host);
+        let tls_connector = self.tls_connector()?;
+        endpoint_resolver().connect(url, &tls_connector)
+    }
+
+    // Same as connect without blocking the runtime while waiting for the venue's limits
+    pub(crate) async fn connect_async(
+        &'static self,
+        url: &Url,
+    ) -> Result<(AsyncSocket, Response), Box<dyn Error>> {
+        let host = url.host_str().ok_or("No host name in the url")?.to_string();
+        let _permit = spawn_blocking(move || self.acquire(&host)).await?;
+        let tls_connector = self.tls_connector()?;
+        endpoint_resolver().connect_async(url, &tls_connector).await
+    }
+
+    // Channels per socket when spreading subscriptions over several connections,
+    // the requested number within the venue's limit
+    pub(crate) fn streams_per_connection(&self, url: &str, requested: usize) -> usize {
+        let limit = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(venue_limits))
+            .unwrap_or(DEFAULT_LIMITS)
+            .max_streams_per_connection;
+        requested.clamp(1, limit)
+    }
+}
+
+// Shared by every exchange connection of the process
+pub(crate) fn connection_manager() -> &'static ConnectionManager {
+    static MANAGER: OnceLock<ConnectionManager> = OnceLock::new();
+    MANAGER.get_or_init(ConnectionManager::new)
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_delay() {
+        let limits = VenueLimits {
+            max_concurrent_connects: 1,
+            max_connects: 2,
+            window: Duration::from_secs(10),
+            max_streams_per_connection: 3,
+        };
+        let now = Instant::now();
+        let mut state = VenueState::default();
+        assert_eq!(connect_delay(&mut state, &limits, now), None);
+
+        // One connection at a time
+        state.connecting = 1;
+        state.recent_connects.push_back(now);
+        assert_eq!(connect_delay(&mut state, &limits, now), Some(Duration::MAX));
+        state.connecting = 0;
+
+        // Two per 10 seconds, the next one when the oldest leaves the window
+        state
+            .recent_connects
+            .push_back(now + Duration::from_secs(4));
+        assert_eq!(
+            connect_delay(&mut state, &limits, now + Duration::from_secs(6)),
+            Some(Duration::from_secs(4))
+        );
+        assert_eq!(
+            connect_delay(&mut state, &limits, now + Duration::from_secs(10)),
+            None
+        );
+        assert_eq!(state.recent_connects.len(), 1);
+    }
+
+    #[test]
+    fn test_streams_per_connection() {
+        let manager = ConnectionManager::new();
+        assert_eq!(
+            manager.streams_per_connection("wss://stream.binance.com:9443/ws", 2000),
+            1024
+        );
+        assert_eq!(
+            manager.streams_per_connection("wss://ws.bitstamp.net/", 50),
+            50
+        );
+        assert_eq!(
+            manager.streams_per_connection("wss://ws.bitstamp.net/", 0),
+            1
+        );
+    }
+}
@@ -0,0 +1,581 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::connectors::{
+    connect_connector, error_code, orderbook_from_data, param_choice, process_message,
+    unsupported_param, ErrorAction, ExchangeConnector, ExchangeError,
+};
+use crate::merge::with_best_levels;
+use crate::number::{json_decimal, json_f64};
+use crate::symbols::joined_symbol;
+use crate::tape::{TakerSide, TapeTrade};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::error::Error;
+use std::time::Duration;
+use tungstenite::client::AutoStream;
+use tungstenite::WebSocket;
+
+// Best bid and ask of the bookTicker stream, sent on every change of the top of book
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookTicker {
+    // Orders the ticker with the depth updates, whose lastUpdateId is on the same sequence
+    pub update_id: u64,
+    pub bid_price: Decimal,
+    pub bid_amount: Decimal,
+    pub ask_price: Decimal,
+    pub ask_amount: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub price: f64,
+    pub amount: f64,
+    // The buyer was the resting order, so the trade was a sell
+    pub buyer_is_maker: bool,
+}
+
+// A payload of the binance combined stream endpoint, {"stream": "<name>", "data": {...}}
+#[derive(Debug, Clone)]
+pub enum BinanceStreamEvent {
+    Depth {
+        orderbook: OrderBook,
+        last_update_id: Option<u64>,
+    },
+    BookTicker(BookTicker),
+    Trade(Trade),
+}
+
+// Unwraps a combined stream message and parses the payload by the stream it came
+// from, None for other messages such as subscription acks
+pub fn demux_binance_stream(message_text: &str, depth: usize) -> Option<BinanceStreamEvent> {
+    let result = serde_json::from_str::<Value>(message_text).ok()?;
+    let stream = result["stream"].as_str()?;
+    let data = &result["data"];
+    if stream.ends_with("@bookTicker") {
+        Some(BinanceStreamEvent::BookTicker(BookTicker {
+            update_id: data["u"].as_u64()?,
+            bid_price: json_decimal(&data["b"])?,
+            bid_amount: json_decimal(&data["B"])?,
+            ask_price: json_decimal(&data["a"])?,
+            ask_amount: json_decimal(&data["A"])?,
+        }))
+    } else if stream.ends_with("@trade") {
+        Some(BinanceStreamEvent::Trade(Trade {
+            price: json_f64(&data["p"])?,
+            amount: json_f64(&data["q"])?,
+            buyer_is_maker: data["m"].as_bool()?,
+        }))
+    } else if stream.contains("@depth") {
+        Some(BinanceStreamEvent::Depth {
+            orderbook: orderbook_from_data(data, "binance", depth)?,
+            last_update_id: data["lastUpdateId"].as_u64(),
+        })
+    } else {
+        None
+    }
+}
+
+// A depthUpdate of the USD-M futures partial book depth streams, the top levels are in
+// "b" and "a" rather than "bids" and "asks"
+pub fn futures_depth_orderbook(message_text: &str, depth: usize) -> Option<OrderBook> {
+    let result = serde_json::from_str::<Value>(message_text).ok()?;
+    if result["e"] != "depthUpdate" {
+        return None;
+    }
+    let levels = serde_json::json!({"bids": result["b"], "asks": result["a"]});
+    orderbook_from_data(&levels, "binance_futures", depth)
+}
+
+pub async fn binance_connect(
+    symbol: &str,
+    depth: u32,
+) -> Result<WebSocket<AutoStream>, Box<dyn Error>> {
+    connect_connector(&BinanceConnector::new(depth), symbol)
+}
+
+// {"error": {"code": 2, "msg": "Invalid request"}, "id": 1}, only -1003 (too many
+// requests) goes away by itself, the other codes are invalid requests. The spot and
+// futures streams send the same errors
+pub(crate) fn binance_error(message_text: &str) -> Option<ExchangeError> {
+    let result = serde_json::from_str::<Value>(message_text).ok()?;
+    let error = result.get("error")?;
+    let code = error_code(&error["code"]);
+    let action = match code.as_deref() {
+        Some("-1003") => ErrorAction::BackOff,
+        _ => ErrorAction::Disable,
+    };
+    Some(ExchangeError {
+        code,
+        message: error["msg"]
+            .as_str()
+            .or(error.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        action,
+    })
+}
+
+pub struct BinanceConnector {
+    url: String,
+    depth: u32,
+    // Reads the combined stream endpoint, the depth, bookTicker and trade streams of
+    // the symbol on one socket, every payload wrapped with the name of its stream
+    combined: bool,
+    // Reads the USD-M futures streams of fstream.binance.com instead of the spot ones
+    futures: bool,
+    // Host of the REST API, api.binance.us for Binance.US
+    rest_host: &'static str,
+    update_speed: Duration,
+    book_ticker: Option<BookTicker>,
+    last_trade: Option<Trade>,
+    // Latest depth update and its lastUpdateId, the bookTicker updates its top of book
+    // in between depth updates
+    last_depth: Option<(OrderBook, Option<u64>)>,
+    bbo_only: bool,
+    // Trades of the combined stream kept for the tape, only once enabled
+    tape: Option<Vec<TapeTrade>>,
+}
+
+impl BinanceConnector {
+    pub fn new(depth: u32) -> BinanceConnector {
+        BinanceConnector::with_url("wss://stream.binance.com:9443/ws", depth)
+    }
+
+    pub fn with_url(url: &str, depth: u32) -> BinanceConnector {
+        BinanceConnector {
+            url: url.to_string(),
+            depth,
+            combined: false,
+            futures: false,
+            rest_host: "api.binance.com",
+            update_speed: Duration::from_millis(100),
+            book_ticker: None,
+            last_trade: None,
+            last_depth: None,
+            bbo_only: false,
+            tape: None,
+        }
+    }
+
+    // The streams are subscribed with a message like on the raw endpoint, rather than
+    // in the url's ?streams=, so the url doesn't depend on the symbol
+    pub fn combined(depth: u32) -> BinanceConnector {
+        BinanceConnector::combined_with_url("wss://stream.binance.com:9443/stream", depth)
+    }
+
+    pub fn combined_with_url(url: &str, depth: u32) -> BinanceConnector {
+        BinanceConnector {
+            combined: true,
+            ..BinanceConnector::with_url(url, depth)
+        }
+    }
+
+    // The partial book depth streams of the USD-M futures, merged as binance_futures
+    // next to the spot book or as the perpetual leg of the basis stream
+    pub fn futures(depth: u32) -> BinanceConnector {
+        BinanceConnector::futures_with_url("wss://fstream.binance.com/ws", depth)
+    }
+
+    pub fn futures_with_url(url: &str, depth: u32) -> BinanceConnector {
+        BinanceConnector {
+            futures: true,
+            ..BinanceConnector::with_url(url, depth)
+        }
+    }
+
+    // The same streams on Binance.US, for US users, whose symbols and messages are
+    // those of the global endpoint
+    pub fn us(self) -> BinanceConnector {
+        BinanceConnector {
+            url: self.url.replace("stream.binance.com", "stream.binance.us"),
+            rest_host: "api.binance.us",
+            ..self
+        }
+    }
+
+    // Latest top of book and trade of the combined stream
+    pub fn book_ticker(&self) -> Option<&BookTicker> {
+        self.book_ticker.as_ref()
+    }
+
+    pub fn last_trade(&self) -> Option<&Trade> {
+        self.last_trade.as_ref()
+    }
+
+    // binance support two update speeds - 1000ms or 100ms, and 250ms, 500ms or 100ms for
+    // the futures, the suffix-less streams are the 1000ms and 250ms ones
+    fn speed_suffix(&self) -> &'static str {
+        match (self.futures, self.update_speed.as_millis()) {
+            (false, 1000..) => "",
+            (false, _) => "@100ms",
+            (true, 500..) => "@500ms",
+            (true, 250..) => "",
+            (true, _) => "@100ms",
+        }
+    }
+
+    fn stream_names(&self, symbol: &str) -> String {
+        let symbol = symbol.to_lowercase();
+        let depth_stream = format!(r#""{}@depth{}{}""#, symbol, self.depth, self.speed_suffix());
+        if self.combined {
+            format!(
+                r#"{}, "{}@bookTicker", "{}@trade""#,
+                depth_stream, symbol, symbol
+            )
+        } else {
+            depth_stream
+        }
+    }
+}
+
+impl ExchangeConnector for BinanceConnector {
+    fn name(&self) -> &str {
+        if self.futures {
+            "Binance Futures"
+        } else {
+            "Binance"
+        }
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        joined_symbol(symbol).to_lowercase()
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        if self.futures {
+            return Some(format!(
+                "https://fapi.binance.com/fapi/v1/depth?symbol={}&limit=5",
+                symbol.to_uppercase()
+            ));
+        }
+        Some(format!(
+            "https://{}/api/v3/exchangeInfo?symbol={}",
+            self.rest_host,
+            symbol.to_uppercase()
+        ))
+    }
+
+    fn instruments_url(&self) -> Option<String> {
+        if self.futures {
+            return Some("https://fapi.binance.com/fapi/v1/exchangeInfo".to_string());
+        }
+        Some(format!("https://{}/api/v3/exchangeInfo", self.rest_host))
+    }
+
+    // {"symbols": [{"symbol": "BTCUSDT", "status": "TRADING", ...}, ...]}
+    fn listed_symbols(&self, body: &str) -> Vec<String> {
+        let info = serde_json::from_str::<Value>(body).unwrap_or_default();
+        let symbols = info["symbols"].as_array().cloned().unwrap_or_default();
+        symbols
+            .iter()
+            .filter(|symbol| symbol["status"] == "TRADING")
+            .filter_map(|symbol| Some(symbol["symbol"].as_str()?.to_lowercase()))
+            .collect()
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![format!(
+            r#"{{"method": "SUBSCRIBE", "params": [{}], "id": 1}}"#,
+            self.stream_names(symbol)
+        )]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![format!(
+            r#"{{"method": "UNSUBSCRIBE", "params": [{}], "id": 2}}"#,
+            self.stream_names(symbol)
+        )]
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["result"].is_null() && ack["id"] == 1
+    }
+
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        binance_error(message_text)
+    }
+
+    // The event time of the futures depth updates, in ms, the spot partial book depth
+    // streams have none
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        if !self.futures {
+            return None;
+        }
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["E"].as_u64()
+    }
+
+    // Partial book depth streams send the top levels on every update
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        if self.futures {
+            return futures_depth_orderbook(message_text, depth);
+        }
+        if !self.combined {
+            return process_message(message_text, "binance", depth);
+        }
+        match demux_binance_stream(message_text, depth)? {
+            BinanceStreamEvent::Depth {
+                orderbook,
+                last_update_id,
+            } => {
+                self.last_depth = Some((orderbook.clone(), last_update_id));
+                self.bbo_only = false;
+                Some(orderbook)
+            }
+            // The ticker is sent on every change of the top of book, far more often than
+            // the 100ms depth updates, tickers the depth update already covers are skipped
+            BinanceStreamEvent::BookTicker(book_ticker) => {
+                let level = |price, amount| PriceAmountLevel {
+                    exchange: "binance".to_string(),
+                    price,
+                    amount,
+                };
+                let orderbook = match &self.last_depth {
+                    Some((orderbook, last_update_id))
+                        if last_update_id.is_none_or(|id| book_ticker.update_id > id) =>
+                    {
+                        with_best_levels(
+                            orderbook,
+                            level(book_ticker.bid_price, book_ticker.bid_amount),
+                            level(book_ticker.ask_price, book_ticker.ask_amount),
+                            depth,
+                        )
+                    }
+                    _ => {
+                        self.book_ticker = Some(book_ticker);
+                        return None;
+                    }
+                };
+                self.book_ticker = Some(book_ticker);
+                self.bbo_only = true;
+                Some(orderbook)
+            }
+            BinanceStreamEvent::Trade(trade) => {
+                if let Some(tape) = &mut self.tape {
+                    tape.push(TapeTrade {
+                        exchange: "binance".to_string(),
+                        price: trade.price,
+                        amount: trade.amount,
+                        side: if trade.buyer_is_maker {
+                            TakerSide::Sell
+                        } else {
+                            TakerSide::Buy
+                        },
+                    });
+                }
+                self.last_trade = Some(trade);
+                None
+            }
+        }
+    }
+
+    fn bbo_only(&self) -> bool {
+        self.bbo_only
+    }
+
+    // The trade stream is only read on the combined stream endpoint
+    fn enable_trades(&mut self) {
+        self.tape = Some(Vec::new());
+    }
+
+    fn take_trades(&mut self) -> Vec<TapeTrade> {
+        self.tape.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn set_update_speed(&mut self, speed: Duration) {
+        self.update_speed = speed;
+    }
+
+    // update_speed_ms, the speed of the depth stream like --update-speed-ms, and depth,
+    // the levels of the partial book depth stream
+    fn set_subscription_param(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        match key {
+            "update_speed_ms" => {
+                let speeds: &[&str] = if self.futures {
+                    &["100", "250", "500"]
+                } else {
+                    &["100", "1000"]
+                };
+                let speed = param_choice(key, value, speeds)?.parse()?;
+                self.update_speed = Duration::from_millis(speed);
+            }
+            "depth" => self.depth = param_choice(key, value, &["5", "10", "20"])?.parse()?,
+            _ => return Err(unsupported_param(key, &["update_speed_ms", "depth"])),
+        }
+        Ok(())
+    }
+}
+
+crate::register_connector!("binance", false, |depth| Box::new(BinanceConnector::new(
+    depth
+)));
+crate::register_connector!("binance", true, |depth| Box::new(
+    BinanceConnector::futures(depth)
+));
+crate::register_connector!("binance_futures", false, |depth| {
+    Box::new(BinanceConnector::futures(depth))
+});
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+    use crate::number::decimal_to_f64;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_demux_binance_stream() {
+        let depth = r#"{"stream":"btcusdt@depth10@100ms","data":{"lastUpdateId":1,"bids":[["10.0","1.0"]],"asks":[["11.0","0.8"]]}}"#;
+        let book_ticker = r#"{"stream":"btcusdt@bookTicker","data":{"u":2,"s":"BTCUSDT","b":"10.5","B":"0.3","a":"10.9","A":"0.1"}}"#;
+        let trade = r#"{"stream":"btcusdt@trade","data":{"e":"trade","s":"BTCUSDT","p":"10.9","q":"0.05","m":false}}"#;
+
+        match demux_binance_stream(depth, 10) {
+            Some(BinanceStreamEvent::Depth {
+                orderbook,
+                last_update_id,
+            }) => {
+                assert_eq!(last_update_id, Some(1));
+                assert_eq!(orderbook.bids[0].price, dec!(10.0));
+                assert_eq!(orderbook.asks[0].exchange, "binance");
+            }
+            event => panic!("Expected a depth update, got {:?}", event),
+        }
+        assert!(matches!(
+            demux_binance_stream(book_ticker, 10),
+            Some(BinanceStreamEvent::BookTicker(BookTicker {
+                bid_price, ask_amount, ..
+            })) if bid_price == dec!(10.5) && ask_amount == dec!(0.1)
+        ));
+        assert!(matches!(
+            demux_binance_stream(trade, 10),
+            Some(BinanceStreamEvent::Trade(Trade {
+                buyer_is_maker: false,
+                ..
+            }))
+        ));
+        // Acks and unwrapped messages aren't combined stream payloads
+        assert!(demux_binance_stream(r#"{"result":null,"id":1}"#, 10).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_binance_connect() {
+        let symbol = "BTCUSDT";
+        let depth = 5;
+
+        let result = binance_connect(symbol, depth).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_binance_conformance() {
+        run_conformance(
+            |url| Box::new(BinanceConnector::with_url(url, 10)),
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"result":null,"id":1}"#.to_string(),
+                snapshot: r#"{"lastUpdateId": 1, "bids": [["10.0", "1.0"], ["9.5", "2.0"]], "asks": [["11.0", "0.8"]]}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0), (9.5, 2.0)], vec![(11.0, 0.8)]),
+                // A later snapshot replaces the book
+                updates: vec![(
+                    r#"{"lastUpdateId": 2, "bids": [["10.1", "0.5"], ["9.8", "3.0"]], "asks": [["10.9", "0.1"]]}"#.to_string(),
+                    (vec![(10.1, 0.5), (9.8, 3.0)], vec![(10.9, 0.1)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+
+    #[test]
+    fn test_binance_futures_conformance() {
+        run_conformance(
+            |url| Box::new(BinanceConnector::futures_with_url(url, 10)),
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"result":null,"id":1}"#.to_string(),
+                snapshot: r#"{"e":"depthUpdate","E":1571889248277,"T":1571889248276,"s":"BTCUSDT","U":390497796,"u":390497878,"pu":390497794,"b":[["10.0","1.0"],["9.5","2.0"]],"a":[["11.0","0.8"]]}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0), (9.5, 2.0)], vec![(11.0, 0.8)]),
+                // A later snapshot replaces the book
+                updates: vec![(
+                    r#"{"e":"depthUpdate","E":1571889248377,"T":1571889248376,"s":"BTCUSDT","U":390497879,"u":390497950,"pu":390497878,"b":[["10.1","0.5"],["9.8","3.0"]],"a":[["10.9","0.1"]]}"#.to_string(),
+                    (vec![(10.1, 0.5), (9.8, 3.0)], vec![(10.9, 0.1)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+
+    #[test]
+    fn test_binance_combined_conformance() {
+        run_conformance(
+            |url| Box::new(BinanceConnector::combined_with_url(url, 10)),
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"result":null,"id":1}"#.to_string(),
+                snapshot: r#"{"stream": "btcusdt@depth10@100ms", "data": {"lastUpdateId": 1, "bids": [["10.0", "1.0"]], "asks": [["11.0", "0.8"]]}}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8)]),
+                // A later snapshot replaces the book
+                updates: vec![(
+                    r#"{"stream": "btcusdt@depth10@100ms", "data": {"lastUpdateId": 2, "bids": [["10.1", "0.5"], ["9.8", "3.0"]], "asks": [["10.9", "0.1"]]}}"#.to_string(),
+                    (vec![(10.1, 0.5), (9.8, 3.0)], vec![(10.9, 0.1)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+
+    #[test]
+    fn test_binance_book_ticker() {
+        let mut connector = BinanceConnector::combined(10);
+        let depth = r#"{"stream":"btcusdt@depth10@100ms","data":{"lastUpdateId":5,"bids":[["10.0","1.0"],["9.5","2.0"]],"asks":[["11.0","0.8"],["11.5","0.7"]]}}"#;
+        let ticker = |update_id: u64| {
+            format!(
+                r#"{{"stream":"btcusdt@bookTicker","data":{{"u":{},"s":"BTCUSDT","b":"10.2","B":"0.4","a":"11.5","A":"0.3"}}}}"#,
+                update_id
+            )
+        };
+
+        // Nothing to refresh before the first depth update
+        assert!(connector.apply_message(&ticker(4), 10).is_none());
+        assert!(connector.apply_message(depth, 10).is_some());
+        assert!(!connector.bbo_only());
+        // Already covered by the depth update
+        assert!(connector.apply_message(&ticker(5), 10).is_none());
+
+        let orderbook = connector.apply_message(&ticker(6), 10).unwrap();
+        assert!(connector.bbo_only());
+        let levels = |levels: &[PriceAmountLevel]| {
+            levels
+                .iter()
+                .map(|level| (decimal_to_f64(level.price), decimal_to_f64(level.amount)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            levels(&orderbook.bids),
+            vec![(10.2, 0.4), (10.0, 1.0), (9.5, 2.0)]
+        );
+        assert_eq!(levels(&orderbook.asks), vec![(11.5, 0.3)]);
+        assert_eq!(connector.book_ticker().unwrap().update_id, 6);
+
+        // The buyer was the maker, the taker sold
+        let trade = r#"{"stream":"btcusdt@trade","data":{"e":"trade","s":"BTCUSDT","p":"10.2","q":"0.05","m":true}}"#;
+        connector.enable_trades();
+        assert!(connector.apply_message(trade, 10).is_none());
+        assert_eq!(connector.take_trades()[0].side, TakerSide::Sell);
+    }
+
+    #[test]
+    fn test_binance_us() {
+        let connector = BinanceConnector::combined(10).us();
+        assert_eq!(connector.url(), "wss://stream.binance.us:9443/stream");
+        assert_eq!(
+            connector.rest_url("btcusd").unwrap(),
+            "https://api.binance.us/api/v3/exchangeInfo?symbol=BTCUSD"
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::binance::binance_error;
+use crate::connectors::{ExchangeConnector, ExchangeError};
+use crate::number::json_decimal;
+use crate::symbols::joined_symbol;
+use serde_json::Value;
+use std::error::Error;
+
+// Levels of the REST snapshot the updates are applied to
+const SNAPSHOT_LIMIT: u32 = 1000;
+
+// The diff depth stream of Binance's USD-M futures (fstream), the full book rather than
+// the top levels of the partial depth streams. Like on spot the book is built from a
+// REST snapshot with its lastUpdateId, but the update ids work differently: the first
+// update applied is the one whose first (U) and last (u) ids span the snapshot's id,
+// and every next one carries the u of the previous one in pu. A pu that isn't the last
+// u applied means updates were lost and the book is rebuilt
+pub struct BinanceFuturesConnector {
+    url: String,
+    // Base of the REST API the snapshots are fetched from
+    rest_base: String,
+    book: LocalBook,
+    // lastUpdateId of the snapshot, None until it is applied
+    snapshot_id: Option<u64>,
+    // u of the last update applied, None until the first one
+    last_update_id: Option<u64>,
+    // Symbol of the snapshot requested, and the updates received until it's applied
+    snapshot_symbol: Option<String>,
+    pending_updates: Vec<Value>,
+    out_of_sync: bool,
+}
+
+impl BinanceFuturesConnector {
+    pub fn new() -> BinanceFuturesConnector {
+        BinanceFuturesConnector::with_urls(
+            "wss://fstream.binance.com/ws",
+            "https://fapi.binance.com/fapi/v1",
+        )
+    }
+
+    pub fn with_urls(url: &str, rest_base: &str) -> BinanceFuturesConnector {
+        BinanceFuturesConnector {
+            url: url.to_string(),
+            rest_base: rest_base.to_string(),
+            book: LocalBook::new(),
+            snapshot_id: None,
+            last_update_id: None,
+            snapshot_symbol: None,
+            pending_updates: Vec::new(),
+            out_of_sync: false,
+        }
+    }
+
+    fn stream_message(&self, method: &str, id: u32, symbol: &str) -> String {
+        format!(
+            r#"{{"method": "{}", "params": ["{}@depth@100ms"], "id": {}}}"#,
+            method, symbol, id
+        )
+    }
+
+    fn snapshot_url(&self, symbol: &str) -> String {
+        format!(
+            "{}/depth?symbol={}&limit={}",
+            self.rest_base,
+            symbol.to_uppercase(),
+            SNAPSHOT_LIMIT
+        )
+    }
+
+    // Replaces the book with the REST snapshot and returns its lastUpdateId
+    fn replace_book(&mut self, body: &str) -> Result<u64, Box<dyn Error>> {
+        let snapshot = serde_json::from_str::<Value>(body)?;
+        let id = snapshot["lastUpdateId"]
+            .as_u64()
+            .ok_or_else(|| format!("No lastUpdateId in the Binance futures snapshot: {}", body))?;
+        self.book.replace(
+            parse_binance_futures_levels(&snapshot["bids"]),
+            parse_binance_futures_levels(&snapshot["asks"]),
+        );
+        Ok(id)
+    }
+
+    // Applies an update following the book, returns false for the ones already in the
+    // snapshot and after a gap
+    fn apply_update(&mut self, update: &Value) -> bool {
+        let (first_id, last_id, snapshot_id) =
+            match (update["U"].as_u64(), update["u"].as_u64(), self.snapshot_id) {
+                (Some(first_id), Some(last_id), Some(snapshot_id)) => {
+                    (first_id, last_id, snapshot_id)
+                }
+                _ => return false,
+            };
+        if self.out_of_sync {
+            return false;
+        }
+
+        match self.last_update_id {
+            Some(last_update_id) => {
+                if update["pu"].as_u64() != Some(last_update_id) {
+                    self.out_of_sync = true;
+                    return false;
+                }
+            }
+            None => {
+                // Already in the snapshot
+                if last_id < snapshot_id {
+                    return false;
+                }
+                if first_id > snapshot_id {
+                    self.out_of_sync = true;
+                    return false;
+                }
+            }
+        }
+
+        let bids = parse_binance_futures_levels(&update["b"]);
+        let asks = parse_binance_futures_levels(&update["a"]);
+        self.book.apply_updates(bids, asks, "binance_futures");
+        self.last_update_id = Some(last_id);
+        true
+    }
+}
+
+impl Default for BinanceFuturesConnector {
+    fn default() -> Self {
+        BinanceFuturesConnector::new()
+    }
+}
+
+// Levels are [price, amount] strings, an amount of "0" removes the level
+fn parse_binance_futures_levels(levels: &Value) -> Vec<PriceAmountLevel> {
+    levels
+        .as_array()
+        .map(|levels| {
+            levels
+                .iter()
+                .filter_map(|level| {
+                    Some(PriceAmountLevel {
+                        exchange: "binance_futures".to_string(),
+                        price: json_decimal(&level[0])?,
+                        amount: json_decimal(&level[1])?,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl ExchangeConnector for BinanceFuturesConnector {
+    fn name(&self) -> &str {
+        "Binance Futures (diff depth)"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        joined_symbol(symbol).to_lowercase()
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(self.snapshot_url(symbol))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.stream_message("SUBSCRIBE", 1, symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.stream_message("UNSUBSCRIBE", 2, symbol)]
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["result"].is_null() && ack["id"] == 1
+    }
+
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        binance_error(message_text)
+    }
+
+    // The event time of the update, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["E"].as_u64()
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let update = serde_json::from_str::<Value>(message_text).ok()?;
+        if update["e"] != "depthUpdate" || self.out_of_sync {
+            return None;
+        }
+
+        // Kept until the snapshot requested is applied
+        if self.snapshot_id.is_none() {
+            self.snapshot_symbol = Some(update["s"].as_str()?.to_string());
+            self.pending_updates.push(update);
+            return None;
+        }
+        self.apply_update(&update)
+            .then(|| self.book.orderbook(depth))
+    }
+
+    fn snapshot_request(&self) -> Option<String> {
+        self.snapshot_symbol
+            .as_ref()
+            .map(|symbol| self.snapshot_url(symbol))
+    }
+
+    // The book is sent once an update spanning the snapshot's id was applied
+    fn apply_snapshot(
+        &mut self,
+        snapshot: Result<String, String>,
+        depth: usize,
+    ) -> Option<OrderBook> {
+        self.snapshot_symbol = None;
+        let pending_updates = std::mem::take(&mut self.pending_updates);
+        let replaced = match snapshot {
+            Ok(body) => self.replace_book(&body),
+            Err(err) => Err(err.into()),
+        };
+        match replaced {
+            Ok(snapshot_id) => self.snapshot_id = Some(snapshot_id),
+            Err(err) => {
+                eprintln!("Failed to fetch the Binance futures snapshot: {}", err);
+                self.out_of_sync = true;
+                return None;
+            }
+        }
+        for update in &pending_updates {
+            self.apply_update(update);
+        }
+        if self.out_of_sync {
+            return None;
+        }
+        self.last_update_id.map(|_| self.book.orderbook(depth))
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    // The snapshot is requested again with the first update of the new subscription
+    fn reset(&mut self) {
+        self.book.clear();
+        self.snapshot_id = None;
+        self.last_update_id = None;
+        self.snapshot_symbol = None;
+        self.pending_updates.clear();
+        self.out_of_sync = false;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+}
+
+crate::register_connector!("binance_futures_diff", false, |_| {
+    Box::new(BinanceFuturesConnector::new())
+});
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+    use crate::mock_exchange::serve_snapshot;
+
+    #[test]
+    fn test_binance_futures_diff_conformance() {
+        let snapshot = r#"{"lastUpdateId":100,"E":1589436922972,"T":1589436922959,"bids":[["10.0","1.0"]],"asks":[["11.0","0.8"]]}"#;
+        let rest_base = format!("{}/fapi/v1", serve_snapshot(snapshot));
+        run_conformance(
+            |url| Box::new(BinanceFuturesConnector::with_urls(url, &rest_base)),
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"result":null,"id":1}"#.to_string(),
+                // Spans the snapshot's lastUpdateId 100
+                snapshot: r#"{"e":"depthUpdate","E":1589436922972,"T":1589436922959,"s":"BTCUSDT","U":99,"u":101,"pu":98,"b":[],"a":[["11.5","0.7"]]}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                updates: vec![(
+                    r#"{"e":"depthUpdate","E":1589436923072,"T":1589436923059,"s":"BTCUSDT","U":102,"u":103,"pu":101,"b":[["10.0","0"],["9.8","3.0"]],"a":[["10.9","0.1"]]}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8), (11.5, 0.7)]),
+                )],
+                gap: Some(r#"{"e":"depthUpdate","E":1589436923172,"T":1589436923159,"s":"BTCUSDT","U":110,"u":112,"pu":109,"b":[["9.7","1.0"]],"a":[]}"#.to_string()),
+            },
+        );
+    }
+}
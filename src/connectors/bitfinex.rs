@@ -0,0 +1,220 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::{error_code, ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::json_decimal;
+use crate::symbols::split_symbol;
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+// Bitfinex's v2 book channel at precision P0 (raw price levels) sends a snapshot and then
+// one changed level per message, as [chanId, [price, count, amount]]. A positive amount
+// is a bid and a negative one an ask, a count of 0 removes the level of the side given
+// by the sign of the amount (1 for bids, -1 for asks)
+pub struct BitfinexConnector {
+    url: String,
+    book: LocalBook,
+    // Channel of the book, data messages carry it and the unsubscription needs it
+    chan_id: Option<i64>,
+}
+
+impl BitfinexConnector {
+    pub fn new() -> BitfinexConnector {
+        BitfinexConnector::with_url("wss://api-pub.bitfinex.com/ws/2")
+    }
+
+    pub fn with_url(url: &str) -> BitfinexConnector {
+        BitfinexConnector {
+            url: url.to_string(),
+            book: LocalBook::new(),
+            chan_id: None,
+        }
+    }
+}
+
+impl Default for BitfinexConnector {
+    fn default() -> Self {
+        BitfinexConnector::new()
+    }
+}
+
+// [price, count, amount] into the side it belongs to, with an amount of 0 when the
+// level is removed
+fn parse_bitfinex_level(level: &Value) -> Option<(bool, PriceAmountLevel)> {
+    let price = json_decimal(&level[0])?;
+    let count = level[1].as_u64()?;
+    let amount = json_decimal(&level[2])?;
+    let level = PriceAmountLevel {
+        exchange: "bitfinex".to_string(),
+        price,
+        amount: if count == 0 {
+            Decimal::ZERO
+        } else {
+            amount.abs()
+        },
+    };
+    Some((amount > Decimal::ZERO, level))
+}
+
+fn split_sides(levels: &[Value]) -> (Vec<PriceAmountLevel>, Vec<PriceAmountLevel>) {
+    let mut bids = Vec::new();
+    let mut asks = Vec::new();
+    for (is_bid, level) in levels.iter().filter_map(parse_bitfinex_level) {
+        if is_bid {
+            bids.push(level);
+        } else {
+            asks.push(level);
+        }
+    }
+    (bids, asks)
+}
+
+impl ExchangeConnector for BitfinexConnector {
+    fn name(&self) -> &str {
+        "Bitfinex"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // tBTCUSD, tBTCUST for USDT, and tDOGE:USD when the base has more than 3 letters
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => {
+                let quote = if quote == "USDT" {
+                    "UST".to_string()
+                } else {
+                    quote
+                };
+                if base.len() > 3 || quote.len() > 3 {
+                    format!("t{}:{}", base, quote)
+                } else {
+                    format!("t{}{}", base, quote)
+                }
+            }
+            None => format!("t{}", symbol.to_uppercase()),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api-pub.bitfinex.com/v2/book/{}/P0",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![format!(
+            r#"{{"event": "subscribe", "channel": "book", "symbol": "{}", "prec": "P0", "freq": "F0", "len": "25"}}"#,
+            symbol
+        )]
+    }
+
+    // Bitfinex unsubscribes a channel by its id
+    fn unsubscribe_messages(&self, _symbol: &str) -> Vec<String> {
+        match self.chan_id {
+            Some(chan_id) => vec![format!(
+                r#"{{"event": "unsubscribe", "chanId": {}}}"#,
+                chan_id
+            )],
+            None => Vec::new(),
+        }
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["event"] == "subscribed" && ack["channel"] == "book"
+    }
+
+    // The info event with the API version is sent on connect
+    fn is_status_message(&self, message_text: &str) -> bool {
+        let status = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        status["event"] == "info" && status.get("code").is_none()
+    }
+
+    // {"event": "error", "msg": "symbol: invalid", "code": 10300}, 10305 is the limit of
+    // open channels. The info event 20051 asks to reconnect before a server restart
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let code = error_code(&result["code"]);
+        let action = match (result["event"].as_str()?, code.as_deref()) {
+            ("error", Some("10305")) => ErrorAction::BackOff,
+            ("error", _) => ErrorAction::Disable,
+            ("info", Some("20051")) => ErrorAction::Resubscribe,
+            _ => return None,
+        };
+        Some(ExchangeError {
+            code,
+            message: result["msg"].as_str().unwrap_or_default().to_string(),
+            action,
+        })
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        // Events are objects, data messages arrays, heartbeats are [chanId, "hb"]
+        let chan_id = result[0].as_i64()?;
+        let levels = result[1].as_array()?;
+        self.chan_id = Some(chan_id);
+        if levels.first().is_some_and(Value::is_array) {
+            let (bids, asks) = split_sides(levels);
+            self.book.replace(bids, asks);
+        } else {
+            let (bids, asks) = split_sides(std::slice::from_ref(&result[1]));
+            self.book.apply_updates(bids, asks, "bitfinex");
+        }
+        Some(self.book.orderbook(depth))
+    }
+
+    fn reset(&mut self) {
+        self.book.clear();
+        self.chan_id = None;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+}
+
+crate::register_connector!("bitfinex", false, |_| Box::new(BitfinexConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_bitfinex_symbols() {
+        let connector = BitfinexConnector::new();
+        assert_eq!(connector.normalize_symbol("btcusd"), "tBTCUSD");
+        assert_eq!(connector.normalize_symbol("btcusdt"), "tBTCUST");
+        assert_eq!(connector.normalize_symbol("dogeusd"), "tDOGE:USD");
+    }
+
+    #[test]
+    fn test_bitfinex_conformance() {
+        run_conformance(
+            |url| Box::new(BitfinexConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusd",
+                ack: r#"{"event":"subscribed","channel":"book","chanId":17082,"symbol":"tBTCUSD","prec":"P0","freq":"F0","len":"25","pair":"BTCUSD"}"#.to_string(),
+                snapshot: r#"[17082,[[10.0,2,1.0],[11.0,1,-0.8]]]"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8)]),
+                // One level per update: a count of 0 with a positive amount removes
+                // the bid at 10.0, then a bid is added and the ask amount changed
+                updates: vec![(
+                    r#"[17082,[10.0,0,1]]"#.to_string(),
+                    (vec![], vec![(11.0, 0.8)]),
+                ), (
+                    r#"[17082,[10.5,1,1.5]]"#.to_string(),
+                    (vec![(10.5, 1.5)], vec![(11.0, 0.8)]),
+                ), (
+                    r#"[17082,[11.0,2,-0.5]]"#.to_string(),
+                    (vec![(10.5, 1.5)], vec![(11.0, 0.5)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+}
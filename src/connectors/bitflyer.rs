@@ -0,0 +1,249 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::{error_code, ErrorAction, ExchangeConnector, ExchangeError};
+use crate::fx::{convert_quote, FxRateSource};
+use crate::number::json_decimal;
+use crate::symbols::split_symbol;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::sync::Arc;
+
+// bitFlyer's Realtime API (JSON-RPC 2.0) publishes the JPY books of its markets on two
+// channels: lightning_board_snapshot with the full book and lightning_board with the
+// changed levels. Both are subscribed, the changes are applied to the latest snapshot
+// and those received before the first one are dropped. The messages have no sequence
+// numbers, but each carries the mid_price of the exchange's book: a changed book whose
+// mid isn't that price missed changes and is resynced. Like Upbit's, the JPY prices
+// are converted with the rate of the JPY source when one is set, e.g. --fx-rate
+// jpy=150, bitFlyer has no market of its own to take it from. Without one the book is
+// merged in JPY, for a server serving a JPY symbol
+pub struct BitflyerConnector {
+    url: String,
+    book: LocalBook,
+    has_snapshot: bool,
+    out_of_sync: bool,
+    fx_rate_source: Option<Arc<dyn FxRateSource>>,
+}
+
+impl BitflyerConnector {
+    pub fn new() -> BitflyerConnector {
+        BitflyerConnector::with_url("wss://ws.lightstream.bitflyer.com/json-rpc")
+    }
+
+    pub fn with_url(url: &str) -> BitflyerConnector {
+        BitflyerConnector {
+            url: url.to_string(),
+            book: LocalBook::new(),
+            has_snapshot: false,
+            out_of_sync: false,
+            fx_rate_source: None,
+        }
+    }
+
+    // Only checked while both sides have levels
+    fn matches_mid_price(&self, mid_price: &Value) -> bool {
+        match (
+            json_decimal(mid_price),
+            self.book.bids.best(),
+            self.book.asks.best(),
+        ) {
+            (Some(mid_price), Some(bid), Some(ask)) => {
+                (bid.price + ask.price) / Decimal::TWO == mid_price
+            }
+            _ => true,
+        }
+    }
+
+    fn channel_messages(&self, method: &str, symbol: &str) -> Vec<String> {
+        ["lightning_board_snapshot", "lightning_board"]
+            .iter()
+            .enumerate()
+            .map(|(index, channel)| {
+                format!(
+                    r#"{{"method": "{}", "params": {{"channel": "{}_{}"}}, "id": {}}}"#,
+                    method,
+                    channel,
+                    symbol,
+                    index + 1
+                )
+            })
+            .collect()
+    }
+}
+
+impl Default for BitflyerConnector {
+    fn default() -> Self {
+        BitflyerConnector::new()
+    }
+}
+
+// Levels are {"price": 15000000.0, "size": 0.1}, a size of 0 removes the level
+fn parse_bitflyer_levels(levels: &Value) -> Vec<PriceAmountLevel> {
+    levels
+        .as_array()
+        .map(|levels| {
+            levels
+                .iter()
+                .filter_map(|level| {
+                    Some(PriceAmountLevel {
+                        exchange: "bitflyer".to_string(),
+                        price: json_decimal(&level["price"])?,
+                        amount: json_decimal(&level["size"])?,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl ExchangeConnector for BitflyerConnector {
+    fn name(&self) -> &str {
+        "bitFlyer"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // BTC_JPY, the JPY market of the base asset whatever the served quote
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, _)) => format!("{}_JPY", base),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api.bitflyer.com/v1/getboard?product_code={}",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        self.channel_messages("subscribe", symbol)
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        self.channel_messages("unsubscribe", symbol)
+    }
+
+    // {"jsonrpc": "2.0", "id": 1, "result": true}, one for each channel
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["result"] == true && ack["id"].is_u64()
+    }
+
+    // {"jsonrpc": "2.0", "id": 1, "error": {"code": -32602, "message": "Invalid params"}},
+    // the JSON-RPC codes are all invalid requests
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let error = result.get("error")?;
+        Some(ExchangeError {
+            code: error_code(&error["code"]),
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+            action: ErrorAction::Disable,
+        })
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["method"] != "channelMessage" {
+            return None;
+        }
+        let channel = result["params"]["channel"].as_str()?;
+        let message = &result["params"]["message"];
+        let bids = parse_bitflyer_levels(&message["bids"]);
+        let asks = parse_bitflyer_levels(&message["asks"]);
+        if channel.starts_with("lightning_board_snapshot_") {
+            self.book.replace(bids, asks);
+            self.has_snapshot = true;
+            self.out_of_sync = false;
+        } else if channel.starts_with("lightning_board_") && self.has_snapshot {
+            if self.out_of_sync {
+                return None;
+            }
+            self.book.apply_updates(bids, asks, "bitflyer");
+            if !self.matches_mid_price(&message["mid_price"]) {
+                self.out_of_sync = true;
+                return None;
+            }
+        } else {
+            return None;
+        }
+
+        let orderbook = self.book.orderbook(depth);
+        match &self.fx_rate_source {
+            // The JPY book can't be merged before the rate is known
+            Some(source) => Some(convert_quote(&orderbook, source.rate()?)),
+            None => Some(orderbook),
+        }
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    fn reset(&mut self) {
+        self.book.clear();
+        self.has_snapshot = false;
+        self.out_of_sync = false;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+
+    fn set_fx_rate_source(&mut self, currency: &str, source: Arc<dyn FxRateSource>) {
+        if currency == "JPY" {
+            self.fx_rate_source = Some(source);
+        }
+    }
+}
+
+crate::register_connector!("bitflyer", false, |_| Box::new(BitflyerConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+    use crate::fx::FixedRate;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_bitflyer_conformance() {
+        run_conformance(
+            |url| Box::new(BitflyerConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcjpy",
+                ack: r#"{"jsonrpc":"2.0","id":1,"result":true}"#.to_string(),
+                snapshot: r#"{"jsonrpc":"2.0","method":"channelMessage","params":{"channel":"lightning_board_snapshot_BTC_JPY","message":{"mid_price":10.5,"bids":[{"price":10.0,"size":1.0}],"asks":[{"price":11.0,"size":0.8},{"price":11.5,"size":0.7}]}}}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                updates: vec![(
+                    r#"{"jsonrpc":"2.0","method":"channelMessage","params":{"channel":"lightning_board_BTC_JPY","message":{"mid_price":10.35,"bids":[{"price":10.0,"size":0},{"price":9.8,"size":3.0}],"asks":[{"price":10.9,"size":0.1}]}}}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8), (11.5, 0.7)]),
+                )],
+                // The bid at 9.9 leaves the mid at 10.4, the exchange's book moved on
+                gap: Some(r#"{"jsonrpc":"2.0","method":"channelMessage","params":{"channel":"lightning_board_BTC_JPY","message":{"mid_price":10.3,"bids":[{"price":9.9,"size":1.0}],"asks":[]}}}"#.to_string()),
+            },
+        );
+    }
+
+    #[test]
+    fn test_bitflyer_jpy_conversion() {
+        let mut connector = BitflyerConnector::new();
+        assert_eq!(connector.normalize_symbol("btcusdt"), "BTC_JPY");
+        let board = r#"{"jsonrpc":"2.0","method":"channelMessage","params":{"channel":"lightning_board_BTC_JPY","message":{"mid_price":15015000,"bids":[{"price":15000000,"size":0.1}],"asks":[]}}}"#;
+        let snapshot = r#"{"jsonrpc":"2.0","method":"channelMessage","params":{"channel":"lightning_board_snapshot_BTC_JPY","message":{"mid_price":15015000,"bids":[{"price":15000000,"size":0.5}],"asks":[{"price":15030000,"size":0.2}]}}}"#;
+        // Changes before the first snapshot are dropped
+        assert!(connector.apply_message(board, 10).is_none());
+
+        connector.set_fx_rate_source("JPY", Arc::new(FixedRate(150.0)));
+        let orderbook = connector.apply_message(snapshot, 10).unwrap();
+        assert_eq!(orderbook.bids[0].price, dec!(100_000.0));
+        assert_eq!(orderbook.asks[0].price, dec!(100_200.0));
+        let orderbook = connector.apply_message(board, 10).unwrap();
+        assert_eq!(orderbook.bids[0].amount, dec!(0.1));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::book::{LocalBook, OrderBook};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::okx::{
+    apply_texts, okx_checksum, parse_okx_levels, to_price_amount_levels, top_levels, LevelTexts,
+};
+use crate::connectors::{error_code, ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::json_f64;
+use crate::symbols::split_symbol;
+use serde_json::Value;
+use std::collections::HashMap;
+
+// Bitget's v2 books channel sends a snapshot of the full book and then the changed
+// levels. Like OKX, every message carries the CRC32 of the top 25 levels of the book
+// after it is applied, computed over the prices and sizes as Bitget sent them. A
+// mismatch means the local book diverged, and it is only rebuilt by a new snapshot
+pub struct BitgetConnector {
+    url: String,
+    book: LocalBook,
+    bid_texts: LevelTexts,
+    ask_texts: LevelTexts,
+    out_of_sync: bool,
+}
+
+impl BitgetConnector {
+    pub fn new() -> BitgetConnector {
+        BitgetConnector::with_url("wss://ws.bitget.com/v2/ws/public")
+    }
+
+    pub fn with_url(url: &str) -> BitgetConnector {
+        BitgetConnector {
+            url: url.to_string(),
+            book: LocalBook::new(),
+            bid_texts: HashMap::new(),
+            ask_texts: HashMap::new(),
+            out_of_sync: false,
+        }
+    }
+
+    fn books_message(&self, op: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"op": "{}", "args": [{{"instType": "SPOT", "channel": "books", "instId": "{}"}}]}}"#,
+            op, symbol
+        )
+    }
+
+    // The checksum of the local book, as a signed 32 bit integer like Bitget sends it
+    fn checksum(&self) -> i32 {
+        let bids = top_levels(&self.bid_texts, true);
+        let asks = top_levels(&self.ask_texts, false);
+        okx_checksum(
+            bids.iter()
+                .map(|(_, price, size)| (price.as_str(), size.as_str())),
+            asks.iter()
+                .map(|(_, price, size)| (price.as_str(), size.as_str())),
+        )
+    }
+
+    fn clear(&mut self) {
+        self.book.clear();
+        self.bid_texts.clear();
+        self.ask_texts.clear();
+    }
+}
+
+impl Default for BitgetConnector {
+    fn default() -> Self {
+        BitgetConnector::new()
+    }
+}
+
+impl ExchangeConnector for BitgetConnector {
+    fn name(&self) -> &str {
+        "Bitget"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // BTCUSDT
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => format!("{}{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api.bitget.com/api/v2/spot/market/orderbook?symbol={}&limit=100",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.books_message("subscribe", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.books_message("unsubscribe", symbol)]
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["event"] == "subscribe"
+    }
+
+    // {"event": "error", "code": 30001, "msg": "instType:SPOT,channel:books,instId:XYZ
+    // doesn't exist"}, 30006 is the rate limit
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["event"] != "error" {
+            return None;
+        }
+        let code = error_code(&result["code"]);
+        let action = match code.as_deref() {
+            Some("30006") => ErrorAction::BackOff,
+            _ => ErrorAction::Disable,
+        };
+        Some(ExchangeError {
+            code,
+            message: result["msg"].as_str().unwrap_or_default().to_string(),
+            action,
+        })
+    }
+
+    // The ts of the data, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        Some(json_f64(&result["data"][0]["ts"])? as u64)
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["arg"]["channel"] != "books" {
+            return None;
+        }
+        let data = &result["data"][0];
+        let bids = parse_okx_levels(&data["bids"])?;
+        let asks = parse_okx_levels(&data["asks"])?;
+        match result["action"].as_str()? {
+            "snapshot" => {
+                self.clear();
+                apply_texts(&mut self.bid_texts, &bids);
+                apply_texts(&mut self.ask_texts, &asks);
+                self.book.replace(
+                    to_price_amount_levels(&bids, "bitget"),
+                    to_price_amount_levels(&asks, "bitget"),
+                );
+                self.out_of_sync = false;
+            }
+            "update" => {
+                if self.out_of_sync {
+                    return None;
+                }
+                apply_texts(&mut self.bid_texts, &bids);
+                apply_texts(&mut self.ask_texts, &asks);
+                self.book.apply_updates(
+                    to_price_amount_levels(&bids, "bitget"),
+                    to_price_amount_levels(&asks, "bitget"),
+                    "bitget",
+                );
+            }
+            _ => return None,
+        }
+
+        // The levels only contribute to the merged book once the checksum confirms them
+        if data["checksum"].as_i64() != Some(self.checksum() as i64) {
+            eprintln!("Bitget checksum mismatch, the book is rebuilt from a new snapshot");
+            self.out_of_sync = true;
+            return None;
+        }
+        Some(self.book.orderbook(depth))
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+        self.out_of_sync = false;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+}
+
+crate::register_connector!("bitget", false, |_| Box::new(BitgetConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_bitget_conformance() {
+        run_conformance(
+            |url| Box::new(BitgetConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"event":"subscribe","arg":{"instType":"SPOT","channel":"books","instId":"BTCUSDT"}}"#.to_string(),
+                // The trailing zeros are part of the checksum
+                snapshot: r#"{"action":"snapshot","arg":{"instType":"SPOT","channel":"books","instId":"BTCUSDT"},"data":[{"asks":[["11.00","0.8000"],["11.50","0.7000"]],"bids":[["10.00","1.0000"]],"checksum":343925024,"seq":100,"ts":"1695716059516"}],"ts":1695716059516}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                updates: vec![(
+                    r#"{"action":"update","arg":{"instType":"SPOT","channel":"books","instId":"BTCUSDT"},"data":[{"asks":[["10.90","0.1000"]],"bids":[["10.00","0"],["9.80","3.0000"]],"checksum":439992114,"seq":101,"ts":"1695716059616"}],"ts":1695716059616}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8), (11.5, 0.7)]),
+                )],
+                gap: Some(r#"{"action":"update","arg":{"instType":"SPOT","channel":"books","instId":"BTCUSDT"},"data":[{"asks":[],"bids":[["9.70","1.0000"]],"checksum":12345,"seq":102,"ts":"1695716059716"}],"ts":1695716059716}"#.to_string()),
+            },
+        );
+    }
+}
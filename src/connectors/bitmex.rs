@@ -0,0 +1,223 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::{ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::json_decimal;
+use crate::symbols::split_symbol;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::collections::HashMap;
+
+// BitMEX's orderBookL2 table sends a partial of the full book and then inserts, updates
+// and deletes of its rows. Rows are keyed by an id, the updates and deletes carry the
+// id without the price, so the price of every id is kept next to the book. An update
+// or delete of an unknown id means the book diverged, and it is only rebuilt by a new
+// partial
+pub struct BitmexConnector {
+    url: String,
+    book: LocalBook,
+    // Side and price of every row, by id
+    rows: HashMap<u64, (bool, Decimal)>,
+    out_of_sync: bool,
+}
+
+impl BitmexConnector {
+    pub fn new() -> BitmexConnector {
+        BitmexConnector::with_url("wss://ws.bitmex.com/realtime")
+    }
+
+    pub fn with_url(url: &str) -> BitmexConnector {
+        BitmexConnector {
+            url: url.to_string(),
+            book: LocalBook::new(),
+            rows: HashMap::new(),
+            out_of_sync: false,
+        }
+    }
+
+    fn table_message(&self, op: &str, symbol: &str) -> String {
+        format!(r#"{{"op": "{}", "args": ["orderBookL2:{}"]}}"#, op, symbol)
+    }
+
+    fn clear(&mut self) {
+        self.book.clear();
+        self.rows.clear();
+    }
+
+    // The levels of the rows by side, their price looked up by id when the action only
+    // carries the id. None when a row isn't known
+    fn levels(
+        &mut self,
+        action: &str,
+        rows: &[Value],
+    ) -> Option<(Vec<PriceAmountLevel>, Vec<PriceAmountLevel>)> {
+        let mut bids = Vec::new();
+        let mut asks = Vec::new();
+        for row in rows {
+            let id = row["id"].as_u64()?;
+            let (is_bid, price) = match action {
+                "partial" | "insert" => {
+                    let row_side = (row["side"] == "Buy", json_decimal(&row["price"])?);
+                    self.rows.insert(id, row_side);
+                    row_side
+                }
+                "delete" => self.rows.remove(&id)?,
+                _ => *self.rows.get(&id)?,
+            };
+            let amount = match action {
+                "delete" => Decimal::ZERO,
+                _ => json_decimal(&row["size"])?,
+            };
+            let level = PriceAmountLevel {
+                exchange: "bitmex".to_string(),
+                price,
+                amount,
+            };
+            if is_bid {
+                bids.push(level);
+            } else {
+                asks.push(level);
+            }
+        }
+        Some((bids, asks))
+    }
+}
+
+impl Default for BitmexConnector {
+    fn default() -> Self {
+        BitmexConnector::new()
+    }
+}
+
+impl ExchangeConnector for BitmexConnector {
+    fn name(&self) -> &str {
+        "BitMEX"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // XBTUSD, BitMEX lists bitcoin as XBT
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) if base == "BTC" => format!("XBT{}", quote),
+            Some((base, quote)) => format!("{}{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://www.bitmex.com/api/v1/orderBook/L2?symbol={}&depth=25",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.table_message("subscribe", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.table_message("unsubscribe", symbol)]
+    }
+
+    // {"success": true, "subscribe": "orderBookL2:XBTUSD", "request": {...}}
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["success"] == true
+            && ack["subscribe"]
+                .as_str()
+                .is_some_and(|table| table.starts_with("orderBookL2:"))
+    }
+
+    // The welcome message sent before the ack
+    fn is_status_message(&self, message_text: &str) -> bool {
+        let message = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        message.get("info").is_some() && message.get("version").is_some()
+    }
+
+    // {"status": 400, "error": "Unknown or expired symbol.", "request": {...}}, 429 is
+    // the rate limit
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let message = result["error"].as_str()?;
+        let code = result["status"].as_u64().map(|status| status.to_string());
+        let action = match code.as_deref() {
+            Some("429") => ErrorAction::BackOff,
+            _ => ErrorAction::Disable,
+        };
+        Some(ExchangeError {
+            code,
+            message: message.to_string(),
+            action,
+        })
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["table"] != "orderBookL2" {
+            return None;
+        }
+        let action = result["action"].as_str()?;
+        let rows = result["data"].as_array()?;
+        // Rows sent before the partial aren't part of the book
+        if action != "partial" && (self.out_of_sync || self.rows.is_empty()) {
+            return None;
+        }
+        if action == "partial" {
+            self.clear();
+            self.out_of_sync = false;
+        }
+        let Some((bids, asks)) = self.levels(action, rows) else {
+            eprintln!("BitMEX sent an unknown row, the book is rebuilt from a new partial");
+            self.out_of_sync = true;
+            return None;
+        };
+        match action {
+            "partial" => self.book.replace(bids, asks),
+            "insert" | "update" | "delete" => self.book.apply_updates(bids, asks, "bitmex"),
+            _ => return None,
+        }
+        Some(self.book.orderbook(depth))
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+        self.out_of_sync = false;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+}
+
+crate::register_connector!("bitmex", false, |_| Box::new(BitmexConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_bitmex_conformance() {
+        run_conformance(
+            |url| Box::new(BitmexConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusd",
+                ack: r#"{"success":true,"subscribe":"orderBookL2:XBTUSD","request":{"op":"subscribe","args":["orderBookL2:XBTUSD"]}}"#.to_string(),
+                snapshot: r#"{"table":"orderBookL2","action":"partial","keys":["symbol","id","side"],"data":[{"symbol":"XBTUSD","id":17999992000,"side":"Sell","size":80,"price":11.5},{"symbol":"XBTUSD","id":17999993000,"side":"Sell","size":100,"price":11.0},{"symbol":"XBTUSD","id":17999994000,"side":"Buy","size":200,"price":10.0}]}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 200.0)], vec![(11.0, 100.0), (11.5, 80.0)]),
+                updates: vec![(
+                    r#"{"table":"orderBookL2","action":"update","data":[{"symbol":"XBTUSD","id":17999993000,"side":"Sell","size":40}]}"#.to_string(),
+                    (vec![(10.0, 200.0)], vec![(11.0, 40.0), (11.5, 80.0)]),
+                )],
+                gap: Some(r#"{"table":"orderBookL2","action":"delete","data":[{"symbol":"XBTUSD","id":17999999000,"side":"Buy"}]}"#.to_string()),
+            },
+        );
+    }
+}
@@ -0,0 +1,504 @@
+use crate::book::{LocalBook, OrderBook};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connection_manager::connection_manager;
+use crate::connectors::{
+    connect_connector, error_code, parse_levels, process_message, AsyncSocket, ErrorAction,
+    ExchangeConnector, ExchangeError,
+};
+use crate::number::json_f64;
+use crate::symbols::joined_symbol;
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::error::Error;
+use tungstenite::client::AutoStream;
+use tungstenite::{Message, WebSocket};
+use url::Url;
+
+// Bitstamp WebSocket server URL
+pub(crate) const BITSTAMP_URL: &str = "wss://ws.bitstamp.net/";
+// Base of the REST API the snapshots of the diff book are fetched from
+pub(crate) const BITSTAMP_REST_BASE: &str = "https://www.bitstamp.net/api/v2";
+
+// Bitstamp lists its pairs in lowercase with the assets back to back, e.g. btcusd
+pub(crate) fn bitstamp_channel(symbol: &str) -> String {
+    format!("detail_order_book_{}", joined_symbol(symbol).to_lowercase())
+}
+
+// The channel of the symbol's book, the full book on every change, or with diff only
+// the levels that changed
+pub(crate) fn bitstamp_book_channel(symbol: &str, diff: bool) -> String {
+    if diff {
+        format!("diff_order_book_{}", joined_symbol(symbol).to_lowercase())
+    } else {
+        bitstamp_channel(symbol)
+    }
+}
+
+// Microtimestamps are sent as strings of µs
+fn microtimestamp(value: &Value) -> Option<u64> {
+    match value.as_str() {
+        Some(microtimestamp) => microtimestamp.parse().ok(),
+        None => value.as_u64(),
+    }
+}
+
+// Bitstamp's diff_order_book channel only sends the levels that changed, with the
+// microtimestamp of the change and an amount of 0 removing the level, instead of the
+// full detail book on every change. The book is built from a REST snapshot requested
+// with the first change, and the changes up to the snapshot's microtimestamp, already
+// in it, are dropped. The channel has no sequence numbers, a reconnection starts over
+// from a new snapshot
+pub(crate) struct BitstampDiffBook {
+    rest_base: String,
+    pub(crate) book: LocalBook,
+    // Microtimestamp the book is at, None until the snapshot is applied
+    microtimestamp: Option<u64>,
+    // Symbol of the snapshot requested, and the changes received until it's applied
+    snapshot_symbol: Option<String>,
+    pending_changes: Vec<Value>,
+    pub(crate) out_of_sync: bool,
+}
+
+impl BitstampDiffBook {
+    pub fn new(rest_base: &str) -> BitstampDiffBook {
+        BitstampDiffBook {
+            rest_base: rest_base.to_string(),
+            book: LocalBook::new(),
+            microtimestamp: None,
+            snapshot_symbol: None,
+            pending_changes: Vec::new(),
+            out_of_sync: false,
+        }
+    }
+
+    fn snapshot_url(&self, symbol: &str) -> String {
+        format!("{}/order_book/{}/", self.rest_base, symbol)
+    }
+
+    // Replaces the book with the REST snapshot and returns its microtimestamp
+    fn replace_book(&mut self, body: &str) -> Result<u64, Box<dyn Error>> {
+        let snapshot = serde_json::from_str::<Value>(body)?;
+        let snapshot_microtimestamp = microtimestamp(&snapshot["microtimestamp"])
+            .ok_or_else(|| format!("No microtimestamp in the Bitstamp snapshot: {}", body))?;
+        self.book.replace(
+            parse_levels(&snapshot["bids"], "bitstamp").unwrap_or_default(),
+            parse_levels(&snapshot["asks"], "bitstamp").unwrap_or_default(),
+        );
+        Ok(snapshot_microtimestamp)
+    }
+
+    // Applies a change newer than the book, returns false for the ones already in it
+    fn apply_change(&mut self, change: &Value) -> bool {
+        let change_microtimestamp = match microtimestamp(&change["microtimestamp"]) {
+            Some(change_microtimestamp) => change_microtimestamp,
+            None => return false,
+        };
+        match self.microtimestamp {
+            Some(book_microtimestamp) if change_microtimestamp > book_microtimestamp => {}
+            _ => return false,
+        }
+
+        self.book.apply_updates(
+            parse_levels(&change["bids"], "bitstamp").unwrap_or_default(),
+            parse_levels(&change["asks"], "bitstamp").unwrap_or_default(),
+            "bitstamp",
+        );
+        self.microtimestamp = Some(change_microtimestamp);
+        true
+    }
+
+    pub fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["event"] != "data" {
+            return None;
+        }
+        let symbol = result["channel"]
+            .as_str()?
+            .strip_prefix("diff_order_book_")?;
+        if self.out_of_sync {
+            return None;
+        }
+
+        // Kept until the snapshot requested is applied
+        if self.microtimestamp.is_none() {
+            self.snapshot_symbol = Some(symbol.to_string());
+            self.pending_changes.push(result["data"].clone());
+            return None;
+        }
+        self.apply_change(&result["data"])
+            .then(|| self.book.orderbook(depth))
+    }
+
+    pub fn snapshot_request(&self) -> Option<String> {
+        self.snapshot_symbol
+            .as_ref()
+            .map(|symbol| self.snapshot_url(symbol))
+    }
+
+    // The snapshot is sent as it is when the changes kept were all already in it
+    pub fn apply_snapshot(
+        &mut self,
+        snapshot: Result<String, String>,
+        depth: usize,
+    ) -> Option<OrderBook> {
+        self.snapshot_symbol = None;
+        let pending_changes = std::mem::take(&mut self.pending_changes);
+        let replaced = match snapshot {
+            Ok(body) => self.replace_book(&body),
+            Err(err) => Err(err.into()),
+        };
+        match replaced {
+            Ok(snapshot_microtimestamp) => self.microtimestamp = Some(snapshot_microtimestamp),
+            Err(err) => {
+                eprintln!("Failed to fetch the Bitstamp snapshot: {}", err);
+                self.out_of_sync = true;
+                return None;
+            }
+        }
+        for change in &pending_changes {
+            self.apply_change(change);
+        }
+        Some(self.book.orderbook(depth))
+    }
+
+    // The snapshot is requested again with the first change of the new subscription
+    pub fn reset(&mut self) {
+        self.book.clear();
+        self.microtimestamp = None;
+        self.snapshot_symbol = None;
+        self.pending_changes.clear();
+        self.out_of_sync = false;
+    }
+}
+
+// Returns the channel a bitstamp message was published on
+pub(crate) fn bitstamp_message_channel(message_text: &str) -> Option<String> {
+    let result = serde_json::from_str::<Value>(message_text).ok()?;
+    result["channel"]
+        .as_str()
+        .map(|channel| channel.to_string())
+}
+
+// Subscribes one socket to several channels, bitstamp acknowledges each channel separately
+pub(crate) async fn bitstamp_connect_channels(
+    url: &str,
+    channels: &[String],
+) -> Result<AsyncSocket, Box<dyn Error>> {
+    let bitstamp_url = Url::parse(url)?;
+
+    // Connect to the Bitstamp WebSocket server
+    let (mut bitstamp_socket, _) = connection_manager()
+        .connect_async(&bitstamp_url)
+        .await
+        .map_err(|err| format!("Failed to connect to Bitstamp: {}", err))?;
+
+    for bitstamp_channel in channels {
+        // Construct the Bitstamp subscription message
+        let bitstamp_message = format!(
+            r#"
+            {{
+                "event": "bts:subscribe",
+                "data": {{
+                    "channel": "{}"
+                }}
+            }}
+            "#,
+            bitstamp_channel
+        );
+
+        // Send the subscription messages as text frames
+        bitstamp_socket
+            .send(Message::Text(bitstamp_message))
+            .await
+            .map_err(|err| format!("Failed to send Bitstamp subscription message: {}", err))?;
+    }
+
+    // Wait for every channel to be acknowledged, data of the channels subscribed
+    // first can arrive before the later acknowledgements and is skipped
+    let mut pending_channels = channels.to_vec();
+    while !pending_channels.is_empty() {
+        let connection_message = match bitstamp_socket.next().await {
+            Some(Ok(connection_message)) => connection_message,
+            _ => return Err("Failed to receive the subscription message from Bitstamp".into()),
+        };
+
+        let connection_message_text = match connection_message {
+            Message::Text(connection_message_text) => connection_message_text,
+            // Pings are answered by tungstenite on the next read
+            Message::Ping(_) | Message::Pong(_) => continue,
+            _ => return Err("Received an unexpected message type from Bitstamp".into()),
+        };
+        let ack = serde_json::from_str::<Value>(&connection_message_text).unwrap_or_default();
+        match ack["event"].as_str() {
+            Some("bts:subscription_succeeded") => {
+                pending_channels.retain(|channel| ack["channel"] != channel.as_str());
+            }
+            Some("data") => {}
+            _ => return Err("Failed to connect with Bitstamp Stream".into()),
+        }
+    }
+    println!("Connected with Bitstamp Stream successfully");
+
+    Ok(bitstamp_socket)
+}
+
+// Single channel connection, the server itself subscribes through BitstampPool
+pub async fn bitstamp_connect(symbol: &str) -> Result<WebSocket<AutoStream>, Box<dyn Error>> {
+    connect_connector(&BitstampConnector::new(), symbol)
+}
+
+pub struct BitstampConnector {
+    url: String,
+    // The book maintained from the diff_order_book channel, None when reading the
+    // detail_order_book channel
+    diff_book: Option<BitstampDiffBook>,
+}
+
+impl BitstampConnector {
+    pub fn new() -> BitstampConnector {
+        BitstampConnector::with_url("wss://ws.bitstamp.net/")
+    }
+
+    pub fn with_url(url: &str) -> BitstampConnector {
+        BitstampConnector {
+            url: url.to_string(),
+            diff_book: None,
+        }
+    }
+
+    pub fn diff() -> BitstampConnector {
+        BitstampConnector::diff_with_urls("wss://ws.bitstamp.net/", BITSTAMP_REST_BASE)
+    }
+
+    pub fn diff_with_urls(url: &str, rest_base: &str) -> BitstampConnector {
+        BitstampConnector {
+            url: url.to_string(),
+            diff_book: Some(BitstampDiffBook::new(rest_base)),
+        }
+    }
+
+    fn channel(&self, symbol: &str) -> String {
+        bitstamp_book_channel(symbol, self.diff_book.is_some())
+    }
+}
+
+impl Default for BitstampConnector {
+    fn default() -> Self {
+        BitstampConnector::new()
+    }
+}
+
+impl ExchangeConnector for BitstampConnector {
+    fn name(&self) -> &str {
+        "Bitstamp"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        joined_symbol(symbol).to_lowercase()
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://www.bitstamp.net/api/v2/ticker/{}/",
+            symbol
+        ))
+    }
+
+    fn instruments_url(&self) -> Option<String> {
+        Some("https://www.bitstamp.net/api/v2/trading-pairs-info/".to_string())
+    }
+
+    // [{"name": "BTC/USD", "url_symbol": "btcusd", "trading": "Enabled", ...}, ...]
+    fn listed_symbols(&self, body: &str) -> Vec<String> {
+        let pairs = serde_json::from_str::<Value>(body).unwrap_or_default();
+        let pairs = pairs.as_array().cloned().unwrap_or_default();
+        pairs
+            .iter()
+            .filter(|pair| pair["trading"] == "Enabled")
+            .filter_map(|pair| Some(pair["url_symbol"].as_str()?.to_string()))
+            .collect()
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![format!(
+            r#"{{"event": "bts:subscribe", "data": {{"channel": "{}"}}}}"#,
+            self.channel(symbol)
+        )]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![format!(
+            r#"{{"event": "bts:unsubscribe", "data": {{"channel": "{}"}}}}"#,
+            self.channel(symbol)
+        )]
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["event"] == "bts:subscription_succeeded"
+    }
+
+    // {"event": "bts:error", "channel": "", "data": {"code": null, "message": "..."}},
+    // Bitstamp doesn't tell transient errors apart, so the channel is resubscribed
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["event"] != "bts:error" {
+            return None;
+        }
+        Some(ExchangeError {
+            code: error_code(&result["data"]["code"]),
+            message: result["data"]["message"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            action: ErrorAction::Resubscribe,
+        })
+    }
+
+    // Answered with {"event": "bts:heartbeat", "channel": "", "data": {"status": ...}}
+    fn keepalive_message(&self) -> Option<String> {
+        Some(r#"{"event": "bts:heartbeat"}"#.to_string())
+    }
+
+    // The microtimestamp of the book, in µs
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        Some(json_f64(&result["data"]["microtimestamp"])? as u64 / 1000)
+    }
+
+    // The detail order book channel sends the full book on every update
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        match &mut self.diff_book {
+            Some(diff_book) => diff_book.apply_message(message_text, depth),
+            None => process_message(message_text, "bitstamp", depth),
+        }
+    }
+
+    fn snapshot_request(&self) -> Option<String> {
+        self.diff_book.as_ref()?.snapshot_request()
+    }
+
+    fn apply_snapshot(
+        &mut self,
+        snapshot: Result<String, String>,
+        depth: usize,
+    ) -> Option<OrderBook> {
+        self.diff_book.as_mut()?.apply_snapshot(snapshot, depth)
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.diff_book
+            .as_ref()
+            .is_some_and(|diff_book| diff_book.out_of_sync)
+    }
+
+    fn reset(&mut self) {
+        if let Some(diff_book) = &mut self.diff_book {
+            diff_book.reset();
+        }
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        if let Some(diff_book) = &mut self.diff_book {
+            diff_book.book.compactor = Compactor::new(Some(policy));
+        }
+    }
+}
+
+crate::register_connector!("bitstamp", false, |_| Box::new(BitstampConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_bitstamp_diff_book() {
+        let change = |microtimestamp: u64, bids: &str, asks: &str| {
+            format!(
+                r#"{{"data": {{"microtimestamp": "{}", "bids": {}, "asks": {}}}, "channel": "diff_order_book_btcusd", "event": "data"}}"#,
+                microtimestamp, bids, asks
+            )
+        };
+        let snapshot = r#"{"microtimestamp": "1000", "bids": [["100.0", "1.0"], ["99.0", "2.0"]], "asks": [["101.0", "1.5"]]}"#;
+        let mut diff_book = BitstampDiffBook::new("http://127.0.0.1/api/v2");
+
+        // Kept until the snapshot is applied, older than the snapshot and dropped
+        assert!(diff_book
+            .apply_message(&change(900, r#"[["100.0", "0"]]"#, "[]"), 10)
+            .is_none());
+        assert_eq!(
+            diff_book.snapshot_request().unwrap(),
+            "http://127.0.0.1/api/v2/order_book/btcusd/"
+        );
+        assert!(diff_book
+            .apply_message(&change(1002, "[]", r#"[["102.0", "0.5"]]"#), 10)
+            .is_none());
+        let orderbook = diff_book
+            .apply_snapshot(Ok(snapshot.to_string()), 10)
+            .unwrap();
+        assert!(diff_book.snapshot_request().is_none());
+        assert_eq!(orderbook.bids.len(), 2);
+        assert_eq!(orderbook.bids[0].price, dec!(100.0));
+        assert_eq!(orderbook.asks[1].price, dec!(102.0));
+
+        // Older than the book
+        assert!(diff_book
+            .apply_message(&change(1001, r#"[["100.0", "0"]]"#, "[]"), 10)
+            .is_none());
+        let orderbook = diff_book
+            .apply_message(
+                &change(1003, r#"[["100.0", "0"]]"#, r#"[["100.5", "0.3"]]"#),
+                10,
+            )
+            .unwrap();
+        assert_eq!(orderbook.bids[0].price, dec!(99.0));
+        assert_eq!(orderbook.asks[0].price, dec!(100.5));
+        assert_eq!(orderbook.asks[1].amount, dec!(1.5));
+
+        diff_book.reset();
+        assert!(diff_book.book.bids.is_empty());
+
+        // A failed fetch resyncs
+        diff_book.apply_message(&change(1004, "[]", "[]"), 10);
+        assert!(diff_book
+            .apply_snapshot(Err("timed out".to_string()), 10)
+            .is_none());
+        assert!(diff_book.out_of_sync);
+        assert!(diff_book.snapshot_request().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bitstamp_connect() {
+        let symbol = "btcusd";
+
+        let result = bitstamp_connect(symbol).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_bitstamp_conformance() {
+        run_conformance(
+            |url| Box::new(BitstampConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusd",
+                ack: r#"{"event":"bts:subscription_succeeded","channel":"detail_order_book_btcusd","data":{}}"#.to_string(),
+                snapshot: r#"{"event": "data", "channel": "detail_order_book_btcusd", "data": {"bids": [["10.0", "1.0", "1"]], "asks": [["11.0", "0.8", "2"], ["11.5", "0.7", "3"]]}}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                // A later snapshot replaces the book
+                updates: vec![(
+                    r#"{"event": "data", "channel": "detail_order_book_btcusd", "data": {"bids": [["10.1", "0.5", "4"], ["9.8", "3.0", "5"]], "asks": [["10.9", "0.1", "6"]]}}"#.to_string(),
+                    (vec![(10.1, 0.5), (9.8, 3.0)], vec![(10.9, 0.1)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use crate::book::{OrderBook, OrderBookSide, VenueMetadata};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::{parse_levels, ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::{decimal_to_f64, json_f64};
+use crate::symbols::joined_symbol;
+use serde_json::Value;
+
+// Bybit sends a full snapshot first and then deltas, so unlike the partial book
+// streams of binance and bitstamp the levels have to be maintained between messages
+#[derive(Debug, Clone)]
+pub(crate) struct BybitOrderBook {
+    pub bids: OrderBookSide,
+    pub asks: OrderBookSide,
+    pub last_update_id: u64,
+    pub metadata: VenueMetadata,
+    // Set when a delta doesn't follow the last update id, deltas are ignored
+    // until the book is rebuilt from a new snapshot
+    pub out_of_sync: bool,
+    pub compactor: Compactor,
+}
+
+impl Default for BybitOrderBook {
+    fn default() -> BybitOrderBook {
+        BybitOrderBook {
+            bids: OrderBookSide::bids(),
+            asks: OrderBookSide::asks(),
+            last_update_id: 0,
+            metadata: VenueMetadata::default(),
+            out_of_sync: false,
+            compactor: Compactor::default(),
+        }
+    }
+}
+
+impl BybitOrderBook {
+    pub fn new() -> BybitOrderBook {
+        BybitOrderBook::default()
+    }
+
+    // Ticker deltas only carry the fields that changed, so missing fields keep their last value
+    fn apply_ticker(&mut self, data: &Value, exchange: &str) {
+        let parse_f64 = |key: &str| json_f64(&data[key]);
+
+        self.metadata.exchange = exchange.to_string();
+        if let Some(mark_price) = parse_f64("markPrice") {
+            self.metadata.mark_price = Some(mark_price);
+        }
+        if let Some(funding_rate) = parse_f64("fundingRate") {
+            self.metadata.funding_rate = Some(funding_rate);
+        }
+        if let Some(next_funding_time) = data["nextFundingTime"]
+            .as_str()
+            .and_then(|s| s.parse::<u64>().ok())
+        {
+            self.metadata.next_funding_time = Some(next_funding_time);
+        }
+    }
+
+    // Applies a snapshot or delta message from the orderbook topic and returns the
+    // trimmed book, or None if the message is not an orderbook update.
+    // Ticker messages update the funding rate and mark price attached to the next book
+    pub fn apply_message(
+        &mut self,
+        message_text: &str,
+        exchange: &str,
+        depth: usize,
+    ) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let topic = result["topic"].as_str()?;
+        if topic.starts_with("tickers.") {
+            self.apply_ticker(&result["data"], exchange);
+            return None;
+        }
+        if !topic.starts_with("orderbook.") {
+            return None;
+        }
+
+        let data = &result["data"];
+        let bids = parse_levels(&data["b"], exchange)?;
+        let asks = parse_levels(&data["a"], exchange)?;
+        let update_id = data["u"].as_u64().unwrap_or(self.last_update_id + 1);
+
+        match result["type"].as_str()? {
+            "snapshot" => {
+                self.bids.replace(bids);
+                self.asks.replace(asks);
+                self.out_of_sync = false;
+            }
+            "delta" => {
+                if self.out_of_sync || update_id != self.last_update_id + 1 {
+                    self.out_of_sync = true;
+                    return None;
+                }
+                self.bids.apply_updates(bids);
+                self.asks.apply_updates(asks);
+                if self.compactor.on_update(&mut self.bids, &mut self.asks) {
+                    let stats = self.compactor.stats();
+                    println!(
+                        "Compacted {} book: {} bids and {} asks pruned in {} compactions",
+                        exchange, stats.pruned_bids, stats.pruned_asks, stats.compactions
+                    );
+                }
+            }
+            _ => return None,
+        }
+        self.last_update_id = update_id;
+
+        let selected_bids = self.bids.top(depth);
+        let selected_asks = self.asks.top(depth);
+
+        let spread = match (selected_bids.first(), selected_asks.first()) {
+            (Some(first_bid), Some(first_ask)) => decimal_to_f64(first_bid.price - first_ask.price),
+            _ => 0.0,
+        };
+
+        let venues = if self.metadata.exchange.is_empty() {
+            Vec::new()
+        } else {
+            vec![self.metadata.clone()]
+        };
+
+        Some(OrderBook {
+            bids: selected_bids,
+            asks: selected_asks,
+            spread,
+            venues,
+        })
+    }
+}
+
+// Bybit's v5 public streams of the USDT perpetuals (linear) or of the spot market,
+// both send the orderbook topic as a snapshot followed by deltas
+pub struct BybitConnector {
+    url: String,
+    // linear or spot, the category of the REST API
+    category: &'static str,
+    orderbook: BybitOrderBook,
+}
+
+impl BybitConnector {
+    pub fn new() -> BybitConnector {
+        BybitConnector::with_url("wss://stream.bybit.com/v5/public/linear")
+    }
+
+    pub fn with_url(url: &str) -> BybitConnector {
+        BybitConnector {
+            url: url.to_string(),
+            category: "linear",
+            orderbook: BybitOrderBook::new(),
+        }
+    }
+
+    pub fn spot() -> BybitConnector {
+        BybitConnector::spot_with_url("wss://stream.bybit.com/v5/public/spot")
+    }
+
+    pub fn spot_with_url(url: &str) -> BybitConnector {
+        BybitConnector {
+            category: "spot",
+            ..BybitConnector::with_url(url)
+        }
+    }
+
+    // bybit linear books are available with 1, 50, 200 or 500 levels and spot books with
+    // 1, 50 or 200, we trim to depth later
+    // the tickers topic carries the funding rate and mark price of the perpetual
+    fn topics(&self, symbol: &str) -> String {
+        if self.category == "spot" {
+            return format!(r#""orderbook.50.{}""#, symbol.to_uppercase());
+        }
+        format!(
+            r#""orderbook.50.{}", "tickers.{}""#,
+            symbol.to_uppercase(),
+            symbol.to_uppercase()
+        )
+    }
+}
+
+impl Default for BybitConnector {
+    fn default() -> Self {
+        BybitConnector::new()
+    }
+}
+
+impl ExchangeConnector for BybitConnector {
+    fn name(&self) -> &str {
+        "Bybit"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        joined_symbol(symbol)
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api.bybit.com/v5/market/instruments-info?category={}&symbol={}",
+            self.category, symbol
+        ))
+    }
+
+    fn instruments_url(&self) -> Option<String> {
+        Some(format!(
+            "https://api.bybit.com/v5/market/instruments-info?category={}",
+            self.category
+        ))
+    }
+
+    // {"result": {"list": [{"symbol": "BTCUSDT", "status": "Trading", ...}, ...]}}
+    fn listed_symbols(&self, body: &str) -> Vec<String> {
+        let info = serde_json::from_str::<Value>(body).unwrap_or_default();
+        let instruments = info["result"]["list"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        instruments
+            .iter()
+            .filter(|instrument| instrument["status"] == "Trading")
+            .filter_map(|instrument| Some(instrument["symbol"].as_str()?.to_string()))
+            .collect()
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![format!(
+            r#"{{"op": "subscribe", "args": [{}]}}"#,
+            self.topics(symbol)
+        )]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![format!(
+            r#"{{"op": "unsubscribe", "args": [{}]}}"#,
+            self.topics(symbol)
+        )]
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["op"] == "subscribe" && ack["success"] == true
+    }
+
+    // {"success": false, "ret_msg": "error:handler not found", "op": "subscribe"}, the
+    // topics of the subscription don't exist
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["success"] != false {
+            return None;
+        }
+        Some(ExchangeError {
+            code: None,
+            message: result["ret_msg"].as_str().unwrap_or_default().to_string(),
+            action: ErrorAction::Disable,
+        })
+    }
+
+    // The ts of the message, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["ts"].as_u64()
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        self.orderbook.apply_message(message_text, "bybit", depth)
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.orderbook.out_of_sync
+    }
+
+    // The compactor is kept, so its stats cover the whole run
+    fn reset(&mut self) {
+        self.orderbook = BybitOrderBook {
+            compactor: self.orderbook.compactor.clone(),
+            ..BybitOrderBook::new()
+        };
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.orderbook.compactor = Compactor::new(Some(policy));
+    }
+}
+
+crate::register_connector!("bybit", true, |_| Box::new(BybitConnector::new()));
+crate::register_connector!("bybit", false, |_| Box::new(BybitConnector::spot()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_bybit_apply_message() {
+        let snapshot = r#"
+            {
+                "topic": "orderbook.50.BTCUSDT",
+                "type": "snapshot",
+                "data": {
+                    "s": "BTCUSDT",
+                    "b": [ [ "10.0", "1.0" ], [ "9.5", "2.0" ] ],
+                    "a": [ [ "11.0", "0.8" ], [ "11.5", "0.7" ] ],
+                    "u": 1
+                }
+            }
+        "#;
+        let delta = r#"
+            {
+                "topic": "orderbook.50.BTCUSDT",
+                "type": "delta",
+                "data": {
+                    "s": "BTCUSDT",
+                    "b": [ [ "10.0", "0" ], [ "9.8", "3.0" ] ],
+                    "a": [ [ "11.0", "0.5" ] ],
+                    "u": 2
+                }
+            }
+        "#;
+
+        let mut bybit_orderbook = BybitOrderBook::new();
+        let orderbook = bybit_orderbook
+            .apply_message(snapshot, "bybit", 10)
+            .unwrap();
+        assert_eq!(orderbook.bids[0].price, dec!(10.0));
+        assert_eq!(orderbook.spread, -1.0);
+
+        let orderbook = bybit_orderbook.apply_message(delta, "bybit", 10).unwrap();
+        assert_eq!(orderbook.bids.len(), 2);
+        assert_eq!(orderbook.bids[0].price, dec!(9.8));
+        assert_eq!(orderbook.bids[0].amount, dec!(3.0));
+        assert_eq!(orderbook.asks[0].amount, dec!(0.5));
+        assert_eq!(bybit_orderbook.last_update_id, 2);
+
+        assert!(orderbook.venues.is_empty());
+
+        // A skipped update id leaves the book out of sync until the next snapshot
+        let gap = delta.replace("\"u\": 2", "\"u\": 4");
+        assert!(bybit_orderbook.apply_message(&gap, "bybit", 10).is_none());
+        assert!(bybit_orderbook.out_of_sync);
+        assert!(bybit_orderbook
+            .apply_message(snapshot, "bybit", 10)
+            .is_some());
+        assert!(!bybit_orderbook.out_of_sync);
+    }
+
+    #[test]
+    fn test_bybit_apply_ticker() {
+        let snapshot = r#"
+            {
+                "topic": "orderbook.50.BTCUSDT",
+                "type": "snapshot",
+                "data": { "b": [ [ "10.0", "1.0" ] ], "a": [ [ "11.0", "0.8" ] ], "u": 1 }
+            }
+        "#;
+        let ticker = r#"
+            {
+                "topic": "tickers.BTCUSDT",
+                "type": "snapshot",
+                "data": {
+                    "symbol": "BTCUSDT",
+                    "markPrice": "10.5",
+                    "fundingRate": "0.0001",
+                    "nextFundingTime": "1673280000000"
+                }
+            }
+        "#;
+        let ticker_delta = r#"
+            {
+                "topic": "tickers.BTCUSDT",
+                "type": "delta",
+                "data": { "symbol": "BTCUSDT", "markPrice": "10.6" }
+            }
+        "#;
+
+        let mut bybit_orderbook = BybitOrderBook::new();
+        assert!(bybit_orderbook.apply_message(ticker, "bybit", 10).is_none());
+        assert!(bybit_orderbook
+            .apply_message(ticker_delta, "bybit", 10)
+            .is_none());
+
+        let orderbook = bybit_orderbook
+            .apply_message(snapshot, "bybit", 10)
+            .unwrap();
+
+        assert_eq!(
+            orderbook.venues,
+            vec![VenueMetadata {
+                exchange: "bybit".to_string(),
+                mark_price: Some(10.6),
+                funding_rate: Some(0.0001),
+                next_funding_time: Some(1673280000000),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_bybit_conformance() {
+        run_conformance(
+            |url| Box::new(BybitConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "BTCUSDT",
+                ack: r#"{"success":true,"ret_msg":"","conn_id":"1","op":"subscribe"}"#.to_string(),
+                snapshot: r#"{"topic": "orderbook.50.BTCUSDT", "type": "snapshot", "data": {"b": [["10.0", "1.0"]], "a": [["11.0", "0.8"]], "u": 1}}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8)]),
+                updates: vec![(
+                    r#"{"topic": "orderbook.50.BTCUSDT", "type": "delta", "data": {"b": [["10.0", "0"], ["9.8", "3.0"]], "a": [["10.9", "0.1"]], "u": 2}}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8)]),
+                )],
+                gap: Some(r#"{"topic": "orderbook.50.BTCUSDT", "type": "delta", "data": {"b": [["9.7", "1.0"]], "a": [], "u": 5}}"#.to_string()),
+            },
+        );
+    }
+
+    #[test]
+    fn test_bybit_spot_conformance() {
+        run_conformance(
+            |url| Box::new(BybitConnector::spot_with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"success":true,"ret_msg":"subscribe","conn_id":"2","op":"subscribe"}"#.to_string(),
+                snapshot: r#"{"topic": "orderbook.50.BTCUSDT", "ts": 1672304484978, "type": "snapshot", "data": {"s": "BTCUSDT", "b": [["10.0", "1.0"]], "a": [["11.0", "0.8"]], "u": 18521288, "seq": 7961638724}}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8)]),
+                updates: vec![(
+                    r#"{"topic": "orderbook.50.BTCUSDT", "ts": 1672304484988, "type": "delta", "data": {"s": "BTCUSDT", "b": [["10.0", "0"], ["9.8", "3.0"]], "a": [["10.9", "0.1"]], "u": 18521289, "seq": 7961638725}}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8)]),
+                )],
+                gap: Some(r#"{"topic": "orderbook.50.BTCUSDT", "ts": 1672304484998, "type": "delta", "data": {"s": "BTCUSDT", "b": [["9.7", "1.0"]], "a": [], "u": 18521295, "seq": 7961638731}}"#.to_string()),
+            },
+        );
+    }
+}
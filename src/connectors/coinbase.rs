@@ -0,0 +1,263 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::ExchangeConnector;
+use crate::number::{json_decimal, json_f64};
+use crate::symbols::split_symbol;
+use crate::tape::{TakerSide, TapeTrade};
+use serde_json::Value;
+
+// Coinbase Advanced Trade's level2 channel sends a snapshot and then the changed
+// levels. Every message of the connection carries the next sequence_num, whatever its
+// channel, a gap means updates were lost and the book is only rebuilt by a new snapshot
+pub struct CoinbaseConnector {
+    url: String,
+    book: LocalBook,
+    last_sequence: Option<u64>,
+    out_of_sync: bool,
+    // Trades of the market_trades channel, only subscribed once enabled
+    tape: Option<Vec<TapeTrade>>,
+}
+
+impl CoinbaseConnector {
+    pub fn new() -> CoinbaseConnector {
+        CoinbaseConnector::with_url("wss://advanced-trade-ws.coinbase.com")
+    }
+
+    pub fn with_url(url: &str) -> CoinbaseConnector {
+        CoinbaseConnector {
+            url: url.to_string(),
+            book: LocalBook::new(),
+            last_sequence: None,
+            out_of_sync: false,
+            tape: None,
+        }
+    }
+
+    fn channel_messages(&self, message_type: &str, symbol: &str) -> Vec<String> {
+        let channels: &[&str] = match self.tape {
+            Some(_) => &["level2", "market_trades"],
+            None => &["level2"],
+        };
+        channels
+            .iter()
+            .map(|channel| {
+                format!(
+                    r#"{{"type": "{}", "product_ids": ["{}"], "channel": "{}"}}"#,
+                    message_type, symbol, channel
+                )
+            })
+            .collect()
+    }
+}
+
+impl Default for CoinbaseConnector {
+    fn default() -> Self {
+        CoinbaseConnector::new()
+    }
+}
+
+// Updates of both sides come in one list, {"side": "bid" | "offer", "price_level": "10.5",
+// "new_quantity": "0.2"}, a quantity of zero removes the level
+fn parse_coinbase_updates(
+    updates: &Value,
+) -> Option<(Vec<PriceAmountLevel>, Vec<PriceAmountLevel>)> {
+    let mut bids = Vec::new();
+    let mut asks = Vec::new();
+    for update in updates.as_array()? {
+        let parse_decimal = |key: &str| json_decimal(&update[key]);
+        let (Some(price), Some(amount)) =
+            (parse_decimal("price_level"), parse_decimal("new_quantity"))
+        else {
+            continue;
+        };
+        let level = PriceAmountLevel {
+            exchange: "coinbase".to_string(),
+            price,
+            amount,
+        };
+        match update["side"].as_str() {
+            Some("bid") => bids.push(level),
+            Some("offer") => asks.push(level),
+            _ => {}
+        }
+    }
+    Some((bids, asks))
+}
+
+// {"product_id": "BTC-USD", "price": "10.5", "size": "0.2", "side": "BUY", ...}. Like on
+// its Exchange feed, Coinbase reports the side of the maker order, so it is inverted
+fn parse_coinbase_trade(trade: &Value) -> Option<TapeTrade> {
+    Some(TapeTrade {
+        exchange: "coinbase".to_string(),
+        price: json_f64(&trade["price"])?,
+        amount: json_f64(&trade["size"])?,
+        side: TakerSide::from_venue(trade["side"].as_str()?, true)?,
+    })
+}
+
+impl ExchangeConnector for CoinbaseConnector {
+    fn name(&self) -> &str {
+        "Coinbase"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // BTC-USD
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => format!("{}-{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api.coinbase.com/api/v3/brokerage/market/products/{}",
+            symbol
+        ))
+    }
+
+    fn instruments_url(&self) -> Option<String> {
+        Some("https://api.coinbase.com/api/v3/brokerage/market/products".to_string())
+    }
+
+    // {"products": [{"product_id": "BTC-USD", "status": "online", ...}, ...]}
+    fn listed_symbols(&self, body: &str) -> Vec<String> {
+        let products = serde_json::from_str::<Value>(body).unwrap_or_default();
+        let products = products["products"].as_array().cloned().unwrap_or_default();
+        products
+            .iter()
+            .filter(|product| product["status"] == "online")
+            .filter_map(|product| Some(product["product_id"].as_str()?.to_string()))
+            .collect()
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        self.channel_messages("subscribe", symbol)
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        self.channel_messages("unsubscribe", symbol)
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["channel"] == "subscriptions"
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let sequence = result["sequence_num"].as_u64();
+        let in_sequence = match (self.last_sequence, sequence) {
+            (Some(last_sequence), Some(sequence)) => sequence == last_sequence + 1,
+            _ => true,
+        };
+        self.last_sequence = sequence.or(self.last_sequence);
+        // A gap before a trade leaves the book out of sync as well
+        self.out_of_sync |= !in_sequence;
+        if result["channel"] == "market_trades" {
+            let tape = self.tape.as_mut()?;
+            // The snapshot holds the trades before the subscription
+            for event in result["events"].as_array()? {
+                if event["type"] == "update" {
+                    let trades = event["trades"].as_array()?;
+                    tape.extend(trades.iter().filter_map(parse_coinbase_trade));
+                }
+            }
+            return None;
+        }
+        if result["channel"] != "l2_data" {
+            return None;
+        }
+
+        let mut applied = false;
+        for event in result["events"].as_array()? {
+            let (bids, asks) = parse_coinbase_updates(&event["updates"])?;
+            match event["type"].as_str()? {
+                "snapshot" => {
+                    self.book.replace(bids, asks);
+                    self.out_of_sync = false;
+                }
+                "update" => {
+                    if self.out_of_sync {
+                        return None;
+                    }
+                    self.book.apply_updates(bids, asks, "coinbase");
+                }
+                _ => continue,
+            }
+            applied = true;
+        }
+        applied.then(|| self.book.orderbook(depth))
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    // The sequence restarts with the new connection
+    fn reset(&mut self) {
+        self.book.clear();
+        self.last_sequence = None;
+        self.out_of_sync = false;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+
+    fn enable_trades(&mut self) {
+        self.tape = Some(Vec::new());
+    }
+
+    fn take_trades(&mut self) -> Vec<TapeTrade> {
+        self.tape.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+}
+
+crate::register_connector!("coinbase", false, |_| Box::new(CoinbaseConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_coinbase_conformance() {
+        run_conformance(
+            |url| Box::new(CoinbaseConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusd",
+                ack: r#"{"channel":"subscriptions","client_id":"","timestamp":"2023-02-09T20:32:50.714964855Z","sequence_num":0,"events":[{"subscriptions":{"level2":["BTC-USD"]}}]}"#.to_string(),
+                snapshot: r#"{"channel":"l2_data","sequence_num":1,"events":[{"type":"snapshot","product_id":"BTC-USD","updates":[{"side":"bid","price_level":"10.0","new_quantity":"1.0"},{"side":"offer","price_level":"11.0","new_quantity":"0.8"}]}]}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8)]),
+                updates: vec![(
+                    r#"{"channel":"l2_data","sequence_num":2,"events":[{"type":"update","product_id":"BTC-USD","updates":[{"side":"bid","price_level":"10.0","new_quantity":"0"},{"side":"bid","price_level":"9.8","new_quantity":"3.0"},{"side":"offer","price_level":"10.9","new_quantity":"0.1"}]}]}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8)]),
+                )],
+                gap: Some(r#"{"channel":"l2_data","sequence_num":5,"events":[{"type":"update","product_id":"BTC-USD","updates":[{"side":"bid","price_level":"9.7","new_quantity":"1.0"}]}]}"#.to_string()),
+            },
+        );
+    }
+
+    #[test]
+    fn test_coinbase_trades() {
+        let mut connector = CoinbaseConnector::new();
+        assert_eq!(connector.subscribe_messages("BTC-USD").len(), 1);
+        connector.enable_trades();
+        assert!(connector.subscribe_messages("BTC-USD")[1].contains("market_trades"));
+
+        let snapshot = r#"{"channel":"market_trades","sequence_num":1,"events":[{"type":"snapshot","trades":[{"trade_id":"1","product_id":"BTC-USD","price":"10.4","size":"1.0","side":"BUY","time":"2023-02-09T20:32:50.714964855Z"}]}]}"#;
+        let update = r#"{"channel":"market_trades","sequence_num":2,"events":[{"type":"update","trades":[{"trade_id":"2","product_id":"BTC-USD","price":"10.5","size":"0.2","side":"SELL","time":"2023-02-09T20:32:51.714964855Z"}]}]}"#;
+        assert!(connector.apply_message(snapshot, 10).is_none());
+        assert!(connector.apply_message(update, 10).is_none());
+        // The maker sold, so the taker bought
+        let trades = connector.take_trades();
+        assert_eq!(trades.len(), 1);
+        assert_eq!((trades[0].price, trades[0].side), (10.5, TakerSide::Buy));
+        assert!(connector.take_trades().is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::connectors::{error_code, ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::{decimal_to_f64, json_decimal};
+use crate::symbols::split_symbol;
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// Crypto.com Exchange's book.{instrument}.{depth} channel, subscribed in SNAPSHOT mode,
+// pushes the top 10 or 50 levels every 100 ms, like binance's partial book streams.
+// Crypto.com sends a public/heartbeat every 30 s and closes connections that don't
+// answer it with a public/respond-heartbeat of the same id
+pub struct CryptocomConnector {
+    url: String,
+    depth: u32,
+    // Nonce of the requests, in milliseconds
+    clock: fn() -> u64,
+}
+
+impl CryptocomConnector {
+    pub fn new(depth: u32) -> CryptocomConnector {
+        CryptocomConnector::with_url("wss://stream.crypto.com/exchange/v1/market", depth)
+    }
+
+    pub fn with_url(url: &str, depth: u32) -> CryptocomConnector {
+        CryptocomConnector {
+            url: url.to_string(),
+            depth,
+            clock: unix_millis,
+        }
+    }
+
+    fn channel(&self, symbol: &str) -> String {
+        let levels = if self.depth <= 10 { 10 } else { 50 };
+        format!("book.{}.{}", symbol, levels)
+    }
+
+    fn channel_message(&self, id: u32, method: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"id": {}, "method": "{}", "params": {{"channels": ["{}"], "book_subscription_type": "SNAPSHOT", "book_update_frequency": 100}}, "nonce": {}}}"#,
+            id,
+            method,
+            self.channel(symbol),
+            (self.clock)()
+        )
+    }
+}
+
+// Levels are [price, quantity, number of orders] strings
+fn parse_cryptocom_levels(levels: &Value) -> Option<Vec<PriceAmountLevel>> {
+    Some(
+        levels
+            .as_array()?
+            .iter()
+            .filter_map(|level| {
+                Some(PriceAmountLevel {
+                    exchange: "cryptocom".to_string(),
+                    price: json_decimal(&level[0])?,
+                    amount: json_decimal(&level[1])?,
+                })
+            })
+            .collect(),
+    )
+}
+
+impl ExchangeConnector for CryptocomConnector {
+    fn name(&self) -> &str {
+        "Crypto.com"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // BTC_USDT
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => format!("{}_{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api.crypto.com/exchange/v1/public/get-book?instrument_name={}&depth=10",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message(1, "subscribe", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message(2, "unsubscribe", symbol)]
+    }
+
+    // The response to the request, the books are pushed with an id of -1
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["id"] == 1 && ack["method"] == "subscribe" && ack["code"] == 0
+    }
+
+    // {"id": 1, "method": "subscribe", "code": 10004, "message": "BAD_REQUEST"}, 10006
+    // is the rate limit
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["code"].as_i64().is_none_or(|code| code == 0) {
+            return None;
+        }
+        let code = error_code(&result["code"]);
+        let action = match code.as_deref() {
+            Some("10006") => ErrorAction::BackOff,
+            _ => ErrorAction::Disable,
+        };
+        Some(ExchangeError {
+            code,
+            message: result["message"].as_str().unwrap_or_default().to_string(),
+            action,
+        })
+    }
+
+    fn heartbeat_reply(&self, message_text: &str) -> Option<String> {
+        let heartbeat = serde_json::from_str::<Value>(message_text).ok()?;
+        if heartbeat["method"] != "public/heartbeat" {
+            return None;
+        }
+        Some(format!(
+            r#"{{"id": {}, "method": "public/respond-heartbeat"}}"#,
+            heartbeat["id"].as_u64()?
+        ))
+    }
+
+    // The t of the book, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["result"]["data"][0]["t"].as_u64()
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["method"] != "subscribe" || result["result"]["channel"] != "book" {
+            return None;
+        }
+        let book = &result["result"]["data"][0];
+        let mut orderbook = OrderBook::new();
+        orderbook.bids = parse_cryptocom_levels(&book["bids"])?;
+        orderbook.asks = parse_cryptocom_levels(&book["asks"])?;
+        orderbook.bids.truncate(depth);
+        orderbook.asks.truncate(depth);
+        if let (Some(best_bid), Some(best_ask)) = (orderbook.bids.first(), orderbook.asks.first()) {
+            orderbook.spread = decimal_to_f64(best_bid.price - best_ask.price);
+        }
+        Some(orderbook)
+    }
+}
+
+crate::register_connector!("cryptocom", false, |depth| Box::new(
+    CryptocomConnector::new(depth)
+));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_cryptocom_heartbeat() {
+        let connector = CryptocomConnector::new(10);
+        assert_eq!(
+            connector
+                .heartbeat_reply(r#"{"id":1587523073344,"method":"public/heartbeat","code":0}"#),
+            Some(r#"{"id": 1587523073344, "method": "public/respond-heartbeat"}"#.to_string())
+        );
+        assert_eq!(
+            connector.heartbeat_reply(r#"{"id":1,"method":"subscribe","code":0}"#),
+            None
+        );
+        assert_eq!(connector.normalize_symbol("btcusdt"), "BTC_USDT");
+    }
+
+    #[test]
+    fn test_cryptocom_conformance() {
+        run_conformance(
+            |url| {
+                Box::new(CryptocomConnector {
+                    clock: || 1587523073344,
+                    ..CryptocomConnector::with_url(url, 10)
+                })
+            },
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"id":1,"method":"subscribe","code":0}"#.to_string(),
+                snapshot: r#"{"id":-1,"method":"subscribe","code":0,"result":{"instrument_name":"BTC_USDT","subscription":"book.BTC_USDT.10","channel":"book","depth":10,"data":[{"bids":[["10.0","1.0","2"]],"asks":[["11.0","0.8","1"],["11.5","0.7","3"]],"t":1654780033786,"tt":1654780033755,"u":542048017824}]}}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                // A later snapshot replaces the book
+                updates: vec![(
+                    r#"{"id":-1,"method":"subscribe","code":0,"result":{"instrument_name":"BTC_USDT","subscription":"book.BTC_USDT.10","channel":"book","depth":10,"data":[{"bids":[["10.1","0.5","1"],["9.8","3.0","4"]],"asks":[["10.9","0.1","1"]],"t":1654780033886,"tt":1654780033855,"u":542048017924}]}}"#.to_string(),
+                    (vec![(10.1, 0.5), (9.8, 3.0)], vec![(10.9, 0.1)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::{error_code, ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::json_decimal;
+use crate::symbols::split_symbol;
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+// Deribit's book.{instrument}.100ms channel sends a snapshot of the book and then the
+// changed levels of the futures and options instruments. Every change carries its
+// change_id and the prev_change_id of the change before it, a mismatch means changes
+// were lost and the book is only rebuilt by a new snapshot. Amounts are in the
+// instrument's contract units, USD for the inverse futures
+pub struct DeribitConnector {
+    url: String,
+    book: LocalBook,
+    last_change_id: Option<u64>,
+    out_of_sync: bool,
+}
+
+impl DeribitConnector {
+    pub fn new() -> DeribitConnector {
+        DeribitConnector::with_url("wss://www.deribit.com/ws/api/v2")
+    }
+
+    pub fn with_url(url: &str) -> DeribitConnector {
+        DeribitConnector {
+            url: url.to_string(),
+            book: LocalBook::new(),
+            last_change_id: None,
+            out_of_sync: false,
+        }
+    }
+
+    fn channel_message(&self, method: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"jsonrpc": "2.0", "id": 1, "method": "{}", "params": {{"channels": ["book.{}.100ms"]}}}}"#,
+            method, symbol
+        )
+    }
+}
+
+impl Default for DeribitConnector {
+    fn default() -> Self {
+        DeribitConnector::new()
+    }
+}
+
+// Levels are [action, price, amount], the delete action removes the level
+fn parse_deribit_levels(levels: &Value) -> Option<Vec<PriceAmountLevel>> {
+    Some(
+        levels
+            .as_array()?
+            .iter()
+            .filter_map(|level| {
+                let amount = match level[0].as_str()? {
+                    "delete" => Decimal::ZERO,
+                    _ => json_decimal(&level[2])?,
+                };
+                Some(PriceAmountLevel {
+                    exchange: "deribit".to_string(),
+                    price: json_decimal(&level[1])?,
+                    amount,
+                })
+            })
+            .collect(),
+    )
+}
+
+impl ExchangeConnector for DeribitConnector {
+    fn name(&self) -> &str {
+        "Deribit"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // Instruments are served as Deribit lists them, e.g. btc-27dec24 or
+    // btc-27dec24-60000-c, and a pair like btcusd is the perpetual of its base,
+    // BTC-PERPETUAL
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        if symbol.contains('-') {
+            return symbol.to_uppercase();
+        }
+        match split_symbol(symbol) {
+            Some((base, _)) => format!("{}-PERPETUAL", base),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://www.deribit.com/api/v2/public/get_order_book?instrument_name={}",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message("public/subscribe", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message("public/unsubscribe", symbol)]
+    }
+
+    // {"jsonrpc": "2.0", "id": 1, "result": ["book.BTC-PERPETUAL.100ms"]}
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["result"].as_array().is_some_and(|channels| {
+            channels
+                .iter()
+                .any(|channel| channel.as_str().is_some_and(|c| c.starts_with("book.")))
+        })
+    }
+
+    // {"jsonrpc": "2.0", "id": 1, "error": {"code": -32602, "message": "Invalid params"}},
+    // 10028 is the rate limit
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let error = result.get("error")?;
+        let code = error_code(&error["code"]);
+        let action = match code.as_deref() {
+            Some("10028") => ErrorAction::BackOff,
+            _ => ErrorAction::Disable,
+        };
+        Some(ExchangeError {
+            code,
+            message: error["message"]
+                .as_str()
+                .or(error.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            action,
+        })
+    }
+
+    // The timestamp of the book, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["params"]["data"]["timestamp"].as_u64()
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["method"] != "subscription"
+            || !result["params"]["channel"]
+                .as_str()
+                .is_some_and(|channel| channel.starts_with("book."))
+        {
+            return None;
+        }
+        let data = &result["params"]["data"];
+        let bids = parse_deribit_levels(&data["bids"])?;
+        let asks = parse_deribit_levels(&data["asks"])?;
+        match data["type"].as_str()? {
+            "snapshot" => {
+                self.book.replace(bids, asks);
+                self.out_of_sync = false;
+            }
+            "change" => {
+                let in_sequence = self.last_change_id.is_some_and(|last_change_id| {
+                    data["prev_change_id"].as_u64() == Some(last_change_id)
+                });
+                if self.out_of_sync || !in_sequence {
+                    self.out_of_sync = true;
+                    return None;
+                }
+                self.book.apply_updates(bids, asks, "deribit");
+            }
+            _ => return None,
+        }
+        self.last_change_id = data["change_id"].as_u64();
+        Some(self.book.orderbook(depth))
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    fn reset(&mut self) {
+        self.book.clear();
+        self.last_change_id = None;
+        self.out_of_sync = false;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+}
+
+crate::register_connector!("deribit", false, |_| Box::new(DeribitConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_deribit_instruments() {
+        let connector = DeribitConnector::new();
+        assert_eq!(connector.normalize_symbol("btcusd"), "BTC-PERPETUAL");
+        assert_eq!(connector.normalize_symbol("eth-27dec24"), "ETH-27DEC24");
+        assert_eq!(
+            connector.normalize_symbol("btc-27dec24-60000-c"),
+            "BTC-27DEC24-60000-C"
+        );
+    }
+
+    #[test]
+    fn test_deribit_conformance() {
+        run_conformance(
+            |url| Box::new(DeribitConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusd",
+                ack: r#"{"jsonrpc":"2.0","id":1,"result":["book.BTC-PERPETUAL.100ms"],"usIn":1554373548000000,"usOut":1554373548000100,"usDiff":100}"#.to_string(),
+                snapshot: r#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"book.BTC-PERPETUAL.100ms","data":{"type":"snapshot","timestamp":1554373962454,"instrument_name":"BTC-PERPETUAL","change_id":297217,"bids":[["new",10.0,1.0]],"asks":[["new",11.0,0.8],["new",11.5,0.7]]}}}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                updates: vec![(
+                    r#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"book.BTC-PERPETUAL.100ms","data":{"type":"change","timestamp":1554373911330,"instrument_name":"BTC-PERPETUAL","prev_change_id":297217,"change_id":297218,"bids":[["delete",10.0,0],["new",9.8,3.0]],"asks":[["new",10.9,0.1]]}}}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8), (11.5, 0.7)]),
+                )],
+                gap: Some(r#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"book.BTC-PERPETUAL.100ms","data":{"type":"change","timestamp":1554373911530,"instrument_name":"BTC-PERPETUAL","prev_change_id":297220,"change_id":297221,"bids":[["new",9.7,1.0]],"asks":[]}}}"#.to_string()),
+            },
+        );
+    }
+}
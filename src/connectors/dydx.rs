@@ -0,0 +1,218 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::{ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::json_decimal;
+use crate::symbols::split_symbol;
+use serde_json::Value;
+use std::sync::Mutex;
+
+type Levels = (Vec<PriceAmountLevel>, Vec<PriceAmountLevel>);
+
+// dYdX v4's indexer streams the v4_orderbook channel of the perpetual markets, the
+// decentralized perp liquidity. The subscribed message acknowledging the subscription
+// holds the full book, so like Gemini's its levels are kept until the next message is
+// applied. The changed levels follow in channel_data messages. Every message of the
+// connection carries a message_id one above the previous one, a jump means messages
+// were lost and the book is only rebuilt by subscribing again
+pub struct DydxConnector {
+    url: String,
+    book: LocalBook,
+    // Levels and message_id of the subscribed message
+    initial_book: Mutex<Option<(Levels, u64)>>,
+    last_message_id: Option<u64>,
+    out_of_sync: bool,
+}
+
+impl DydxConnector {
+    pub fn new() -> DydxConnector {
+        DydxConnector::with_url("wss://indexer.dydx.trade/v4/ws")
+    }
+
+    pub fn with_url(url: &str) -> DydxConnector {
+        DydxConnector {
+            url: url.to_string(),
+            book: LocalBook::new(),
+            initial_book: Mutex::new(None),
+            last_message_id: None,
+            out_of_sync: false,
+        }
+    }
+
+    fn channel_message(&self, message_type: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"type": "{}", "channel": "v4_orderbook", "id": "{}"}}"#,
+            message_type, symbol
+        )
+    }
+}
+
+impl Default for DydxConnector {
+    fn default() -> Self {
+        DydxConnector::new()
+    }
+}
+
+// The full book has {"price", "size"} levels and the changes [price, size] ones, a size
+// of zero removes the level
+fn parse_dydx_levels(levels: &Value) -> Vec<PriceAmountLevel> {
+    levels
+        .as_array()
+        .map(|levels| {
+            levels
+                .iter()
+                .filter_map(|level| {
+                    let (price, size) = match level.as_array() {
+                        Some(_) => (&level[0], &level[1]),
+                        None => (&level["price"], &level["size"]),
+                    };
+                    Some(PriceAmountLevel {
+                        exchange: "dydx".to_string(),
+                        price: json_decimal(price)?,
+                        amount: json_decimal(size)?,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// A side without changes is left out of the message
+fn parse_dydx_contents(message: &Value) -> Levels {
+    let contents = &message["contents"];
+    (
+        parse_dydx_levels(&contents["bids"]),
+        parse_dydx_levels(&contents["asks"]),
+    )
+}
+
+impl ExchangeConnector for DydxConnector {
+    fn name(&self) -> &str {
+        "dYdX"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // BTC-USD, the perpetuals are all quoted in USD (settled in USDC)
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, _)) => format!("{}-USD", base),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://indexer.dydx.trade/v4/orderbooks/perpetualMarket/{}",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message("subscribe", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message("unsubscribe", symbol)]
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let message = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        if message["type"] != "subscribed" || message["channel"] != "v4_orderbook" {
+            return false;
+        }
+        let Some(message_id) = message["message_id"].as_u64() else {
+            return false;
+        };
+        *self.initial_book.lock().unwrap() = Some((parse_dydx_contents(&message), message_id));
+        true
+    }
+
+    // The connected message sent before the ack
+    fn is_status_message(&self, message_text: &str) -> bool {
+        let message = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        message["type"] == "connected"
+    }
+
+    // {"type": "error", "message": "Invalid subscribe message: ...", "connection_id": ...},
+    // the indexer sends no error codes
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["type"] != "error" && result.get("error").is_none() {
+            return None;
+        }
+        let message = result["message"].as_str().or(result["error"].as_str());
+        Some(ExchangeError {
+            code: None,
+            message: message.unwrap_or_default().to_string(),
+            action: ErrorAction::Disable,
+        })
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        if let Some(((bids, asks), message_id)) = self.initial_book.get_mut().unwrap().take() {
+            self.book.replace(bids, asks);
+            self.last_message_id = Some(message_id);
+            self.out_of_sync = false;
+        }
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["type"] != "channel_data" || result["channel"] != "v4_orderbook" {
+            return None;
+        }
+        let in_sequence = self.last_message_id.is_some_and(|last_message_id| {
+            result["message_id"].as_u64() == Some(last_message_id + 1)
+        });
+        if self.out_of_sync || !in_sequence {
+            self.out_of_sync = true;
+            return None;
+        }
+        self.last_message_id = result["message_id"].as_u64();
+        let (bids, asks) = parse_dydx_contents(&result);
+        self.book.apply_updates(bids, asks, "dydx");
+        Some(self.book.orderbook(depth))
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    fn reset(&mut self) {
+        self.book.clear();
+        *self.initial_book.get_mut().unwrap() = None;
+        self.last_message_id = None;
+        self.out_of_sync = false;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+}
+
+crate::register_connector!("dydx", false, |_| Box::new(DydxConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_dydx_conformance() {
+        run_conformance(
+            |url| Box::new(DydxConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusd",
+                // The full book, applied with the first change
+                ack: r#"{"type":"subscribed","connection_id":"e2a0d4b4-3fd1-4e6e-a0a3-4e4b6f1f7a51","message_id":1,"channel":"v4_orderbook","id":"BTC-USD","contents":{"bids":[{"price":"10.0","size":"1.0"}],"asks":[{"price":"11.0","size":"0.8"}]}}"#.to_string(),
+                snapshot: r#"{"type":"channel_data","connection_id":"e2a0d4b4-3fd1-4e6e-a0a3-4e4b6f1f7a51","message_id":2,"id":"BTC-USD","channel":"v4_orderbook","version":"1.0.0","contents":{"asks":[["11.5","0.7"]]}}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                updates: vec![(
+                    r#"{"type":"channel_data","connection_id":"e2a0d4b4-3fd1-4e6e-a0a3-4e4b6f1f7a51","message_id":3,"id":"BTC-USD","channel":"v4_orderbook","version":"1.0.0","contents":{"bids":[["10.0","0"],["9.8","3.0"]],"asks":[["10.9","0.1"]]}}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8), (11.5, 0.7)]),
+                )],
+                gap: Some(r#"{"type":"channel_data","connection_id":"e2a0d4b4-3fd1-4e6e-a0a3-4e4b6f1f7a51","message_id":6,"id":"BTC-USD","channel":"v4_orderbook","version":"1.0.0","contents":{"bids":[["9.7","1.0"]]}}"#.to_string()),
+            },
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::{error_code, ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::json_decimal;
+use crate::symbols::split_symbol;
+use serde_json::Value;
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Levels of the REST snapshot the updates are applied to
+const SNAPSHOT_LIMIT: u32 = 100;
+
+fn unix_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+// Gate.io's spot.order_book_update channel only sends the changed levels, with the
+// first (U) and last (u) update id of each message, and the book is built from a REST
+// snapshot with its update id. Updates up to the snapshot's id are dropped, the first
+// one applied has to cover the id following it, and every next one has to start right
+// after the previous one, otherwise updates were lost and the book is rebuilt
+pub struct GateConnector {
+    url: String,
+    // Base of the REST API the snapshots are fetched from
+    rest_base: String,
+    book: LocalBook,
+    // Update id the book is at, None until the snapshot is applied
+    last_update_id: Option<u64>,
+    // Symbol of the snapshot requested, and the updates received until it's applied
+    snapshot_symbol: Option<String>,
+    pending_updates: Vec<Value>,
+    out_of_sync: bool,
+    // Request time of the subscription messages, in seconds
+    clock: fn() -> u64,
+}
+
+impl GateConnector {
+    pub fn new() -> GateConnector {
+        GateConnector::with_urls("wss://api.gateio.ws/ws/v4/", "https://api.gateio.ws/api/v4")
+    }
+
+    pub fn with_urls(url: &str, rest_base: &str) -> GateConnector {
+        GateConnector {
+            url: url.to_string(),
+            rest_base: rest_base.to_string(),
+            book: LocalBook::new(),
+            last_update_id: None,
+            snapshot_symbol: None,
+            pending_updates: Vec::new(),
+            out_of_sync: false,
+            clock: unix_seconds,
+        }
+    }
+
+    fn channel_message(&self, event: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"time": {}, "channel": "spot.order_book_update", "event": "{}", "payload": ["{}", "100ms"]}}"#,
+            (self.clock)(),
+            event,
+            symbol
+        )
+    }
+
+    fn snapshot_url(&self, symbol: &str) -> String {
+        format!(
+            "{}/spot/order_book?currency_pair={}&limit={}&with_id=true",
+            self.rest_base, symbol, SNAPSHOT_LIMIT
+        )
+    }
+
+    // Replaces the book with the REST snapshot and returns its update id
+    fn replace_book(&mut self, body: &str) -> Result<u64, Box<dyn Error>> {
+        let snapshot = serde_json::from_str::<Value>(body)?;
+        let id = snapshot["id"]
+            .as_u64()
+            .ok_or_else(|| format!("No update id in the Gate.io snapshot: {}", body))?;
+        self.book.replace(
+            parse_gate_levels(&snapshot["bids"]).unwrap_or_default(),
+            parse_gate_levels(&snapshot["asks"]).unwrap_or_default(),
+        );
+        Ok(id)
+    }
+
+    // Applies an update following the book, returns false for the ones already in it
+    // and after a gap
+    fn apply_update(&mut self, update: &Value) -> bool {
+        let (first_id, last_id, book_id) = match (
+            update["U"].as_u64(),
+            update["u"].as_u64(),
+            self.last_update_id,
+        ) {
+            (Some(first_id), Some(last_id), Some(book_id)) => (first_id, last_id, book_id),
+            _ => return false,
+        };
+        if self.out_of_sync {
+            return false;
+        }
+        // Already in the book
+        if last_id <= book_id {
+            return false;
+        }
+        if first_id > book_id + 1 {
+            self.out_of_sync = true;
+            return false;
+        }
+
+        let bids = parse_gate_levels(&update["b"]).unwrap_or_default();
+        let asks = parse_gate_levels(&update["a"]).unwrap_or_default();
+        self.book.apply_updates(bids, asks, "gate");
+        self.last_update_id = Some(last_id);
+        true
+    }
+}
+
+impl Default for GateConnector {
+    fn default() -> Self {
+        GateConnector::new()
+    }
+}
+
+// Levels are [price, amount] strings, an amount of "0" removes the level
+fn parse_gate_levels(levels: &Value) -> Option<Vec<PriceAmountLevel>> {
+    Some(
+        levels
+            .as_array()?
+            .iter()
+            .filter_map(|level| {
+                Some(PriceAmountLevel {
+                    exchange: "gate".to_string(),
+                    price: json_decimal(&level[0])?,
+                    amount: json_decimal(&level[1])?,
+                })
+            })
+            .collect(),
+    )
+}
+
+impl ExchangeConnector for GateConnector {
+    fn name(&self) -> &str {
+        "Gate.io"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // BTC_USDT
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => format!("{}_{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(self.snapshot_url(symbol))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message("subscribe", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message("unsubscribe", symbol)]
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["event"] == "subscribe" && ack["result"]["status"] == "success"
+    }
+
+    // {"event": "subscribe", "error": {"code": 2, "message": "unknown currency pair"}},
+    // 1 is an invalid request and 2 an invalid argument, 3 a server error
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let error = result.get("error").filter(|error| !error.is_null())?;
+        let code = error_code(&error["code"]);
+        let action = match code.as_deref() {
+            Some("1" | "2") => ErrorAction::Disable,
+            _ => ErrorAction::Resubscribe,
+        };
+        Some(ExchangeError {
+            code,
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+            action,
+        })
+    }
+
+    // The t of the update, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["result"]["t"].as_u64()
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["channel"] != "spot.order_book_update" || result["event"] != "update" {
+            return None;
+        }
+        if self.out_of_sync {
+            return None;
+        }
+
+        // Kept until the snapshot requested is applied
+        let update = &result["result"];
+        if self.last_update_id.is_none() {
+            self.snapshot_symbol = Some(update["s"].as_str()?.to_string());
+            self.pending_updates.push(update.clone());
+            return None;
+        }
+        self.apply_update(update)
+            .then(|| self.book.orderbook(depth))
+    }
+
+    fn snapshot_request(&self) -> Option<String> {
+        self.snapshot_symbol
+            .as_ref()
+            .map(|symbol| self.snapshot_url(symbol))
+    }
+
+    // The book is sent once an update following the snapshot was applied
+    fn apply_snapshot(
+        &mut self,
+        snapshot: Result<String, String>,
+        depth: usize,
+    ) -> Option<OrderBook> {
+        self.snapshot_symbol = None;
+        let pending_updates = std::mem::take(&mut self.pending_updates);
+        let replaced = match snapshot {
+            Ok(body) => self.replace_book(&body),
+            Err(err) => Err(err.into()),
+        };
+        match replaced {
+            Ok(snapshot_id) => self.last_update_id = Some(snapshot_id),
+            Err(err) => {
+                eprintln!("Failed to fetch the Gate.io snapshot: {}", err);
+                self.out_of_sync = true;
+                return None;
+            }
+        }
+        let mut applied = false;
+        for update in &pending_updates {
+            applied |= self.apply_update(update);
+        }
+        (applied && !self.out_of_sync).then(|| self.book.orderbook(depth))
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    // The snapshot is requested again with the first update of the new subscription
+    fn reset(&mut self) {
+        self.book.clear();
+        self.last_update_id = None;
+        self.snapshot_symbol = None;
+        self.pending_updates.clear();
+        self.out_of_sync = false;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+}
+
+crate::register_connector!("gate", false, |_| Box::new(GateConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+    use crate::mock_exchange::serve_snapshot;
+
+    #[test]
+    fn test_gate_conformance() {
+        let rest_base = format!(
+            "{}/api/v4",
+            serve_snapshot(
+                r#"{"id":100,"current":1623898993123,"update":1623898993121,"asks":[["11.0","0.8"]],"bids":[["10.0","1.0"]]}"#,
+            )
+        );
+        run_conformance(
+            |url| {
+                Box::new(GateConnector {
+                    clock: || 1606292218,
+                    ..GateConnector::with_urls(url, &rest_base)
+                })
+            },
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"time":1606292218,"channel":"spot.order_book_update","event":"subscribe","result":{"status":"success"}}"#.to_string(),
+                // The first update after the snapshot's id 100
+                snapshot: r#"{"time":1606294781,"channel":"spot.order_book_update","event":"update","result":{"t":1606294781123,"e":"depthUpdate","E":1606294781,"s":"BTC_USDT","U":99,"u":101,"b":[],"a":[["11.5","0.7"]]}}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                updates: vec![(
+                    r#"{"time":1606294782,"channel":"spot.order_book_update","event":"update","result":{"t":1606294782123,"e":"depthUpdate","E":1606294782,"s":"BTC_USDT","U":102,"u":103,"b":[["10.0","0"],["9.8","3.0"]],"a":[["10.9","0.1"]]}}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8), (11.5, 0.7)]),
+                )],
+                gap: Some(r#"{"time":1606294783,"channel":"spot.order_book_update","event":"update","result":{"t":1606294783123,"e":"depthUpdate","E":1606294783,"s":"BTC_USDT","U":110,"u":112,"b":[["9.7","1.0"]],"a":[]}}"#.to_string()),
+            },
+        );
+    }
+}
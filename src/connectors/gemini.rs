@@ -0,0 +1,178 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::ExchangeConnector;
+use crate::number::json_decimal;
+use crate::symbols::split_symbol;
+use serde_json::Value;
+use std::sync::Mutex;
+
+type Levels = (Vec<PriceAmountLevel>, Vec<PriceAmountLevel>);
+
+// Gemini's v2 market data l2 channel sends the full book in a first l2_updates message,
+// with the recent trades and auction events, and then the changed levels. There is no
+// subscription ack, the full book confirms the subscription, so its levels are kept
+// until the next message is applied instead of being dropped with the ack
+pub struct GeminiConnector {
+    url: String,
+    book: LocalBook,
+    initial_book: Mutex<Option<Levels>>,
+}
+
+impl GeminiConnector {
+    pub fn new() -> GeminiConnector {
+        GeminiConnector::with_url("wss://api.gemini.com/v2/marketdata")
+    }
+
+    pub fn with_url(url: &str) -> GeminiConnector {
+        GeminiConnector {
+            url: url.to_string(),
+            book: LocalBook::new(),
+            initial_book: Mutex::new(None),
+        }
+    }
+
+    fn l2_message(&self, message_type: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"type": "{}", "subscriptions": [{{"name": "l2", "symbols": ["{}"]}}]}}"#,
+            message_type, symbol
+        )
+    }
+}
+
+impl Default for GeminiConnector {
+    fn default() -> Self {
+        GeminiConnector::new()
+    }
+}
+
+// Changes of both sides come in one list, ["buy" | "sell", price, quantity], a quantity
+// of zero removes the level
+fn parse_gemini_changes(changes: &Value) -> Option<Levels> {
+    let mut bids = Vec::new();
+    let mut asks = Vec::new();
+    for change in changes.as_array()? {
+        let parse_decimal = |index: usize| json_decimal(&change[index]);
+        let (Some(price), Some(amount)) = (parse_decimal(1), parse_decimal(2)) else {
+            continue;
+        };
+        let level = PriceAmountLevel {
+            exchange: "gemini".to_string(),
+            price,
+            amount,
+        };
+        match change[0].as_str() {
+            Some("buy") => bids.push(level),
+            Some("sell") => asks.push(level),
+            _ => {}
+        }
+    }
+    Some((bids, asks))
+}
+
+// The full book is the only l2_updates message carrying the trades
+fn parse_initial_book(message: &Value) -> Option<Levels> {
+    if message["type"] != "l2_updates" || message.get("trades").is_none() {
+        return None;
+    }
+    parse_gemini_changes(&message["changes"])
+}
+
+impl ExchangeConnector for GeminiConnector {
+    fn name(&self) -> &str {
+        "Gemini"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // BTCUSD
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => format!("{}{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api.gemini.com/v1/book/{}",
+            symbol.to_lowercase()
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.l2_message("subscribe", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.l2_message("unsubscribe", symbol)]
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let message = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        let Some(levels) = parse_initial_book(&message) else {
+            return false;
+        };
+        *self.initial_book.lock().unwrap() = Some(levels);
+        true
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        if let Some((bids, asks)) = self.initial_book.get_mut().unwrap().take() {
+            self.book.replace(bids, asks);
+        }
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["type"] != "l2_updates" {
+            return None;
+        }
+        match parse_initial_book(&result) {
+            Some((bids, asks)) => self.book.replace(bids, asks),
+            None => {
+                let (bids, asks) = parse_gemini_changes(&result["changes"])?;
+                self.book.apply_updates(bids, asks, "gemini");
+            }
+        }
+        Some(self.book.orderbook(depth))
+    }
+
+    fn reset(&mut self) {
+        self.book.clear();
+        *self.initial_book.get_mut().unwrap() = None;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+}
+
+crate::register_connector!("gemini", false, |_| Box::new(GeminiConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_gemini_conformance() {
+        run_conformance(
+            |url| Box::new(GeminiConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusd",
+                // The full book, applied with the first change
+                ack: r#"{"type":"l2_updates","symbol":"BTCUSD","changes":[["buy","10.0","1.0"],["sell","11.0","0.8"]],"trades":[{"type":"trade","symbol":"BTCUSD","event_id":169841458,"timestamp":1560976400428,"price":"10.5","quantity":"0.1","side":"sell"}],"auction_events":[]}"#.to_string(),
+                snapshot: r#"{"type":"l2_updates","symbol":"BTCUSD","changes":[["sell","11.5","0.7"]]}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                updates: vec![(
+                    r#"{"type":"l2_updates","symbol":"BTCUSD","changes":[["buy","10.0","0"],["buy","9.8","3.0"],["sell","10.9","0.1"]]}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8), (11.5, 0.7)]),
+                ), (
+                    r#"{"type":"l2_updates","symbol":"BTCUSD","changes":[["buy","9.8","2.5"],["sell","11.5","0"]]}"#.to_string(),
+                    (vec![(9.8, 2.5)], vec![(10.9, 0.1), (11.0, 0.8)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::connectors::{error_code, ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::{decimal_to_f64, json_decimal};
+use crate::symbols::joined_symbol;
+use serde_json::Value;
+
+// HTX (formerly Huobi) sends every frame gzip compressed in a binary frame, inflated by
+// compression::message_text before it reaches the connector. The mbp.refresh topic
+// pushes the top 5, 10 or 20 levels on every change, like binance's partial book
+// streams. HTX pings every 5 s with {"ping": ts} and closes the connection after two
+// pings without a {"pong": ts}
+pub struct HtxConnector {
+    url: String,
+    depth: u32,
+}
+
+impl HtxConnector {
+    pub fn new(depth: u32) -> HtxConnector {
+        HtxConnector::with_url("wss://api.huobi.pro/ws", depth)
+    }
+
+    pub fn with_url(url: &str, depth: u32) -> HtxConnector {
+        HtxConnector {
+            url: url.to_string(),
+            depth,
+        }
+    }
+
+    fn topic(&self, symbol: &str) -> String {
+        let levels = match self.depth {
+            0..=5 => 5,
+            6..=10 => 10,
+            _ => 20,
+        };
+        format!("market.{}.mbp.refresh.{}", symbol, levels)
+    }
+}
+
+// Levels are [price, size] numbers
+fn parse_htx_levels(levels: &Value) -> Option<Vec<PriceAmountLevel>> {
+    Some(
+        levels
+            .as_array()?
+            .iter()
+            .filter_map(|level| {
+                Some(PriceAmountLevel {
+                    exchange: "htx".to_string(),
+                    price: json_decimal(&level[0])?,
+                    amount: json_decimal(&level[1])?,
+                })
+            })
+            .collect(),
+    )
+}
+
+impl ExchangeConnector for HtxConnector {
+    fn name(&self) -> &str {
+        "HTX"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        joined_symbol(symbol).to_lowercase()
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api.huobi.pro/market/depth?symbol={}&type=step0",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![format!(r#"{{"sub": "{}", "id": "1"}}"#, self.topic(symbol))]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![format!(
+            r#"{{"unsub": "{}", "id": "2"}}"#,
+            self.topic(symbol)
+        )]
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["status"] == "ok" && ack.get("subbed").is_some()
+    }
+
+    // {"status": "error", "err-code": "bad-request", "err-msg": "invalid topic ..."}
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["status"] != "error" {
+            return None;
+        }
+        Some(ExchangeError {
+            code: error_code(&result["err-code"]),
+            message: result["err-msg"].as_str().unwrap_or_default().to_string(),
+            action: ErrorAction::Disable,
+        })
+    }
+
+    fn heartbeat_reply(&self, message_text: &str) -> Option<String> {
+        let ping = serde_json::from_str::<Value>(message_text).ok()?;
+        Some(format!(r#"{{"pong": {}}}"#, ping["ping"].as_u64()?))
+    }
+
+    // The ts of the message, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["ts"].as_u64()
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if !result["ch"].as_str()?.contains(".mbp.refresh.") {
+            return None;
+        }
+        let mut orderbook = OrderBook::new();
+        orderbook.bids = parse_htx_levels(&result["tick"]["bids"])?;
+        orderbook.asks = parse_htx_levels(&result["tick"]["asks"])?;
+        orderbook.bids.truncate(depth);
+        orderbook.asks.truncate(depth);
+        if let (Some(best_bid), Some(best_ask)) = (orderbook.bids.first(), orderbook.asks.first()) {
+            orderbook.spread = decimal_to_f64(best_bid.price - best_ask.price);
+        }
+        Some(orderbook)
+    }
+}
+
+crate::register_connector!("htx", false, |depth| Box::new(HtxConnector::new(depth)));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::message_text;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use tungstenite::Message;
+
+    const SNAPSHOT: &str = r#"{"ch":"market.btcusdt.mbp.refresh.20","ts":1573199608679,"tick":{"seqNum":100020146795,"bids":[[10.0,1.0]],"asks":[[11.0,0.8],[11.5,0.7]]}}"#;
+
+    #[test]
+    fn test_htx_gzip_frames() {
+        let mut connector = HtxConnector::new(10);
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(SNAPSHOT.as_bytes()).unwrap();
+        let frame = Message::Binary(gzip.finish().unwrap());
+
+        let orderbook = connector.apply_message(&message_text(&frame), 10).unwrap();
+        assert_eq!(orderbook.asks.len(), 2);
+        assert_eq!(
+            connector.heartbeat_reply(r#"{"ping":1492420473027}"#),
+            Some(r#"{"pong": 1492420473027}"#.to_string())
+        );
+        assert_eq!(connector.heartbeat_reply(SNAPSHOT), None);
+    }
+
+    #[test]
+    fn test_htx_conformance() {
+        run_conformance(
+            |url| Box::new(HtxConnector::with_url(url, 20)),
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"id":"1","status":"ok","subbed":"market.btcusdt.mbp.refresh.20","ts":1489474081631}"#.to_string(),
+                snapshot: SNAPSHOT.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                // A later snapshot replaces the book
+                updates: vec![(
+                    r#"{"ch":"market.btcusdt.mbp.refresh.20","ts":1573199608779,"tick":{"seqNum":100020146895,"bids":[[10.1,0.5],[9.8,3.0]],"asks":[[10.9,0.1]]}}"#.to_string(),
+                    (vec![(10.1, 0.5), (9.8, 3.0)], vec![(10.9, 0.1)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+}
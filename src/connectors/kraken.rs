@@ -0,0 +1,254 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::{param_choice, unsupported_param, ExchangeConnector};
+use crate::number::{json_decimal, json_f64};
+use crate::symbols::split_symbol;
+use crate::tape::{TakerSide, TapeTrade};
+use serde_json::Value;
+use std::error::Error;
+
+// Depths the book channel can be subscribed with
+const KRAKEN_DEPTHS: [u32; 5] = [10, 25, 100, 500, 1000];
+
+// Kraken's v2 book channel sends a snapshot and then the changed levels. The book is
+// only maintained to the subscribed depth, levels pushed out of it aren't removed by
+// an update, so the local book is truncated after every message
+pub struct KrakenConnector {
+    url: String,
+    subscribed_depth: u32,
+    book: LocalBook,
+    // Trades of the trade channel, only subscribed once enabled
+    tape: Option<Vec<TapeTrade>>,
+}
+
+impl KrakenConnector {
+    pub fn new(depth: u32) -> KrakenConnector {
+        KrakenConnector::with_url("wss://ws.kraken.com/v2", depth)
+    }
+
+    pub fn with_url(url: &str, depth: u32) -> KrakenConnector {
+        KrakenConnector {
+            url: url.to_string(),
+            subscribed_depth: KRAKEN_DEPTHS
+                .into_iter()
+                .find(|kraken_depth| *kraken_depth >= depth)
+                .unwrap_or(1000),
+            book: LocalBook::new(),
+            tape: None,
+        }
+    }
+
+    fn trade_message(&self, method: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"method": "{}", "params": {{"channel": "trade", "symbol": ["{}"]}}}}"#,
+            method, symbol
+        )
+    }
+
+    fn channel_messages(&self, method: &str, symbol: &str) -> Vec<String> {
+        let mut messages = vec![self.book_message(method, symbol)];
+        if self.tape.is_some() {
+            messages.push(self.trade_message(method, symbol));
+        }
+        messages
+    }
+
+    fn book_message(&self, method: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"method": "{}", "params": {{"channel": "book", "symbol": ["{}"], "depth": {}}}}}"#,
+            method, symbol, self.subscribed_depth
+        )
+    }
+}
+
+// Levels are objects with numbers, {"price": 10.5, "qty": 0.2}
+fn parse_kraken_levels(levels: &Value) -> Option<Vec<PriceAmountLevel>> {
+    Some(
+        levels
+            .as_array()?
+            .iter()
+            .filter_map(|level| {
+                Some(PriceAmountLevel {
+                    exchange: "kraken".to_string(),
+                    price: json_decimal(&level["price"])?,
+                    amount: json_decimal(&level["qty"])?,
+                })
+            })
+            .collect(),
+    )
+}
+
+// {"symbol": "BTC/USD", "side": "sell", "price": 10.5, "qty": 0.2, ...}, the side is
+// the taker's
+fn parse_kraken_trade(trade: &Value) -> Option<TapeTrade> {
+    Some(TapeTrade {
+        exchange: "kraken".to_string(),
+        price: json_f64(&trade["price"])?,
+        amount: json_f64(&trade["qty"])?,
+        side: TakerSide::from_venue(trade["side"].as_str()?, false)?,
+    })
+}
+
+impl ExchangeConnector for KrakenConnector {
+    fn name(&self) -> &str {
+        "Kraken"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // BTC/USD
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => format!("{}/{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn instruments_url(&self) -> Option<String> {
+        Some("https://api.kraken.com/0/public/AssetPairs".to_string())
+    }
+
+    // {"result": {"XXBTZUSD": {"wsname": "XBT/USD", "status": "online", ...}, ...}}, the
+    // v1 names of the pairs, the v2 API writes XBT and XDG as BTC and DOGE
+    fn listed_symbols(&self, body: &str) -> Vec<String> {
+        let pairs = serde_json::from_str::<Value>(body).unwrap_or_default();
+        let pairs = pairs["result"].as_object().cloned().unwrap_or_default();
+        pairs
+            .values()
+            .filter(|pair| pair["status"] == "online")
+            .filter_map(|pair| {
+                let (base, quote) = pair["wsname"].as_str()?.split_once('/')?;
+                let base = match base {
+                    "XBT" => "BTC",
+                    "XDG" => "DOGE",
+                    base => base,
+                };
+                Some(format!("{}/{}", base, quote))
+            })
+            .collect()
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        self.channel_messages("subscribe", symbol)
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        self.channel_messages("unsubscribe", symbol)
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["method"] == "subscribe" && ack["success"] == true
+    }
+
+    // The system status is sent on connect, before the ack
+    fn is_status_message(&self, message_text: &str) -> bool {
+        let status = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        status["channel"] == "status"
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["channel"] == "trade" && result["type"] == "update" {
+            let tape = self.tape.as_mut()?;
+            tape.extend(
+                result["data"]
+                    .as_array()?
+                    .iter()
+                    .filter_map(parse_kraken_trade),
+            );
+            return None;
+        }
+        if result["channel"] != "book" {
+            return None;
+        }
+        let data = &result["data"][0];
+        let bids = parse_kraken_levels(&data["bids"])?;
+        let asks = parse_kraken_levels(&data["asks"])?;
+        match result["type"].as_str()? {
+            "snapshot" => self.book.replace(bids, asks),
+            "update" => self.book.apply_updates(bids, asks, "kraken"),
+            _ => return None,
+        }
+        self.book.truncate(self.subscribed_depth as usize);
+        Some(self.book.orderbook(depth))
+    }
+
+    fn reset(&mut self) {
+        self.book.clear();
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+
+    fn enable_trades(&mut self) {
+        self.tape = Some(Vec::new());
+    }
+
+    fn take_trades(&mut self) -> Vec<TapeTrade> {
+        self.tape.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    // depth, the depth of the book channel instead of the first one covering the server's
+    fn set_subscription_param(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        if key != "depth" {
+            return Err(unsupported_param(key, &["depth"]));
+        }
+        let depths = KRAKEN_DEPTHS.map(|depth| depth.to_string());
+        let depths: Vec<&str> = depths.iter().map(String::as_str).collect();
+        self.subscribed_depth = param_choice(key, value, &depths)?.parse()?;
+        Ok(())
+    }
+}
+
+crate::register_connector!("kraken", false, |depth| Box::new(KrakenConnector::new(
+    depth
+)));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_kraken_conformance() {
+        run_conformance(
+            |url| Box::new(KrakenConnector::with_url(url, 10)),
+            ConformanceFixture {
+                symbol: "btcusd",
+                ack: r#"{"method":"subscribe","result":{"channel":"book","symbol":"BTC/USD","depth":10,"snapshot":true},"success":true}"#.to_string(),
+                snapshot: r#"{"channel":"book","type":"snapshot","data":[{"symbol":"BTC/USD","bids":[{"price":10.0,"qty":1.0}],"asks":[{"price":11.0,"qty":0.8},{"price":11.5,"qty":0.7}],"checksum":1}]}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                updates: vec![(
+                    r#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":10.0,"qty":0.0},{"price":9.8,"qty":3.0}],"asks":[{"price":10.9,"qty":0.1}],"checksum":2}]}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8), (11.5, 0.7)]),
+                ), (
+                    r#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":9.8,"qty":2.5}],"asks":[{"price":11.5,"qty":0.0}],"checksum":3}]}"#.to_string(),
+                    (vec![(9.8, 2.5)], vec![(10.9, 0.1), (11.0, 0.8)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+
+    #[test]
+    fn test_kraken_trades() {
+        let mut connector = KrakenConnector::new(10);
+        let trade = r#"{"channel":"trade","type":"update","data":[{"symbol":"BTC/USD","side":"sell","price":10.5,"qty":0.2,"ord_type":"market","trade_id":4665906,"timestamp":"2023-09-25T07:49:37.708706Z"}]}"#;
+        // Not subscribed until enabled
+        assert_eq!(connector.subscribe_messages("BTC/USD").len(), 1);
+        assert!(connector.apply_message(trade, 10).is_none());
+        assert!(connector.take_trades().is_empty());
+
+        connector.enable_trades();
+        assert!(connector.subscribe_messages("BTC/USD")[1].contains(r#""channel": "trade""#));
+        assert!(connector.apply_message(trade, 10).is_none());
+        let trades = connector.take_trades();
+        assert_eq!(trades.len(), 1);
+        assert_eq!((trades[0].amount, trades[0].side), (0.2, TakerSide::Sell));
+    }
+}
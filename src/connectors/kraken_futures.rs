@@ -0,0 +1,222 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::{ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::json_decimal;
+use crate::symbols::split_symbol;
+use serde_json::Value;
+
+// Kraken Futures (futures.kraken.com) is a separate exchange from Kraken spot, with its
+// own v1 feeds: the book feed sends a book_snapshot and then one changed level per
+// message. Every message of a product carries the next seq, a gap means levels were
+// lost and the book is only rebuilt by subscribing again for a new snapshot
+pub struct KrakenFuturesConnector {
+    url: String,
+    book: LocalBook,
+    last_seq: Option<u64>,
+    out_of_sync: bool,
+}
+
+impl KrakenFuturesConnector {
+    pub fn new() -> KrakenFuturesConnector {
+        KrakenFuturesConnector::with_url("wss://futures.kraken.com/ws/v1")
+    }
+
+    pub fn with_url(url: &str) -> KrakenFuturesConnector {
+        KrakenFuturesConnector {
+            url: url.to_string(),
+            book: LocalBook::new(),
+            last_seq: None,
+            out_of_sync: false,
+        }
+    }
+
+    fn feed_message(&self, event: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"event": "{}", "feed": "book", "product_ids": ["{}"]}}"#,
+            event, symbol
+        )
+    }
+}
+
+impl Default for KrakenFuturesConnector {
+    fn default() -> Self {
+        KrakenFuturesConnector::new()
+    }
+}
+
+// Levels of the snapshot are {"price": 10.5, "qty": 0.2}
+fn parse_kraken_futures_levels(levels: &Value) -> Vec<PriceAmountLevel> {
+    levels
+        .as_array()
+        .map(|levels| {
+            levels
+                .iter()
+                .filter_map(|level| {
+                    Some(PriceAmountLevel {
+                        exchange: "kraken_futures".to_string(),
+                        price: json_decimal(&level["price"])?,
+                        amount: json_decimal(&level["qty"])?,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl ExchangeConnector for KrakenFuturesConnector {
+    fn name(&self) -> &str {
+        "Kraken Futures"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // PF_XBTUSD, the linear perpetual of the base asset quoted in USD, Kraken lists
+    // bitcoin as XBT. Product ids like PI_XBTUSD or FI_XBTUSD_241227 are kept as they are
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        if symbol.contains('_') {
+            return symbol.to_uppercase();
+        }
+        match split_symbol(symbol) {
+            Some((base, _)) if base == "BTC" => "PF_XBTUSD".to_string(),
+            Some((base, _)) => format!("PF_{}USD", base),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://futures.kraken.com/derivatives/api/v3/orderbook?symbol={}",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.feed_message("subscribe", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.feed_message("unsubscribe", symbol)]
+    }
+
+    // {"event": "subscribed", "feed": "book", "product_ids": ["PF_XBTUSD"]}
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["event"] == "subscribed" && ack["feed"] == "book"
+    }
+
+    // The info message with the API version sent on connect, before the ack
+    fn is_status_message(&self, message_text: &str) -> bool {
+        let message = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        message["event"] == "info"
+    }
+
+    // {"event": "error", "message": "Invalid product id"}, without error codes
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["event"] != "error" {
+            return None;
+        }
+        Some(ExchangeError {
+            code: None,
+            message: result["message"].as_str().unwrap_or_default().to_string(),
+            action: ErrorAction::Disable,
+        })
+    }
+
+    // The timestamp of the message, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["timestamp"].as_u64()
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let seq = result["seq"].as_u64();
+        match result["feed"].as_str()? {
+            "book_snapshot" => {
+                self.book.replace(
+                    parse_kraken_futures_levels(&result["bids"]),
+                    parse_kraken_futures_levels(&result["asks"]),
+                );
+                self.out_of_sync = false;
+            }
+            // {"feed": "book", "side": "sell", "seq": 2, "price": 10.9, "qty": 0.1}, a qty
+            // of 0 removes the level
+            "book" => {
+                let in_sequence =
+                    matches!((self.last_seq, seq), (Some(last), Some(seq)) if seq == last + 1);
+                if self.out_of_sync || !in_sequence {
+                    self.out_of_sync = true;
+                    return None;
+                }
+                let level = PriceAmountLevel {
+                    exchange: "kraken_futures".to_string(),
+                    price: json_decimal(&result["price"])?,
+                    amount: json_decimal(&result["qty"])?,
+                };
+                match result["side"].as_str()? {
+                    "buy" => self
+                        .book
+                        .apply_updates(vec![level], Vec::new(), "kraken_futures"),
+                    "sell" => self
+                        .book
+                        .apply_updates(Vec::new(), vec![level], "kraken_futures"),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        }
+        self.last_seq = seq;
+        Some(self.book.orderbook(depth))
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    fn reset(&mut self) {
+        self.book.clear();
+        self.last_seq = None;
+        self.out_of_sync = false;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+}
+
+crate::register_connector!("kraken_futures", false, |_| {
+    Box::new(KrakenFuturesConnector::new())
+});
+crate::register_connector!("kraken", true, |_| Box::new(KrakenFuturesConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_kraken_futures_conformance() {
+        let connector = KrakenFuturesConnector::new();
+        assert_eq!(connector.normalize_symbol("ethusd"), "PF_ETHUSD");
+        assert_eq!(connector.normalize_symbol("pi_xbtusd"), "PI_XBTUSD");
+
+        run_conformance(
+            |url| Box::new(KrakenFuturesConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusd",
+                ack: r#"{"event":"subscribed","feed":"book","product_ids":["PF_XBTUSD"]}"#.to_string(),
+                snapshot: r#"{"feed":"book_snapshot","product_id":"PF_XBTUSD","timestamp":1612269825817,"seq":326072249,"tickSize":null,"bids":[{"price":10.0,"qty":1.0}],"asks":[{"price":11.0,"qty":0.8},{"price":11.5,"qty":0.7}]}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                updates: vec![(
+                    r#"{"feed":"book","product_id":"PF_XBTUSD","side":"sell","seq":326072250,"price":10.9,"qty":0.1,"timestamp":1612269825821}"#.to_string(),
+                    (vec![(10.0, 1.0)], vec![(10.9, 0.1), (11.0, 0.8), (11.5, 0.7)]),
+                )],
+                gap: Some(r#"{"feed":"book","product_id":"PF_XBTUSD","side":"buy","seq":326072255,"price":9.7,"qty":1.0,"timestamp":1612269825830}"#.to_string()),
+            },
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::connectors::{error_code, http_request, ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::{decimal_to_f64, json_decimal};
+use crate::symbols::split_symbol;
+use serde_json::Value;
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+// KuCoin hands out the WebSocket endpoint with a token from its bullet-public REST
+// endpoint, so every connection starts with that call. The level2Depth5 and
+// level2Depth50 topics push the top of the book on every change, like binance's
+// partial book streams. KuCoin expects a ping every pingInterval (18 s), connections
+// without one are closed by the exchange
+pub struct KucoinConnector {
+    // The bullet-public endpoint, or the WebSocket endpoint when bootstrap isn't set
+    url: String,
+    bootstrap: bool,
+    depth: u32,
+}
+
+impl KucoinConnector {
+    pub fn new(depth: u32) -> KucoinConnector {
+        KucoinConnector {
+            url: "https://api.kucoin.com/api/v1/bullet-public".to_string(),
+            bootstrap: true,
+            depth,
+        }
+    }
+
+    // Connects to the endpoint without a token, e.g. a test exchange
+    pub fn with_endpoint(url: &str, depth: u32) -> KucoinConnector {
+        KucoinConnector {
+            url: url.to_string(),
+            bootstrap: false,
+            depth,
+        }
+    }
+
+    fn topic(&self, symbol: &str) -> String {
+        let levels = if self.depth <= 5 { 5 } else { 50 };
+        format!("/spotMarket/level2Depth{}:{}", levels, symbol)
+    }
+
+    fn topic_message(&self, message_type: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"id": "1", "type": "{}", "topic": "{}", "privateChannel": false, "response": true}}"#,
+            message_type,
+            self.topic(symbol)
+        )
+    }
+}
+
+// The WebSocket url of a bullet-public response, the first instance server with the
+// token and an id of the connection
+fn bullet_url(body: &str, connect_id: u128) -> Result<String, Box<dyn Error>> {
+    let bullet = serde_json::from_str::<Value>(body)?;
+    if bullet["code"] != "200000" {
+        return Err(format!("KuCoin refused the token: {}", body).into());
+    }
+    let token = bullet["data"]["token"].as_str().ok_or("No token")?;
+    let endpoint = bullet["data"]["instanceServers"][0]["endpoint"]
+        .as_str()
+        .ok_or("No instance server")?;
+    let mut url = Url::parse(endpoint)?;
+    url.query_pairs_mut()
+        .append_pair("token", token)
+        .append_pair("connectId", &connect_id.to_string());
+    Ok(url.to_string())
+}
+
+// Levels are [price, size] strings
+fn parse_kucoin_levels(levels: &Value) -> Option<Vec<PriceAmountLevel>> {
+    Some(
+        levels
+            .as_array()?
+            .iter()
+            .filter_map(|level| {
+                Some(PriceAmountLevel {
+                    exchange: "kucoin".to_string(),
+                    price: json_decimal(&level[0])?,
+                    amount: json_decimal(&level[1])?,
+                })
+            })
+            .collect(),
+    )
+}
+
+impl ExchangeConnector for KucoinConnector {
+    fn name(&self) -> &str {
+        "KuCoin"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn connect_url(&self) -> Result<String, Box<dyn Error>> {
+        if !self.bootstrap {
+            return Ok(self.url.clone());
+        }
+        let connect_id = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        bullet_url(&http_request("POST", &Url::parse(&self.url)?)?, connect_id)
+    }
+
+    // BTC-USDT
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => format!("{}-{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api.kucoin.com/api/v1/market/orderbook/level2_20?symbol={}",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.topic_message("subscribe", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.topic_message("unsubscribe", symbol)]
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["type"] == "ack"
+    }
+
+    // The welcome message is sent on connect, before the ack
+    fn is_status_message(&self, message_text: &str) -> bool {
+        let status = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        status["type"] == "welcome"
+    }
+
+    // {"id": "1", "type": "error", "code": 404, "data": "topic ... is not found"}, 509
+    // and 429 are rate limits
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["type"] != "error" {
+            return None;
+        }
+        let code = error_code(&result["code"]);
+        let action = match code.as_deref() {
+            Some("509" | "429") => ErrorAction::BackOff,
+            Some("400" | "404") => ErrorAction::Disable,
+            _ => ErrorAction::Resubscribe,
+        };
+        Some(ExchangeError {
+            code,
+            message: result["data"].as_str().unwrap_or_default().to_string(),
+            action,
+        })
+    }
+
+    // The timestamp of the data, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["data"]["timestamp"].as_u64()
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["type"] != "message" || result["subject"] != "level2" {
+            return None;
+        }
+        let mut orderbook = OrderBook::new();
+        orderbook.bids = parse_kucoin_levels(&result["data"]["bids"])?;
+        orderbook.asks = parse_kucoin_levels(&result["data"]["asks"])?;
+        orderbook.bids.truncate(depth);
+        orderbook.asks.truncate(depth);
+        if let (Some(best_bid), Some(best_ask)) = (orderbook.bids.first(), orderbook.asks.first()) {
+            orderbook.spread = decimal_to_f64(best_bid.price - best_ask.price);
+        }
+        Some(orderbook)
+    }
+}
+
+crate::register_connector!("kucoin", false, |depth| Box::new(KucoinConnector::new(
+    depth
+)));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_kucoin_bullet_url() {
+        let body = r#"{"code":"200000","data":{"token":"2neAiuYvAU61ZD","instanceServers":[{"endpoint":"wss://ws-api-spot.kucoin.com/","encrypt":true,"protocol":"websocket","pingInterval":18000,"pingTimeout":10000}]}}"#;
+        assert_eq!(
+            bullet_url(body, 42).unwrap(),
+            "wss://ws-api-spot.kucoin.com/?token=2neAiuYvAU61ZD&connectId=42"
+        );
+        assert!(bullet_url(r#"{"code":"429000","msg":"Too many requests"}"#, 42).is_err());
+    }
+
+    #[test]
+    fn test_kucoin_conformance() {
+        run_conformance(
+            |url| Box::new(KucoinConnector::with_endpoint(url, 10)),
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"id":"1","type":"ack"}"#.to_string(),
+                snapshot: r#"{"type":"message","topic":"/spotMarket/level2Depth50:BTC-USDT","subject":"level2","data":{"asks":[["11.0","0.8"],["11.5","0.7"]],"bids":[["10.0","1.0"]],"timestamp":1586948108193}}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                // A later snapshot replaces the book
+                updates: vec![(
+                    r#"{"type":"message","topic":"/spotMarket/level2Depth50:BTC-USDT","subject":"level2","data":{"asks":[["10.9","0.1"]],"bids":[["10.1","0.5"],["9.8","3.0"]],"timestamp":1586948109193}}"#.to_string(),
+                    (vec![(10.1, 0.5), (9.8, 3.0)], vec![(10.9, 0.1)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::connectors::{ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::{decimal_to_f64, json_decimal};
+use crate::symbols::split_symbol;
+use serde_json::Value;
+
+// MEXC's spot@public.limit.depth.v3.api channel pushes the top 5, 10 or 20 levels on
+// every change, like binance's partial book streams, which makes it an easy source for
+// the long-tail pairs listed on MEXC. Subscriptions and their failures are both
+// answered with a code of 0, the failures with a msg starting with "Not Subscribed"
+pub struct MexcConnector {
+    url: String,
+    depth: u32,
+}
+
+impl MexcConnector {
+    pub fn new(depth: u32) -> MexcConnector {
+        MexcConnector::with_url("wss://wbs.mexc.com/ws", depth)
+    }
+
+    pub fn with_url(url: &str, depth: u32) -> MexcConnector {
+        MexcConnector {
+            url: url.to_string(),
+            depth,
+        }
+    }
+
+    fn channel(&self, symbol: &str) -> String {
+        let levels = match self.depth {
+            0..=5 => 5,
+            6..=10 => 10,
+            _ => 20,
+        };
+        format!("spot@public.limit.depth.v3.api@{}@{}", symbol, levels)
+    }
+
+    fn channel_message(&self, method: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"method": "{}", "params": ["{}"]}}"#,
+            method,
+            self.channel(symbol)
+        )
+    }
+}
+
+// Levels are {"p": price, "v": quantity} objects of strings
+fn parse_mexc_levels(levels: &Value) -> Option<Vec<PriceAmountLevel>> {
+    Some(
+        levels
+            .as_array()?
+            .iter()
+            .filter_map(|level| {
+                Some(PriceAmountLevel {
+                    exchange: "mexc".to_string(),
+                    price: json_decimal(&level["p"])?,
+                    amount: json_decimal(&level["v"])?,
+                })
+            })
+            .collect(),
+    )
+}
+
+impl ExchangeConnector for MexcConnector {
+    fn name(&self) -> &str {
+        "MEXC"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // BTCUSDT
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => format!("{}{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api.mexc.com/api/v3/depth?symbol={}&limit=20",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message("SUBSCRIPTION", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message("UNSUBSCRIPTION", symbol)]
+    }
+
+    // {"id": 0, "code": 0, "msg": "spot@public.limit.depth.v3.api@BTCUSDT@20"}
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["code"] == 0
+            && ack["msg"]
+                .as_str()
+                .is_some_and(|msg| msg.starts_with("spot@public.limit.depth"))
+    }
+
+    // {"id": 0, "code": 0, "msg": "Not Subscribed successfully! [...]. Reason: Blocked!"},
+    // an unknown symbol or a blocked channel, or a non zero code for invalid requests
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let msg = result["msg"].as_str()?;
+        let failed = msg.starts_with("Not Subscribed");
+        if !failed && result["code"].as_i64().is_none_or(|code| code == 0) {
+            return None;
+        }
+        Some(ExchangeError {
+            code: result["code"]
+                .as_i64()
+                .filter(|code| *code != 0)
+                .map(|code| code.to_string()),
+            message: msg.to_string(),
+            action: ErrorAction::Disable,
+        })
+    }
+
+    // The t of the message, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["t"].as_u64()
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if !result["c"]
+            .as_str()
+            .is_some_and(|channel| channel.starts_with("spot@public.limit.depth"))
+        {
+            return None;
+        }
+        let mut orderbook = OrderBook::new();
+        orderbook.bids = parse_mexc_levels(&result["d"]["bids"])?;
+        orderbook.asks = parse_mexc_levels(&result["d"]["asks"])?;
+        orderbook.bids.truncate(depth);
+        orderbook.asks.truncate(depth);
+        if let (Some(best_bid), Some(best_ask)) = (orderbook.bids.first(), orderbook.asks.first()) {
+            orderbook.spread = decimal_to_f64(best_bid.price - best_ask.price);
+        }
+        Some(orderbook)
+    }
+}
+
+crate::register_connector!("mexc", false, |depth| Box::new(MexcConnector::new(depth)));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_mexc_errors() {
+        let connector = MexcConnector::new(10);
+        let error = connector
+            .parse_error(r#"{"id":0,"code":0,"msg":"Not Subscribed successfully! [spot@public.limit.depth.v3.api@XYZUSDT@10].  Reason： Blocked! "}"#)
+            .unwrap();
+        assert_eq!(error.action, ErrorAction::Disable);
+        assert!(connector
+            .parse_error(r#"{"id":0,"code":0,"msg":"spot@public.limit.depth.v3.api@BTCUSDT@10"}"#)
+            .is_none());
+        assert!(connector
+            .parse_error(r#"{"id":0,"code":0,"msg":"PONG"}"#)
+            .is_none());
+    }
+
+    #[test]
+    fn test_mexc_conformance() {
+        run_conformance(
+            |url| Box::new(MexcConnector::with_url(url, 10)),
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"id":0,"code":0,"msg":"spot@public.limit.depth.v3.api@BTCUSDT@10"}"#.to_string(),
+                snapshot: r#"{"c":"spot@public.limit.depth.v3.api@BTCUSDT@10","d":{"bids":[{"p":"10.0","v":"1.0"}],"asks":[{"p":"11.0","v":"0.8"},{"p":"11.5","v":"0.7"}],"e":"spot@public.limit.depth.v3.api","r":"3407459756"},"s":"BTCUSDT","t":1661932660144}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                // A later snapshot replaces the book
+                updates: vec![(
+                    r#"{"c":"spot@public.limit.depth.v3.api@BTCUSDT@10","d":{"bids":[{"p":"10.1","v":"0.5"},{"p":"9.8","v":"3.0"}],"asks":[{"p":"10.9","v":"0.1"}],"e":"spot@public.limit.depth.v3.api","r":"3407459757"},"s":"BTCUSDT","t":1661932660244}"#.to_string(),
+                    (vec![(10.1, 0.5), (9.8, 3.0)], vec![(10.9, 0.1)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+}
@@ -0,0 +1,953 @@
+// The exchange connectors. ExchangeConnector describes an exchange stream and every
+// venue of the server is read through one: binance, bitstamp and bybit, whose modules
+// also hold the transport and parsing of their streams used by the bitstamp pool, and
+// one module per additional exchange. Each registers itself with register_connector!,
+// so it can be merged with --connector <name> without any change to the server
+pub mod binance;
+pub mod binance_futures;
+pub mod bitfinex;
+pub mod bitflyer;
+pub mod bitget;
+pub mod bitmex;
+pub mod bitstamp;
+pub(crate) mod bybit;
+pub mod coinbase;
+pub mod cryptocom;
+pub mod deribit;
+pub mod dydx;
+pub mod gate;
+pub mod gemini;
+pub mod htx;
+pub mod kraken;
+pub mod kraken_futures;
+pub mod kucoin;
+pub mod mexc;
+pub mod oanda;
+pub mod okx;
+pub mod openbook;
+pub mod poloniex;
+pub mod uniswap_v3;
+pub mod upbit;
+
+pub use binance::BinanceConnector;
+pub use bitstamp::BitstampConnector;
+pub use bybit::BybitConnector;
+// Moved to the symbols module, kept here for the connectors of other crates
+pub use crate::symbols::split_symbol;
+
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::compaction::CompactionPolicy;
+use crate::compression::message_text;
+use crate::connection_manager::connection_manager;
+use crate::fx::FxRateSource;
+use crate::merge::sort_and_trim_levels;
+use crate::number::{decimal_to_f64, json_decimal};
+use crate::tape::TapeTrade;
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{TcpStream as StdTcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tungstenite::client::AutoStream;
+use tungstenite::{Message, WebSocket};
+use url::{Position, Url};
+
+// Socket of an exchange read by a task of the async runtime, the server's sockets
+pub type AsyncSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// Wait before resubscribing a stream the exchange rate limited
+pub const ERROR_BACKOFF: Duration = Duration::from_secs(5);
+
+// What the server does about an error event of an exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorAction {
+    // Transient, the stream is resubscribed right away
+    Resubscribe,
+    // Rate limited, the stream is resubscribed after ERROR_BACKOFF
+    BackOff,
+    // The subscription can't succeed as is, e.g. an unknown symbol or an invalid
+    // request, the venue is left out until the server restarts
+    Disable,
+}
+
+// An error event sent by an exchange on its stream, with the exchange's own code
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeError {
+    pub code: Option<String>,
+    pub message: String,
+    pub action: ErrorAction,
+}
+
+impl fmt::Display for ExchangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{} (code {})", self.message, code),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// Exchanges send their error codes as numbers or strings
+pub(crate) fn error_code(code: &Value) -> Option<String> {
+    match code {
+        Value::String(code) => Some(code.clone()),
+        Value::Number(code) => Some(code.to_string()),
+        _ => None,
+    }
+}
+
+// Everything the server needs to know about an exchange stream. The url is kept in
+// the connector so tests can point it to the mock exchange instead of the real one.
+// Every venue of the server, binance and bitstamp included, is read through its
+// connector, so a new exchange is a module implementing it and registering itself
+pub trait ExchangeConnector: Send {
+    fn name(&self) -> &str;
+
+    fn url(&self) -> &str;
+
+    // The WebSocket url of a new connection, exchanges handing out the endpoint and a
+    // token from a REST call make the call here
+    fn connect_url(&self) -> Result<String, Box<dyn Error>> {
+        Ok(self.url().to_string())
+    }
+
+    // The server's symbol as the exchange lists it, e.g. btcusdt for BTCUSDT
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        symbol.to_string()
+    }
+
+    // REST endpoint answering for the symbol, used by the doctor to check the exchange's
+    // REST API and that the symbol is listed
+    fn rest_url(&self, _symbol: &str) -> Option<String> {
+        None
+    }
+
+    // REST endpoint listing the instruments of the exchange, used to check the symbol is
+    // listed before connecting, see symbols::check_venue_symbol
+    fn instruments_url(&self) -> Option<String> {
+        None
+    }
+
+    // The symbols of the instrument list, written like normalize_symbol writes them
+    fn listed_symbols(&self, _body: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String>;
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String>;
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool;
+
+    // Messages some exchanges send on connect before the subscription ack, skipped
+    fn is_status_message(&self, _message_text: &str) -> bool {
+        false
+    }
+
+    // The error event of the exchange in the message, e.g. a rejected subscription or a
+    // rate limit, None for every other message
+    fn parse_error(&self, _message_text: &str) -> Option<ExchangeError> {
+        None
+    }
+
+    // The answer to a heartbeat of the exchange that closes connections not answering
+    // it, e.g. HTX's pings, sent back on the socket by the reader loops
+    fn heartbeat_reply(&self, _message_text: &str) -> Option<String> {
+        None
+    }
+
+    // The heartbeat sent to the exchange on a quiet socket, for exchanges expecting one
+    // of their own. A websocket ping is sent when None
+    fn keepalive_message(&self) -> Option<String> {
+        None
+    }
+
+    // The exchange's timestamp of the message in ms since epoch, for the latency of the
+    // venue. None when the exchange sends none, e.g. binance's partial book streams
+    fn event_time(&self, _message_text: &str) -> Option<u64> {
+        None
+    }
+
+    // Applies a message to the local book and returns the trimmed book,
+    // or None if the message didn't contain an orderbook update
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook>;
+
+    // The url of the REST snapshot the book is waiting for, diff books built from one
+    // keep their first updates until the reader fetched it and passed it to apply_snapshot
+    fn snapshot_request(&self) -> Option<String> {
+        None
+    }
+
+    // Builds the book from the body of the snapshot, or the error fetching it, and the
+    // updates kept meanwhile. Returns the trimmed book like apply_message
+    fn apply_snapshot(
+        &mut self,
+        _snapshot: Result<String, String>,
+        _depth: usize,
+    ) -> Option<OrderBook> {
+        None
+    }
+
+    // True when the last book returned only refreshed the best bid and ask from a ticker
+    // stream, the levels below are as of the last depth update
+    fn bbo_only(&self) -> bool {
+        false
+    }
+
+    // True after a sequence gap, the connector has to be reset and resubscribed
+    fn needs_resync(&self) -> bool {
+        false
+    }
+
+    // Drops the local book before resubscribing
+    fn reset(&mut self) {}
+
+    // Only diff maintained books grow over time, partial book streams are bounded
+    // by the exchange and ignore the policy
+    fn set_compaction(&mut self, _policy: CompactionPolicy) {}
+
+    // Venues listing the pair only in their local currency convert their prices with
+    // the rate of that currency, e.g. KRW per USDT, the others ignore it
+    fn set_fx_rate_source(&mut self, _currency: &str, _source: Arc<dyn FxRateSource>) {}
+
+    // Subscribes the venue's trade channel next to its book, for the consolidated tape.
+    // Called before subscribing, venues without a trade channel ignore it
+    fn enable_trades(&mut self) {}
+
+    // The trades read since the last call, with the side of their taker
+    fn take_trades(&mut self) -> Vec<TapeTrade> {
+        Vec::new()
+    }
+
+    // Venues streaming their book at several speeds read it at the one closest to this.
+    // Called before subscribing, venues with a single speed ignore it
+    fn set_update_speed(&mut self, _speed: Duration) {}
+
+    // A venue specific parameter of the subscription, see ServerOptions::venue_params,
+    // e.g. the depth of Kraken's book channel. Called before subscribing, keys or values
+    // the venue doesn't take are errors
+    fn set_subscription_param(&mut self, key: &str, _value: &str) -> Result<(), Box<dyn Error>> {
+        Err(format!(
+            "{} takes no subscription parameters, got {}",
+            self.name(),
+            key
+        )
+        .into())
+    }
+}
+
+// The value of a subscription parameter, one of the ones the venue takes
+pub(crate) fn param_choice<'a>(
+    key: &str,
+    value: &str,
+    choices: &[&'a str],
+) -> Result<&'a str, Box<dyn Error>> {
+    choices
+        .iter()
+        .find(|choice| **choice == value)
+        .copied()
+        .ok_or_else(|| {
+            format!(
+                "{} must be one of {}, got {}",
+                key,
+                choices.join(", "),
+                value
+            )
+            .into()
+        })
+}
+
+// The error of a key a venue doesn't take, with the ones it does
+pub(crate) fn unsupported_param(key: &str, keys: &[&str]) -> Box<dyn Error> {
+    format!("unsupported key {}, expected {}", key, keys.join(" or ")).into()
+}
+
+// A connector reading its exchange at another endpoint, e.g. a regional or a test one,
+// or subscribing another symbol than the one it derives from the server's, see
+// ServerOptions::endpoints and ServerOptions::venue_symbols
+pub(crate) struct VenueOverride {
+    connector: Box<dyn ExchangeConnector>,
+    url: Option<String>,
+    symbol: Option<String>,
+}
+
+impl VenueOverride {
+    pub(crate) fn new(connector: Box<dyn ExchangeConnector>) -> VenueOverride {
+        VenueOverride {
+            connector,
+            url: None,
+            symbol: None,
+        }
+    }
+
+    pub(crate) fn with_url(self, url: &str) -> VenueOverride {
+        VenueOverride {
+            url: Some(url.to_string()),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_symbol(self, symbol: &str) -> VenueOverride {
+        VenueOverride {
+            symbol: Some(symbol.to_string()),
+            ..self
+        }
+    }
+}
+
+impl ExchangeConnector for VenueOverride {
+    fn name(&self) -> &str {
+        self.connector.name()
+    }
+
+    fn url(&self) -> &str {
+        self.url.as_deref().unwrap_or_else(|| self.connector.url())
+    }
+
+    // Exchanges handing out the endpoint of every connection keep theirs
+    fn connect_url(&self) -> Result<String, Box<dyn Error>> {
+        let url = self.connector.connect_url()?;
+        match &self.url {
+            Some(override_url) if url == self.connector.url() => Ok(override_url.clone()),
+            _ => Ok(url),
+        }
+    }
+
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match &self.symbol {
+            Some(venue_symbol) => venue_symbol.clone(),
+            None => self.connector.normalize_symbol(symbol),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        self.connector.rest_url(symbol)
+    }
+
+    fn instruments_url(&self) -> Option<String> {
+        self.connector.instruments_url()
+    }
+
+    fn listed_symbols(&self, body: &str) -> Vec<String> {
+        self.connector.listed_symbols(body)
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        self.connector.subscribe_messages(symbol)
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        self.connector.unsubscribe_messages(symbol)
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        self.connector.is_subscribe_ack(message_text)
+    }
+
+    fn is_status_message(&self, message_text: &str) -> bool {
+        self.connector.is_status_message(message_text)
+    }
+
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        self.connector.parse_error(message_text)
+    }
+
+    fn heartbeat_reply(&self, message_text: &str) -> Option<String> {
+        self.connector.heartbeat_reply(message_text)
+    }
+
+    fn keepalive_message(&self) -> Option<String> {
+        self.connector.keepalive_message()
+    }
+
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        self.connector.event_time(message_text)
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        self.connector.apply_message(message_text, depth)
+    }
+
+    fn snapshot_request(&self) -> Option<String> {
+        self.connector.snapshot_request()
+    }
+
+    fn apply_snapshot(
+        &mut self,
+        snapshot: Result<String, String>,
+        depth: usize,
+    ) -> Option<OrderBook> {
+        self.connector.apply_snapshot(snapshot, depth)
+    }
+
+    fn bbo_only(&self) -> bool {
+        self.connector.bbo_only()
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.connector.needs_resync()
+    }
+
+    fn reset(&mut self) {
+        self.connector.reset()
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.connector.set_compaction(policy)
+    }
+
+    fn set_fx_rate_source(&mut self, currency: &str, source: Arc<dyn FxRateSource>) {
+        self.connector.set_fx_rate_source(currency, source)
+    }
+
+    fn enable_trades(&mut self) {
+        self.connector.enable_trades()
+    }
+
+    fn take_trades(&mut self) -> Vec<TapeTrade> {
+        self.connector.take_trades()
+    }
+
+    fn set_update_speed(&mut self, speed: Duration) {
+        self.connector.set_update_speed(speed)
+    }
+
+    fn set_subscription_param(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        self.connector.set_subscription_param(key, value)
+    }
+}
+
+// What a connection does with a message received before the subscription is acknowledged
+enum AckStep {
+    Acknowledged,
+    Reply(String),
+    Wait,
+    Fail(String),
+}
+
+// Only the first message has to acknowledge the subscription, besides heartbeats and
+// status messages. Exchanges compressing their messages send it in a binary frame
+fn ack_step(connector: &dyn ExchangeConnector, connection_message: &Message) -> AckStep {
+    // Pings are answered by tungstenite on the next read
+    if connection_message.is_ping() || connection_message.is_pong() {
+        return AckStep::Wait;
+    }
+    if !matches!(connection_message, Message::Text(_) | Message::Binary(_)) {
+        return AckStep::Fail(format!(
+            "Failed to connect with {} Stream",
+            connector.name()
+        ));
+    }
+    let connection_message = message_text(connection_message);
+    if connector.is_subscribe_ack(&connection_message) {
+        println!("Connected with {} Stream successfully", connector.name());
+        return AckStep::Acknowledged;
+    }
+    if let Some(reply) = connector.heartbeat_reply(&connection_message) {
+        return AckStep::Reply(reply);
+    }
+    if let Some(error) = connector.parse_error(&connection_message) {
+        return AckStep::Fail(format!(
+            "{} refused the subscription: {}",
+            connector.name(),
+            error
+        ));
+    }
+    if connector.is_status_message(&connection_message) {
+        AckStep::Wait
+    } else {
+        AckStep::Fail(format!(
+            "Failed to connect with {} Stream",
+            connector.name()
+        ))
+    }
+}
+
+pub fn connect_connector(
+    connector: &dyn ExchangeConnector,
+    symbol: &str,
+) -> Result<WebSocket<AutoStream>, Box<dyn Error>> {
+    let url = Url::parse(&connector.connect_url()?)?;
+    let (mut socket, _) = connection_manager().connect(&url)?;
+
+    // Send the subscription messages as text frames
+    for subscribe_message in connector.subscribe_messages(&connector.normalize_symbol(symbol)) {
+        socket.write_message(Message::Text(subscribe_message))?;
+    }
+
+    loop {
+        match ack_step(connector, &socket.read_message()?) {
+            AckStep::Acknowledged => return Ok(socket),
+            AckStep::Reply(reply) => socket.write_message(Message::Text(reply))?,
+            AckStep::Wait => {}
+            AckStep::Fail(err) => return Err(err.into()),
+        }
+    }
+}
+
+// Same as connect_connector on a socket of the async runtime. The connector's
+// connect_url runs on the blocking pool, it may resolve the url over HTTP, so the
+// connector is moved there and handed back with the socket
+pub async fn connect_connector_async(
+    connector: Box<dyn ExchangeConnector>,
+    symbol: &str,
+) -> (
+    Box<dyn ExchangeConnector>,
+    Result<AsyncSocket, Box<dyn Error>>,
+) {
+    let resolved = tokio::task::spawn_blocking(move || {
+        let url = connector.connect_url().map_err(|err| err.to_string());
+        (connector, url)
+    });
+    let (mut connector, url) = match resolved.await {
+        Ok(resolved) => resolved,
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    };
+    let socket = match url {
+        Ok(url) => subscribe_socket_async(connector.as_mut(), &url, symbol).await,
+        Err(err) => Err(err.into()),
+    };
+    (connector, socket)
+}
+
+async fn subscribe_socket_async(
+    connector: &mut dyn ExchangeConnector,
+    url: &str,
+    symbol: &str,
+) -> Result<AsyncSocket, Box<dyn Error>> {
+    let url = Url::parse(url)?;
+    let (mut socket, _) = connection_manager().connect_async(&url).await?;
+
+    for subscribe_message in connector.subscribe_messages(&connector.normalize_symbol(symbol)) {
+        socket.send(Message::Text(subscribe_message)).await?;
+    }
+
+    loop {
+        let connection_message = socket.next().await.ok_or("The socket closed")??;
+        match ack_step(connector, &connection_message) {
+            AckStep::Acknowledged => return Ok(socket),
+            AckStep::Reply(reply) => socket.send(Message::Text(reply)).await?,
+            AckStep::Wait => {}
+            AckStep::Fail(err) => return Err(err.into()),
+        }
+    }
+}
+
+pub fn unsubscribe_connector(
+    connector: &dyn ExchangeConnector,
+    socket: &mut WebSocket<AutoStream>,
+    symbol: &str,
+) -> Result<(), Box<dyn Error>> {
+    for unsubscribe_message in connector.unsubscribe_messages(&connector.normalize_symbol(symbol)) {
+        socket.write_message(Message::Text(unsubscribe_message))?;
+    }
+    Ok(())
+}
+
+pub async fn unsubscribe_connector_async(
+    connector: &mut dyn ExchangeConnector,
+    socket: &mut AsyncSocket,
+    symbol: &str,
+) -> Result<(), Box<dyn Error>> {
+    for unsubscribe_message in connector.unsubscribe_messages(&connector.normalize_symbol(symbol)) {
+        socket.send(Message::Text(unsubscribe_message)).await?;
+    }
+    Ok(())
+}
+
+// Body of the REST snapshot a connector asked for, see ExchangeConnector::snapshot_request
+fn fetch_snapshot(url: &str) -> Result<String, String> {
+    let url = Url::parse(url).map_err(|err| err.to_string())?;
+    http_request("GET", &url).map_err(|err| err.to_string())
+}
+
+// Applies a message, then fetches the snapshot the connector asked for, if any, and
+// builds the book from it
+pub fn apply_connector_message(
+    connector: &mut dyn ExchangeConnector,
+    message_text: &str,
+    depth: usize,
+) -> Option<OrderBook> {
+    let orderbook = connector.apply_message(message_text, depth);
+    match connector.snapshot_request() {
+        Some(url) => connector.apply_snapshot(fetch_snapshot(&url), depth),
+        None => orderbook,
+    }
+}
+
+// Same as apply_connector_message on the async runtime, the snapshot is fetched on the
+// blocking pool instead of holding up the reader's worker for the REST call
+pub async fn apply_connector_message_async(
+    connector: &mut dyn ExchangeConnector,
+    message_text: &str,
+    depth: usize,
+) -> Option<OrderBook> {
+    let orderbook = connector.apply_message(message_text, depth);
+    let url = match connector.snapshot_request() {
+        Some(url) => url,
+        None => return orderbook,
+    };
+    let snapshot = match tokio::task::spawn_blocking(move || fetch_snapshot(&url)).await {
+        Ok(snapshot) => snapshot,
+        Err(err) => Err(err.to_string()),
+    };
+    connector.apply_snapshot(snapshot, depth)
+}
+
+// Longest wait for the REST calls of the connectors, e.g. a token or a book snapshot
+const REST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Body of a REST call with an empty request body, HTTP/1.0 so the response isn't
+// chunked. A status other than 2xx is an error, e.g. a rate limited call
+pub(crate) fn http_request(method: &str, url: &Url) -> Result<String, Box<dyn Error>> {
+    let (status, body) = http_request_with_body(method, url, "")?;
+    if !(200..300).contains(&status) {
+        return Err(format!("HTTP status {} from {}", status, url).into());
+    }
+    Ok(body)
+}
+
+// Status and body of a REST call sending a JSON body, e.g. the webhook sink's POST
+pub(crate) fn http_request_with_body(
+    method: &str,
+    url: &Url,
+    body: &str,
+) -> Result<(u16, String), Box<dyn Error>> {
+    let host = url.host_str().ok_or("No host name in the url")?;
+    let port = url.port_or_known_default().ok_or("No port for the url")?;
+    let stream = connect_with_timeout(host, port)?;
+    stream.set_read_timeout(Some(REST_TIMEOUT))?;
+    let content_type = match body {
+        "" => "",
+        _ => "Content-Type: application/json\r\n",
+    };
+    let request = format!(
+        "{} {} HTTP/1.0\r\nHost: {}\r\n{}Content-Length: {}\r\n\r\n{}",
+        method,
+        &url[Position::BeforePath..],
+        host,
+        content_type,
+        body.len(),
+        body
+    );
+    let mut response = String::new();
+    if url.scheme() == "https" {
+        let mut stream = native_tls::TlsConnector::new()?.connect(host, stream)?;
+        stream.write_all(request.as_bytes())?;
+        stream.read_to_string(&mut response)?;
+    } else {
+        let mut stream = stream;
+        stream.write_all(request.as_bytes())?;
+        stream.read_to_string(&mut response)?;
+    }
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or("Malformed HTTP response")?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or("Malformed HTTP status line")?;
+    Ok((status, body.to_string()))
+}
+
+// Tries the addresses of the host in turn, each for at most REST_TIMEOUT
+fn connect_with_timeout(host: &str, port: u16) -> Result<StdTcpStream, Box<dyn Error>> {
+    let mut last_err = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match StdTcpStream::connect_timeout(&addr, REST_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    match last_err {
+        Some(err) => Err(err.into()),
+        None => Err(format!("No address for {}", host).into()),
+    }
+}
+
+// Parses an array of [price, amount] pairs, as strings or numbers, skipping malformed
+// entries
+pub(crate) fn parse_levels(levels: &Value, exchange: &str) -> Option<Vec<PriceAmountLevel>> {
+    let levels = levels.as_array()?;
+
+    Some(
+        levels
+            .iter()
+            .filter_map(|level| {
+                let price = level.get(0).and_then(json_decimal)?;
+                let amount = level.get(1).and_then(json_decimal)?;
+                Some(PriceAmountLevel {
+                    exchange: exchange.to_string(),
+                    price,
+                    amount,
+                })
+            })
+            .collect(),
+    )
+}
+
+pub fn process_message(message_text: &str, exchange: &str, depth: usize) -> Option<OrderBook> {
+    if let Ok(result) = serde_json::from_str::<Value>(message_text) {
+        // for bitstamp the "bids" and "asks" are inside "data" key
+        // whereas for binance we can directly access the "bids" and "asks"
+        let data = result.get("data").unwrap_or(&result);
+        orderbook_from_data(data, exchange, depth)
+    } else {
+        None // Return early if JSON deserialization fails
+    }
+}
+
+pub(crate) fn orderbook_from_data(data: &Value, exchange: &str, depth: usize) -> Option<OrderBook> {
+    // Return early if bids or asks array is missing
+    let bids = parse_levels(&data["bids"], exchange)?;
+    let asks = parse_levels(&data["asks"], exchange)?;
+
+    let spread = match (bids.first(), asks.first()) {
+        (Some(first_bid), Some(first_ask)) => decimal_to_f64(first_bid.price - first_ask.price),
+        _ => 0.0, // Default value in case bids or asks are empty
+    };
+
+    let selected_bids = sort_and_trim_levels(&bids, depth, false);
+    let selected_asks = sort_and_trim_levels(&asks, depth, true);
+
+    // Return the selected bids and asks along with the actual number of levels selected
+    Some(OrderBook {
+        bids: selected_bids.to_vec(),
+        asks: selected_asks.to_vec(),
+        spread,
+        venues: Vec::new(),
+    })
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_process_message() {
+        let message_text = r#"
+            {
+                "data": {
+                    "bids": [
+                        [ "10.0", "1.0" ],
+                        [ "9.5", "2.0" ]
+                    ],
+                    "asks": [
+                        [ "11.0", "0.8" ],
+                        [ "11.5", "0.7" ]
+                    ]
+                }
+            }
+        "#;
+
+        let exchange = "exchange1";
+        let depth = 2;
+
+        let orderbook = process_message(message_text, exchange, depth).unwrap();
+
+        assert_eq!(orderbook.bids.len(), 2);
+
+        assert_eq!(orderbook.bids[0].exchange, "exchange1");
+        assert_eq!(orderbook.bids[0].price, dec!(10.0));
+        assert_eq!(orderbook.bids[0].amount, dec!(1.0));
+
+        assert_eq!(orderbook.bids[1].exchange, "exchange1");
+        assert_eq!(orderbook.bids[1].price, dec!(9.5));
+        assert_eq!(orderbook.bids[1].amount, dec!(2.0));
+
+        // Assert the ask levels
+        assert_eq!(orderbook.asks.len(), 2);
+
+        assert_eq!(orderbook.asks[0].exchange, "exchange1");
+        assert_eq!(orderbook.asks[0].price, dec!(11.0));
+        assert_eq!(orderbook.asks[0].amount, dec!(0.8));
+
+        assert_eq!(orderbook.asks[1].exchange, "exchange1");
+        assert_eq!(orderbook.asks[1].price, dec!(11.5));
+        assert_eq!(orderbook.asks[1].amount, dec!(0.7));
+
+        // Assert the spread value
+        assert_eq!(orderbook.spread, -1.0);
+    }
+
+    #[test]
+    fn test_normalize_symbol() {
+        assert_eq!(
+            BinanceConnector::new(10).normalize_symbol("BTCUSDT"),
+            "btcusdt"
+        );
+        assert_eq!(
+            BitstampConnector::new().normalize_symbol("BTCUSD"),
+            "btcusd"
+        );
+        assert_eq!(BybitConnector::new().normalize_symbol("btcusdt"), "BTCUSDT");
+        // The canonical form works for every venue
+        assert_eq!(
+            BinanceConnector::new(10).normalize_symbol("BTC-USDT"),
+            "btcusdt"
+        );
+        assert_eq!(
+            BitstampConnector::new().normalize_symbol("BTC/USD"),
+            "btcusd"
+        );
+        assert_eq!(
+            BybitConnector::new().normalize_symbol("BTC-USDT"),
+            "BTCUSDT"
+        );
+    }
+
+    #[test]
+    fn test_endpoint_and_update_speed() {
+        let mut connector: Box<dyn ExchangeConnector> = Box::new(
+            VenueOverride::new(Box::new(BinanceConnector::new(10)))
+                .with_url("wss://data-stream.binance.vision/ws"),
+        );
+        assert_eq!(connector.url(), "wss://data-stream.binance.vision/ws");
+        assert_eq!(
+            connector.connect_url().unwrap(),
+            "wss://data-stream.binance.vision/ws"
+        );
+        assert!(connector.subscribe_messages("btcusdt")[0].contains(r#""btcusdt@depth10@100ms""#));
+        connector.set_update_speed(Duration::from_secs(1));
+        assert!(connector.subscribe_messages("btcusdt")[0].contains(r#""btcusdt@depth10""#));
+
+        let mut futures = BinanceConnector::futures(5);
+        futures.set_update_speed(Duration::from_millis(500));
+        assert!(futures.subscribe_messages("btcusdt")[0].contains(r#""btcusdt@depth5@500ms""#));
+    }
+
+    #[test]
+    fn test_subscription_params() {
+        let mut connector: Box<dyn ExchangeConnector> = Box::new(
+            VenueOverride::new(Box::new(BinanceConnector::new(10)))
+                .with_url("wss://data-stream.binance.vision/ws"),
+        );
+        connector
+            .set_subscription_param("update_speed_ms", "1000")
+            .unwrap();
+        connector.set_subscription_param("depth", "20").unwrap();
+        assert!(connector.subscribe_messages("btcusdt")[0].contains(r#""btcusdt@depth20""#));
+
+        let err = connector
+            .set_subscription_param("update_speed_ms", "250")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "update_speed_ms must be one of 100, 1000, got 250"
+        );
+        let err = connector
+            .set_subscription_param("speed", "100")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unsupported key speed, expected update_speed_ms or depth"
+        );
+        assert!(BinanceConnector::futures(5)
+            .set_subscription_param("update_speed_ms", "250")
+            .is_ok());
+        let err = BitstampConnector::new()
+            .set_subscription_param("depth", "10")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Bitstamp takes no subscription parameters, got depth"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_connector_async() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let exchange = std::thread::spawn(move || {
+            let mut socket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            let subscribe_message = socket.read_message().unwrap().into_text().unwrap();
+            assert!(subscribe_message.contains("SUBSCRIBE"));
+            // A ping before the ack doesn't fail the subscription and gets its pong
+            socket.write_message(Message::Ping(b"hb".to_vec())).unwrap();
+            let ack = r#"{"result":null,"id":1}"#;
+            socket
+                .write_message(Message::Text(ack.to_string()))
+                .unwrap();
+            let snapshot =
+                r#"{"lastUpdateId": 1, "bids": [["10.0", "1.0"]], "asks": [["11.0", "0.8"]]}"#;
+            socket
+                .write_message(Message::Text(snapshot.to_string()))
+                .unwrap();
+            assert_eq!(
+                socket.read_message().unwrap(),
+                Message::Pong(b"hb".to_vec())
+            );
+            socket.read_message().unwrap().into_text().unwrap()
+        });
+
+        let connector = Box::new(BinanceConnector::with_url(&url, 10));
+        let (mut connector, socket) = connect_connector_async(connector, "btcusdt").await;
+        let mut socket = socket.unwrap();
+        let message = socket.next().await.unwrap().unwrap();
+        let orderbook = connector
+            .apply_message(&message_text(&message), 10)
+            .unwrap();
+        assert_eq!(orderbook.bids[0].price, dec!(10.0));
+        assert_eq!(orderbook.asks[0].amount, dec!(0.8));
+
+        unsubscribe_connector_async(connector.as_mut(), &mut socket, "btcusdt")
+            .await
+            .unwrap();
+        assert!(exchange.join().unwrap().contains("UNSUBSCRIBE"));
+    }
+
+    #[test]
+    fn test_http_request_status() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/depth", listener.local_addr().unwrap())).unwrap();
+        std::thread::spawn(move || {
+            for (mut stream, status) in listener
+                .incoming()
+                .flatten()
+                .zip(["200 OK", "429 Too Many Requests"])
+            {
+                let _ = stream.read(&mut [0; 1024]);
+                let response = format!("HTTP/1.0 {}\r\n\r\n{{\"bids\":[]}}", status);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        assert_eq!(http_request("GET", &url).unwrap(), r#"{"bids":[]}"#);
+        // A rate limited call isn't taken for a snapshot
+        let err = http_request("GET", &url).unwrap_err();
+        assert!(err.to_string().contains("429"));
+    }
+
+    #[test]
+    fn test_parse_exchange_errors() {
+        let binance = BinanceConnector::new(10);
+        let error = binance
+            .parse_error(r#"{"error":{"code":-1003,"msg":"Too many requests"},"id":1}"#)
+            .unwrap();
+        assert_eq!(error.code.as_deref(), Some("-1003"));
+        assert_eq!(error.action, ErrorAction::BackOff);
+        assert_eq!(error.to_string(), "Too many requests (code -1003)");
+        assert!(binance.parse_error(r#"{"result":null,"id":1}"#).is_none());
+
+        let error = BitstampConnector::new()
+            .parse_error(r#"{"event":"bts:error","channel":"","data":{"code":null,"message":"Bad subscription string."}}"#)
+            .unwrap();
+        assert_eq!(error.code, None);
+        assert_eq!(error.action, ErrorAction::Resubscribe);
+
+        let error = BybitConnector::new()
+            .parse_error(
+                r#"{"success":false,"ret_msg":"error:handler not found","op":"subscribe"}"#,
+            )
+            .unwrap();
+        assert_eq!(error.action, ErrorAction::Disable);
+    }
+}
@@ -0,0 +1,302 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::connectors::{ErrorAction, ExchangeConnector, ExchangeError};
+use crate::merge::sort_and_trim_levels;
+use crate::number::{decimal_to_f64, json_decimal, json_f64};
+use crate::symbols::split_symbol;
+use serde_json::Value;
+use std::env;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::spawn;
+use std::time::Duration;
+use tungstenite::{accept, Message};
+use url::{Position, Url};
+
+// OANDA sends a heartbeat every 5 s, a stream silent for longer is dead
+const STREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+// OANDA's v20 pricing stream of the FX pairs, e.g. EUR_USD. It isn't a WebSocket but
+// a long HTTP response with one JSON object per line, so every connection of the
+// connector starts a relay on localhost: a WebSocket serving the lines of the stream of
+// the instrument named by the subscription message, acknowledged once OANDA answered.
+// The account and its API token are read from OANDA_ACCOUNT_ID and OANDA_API_TOKEN,
+// the stream from OANDA_STREAM_URL (the live one by default, practice accounts use
+// https://stream-fxpractice.oanda.com)
+pub struct OandaConnector {
+    // Base of the pricing stream
+    url: String,
+    account_id: String,
+    token: String,
+}
+
+impl OandaConnector {
+    pub fn new(url: &str, account_id: &str, token: &str) -> OandaConnector {
+        OandaConnector {
+            url: url.to_string(),
+            account_id: account_id.to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    pub fn from_env() -> OandaConnector {
+        let var = |name: &str| env::var(name).unwrap_or_default();
+        let url = env::var("OANDA_STREAM_URL")
+            .unwrap_or_else(|_| "https://stream-fxtrade.oanda.com".to_string());
+        OandaConnector::new(&url, &var("OANDA_ACCOUNT_ID"), &var("OANDA_API_TOKEN"))
+    }
+}
+
+// Opens the pricing stream of the instruments and returns its HTTP status and the
+// reader of its body. HTTP/1.0 so the lines aren't chunked, and the times in seconds
+// since epoch rather than RFC 3339
+fn open_stream(url: &Url, token: &str) -> Result<(u16, Box<dyn BufRead>), Box<dyn Error>> {
+    let host = url.host_str().ok_or("No host name in the url")?;
+    let port = url.port_or_known_default().ok_or("No port for the url")?;
+    let stream = TcpStream::connect((host, port))?;
+    stream.set_read_timeout(Some(STREAM_TIMEOUT))?;
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nAuthorization: Bearer {}\r\nAccept-Datetime-Format: UNIX\r\n\r\n",
+        &url[Position::BeforePath..],
+        host,
+        token
+    );
+    let mut reader: Box<dyn BufRead> = if url.scheme() == "https" {
+        let mut stream = native_tls::TlsConnector::new()?.connect(host, stream)?;
+        stream.write_all(request.as_bytes())?;
+        Box::new(BufReader::new(stream))
+    } else {
+        let mut stream = stream;
+        stream.write_all(request.as_bytes())?;
+        Box::new(BufReader::new(stream))
+    };
+
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or("Malformed HTTP status line")?;
+    // Skip the headers
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    Ok((status, reader))
+}
+
+// Serves the relay's one WebSocket connection until the stream ends or the server
+// closes the socket
+fn relay(listener: TcpListener, stream_url: String, token: String) -> Result<(), Box<dyn Error>> {
+    let (stream, _) = listener.accept()?;
+    let mut socket = accept(stream).map_err(|err| err.to_string())?;
+    let instruments = match socket.read_message()? {
+        Message::Text(subscribe_message) => serde_json::from_str::<Value>(&subscribe_message)?
+            ["instruments"]
+            .as_str()
+            .ok_or("No instruments in the subscription")?
+            .to_string(),
+        _ => return Err("No subscription message".into()),
+    };
+
+    let url = Url::parse_with_params(&stream_url, &[("instruments", &instruments)])?;
+    let (status, mut reader) = open_stream(&url, &token)?;
+    if status != 200 {
+        // The error of OANDA, e.g. {"errorMessage": "Insufficient authorization ..."}
+        let mut body = String::new();
+        reader.read_to_string(&mut body)?;
+        socket.write_message(Message::Text(body))?;
+        return Ok(());
+    }
+    socket.write_message(Message::Text(format!(
+        r#"{{"type": "STREAM_OPEN", "instruments": "{}"}}"#,
+        instruments
+    )))?;
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            socket.write_message(Message::Text(line))?;
+        }
+    }
+    Ok(())
+}
+
+// Levels are {"price": "1.08512", "liquidity": 1000000}, in units of the base currency
+fn parse_oanda_levels(levels: &Value) -> Vec<PriceAmountLevel> {
+    levels
+        .as_array()
+        .map(|levels| {
+            levels
+                .iter()
+                .filter_map(|level| {
+                    Some(PriceAmountLevel {
+                        exchange: "oanda".to_string(),
+                        price: json_decimal(&level["price"])?,
+                        amount: json_decimal(&level["liquidity"])?,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl ExchangeConnector for OandaConnector {
+    fn name(&self) -> &str {
+        "OANDA"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // The url of a new relay of the account's pricing stream
+    fn connect_url(&self) -> Result<String, Box<dyn Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let relay_url = format!("ws://{}", listener.local_addr()?);
+        let stream_url = format!(
+            "{}/v3/accounts/{}/pricing/stream",
+            self.url, self.account_id
+        );
+        let token = self.token.clone();
+        spawn(move || {
+            if let Err(err) = relay(listener, stream_url, token) {
+                eprintln!("The OANDA relay stopped: {}", err);
+            }
+        });
+        Ok(relay_url)
+    }
+
+    // EUR_USD
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => format!("{}_{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![format!(r#"{{"instruments": "{}"}}"#, symbol)]
+    }
+
+    // Closing the relay's socket ends the stream
+    fn unsubscribe_messages(&self, _symbol: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    // Sent by the relay once the stream is open
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["type"] == "STREAM_OPEN"
+    }
+
+    // {"errorMessage": "Invalid value specified for 'instruments'"}, a bad account,
+    // token or instrument
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let message = result["errorMessage"].as_str()?;
+        Some(ExchangeError {
+            code: result["errorCode"].as_str().map(str::to_string),
+            message: message.to_string(),
+            action: ErrorAction::Disable,
+        })
+    }
+
+    // The time of the price, "1704189600.123456789" in seconds
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let seconds = json_f64(&result["time"])?;
+        Some((seconds * 1000.0) as u64)
+    }
+
+    // Every PRICE carries the whole ladder, the HEARTBEATs none. Outside trading hours
+    // the prices aren't tradeable and the book is empty
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let price = serde_json::from_str::<Value>(message_text).ok()?;
+        if price["type"] != "PRICE" {
+            return None;
+        }
+        if price["tradeable"] == false {
+            return Some(OrderBook::new());
+        }
+        let bids = sort_and_trim_levels(&parse_oanda_levels(&price["bids"]), depth, false);
+        let asks = sort_and_trim_levels(&parse_oanda_levels(&price["asks"]), depth, true);
+        let spread = match (bids.first(), asks.first()) {
+            (Some(bid), Some(ask)) => decimal_to_f64(bid.price - ask.price),
+            _ => 0.0,
+        };
+        Some(OrderBook {
+            bids,
+            asks,
+            spread,
+            venues: Vec::new(),
+        })
+    }
+}
+
+crate::register_connector!("oanda", false, |_| Box::new(OandaConnector::from_env()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::connect_connector;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_oanda_stream() {
+        let prices = [
+            r#"{"type":"PRICE","time":"1704189600.123456789","bids":[{"price":"1.09510","liquidity":1000000},{"price":"1.09508","liquidity":5000000}],"asks":[{"price":"1.09522","liquidity":1000000}],"closeoutBid":"1.09508","closeoutAsk":"1.09522","status":"tradeable","tradeable":true,"instrument":"EUR_USD"}"#,
+            r#"{"type":"HEARTBEAT","time":"1704189605.000000000"}"#,
+            r#"{"type":"PRICE","time":"1704189610.000000000","bids":[{"price":"1.09500","liquidity":1000000}],"asks":[{"price":"1.09530","liquidity":1000000}],"status":"non-tradeable","tradeable":false,"instrument":"EUR_USD"}"#,
+        ]
+        .join("\n");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let read = stream.read(&mut request).unwrap();
+            let request = String::from_utf8_lossy(&request[..read]).to_string();
+            assert!(request.starts_with(
+                "GET /v3/accounts/001-001/pricing/stream?instruments=EUR_USD HTTP/1.0"
+            ));
+            assert!(request.contains("Authorization: Bearer token"));
+            let _ = write!(
+                stream,
+                "HTTP/1.0 200 OK\r\nContent-Type: application/octet-stream\r\n\r\n{}\n",
+                prices
+            );
+        });
+
+        let mut connector = OandaConnector::new(&url, "001-001", "token");
+        let mut socket = connect_connector(&connector, "eurusd").unwrap();
+        let message = socket.read_message().unwrap().into_text().unwrap();
+        assert_eq!(connector.event_time(&message), Some(1704189600123));
+        let orderbook = connector.apply_message(&message, 10).unwrap();
+        assert_eq!(orderbook.bids.len(), 2);
+        assert_eq!(orderbook.bids[0].price, dec!(1.0951));
+        assert_eq!(orderbook.asks[0].amount, dec!(1000000.0));
+
+        let heartbeat = socket.read_message().unwrap().into_text().unwrap();
+        assert!(connector.apply_message(&heartbeat, 10).is_none());
+        // Not tradeable, e.g. over the weekend
+        let closed = socket.read_message().unwrap().into_text().unwrap();
+        assert!(connector
+            .apply_message(&closed, 10)
+            .unwrap()
+            .bids
+            .is_empty());
+    }
+
+    #[test]
+    fn test_oanda_error() {
+        let connector = OandaConnector::new("https://stream-fxpractice.oanda.com", "", "");
+        assert_eq!(connector.normalize_symbol("usdcad"), "USD_CAD");
+        let error = connector
+            .parse_error(r#"{"errorMessage":"Insufficient authorization to perform request."}"#)
+            .unwrap();
+        assert_eq!(error.action, ErrorAction::Disable);
+        assert!(connector.parse_error(r#"{"type":"HEARTBEAT"}"#).is_none());
+    }
+}
@@ -0,0 +1,370 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::{
+    error_code, param_choice, unsupported_param, ErrorAction, ExchangeConnector, ExchangeError,
+};
+use crate::number::json_f64;
+use crate::symbols::split_symbol;
+use crc32fast::Hasher;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+
+// Levels of each side covered by OKX's checksum, Bitget's covers as many
+const OKX_CHECKSUM_DEPTH: usize = 25;
+
+// Price and size of a level as OKX sent them, keyed by the bits of the price. The
+// checksum is computed over these strings, so they are kept next to the parsed book
+pub(crate) type LevelTexts = HashMap<Decimal, (Decimal, String, String)>;
+
+// OKX's books channel sends a snapshot of 400 levels and then the changed levels.
+// Every message carries the CRC32 of the top 25 levels of the book after it is
+// applied, and the seqId of the previous message. A checksum mismatch or a sequence
+// gap means the local book diverged, and it is only rebuilt by a new snapshot
+pub struct OkxConnector {
+    url: String,
+    // books by default, or the tick by tick books-l2-tbt and books50-l2-tbt, which send
+    // the same messages but need a login of a VIP account
+    channel: &'static str,
+    book: LocalBook,
+    bid_texts: LevelTexts,
+    ask_texts: LevelTexts,
+    last_seq_id: Option<i64>,
+    out_of_sync: bool,
+}
+
+impl OkxConnector {
+    pub fn new() -> OkxConnector {
+        OkxConnector::with_url("wss://ws.okx.com:8443/ws/v5/public")
+    }
+
+    pub fn with_url(url: &str) -> OkxConnector {
+        OkxConnector {
+            url: url.to_string(),
+            channel: "books",
+            book: LocalBook::new(),
+            bid_texts: HashMap::new(),
+            ask_texts: HashMap::new(),
+            last_seq_id: None,
+            out_of_sync: false,
+        }
+    }
+
+    fn books_message(&self, op: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"op": "{}", "args": [{{"channel": "{}", "instId": "{}"}}]}}"#,
+            op, self.channel, symbol
+        )
+    }
+
+    // The checksum of the local book, as a signed 32 bit integer like OKX sends it
+    fn checksum(&self) -> i32 {
+        let bids = top_levels(&self.bid_texts, true);
+        let asks = top_levels(&self.ask_texts, false);
+        okx_checksum(
+            bids.iter()
+                .map(|(_, price, size)| (price.as_str(), size.as_str())),
+            asks.iter()
+                .map(|(_, price, size)| (price.as_str(), size.as_str())),
+        )
+    }
+
+    fn clear(&mut self) {
+        self.book.clear();
+        self.bid_texts.clear();
+        self.ask_texts.clear();
+    }
+}
+
+impl Default for OkxConnector {
+    fn default() -> Self {
+        OkxConnector::new()
+    }
+}
+
+// The best OKX_CHECKSUM_DEPTH levels of a side
+pub(crate) fn top_levels(texts: &LevelTexts, descending: bool) -> Vec<&(Decimal, String, String)> {
+    let mut levels: Vec<&(Decimal, String, String)> = texts.values().collect();
+    levels.sort_by_key(|level| level.0);
+    if descending {
+        levels.reverse();
+    }
+    levels.truncate(OKX_CHECKSUM_DEPTH);
+    levels
+}
+
+// CRC32 of "bid1price:bid1size:ask1price:ask1size:bid2price:...", the levels of the
+// longer side continue alone once the other side has none left. Bitget's checksum is
+// computed the same way
+pub(crate) fn okx_checksum<'a>(
+    bids: impl Iterator<Item = (&'a str, &'a str)>,
+    asks: impl Iterator<Item = (&'a str, &'a str)>,
+) -> i32 {
+    let mut bids = bids.fuse();
+    let mut asks = asks.fuse();
+    let mut fields: Vec<&str> = Vec::new();
+    loop {
+        let (bid, ask) = (bids.next(), asks.next());
+        if bid.is_none() && ask.is_none() {
+            break;
+        }
+        for (price, size) in bid.into_iter().chain(ask) {
+            fields.push(price);
+            fields.push(size);
+        }
+    }
+    let mut hasher = Hasher::new();
+    hasher.update(fields.join(":").as_bytes());
+    hasher.finalize() as i32
+}
+
+// Levels are [price, size, deprecated, orders] strings, a size of "0" removes the level
+pub(crate) fn parse_okx_levels(levels: &Value) -> Option<Vec<(Decimal, Decimal, String, String)>> {
+    Some(
+        levels
+            .as_array()?
+            .iter()
+            .filter_map(|level| {
+                let price_text = level[0].as_str()?;
+                let size_text = level[1].as_str()?;
+                Some((
+                    price_text.parse().ok()?,
+                    size_text.parse().ok()?,
+                    price_text.to_string(),
+                    size_text.to_string(),
+                ))
+            })
+            .collect(),
+    )
+}
+
+pub(crate) fn apply_texts(texts: &mut LevelTexts, levels: &[(Decimal, Decimal, String, String)]) {
+    for (price, amount, price_text, size_text) in levels {
+        if amount.is_zero() {
+            texts.remove(price);
+        } else {
+            texts.insert(*price, (*price, price_text.clone(), size_text.clone()));
+        }
+    }
+}
+
+pub(crate) fn to_price_amount_levels(
+    levels: &[(Decimal, Decimal, String, String)],
+    exchange: &str,
+) -> Vec<PriceAmountLevel> {
+    levels
+        .iter()
+        .map(|(price, amount, ..)| PriceAmountLevel {
+            exchange: exchange.to_string(),
+            price: *price,
+            amount: *amount,
+        })
+        .collect()
+}
+
+impl ExchangeConnector for OkxConnector {
+    fn name(&self) -> &str {
+        "OKX"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // BTC-USDT
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => format!("{}-{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://www.okx.com/api/v5/market/books?instId={}",
+            symbol
+        ))
+    }
+
+    fn instruments_url(&self) -> Option<String> {
+        Some("https://www.okx.com/api/v5/public/instruments?instType=SPOT".to_string())
+    }
+
+    // {"data": [{"instId": "BTC-USDT", "state": "live", ...}, ...]}
+    fn listed_symbols(&self, body: &str) -> Vec<String> {
+        let instruments = serde_json::from_str::<Value>(body).unwrap_or_default();
+        let instruments = instruments["data"].as_array().cloned().unwrap_or_default();
+        instruments
+            .iter()
+            .filter(|instrument| instrument["state"] == "live")
+            .filter_map(|instrument| Some(instrument["instId"].as_str()?.to_string()))
+            .collect()
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.books_message("subscribe", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.books_message("unsubscribe", symbol)]
+    }
+
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["event"] == "subscribe"
+    }
+
+    // {"event": "error", "code": "60012", "msg": "Invalid request"}, and the notice of a
+    // service upgrade closing the connection. 60014 and 50011 are rate limits
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["event"] != "error" && result["event"] != "notice" {
+            return None;
+        }
+        let code = error_code(&result["code"]);
+        let action = match code.as_deref() {
+            Some("60014" | "50011") => ErrorAction::BackOff,
+            _ if result["event"] == "notice" => ErrorAction::Resubscribe,
+            _ => ErrorAction::Disable,
+        };
+        Some(ExchangeError {
+            code,
+            message: result["msg"].as_str().unwrap_or_default().to_string(),
+            action,
+        })
+    }
+
+    // The ts of the data, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        Some(json_f64(&result["data"][0]["ts"])? as u64)
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["arg"]["channel"] != self.channel {
+            return None;
+        }
+        let data = &result["data"][0];
+        let bids = parse_okx_levels(&data["bids"])?;
+        let asks = parse_okx_levels(&data["asks"])?;
+        match result["action"].as_str()? {
+            "snapshot" => {
+                self.clear();
+                apply_texts(&mut self.bid_texts, &bids);
+                apply_texts(&mut self.ask_texts, &asks);
+                self.book.replace(
+                    to_price_amount_levels(&bids, "okx"),
+                    to_price_amount_levels(&asks, "okx"),
+                );
+                self.out_of_sync = false;
+            }
+            "update" => {
+                let in_sequence = self
+                    .last_seq_id
+                    .is_none_or(|last_seq_id| data["prevSeqId"].as_i64() == Some(last_seq_id));
+                if self.out_of_sync || !in_sequence {
+                    self.out_of_sync = true;
+                    return None;
+                }
+                apply_texts(&mut self.bid_texts, &bids);
+                apply_texts(&mut self.ask_texts, &asks);
+                self.book.apply_updates(
+                    to_price_amount_levels(&bids, "okx"),
+                    to_price_amount_levels(&asks, "okx"),
+                    "okx",
+                );
+            }
+            _ => return None,
+        }
+        self.last_seq_id = data["seqId"].as_i64();
+
+        // The levels only contribute to the merged book once the checksum confirms them
+        if data["checksum"].as_i64() != Some(self.checksum() as i64) {
+            eprintln!("OKX checksum mismatch, the book is rebuilt from a new snapshot");
+            self.out_of_sync = true;
+            return None;
+        }
+        Some(self.book.orderbook(depth))
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+        self.last_seq_id = None;
+        self.out_of_sync = false;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+
+    // channel, the book channel subscribed
+    fn set_subscription_param(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        if key != "channel" {
+            return Err(unsupported_param(key, &["channel"]));
+        }
+        self.channel = param_choice(key, value, &["books", "books-l2-tbt", "books50-l2-tbt"])?;
+        Ok(())
+    }
+}
+
+crate::register_connector!("okx", false, |_| Box::new(OkxConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_okx_checksum() {
+        // The asks continue alone once there is no bid left
+        let bids = [("3366.1", "7"), ("3366", "6")];
+        let asks = [("3366.8", "9"), ("3368", "8"), ("3372", "8")];
+        assert_eq!(
+            okx_checksum(bids.into_iter(), asks.into_iter()),
+            crc32fast::hash(b"3366.1:7:3366.8:9:3366:6:3368:8:3372:8") as i32
+        );
+    }
+
+    #[test]
+    fn test_okx_channel() {
+        let mut connector = OkxConnector::new();
+        connector
+            .set_subscription_param("channel", "books50-l2-tbt")
+            .unwrap();
+        assert!(
+            connector.subscribe_messages("BTC-USDT")[0].contains(r#""channel": "books50-l2-tbt""#)
+        );
+        // The messages of the other channels are skipped
+        let books = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"snapshot","data":[{"asks":[],"bids":[],"ts":"1597026383085","checksum":0,"seqId":1}]}"#;
+        assert!(connector.apply_message(books, 10).is_none());
+        assert!(connector
+            .set_subscription_param("channel", "books5")
+            .is_err());
+    }
+
+    #[test]
+    fn test_okx_conformance() {
+        run_conformance(
+            |url| Box::new(OkxConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"event":"subscribe","arg":{"channel":"books","instId":"BTC-USDT"},"connId":"a4d3ae55"}"#.to_string(),
+                snapshot: r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"snapshot","data":[{"asks":[["11.0","0.8","0","1"]],"bids":[["10.0","1.0","0","2"]],"ts":"1597026383085","checksum":-1100790240,"prevSeqId":-1,"seqId":100}]}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8)]),
+                updates: vec![(
+                    r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"update","data":[{"asks":[["10.9","0.1","0","1"]],"bids":[["10.0","0","0","0"],["9.8","3.0","0","4"]],"ts":"1597026383086","checksum":-51861650,"prevSeqId":100,"seqId":101}]}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8)]),
+                )],
+                // In sequence, but the checksum doesn't match the book
+                gap: Some(r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"update","data":[{"asks":[],"bids":[["9.7","1.0","0","1"]],"ts":"1597026383087","checksum":12345,"prevSeqId":101,"seqId":102}]}"#.to_string()),
+            },
+        );
+    }
+}
@@ -0,0 +1,429 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::connectors::{
+    error_code, http_request_with_body, ErrorAction, ExchangeConnector, ExchangeError,
+};
+use crate::merge::sort_and_trim_levels;
+use crate::number::decimal_to_f64;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::sync::Mutex;
+use url::Url;
+
+// OpenBook's SOL/USDC market, the default of OPENBOOK_MARKET
+const SOL_USDC_MARKET: &str = "8BnEgHoWFysVcuFFX7QztDmzuH8r5ZFvyP3sYwn1XTh6";
+
+// Flags of the account of an order book side
+const BIDS_FLAG: u64 = 1 << 5;
+const ASKS_FLAG: u64 = 1 << 6;
+
+// Layout of the accounts of OpenBook v1 (the Serum DEX v3 fork): a 5 bytes "serum"
+// padding, the account flags, then the account's state. A side's slab has a 32 bytes
+// header, with the number of nodes ever used first, then nodes of 72 bytes
+const HEADER_LEN: usize = 13;
+const SLAB_NODES: usize = HEADER_LEN + 32;
+const NODE_LEN: usize = 72;
+const LEAF_TAG: u32 = 2;
+
+// What the levels of a market are decoded with: its side accounts and lot sizes, and
+// the decimals of its base and quote mints
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenbookMarket {
+    pub bids: String,
+    pub asks: String,
+    pub base_lot_size: u64,
+    pub quote_lot_size: u64,
+    pub base_decimals: u32,
+    pub quote_decimals: u32,
+}
+
+impl OpenbookMarket {
+    // A price in quote lots per base lot, in quote units per base unit. The lots and lot
+    // sizes are read from the chain as they are, None when they don't make a decimal,
+    // e.g. a base lot size of 0 or a product beyond its 96 bits
+    fn price(&self, price_lots: u64) -> Option<Decimal> {
+        let quote = lots_decimal(price_lots, self.quote_lot_size, self.quote_decimals)?;
+        let base = lots_decimal(1, self.base_lot_size, self.base_decimals)?;
+        Some(quote.checked_div(base)?.normalize())
+    }
+
+    fn amount(&self, quantity_lots: u64) -> Option<Decimal> {
+        Some(lots_decimal(quantity_lots, self.base_lot_size, self.base_decimals)?.normalize())
+    }
+}
+
+// lots * lot_size units of a mint with these decimals
+fn lots_decimal(lots: u64, lot_size: u64, decimals: u32) -> Option<Decimal> {
+    let units = (lots as i128).checked_mul(lot_size as i128)?;
+    Decimal::try_from_i128_with_scale(units, decimals).ok()
+}
+
+// The order book of an OpenBook market on Solana, to compare the depth of the DEX with
+// the centralized venues. Its bids and asks are two accounts, subscribed on Solana's
+// RPC WebSocket with accountSubscribe, and every change of one sends its whole slab of
+// orders. The orders are summed by price and the book is sent once both sides are
+// known. The market is resolved from its account with the RPC's getAccountInfo when
+// connecting, OPENBOOK_MARKET is its address (SOL/USDC by default) and OPENBOOK_RPC_URL
+// the RPC (Solana's public mainnet one by default, its WebSocket on the same host)
+pub struct OpenbookConnector {
+    url: String,
+    rpc_url: String,
+    market_address: String,
+    market: Mutex<Option<OpenbookMarket>>,
+    bids: Option<Vec<PriceAmountLevel>>,
+    asks: Option<Vec<PriceAmountLevel>>,
+}
+
+impl OpenbookConnector {
+    pub fn new(rpc_url: &str, market_address: &str) -> OpenbookConnector {
+        OpenbookConnector {
+            url: rpc_url.replacen("http", "ws", 1),
+            rpc_url: rpc_url.to_string(),
+            market_address: market_address.to_string(),
+            market: Mutex::new(None),
+            bids: None,
+            asks: None,
+        }
+    }
+
+    pub fn from_env() -> OpenbookConnector {
+        let rpc_url = env::var("OPENBOOK_RPC_URL")
+            .unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string());
+        let market = env::var("OPENBOOK_MARKET").unwrap_or_else(|_| SOL_USDC_MARKET.to_string());
+        OpenbookConnector::new(&rpc_url, &market)
+    }
+
+    // A connector of an already resolved market, reading the WebSocket at url
+    pub fn with_market(url: &str, market: OpenbookMarket) -> OpenbookConnector {
+        OpenbookConnector {
+            url: url.to_string(),
+            rpc_url: String::new(),
+            market_address: String::new(),
+            market: Mutex::new(Some(market)),
+            bids: None,
+            asks: None,
+        }
+    }
+
+    fn account_data(&self, address: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let request = format!(
+            r#"{{"jsonrpc": "2.0", "id": 1, "method": "getAccountInfo", "params": ["{}", {{"encoding": "base64"}}]}}"#,
+            address
+        );
+        let (_, body) = http_request_with_body("POST", &Url::parse(&self.rpc_url)?, &request)?;
+        let response = serde_json::from_str::<Value>(&body)?;
+        let data = response["result"]["value"]["data"][0]
+            .as_str()
+            .ok_or_else(|| format!("No account {} on the RPC: {}", address, body))?;
+        Ok(BASE64.decode(data)?)
+    }
+
+    fn resolve_market(&self) -> Result<OpenbookMarket, Box<dyn Error>> {
+        let market = self.account_data(&self.market_address)?;
+        if market.len() < 365 || !market.starts_with(b"serum") {
+            return Err(format!("{} isn't an OpenBook market", self.market_address).into());
+        }
+        // SPL mints have their decimals after the authority and the supply
+        let decimals = |mint: &[u8]| -> Result<u32, Box<dyn Error>> {
+            let mint = self.account_data(&base58(mint))?;
+            Ok(*mint.get(44).ok_or("Malformed mint account")? as u32)
+        };
+        Ok(OpenbookMarket {
+            bids: base58(&market[285..317]),
+            asks: base58(&market[317..349]),
+            base_lot_size: read_u64(&market, 349),
+            quote_lot_size: read_u64(&market, 357),
+            base_decimals: decimals(&market[53..85])?,
+            quote_decimals: decimals(&market[85..117])?,
+        })
+    }
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+// Solana's addresses are the base58 of their 32 bytes
+fn base58(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in bytes {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+    std::iter::repeat_n('1', zeros)
+        .chain(
+            digits
+                .iter()
+                .rev()
+                .map(|digit| ALPHABET[*digit as usize] as char),
+        )
+        .collect()
+}
+
+// The side of a slab and its orders summed by price, None for other accounts
+fn parse_slab(data: &[u8], market: &OpenbookMarket) -> Option<(u64, Vec<PriceAmountLevel>)> {
+    if data.len() < SLAB_NODES || !data.starts_with(b"serum") {
+        return None;
+    }
+    let flags = read_u64(data, 5);
+    let side = flags & (BIDS_FLAG | ASKS_FLAG);
+    if side == 0 {
+        return None;
+    }
+    let bump_index = read_u64(data, HEADER_LEN) as usize;
+    // None once the quantities of a price overflow, the level is dropped
+    let mut quantities: BTreeMap<u64, Option<u64>> = BTreeMap::new();
+    for index in 0..bump_index {
+        let node = SLAB_NODES + index * NODE_LEN;
+        let node = data.get(node..node + NODE_LEN)?;
+        if u32::from_le_bytes([node[0], node[1], node[2], node[3]]) != LEAF_TAG {
+            continue;
+        }
+        // The upper half of the order's key is its price
+        let price_lots = read_u64(node, 16);
+        let quantity_lots = quantities.entry(price_lots).or_insert(Some(0));
+        *quantity_lots =
+            quantity_lots.and_then(|quantity_lots| quantity_lots.checked_add(read_u64(node, 56)));
+    }
+    // Levels whose price or amount isn't a decimal are dropped as well
+    let levels = quantities
+        .into_iter()
+        .filter_map(|(price_lots, quantity_lots)| {
+            Some(PriceAmountLevel {
+                exchange: "openbook".to_string(),
+                price: market.price(price_lots)?,
+                amount: market.amount(quantity_lots?)?,
+            })
+        })
+        .collect();
+    Some((side, levels))
+}
+
+impl ExchangeConnector for OpenbookConnector {
+    fn name(&self) -> &str {
+        "OpenBook"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // The market is resolved before the first connection
+    fn connect_url(&self) -> Result<String, Box<dyn Error>> {
+        let mut market = self.market.lock().unwrap();
+        if market.is_none() {
+            *market = Some(self.resolve_market()?);
+        }
+        Ok(self.url.clone())
+    }
+
+    fn rest_url(&self, _symbol: &str) -> Option<String> {
+        Some(self.rpc_url.clone()).filter(|rpc_url| !rpc_url.is_empty())
+    }
+
+    // The market's sides whatever the symbol, the server's symbol has to be its pair
+    fn subscribe_messages(&self, _symbol: &str) -> Vec<String> {
+        let market = self.market.lock().unwrap();
+        let Some(market) = market.as_ref() else {
+            return Vec::new();
+        };
+        [&market.bids, &market.asks]
+            .iter()
+            .enumerate()
+            .map(|(index, account)| {
+                format!(
+                    r#"{{"jsonrpc": "2.0", "id": {}, "method": "accountSubscribe", "params": ["{}", {{"encoding": "base64", "commitment": "confirmed"}}]}}"#,
+                    index + 1,
+                    account
+                )
+            })
+            .collect()
+    }
+
+    // The subscriptions end with the socket, their ids aren't kept
+    fn unsubscribe_messages(&self, _symbol: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    // {"jsonrpc": "2.0", "result": 23784, "id": 1}, the id of the subscription
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["result"].is_u64() && ack["id"].is_u64()
+    }
+
+    // {"jsonrpc": "2.0", "error": {"code": -32602, "message": "Invalid param"}, "id": 1}
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let error = result.get("error")?;
+        Some(ExchangeError {
+            code: error_code(&error["code"]),
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+            action: ErrorAction::Disable,
+        })
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["method"] != "accountNotification" {
+            return None;
+        }
+        let data = result["params"]["result"]["value"]["data"][0].as_str()?;
+        let data = BASE64.decode(data).ok()?;
+        let (side, levels) = parse_slab(&data, self.market.lock().unwrap().as_ref()?)?;
+        if side == BIDS_FLAG {
+            self.bids = Some(sort_and_trim_levels(&levels, depth, false));
+        } else {
+            self.asks = Some(sort_and_trim_levels(&levels, depth, true));
+        }
+
+        let (bids, asks) = (self.bids.clone()?, self.asks.clone()?);
+        let spread = match (bids.first(), asks.first()) {
+            (Some(bid), Some(ask)) => decimal_to_f64(bid.price - ask.price),
+            _ => 0.0,
+        };
+        Some(OrderBook {
+            bids,
+            asks,
+            spread,
+            venues: Vec::new(),
+        })
+    }
+
+    fn reset(&mut self) {
+        self.bids = None;
+        self.asks = None;
+    }
+}
+
+crate::register_connector!("openbook", false, |_| Box::new(
+    OpenbookConnector::from_env()
+));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    // SOL/USDC with lots of 0.001 SOL and 0.000001 USDC
+    fn market() -> OpenbookMarket {
+        OpenbookMarket {
+            bids: "bids".to_string(),
+            asks: "asks".to_string(),
+            base_lot_size: 1_000_000,
+            quote_lot_size: 1,
+            base_decimals: 9,
+            quote_decimals: 6,
+        }
+    }
+
+    // The accountNotification of a side with orders of (price, quantity) in lots, a
+    // free node between them
+    fn notification(flags: u64, orders: &[(u64, u64)]) -> String {
+        let mut data = b"serum".to_vec();
+        data.extend((flags | 1).to_le_bytes());
+        data.extend(((orders.len() + 1) as u64).to_le_bytes());
+        data.extend([0; 24]);
+        data.extend([3, 0, 0, 0]);
+        data.extend([0; NODE_LEN - 4]);
+        for (price_lots, quantity_lots) in orders {
+            let mut node = vec![0; NODE_LEN];
+            node[0] = LEAF_TAG as u8;
+            node[16..24].copy_from_slice(&price_lots.to_le_bytes());
+            node[56..64].copy_from_slice(&quantity_lots.to_le_bytes());
+            data.extend(node);
+        }
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"accountNotification","params":{{"result":{{"context":{{"slot":5199307}},"value":{{"data":["{}","base64"],"executable":false,"lamports":33594,"owner":"srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX","rentEpoch":635}}}},"subscription":23784}}}}"#,
+            BASE64.encode(data)
+        )
+    }
+
+    #[test]
+    fn test_openbook_slabs() {
+        let mut connector = OpenbookConnector::with_market("ws://localhost", market());
+        assert!(connector.is_subscribe_ack(r#"{"jsonrpc":"2.0","result":23784,"id":1}"#));
+        assert!(connector.subscribe_messages("solusdc")[1].contains(r#"["asks","#));
+
+        // Two bids at the same price are one level, the book waits for the asks
+        let bids = notification(
+            BIDS_FLAG,
+            &[(150_000, 2_000), (150_000, 500), (149_900, 1_000)],
+        );
+        assert!(connector.apply_message(&bids, 10).is_none());
+        let asks = notification(ASKS_FLAG, &[(150_100, 3_000)]);
+        let orderbook = connector.apply_message(&asks, 10).unwrap();
+        assert_eq!(orderbook.bids.len(), 2);
+        assert_eq!(orderbook.bids[0].price, dec!(150));
+        assert_eq!(orderbook.bids[0].amount, dec!(2.5));
+        assert_eq!(orderbook.asks[0].price, dec!(150.1));
+        assert_eq!(orderbook.asks[0].amount, dec!(3));
+        assert!((orderbook.spread + 0.1).abs() < 1e-9);
+
+        connector.reset();
+        assert!(connector.apply_message(&asks, 10).is_none());
+    }
+
+    #[test]
+    fn test_openbook_overflows() {
+        // The same prices as market's, with quote lots of 10^10 units
+        let mut connector = OpenbookConnector::with_market(
+            "ws://localhost",
+            OpenbookMarket {
+                quote_lot_size: 10_000_000_000,
+                quote_decimals: 16,
+                ..market()
+            },
+        );
+        // Quantities summing past u64 and a price past 96 bits drop their level
+        let bids = notification(
+            BIDS_FLAG,
+            &[
+                (150_000, u64::MAX),
+                (150_000, 1),
+                (u64::MAX, 1),
+                (149_900, 1_000),
+            ],
+        );
+        assert!(connector.apply_message(&bids, 10).is_none());
+        let asks = notification(ASKS_FLAG, &[(150_100, 3_000)]);
+        let orderbook = connector.apply_message(&asks, 10).unwrap();
+        assert_eq!(orderbook.bids.len(), 1);
+        assert_eq!(orderbook.bids[0].price, dec!(149.9));
+
+        // A base lot size of 0 has no price
+        let mut connector = OpenbookConnector::with_market(
+            "ws://localhost",
+            OpenbookMarket {
+                base_lot_size: 0,
+                ..market()
+            },
+        );
+        connector.apply_message(&bids, 10);
+        let orderbook = connector.apply_message(&asks, 10).unwrap();
+        assert!(orderbook.bids.is_empty() && orderbook.asks.is_empty());
+    }
+
+    #[test]
+    fn test_base58() {
+        assert_eq!(base58(&[0; 32]), "11111111111111111111111111111111");
+        assert_eq!(base58(&[0, 0, 1]), "112");
+        assert_eq!(base58(b"hello world"), "StV1DL6CwTryKyV");
+    }
+}
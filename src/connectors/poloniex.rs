@@ -0,0 +1,191 @@
+use crate::book::{LocalBook, OrderBook, PriceAmountLevel};
+use crate::compaction::{CompactionPolicy, Compactor};
+use crate::connectors::{ErrorAction, ExchangeConnector, ExchangeError};
+use crate::number::json_decimal;
+use crate::symbols::split_symbol;
+use serde_json::Value;
+
+// Poloniex's v3 book_lv2 channel sends a snapshot of the book and then the changed
+// levels. Every message has an id and the lastId of the message before it, an update
+// whose lastId isn't the id of the previous message means updates were lost, and the
+// book is only rebuilt by a new snapshot
+pub struct PoloniexConnector {
+    url: String,
+    book: LocalBook,
+    last_id: Option<u64>,
+    out_of_sync: bool,
+}
+
+impl PoloniexConnector {
+    pub fn new() -> PoloniexConnector {
+        PoloniexConnector::with_url("wss://ws.poloniex.com/ws/public")
+    }
+
+    pub fn with_url(url: &str) -> PoloniexConnector {
+        PoloniexConnector {
+            url: url.to_string(),
+            book: LocalBook::new(),
+            last_id: None,
+            out_of_sync: false,
+        }
+    }
+
+    fn channel_message(&self, event: &str, symbol: &str) -> String {
+        format!(
+            r#"{{"event": "{}", "channel": ["book_lv2"], "symbols": ["{}"]}}"#,
+            event, symbol
+        )
+    }
+}
+
+impl Default for PoloniexConnector {
+    fn default() -> Self {
+        PoloniexConnector::new()
+    }
+}
+
+// Levels are [price, amount] strings, an amount of "0" removes the level
+fn parse_poloniex_levels(levels: &Value) -> Vec<PriceAmountLevel> {
+    levels
+        .as_array()
+        .map(|levels| {
+            levels
+                .iter()
+                .filter_map(|level| {
+                    Some(PriceAmountLevel {
+                        exchange: "poloniex".to_string(),
+                        price: json_decimal(&level[0])?,
+                        amount: json_decimal(&level[1])?,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl ExchangeConnector for PoloniexConnector {
+    fn name(&self) -> &str {
+        "Poloniex"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // BTC_USDT
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, quote)) => format!("{}_{}", base, quote),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api.poloniex.com/markets/{}/orderBook?limit=5",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message("subscribe", symbol)]
+    }
+
+    fn unsubscribe_messages(&self, symbol: &str) -> Vec<String> {
+        vec![self.channel_message("unsubscribe", symbol)]
+    }
+
+    // {"event": "subscribe", "channel": "book_lv2", "symbols": ["BTC_USDT"]}
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["event"] == "subscribe" && ack["channel"] == "book_lv2"
+    }
+
+    // {"event": "error", "message": "Subscription failed (generic)"}, without error codes
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["event"] != "error" {
+            return None;
+        }
+        Some(ExchangeError {
+            code: None,
+            message: result["message"].as_str().unwrap_or_default().to_string(),
+            action: ErrorAction::Disable,
+        })
+    }
+
+    // The ts of the message, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["data"][0]["ts"].as_u64()
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["channel"] != "book_lv2" {
+            return None;
+        }
+        let data = &result["data"][0];
+        let id = data["id"].as_u64()?;
+        let bids = parse_poloniex_levels(&data["bids"]);
+        let asks = parse_poloniex_levels(&data["asks"]);
+        match result["action"].as_str()? {
+            "snapshot" => {
+                self.book.replace(bids, asks);
+                self.out_of_sync = false;
+            }
+            "update" => {
+                let in_sequence = self.last_id.is_some() && data["lastId"].as_u64() == self.last_id;
+                if self.out_of_sync || !in_sequence {
+                    self.out_of_sync = true;
+                    return None;
+                }
+                self.book.apply_updates(bids, asks, "poloniex");
+            }
+            _ => return None,
+        }
+        self.last_id = Some(id);
+        Some(self.book.orderbook(depth))
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    fn reset(&mut self) {
+        self.book.clear();
+        self.last_id = None;
+        self.out_of_sync = false;
+    }
+
+    fn set_compaction(&mut self, policy: CompactionPolicy) {
+        self.book.compactor = Compactor::new(Some(policy));
+    }
+}
+
+crate::register_connector!("poloniex", false, |_| Box::new(PoloniexConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+
+    #[test]
+    fn test_poloniex_conformance() {
+        run_conformance(
+            |url| Box::new(PoloniexConnector::with_url(url)),
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"event":"subscribe","channel":"book_lv2","symbols":["BTC_USDT"]}"#.to_string(),
+                snapshot: r#"{"channel":"book_lv2","action":"snapshot","data":[{"symbol":"BTC_USDT","createTime":1677729662000,"asks":[["11.0","0.8"],["11.5","0.7"]],"bids":[["10.0","1.0"]],"lastId":164,"id":165,"ts":1677729662012}]}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                updates: vec![(
+                    r#"{"channel":"book_lv2","action":"update","data":[{"symbol":"BTC_USDT","createTime":1677729663000,"asks":[["10.9","0.1"]],"bids":[["10.0","0"],["9.8","3.0"]],"lastId":165,"id":166,"ts":1677729663012}]}"#.to_string(),
+                    (vec![(9.8, 3.0)], vec![(10.9, 0.1), (11.0, 0.8), (11.5, 0.7)]),
+                )],
+                gap: Some(r#"{"channel":"book_lv2","action":"update","data":[{"symbol":"BTC_USDT","createTime":1677729664000,"asks":[],"bids":[["9.7","1.0"]],"lastId":169,"id":170,"ts":1677729664012}]}"#.to_string()),
+            },
+        );
+    }
+}
@@ -0,0 +1,590 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::connectors::{
+    error_code, http_request_with_body, ErrorAction, ExchangeConnector, ExchangeError,
+};
+use crate::number::{decimal_from_f64, decimal_to_f64};
+use crate::symbols::split_symbol;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::error::Error;
+use std::sync::Mutex;
+use url::Url;
+
+// Uniswap v3's USDC/WETH pool of the 0.05% fee tier, the default of UNISWAP_V3_POOL
+const USDC_WETH_POOL: &str = "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640";
+
+// Topics of the pool's Swap, Mint and Burn events, the ones changing its liquidity
+const SWAP_TOPIC: &str = "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67";
+const MINT_TOPIC: &str = "0x7a53080ba414158be7ec69b987b5fb7d07dee101fe85488f0853ae16239d0bde";
+const BURN_TOPIC: &str = "0x0c396cd989a39f4459b5fa1aed6a9a8dcdbc45908acfd67e028cd568da98982c";
+
+// Selectors of the view functions read when connecting
+const TOKEN0: &str = "0x0dfe1681";
+const TOKEN1: &str = "0xd21220a7";
+const TICK_SPACING: &str = "0xd0c93a7c";
+const SLOT0: &str = "0x3850c7bd";
+const LIQUIDITY: &str = "0x1a686502";
+const TICKS: &str = "0xf30dba93";
+const DECIMALS: &str = "0x313ce567";
+const SYMBOL: &str = "0x95d89b41";
+
+// What the ticks of a pool are converted with. Uniswap prices token0 in token1, invert
+// is set when the base of the server's symbol is token1, e.g. WETH of USDC/WETH
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniswapV3Pool {
+    pub token0: String,
+    pub token1: String,
+    pub decimals0: u32,
+    pub decimals1: u32,
+    pub tick_spacing: i32,
+    pub invert: bool,
+}
+
+// The pool's price as the square root of token1 per token0 in the tokens' smallest
+// units, its tick and in range liquidity, and the liquidity added when crossing each
+// tick upwards, known for the ticks from low_tick to high_tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolState {
+    pub sqrt_price: f64,
+    pub tick: i32,
+    pub liquidity: f64,
+    pub liquidity_net: BTreeMap<i32, f64>,
+    pub low_tick: i32,
+    pub high_tick: i32,
+}
+
+fn sqrt_price_at(tick: i32) -> f64 {
+    1.0001f64.powf(tick as f64 / 2.0)
+}
+
+// Converts the liquidity of the pool into a depth ladder of up to depth levels a side,
+// one level per tick spacing. A level is the amount of the base token the pool trades
+// until its price reaches the end of the spacing, priced at that end, the worst price
+// of the amount, so the ladder can be merged with the venues' books
+pub fn synthetic_ladder(
+    pool: &UniswapV3Pool,
+    state: &PoolState,
+    depth: usize,
+) -> (Vec<PriceAmountLevel>, Vec<PriceAmountLevel>) {
+    let price_scale = 10f64.powi(pool.decimals0 as i32 - pool.decimals1 as i32);
+    let amount_scale = 10f64.powi(pool.decimals0 as i32);
+    // (price, amount), the pool's math is in doubles, the levels are its decimals
+    let level = |sqrt_price: f64, amount0: f64| {
+        (
+            sqrt_price * sqrt_price * price_scale,
+            amount0 / amount_scale,
+        )
+    };
+    let net = |tick: i32| state.liquidity_net.get(&tick).copied().unwrap_or(0.0);
+    let lower_tick = state.tick.div_euclid(pool.tick_spacing) * pool.tick_spacing;
+
+    // The pool sells token0 as the price rises
+    let mut asks = Vec::new();
+    let (mut liquidity, mut from) = (state.liquidity, state.sqrt_price);
+    let mut tick = lower_tick + pool.tick_spacing;
+    while asks.len() < depth && tick <= state.high_tick {
+        let to = sqrt_price_at(tick);
+        let amount0 = liquidity * (1.0 / from - 1.0 / to);
+        if amount0 > 0.0 {
+            asks.push(level(to, amount0));
+        }
+        liquidity += net(tick);
+        from = to;
+        tick += pool.tick_spacing;
+    }
+
+    // and buys it as the price falls
+    let mut bids = Vec::new();
+    let (mut liquidity, mut from) = (state.liquidity, state.sqrt_price);
+    let mut tick = lower_tick;
+    while bids.len() < depth && tick >= state.low_tick {
+        let to = sqrt_price_at(tick);
+        let amount0 = liquidity * (1.0 / to - 1.0 / from);
+        if amount0 > 0.0 {
+            bids.push(level(to, amount0));
+        }
+        liquidity -= net(tick);
+        from = to;
+        tick -= pool.tick_spacing;
+    }
+
+    // Selling token0 is buying token1, at the inverse price
+    let (bids, asks) = if pool.invert {
+        let invert = |levels: Vec<(f64, f64)>| {
+            levels
+                .into_iter()
+                .map(|(price, amount)| (1.0 / price, amount * price))
+                .collect()
+        };
+        (invert(asks), invert(bids))
+    } else {
+        (bids, asks)
+    };
+    let to_levels = |levels: Vec<(f64, f64)>| {
+        levels
+            .into_iter()
+            .filter_map(|(price, amount)| {
+                Some(PriceAmountLevel {
+                    exchange: "uniswap_v3".to_string(),
+                    price: decimal_from_f64(price)?,
+                    amount: decimal_from_f64(amount)?,
+                })
+            })
+            .collect()
+    };
+    (to_levels(bids), to_levels(asks))
+}
+
+// The 32 bytes words of ABI encoded data
+fn words(data: &str) -> Vec<&str> {
+    let data = data.trim_start_matches("0x");
+    (0..data.len() / 64)
+        .map(|index| &data[index * 64..(index + 1) * 64])
+        .collect()
+}
+
+fn word_f64(word: &str) -> f64 {
+    word.chars()
+        .filter_map(|digit| digit.to_digit(16))
+        .fold(0.0, |value, digit| value * 16.0 + digit as f64)
+}
+
+// Signed values are sign extended to the word, their low bytes are enough
+fn word_i32(word: &str) -> i32 {
+    u32::from_str_radix(&word[56..], 16).unwrap_or_default() as i32
+}
+
+fn word_i128(word: &str) -> f64 {
+    u128::from_str_radix(&word[32..], 16).unwrap_or_default() as i128 as f64
+}
+
+fn encode_int(value: i64) -> String {
+    let fill = if value < 0 { "f" } else { "0" };
+    format!("{}{:016x}", fill.repeat(48), value as u64)
+}
+
+// ABI encoded strings, or the bytes32 of the older tokens like MKR
+fn decode_string(data: &str) -> String {
+    let data = words(data);
+    let bytes = match data.len() {
+        1 => data[0].to_string(),
+        _ => {
+            let len = word_f64(data.get(1).copied().unwrap_or_default()) as usize;
+            data[2..].concat().chars().take(len * 2).collect()
+        }
+    };
+    let bytes: Vec<u8> = (0..bytes.len() / 2)
+        .filter_map(|index| u8::from_str_radix(&bytes[index * 2..index * 2 + 2], 16).ok())
+        .filter(|byte| *byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+// The synthetic book of a Uniswap v3 pool on Ethereum, to compare the AMM's liquidity
+// with the order books of the venues. The pool's constants, price and the liquidity of
+// the ticks around it are read with eth_call when connecting, then its Swap, Mint and
+// Burn events, subscribed with eth_subscribe on the RPC's WebSocket, keep them current
+// and every event sends the book. The ticks are read for twice the depth around the
+// price, once the price moved by the depth the stream is resubscribed for a new read.
+// UNISWAP_V3_POOL is the pool's address (USDC/WETH 0.05% by default) and ETH_RPC_URL
+// the RPC (a public mainnet one by default, its WebSocket on the same host)
+pub struct UniswapV3Connector {
+    url: String,
+    rpc_url: String,
+    pool_address: String,
+    depth: usize,
+    pool: Mutex<Option<UniswapV3Pool>>,
+    state: Mutex<Option<PoolState>>,
+    out_of_sync: bool,
+}
+
+impl UniswapV3Connector {
+    pub fn new(rpc_url: &str, pool_address: &str, depth: usize) -> UniswapV3Connector {
+        UniswapV3Connector {
+            url: rpc_url.replacen("http", "ws", 1),
+            rpc_url: rpc_url.to_string(),
+            pool_address: pool_address.to_string(),
+            depth,
+            pool: Mutex::new(None),
+            state: Mutex::new(None),
+            out_of_sync: false,
+        }
+    }
+
+    pub fn from_env(depth: usize) -> UniswapV3Connector {
+        let rpc_url = env::var("ETH_RPC_URL")
+            .unwrap_or_else(|_| "https://ethereum-rpc.publicnode.com".to_string());
+        let pool = env::var("UNISWAP_V3_POOL").unwrap_or_else(|_| USDC_WETH_POOL.to_string());
+        UniswapV3Connector::new(&rpc_url, &pool, depth)
+    }
+
+    // A connector of an already read pool, reading the WebSocket at url
+    pub fn with_pool(url: &str, pool: UniswapV3Pool, state: PoolState) -> UniswapV3Connector {
+        UniswapV3Connector {
+            url: url.to_string(),
+            rpc_url: String::new(),
+            pool_address: String::new(),
+            depth: 0,
+            pool: Mutex::new(Some(pool)),
+            state: Mutex::new(Some(state)),
+            out_of_sync: false,
+        }
+    }
+
+    // The results of a batch of eth_call, each a contract and its call data
+    fn eth_calls(&self, calls: &[(&str, String)]) -> Result<Vec<String>, Box<dyn Error>> {
+        let request = calls
+            .iter()
+            .enumerate()
+            .map(|(id, (to, data))| {
+                format!(
+                    r#"{{"jsonrpc": "2.0", "id": {}, "method": "eth_call", "params": [{{"to": "{}", "data": "{}"}}, "latest"]}}"#,
+                    id, to, data
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let url = Url::parse(&self.rpc_url)?;
+        let (_, body) = http_request_with_body("POST", &url, &format!("[{}]", request))?;
+        let responses = serde_json::from_str::<Value>(&body)?;
+        let mut results: HashMap<u64, String> = responses
+            .as_array()
+            .ok_or_else(|| format!("Unexpected answer of the RPC: {}", body))?
+            .iter()
+            .filter_map(|response| {
+                Some((
+                    response["id"].as_u64()?,
+                    response["result"].as_str()?.to_string(),
+                ))
+            })
+            .collect();
+        (0..calls.len() as u64)
+            .map(|id| {
+                results
+                    .remove(&id)
+                    .ok_or_else(|| format!("eth_call failed: {}", body).into())
+            })
+            .collect()
+    }
+
+    fn resolve_pool(&self) -> Result<UniswapV3Pool, Box<dyn Error>> {
+        let pool = self.pool_address.as_str();
+        let results = self.eth_calls(&[
+            (pool, TOKEN0.to_string()),
+            (pool, TOKEN1.to_string()),
+            (pool, TICK_SPACING.to_string()),
+        ])?;
+        let token = |result: &str| format!("0x{}", &words(result)[0][24..]);
+        let (token0, token1) = (token(&results[0]), token(&results[1]));
+        let tokens = self.eth_calls(&[
+            (&token0, DECIMALS.to_string()),
+            (&token0, SYMBOL.to_string()),
+            (&token1, DECIMALS.to_string()),
+            (&token1, SYMBOL.to_string()),
+        ])?;
+        Ok(UniswapV3Pool {
+            token0: decode_string(&tokens[1]),
+            token1: decode_string(&tokens[3]),
+            decimals0: word_f64(words(&tokens[0])[0]) as u32,
+            decimals1: word_f64(words(&tokens[2])[0]) as u32,
+            tick_spacing: word_i32(words(&results[2])[0]),
+            invert: false,
+        })
+    }
+
+    fn read_state(&self, tick_spacing: i32) -> Result<PoolState, Box<dyn Error>> {
+        let pool = self.pool_address.as_str();
+        let results =
+            self.eth_calls(&[(pool, SLOT0.to_string()), (pool, LIQUIDITY.to_string())])?;
+        let slot0 = words(&results[0]);
+        let tick = word_i32(slot0[1]);
+        let lower_tick = tick.div_euclid(tick_spacing) * tick_spacing;
+        let window = 2 * self.depth.max(1) as i32 * tick_spacing;
+        let ticks: Vec<i32> = (lower_tick - window..=lower_tick + window)
+            .step_by(tick_spacing as usize)
+            .collect();
+        let calls: Vec<_> = ticks
+            .iter()
+            .map(|tick| (pool, format!("{}{}", TICKS, encode_int(*tick as i64))))
+            .collect();
+        let liquidity_net = ticks
+            .iter()
+            .zip(self.eth_calls(&calls)?)
+            .map(|(tick, result)| (*tick, word_i128(words(&result)[1])))
+            .filter(|(_, net)| *net != 0.0)
+            .collect();
+        Ok(PoolState {
+            sqrt_price: word_f64(slot0[0]) / 2f64.powi(96),
+            tick,
+            liquidity: word_f64(words(&results[1])[0]),
+            liquidity_net,
+            low_tick: lower_tick - window,
+            high_tick: lower_tick + window,
+        })
+    }
+
+    // A Mint adds liquidity to the ticks of the position, a Burn removes it
+    fn apply_position(&mut self, topics: &[Value], data: &[&str], sign: f64) -> Option<()> {
+        let (lower, upper) = (word_i32(topic(topics, 2)?), word_i32(topic(topics, 3)?));
+        // The Mint's data starts with its sender
+        let amount = sign * word_f64(data.get(if sign > 0.0 { 1 } else { 0 })?);
+        let state = self.state.get_mut().unwrap().as_mut()?;
+        *state.liquidity_net.entry(lower).or_default() += amount;
+        *state.liquidity_net.entry(upper).or_default() -= amount;
+        if lower <= state.tick && state.tick < upper {
+            state.liquidity += amount;
+        }
+        Some(())
+    }
+}
+
+fn topic(topics: &[Value], index: usize) -> Option<&str> {
+    Some(topics.get(index)?.as_str()?.trim_start_matches("0x"))
+}
+
+impl ExchangeConnector for UniswapV3Connector {
+    fn name(&self) -> &str {
+        "Uniswap v3"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // The pool is resolved before the first connection, and its ticks read on every one
+    fn connect_url(&self) -> Result<String, Box<dyn Error>> {
+        let mut pool = self.pool.lock().unwrap();
+        if pool.is_none() {
+            *pool = Some(self.resolve_pool()?);
+        }
+        let tick_spacing = pool.as_ref().map_or(1, |pool| pool.tick_spacing);
+        *self.state.lock().unwrap() = Some(self.read_state(tick_spacing)?);
+        Ok(self.url.clone())
+    }
+
+    fn rest_url(&self, _symbol: &str) -> Option<String> {
+        Some(self.rpc_url.clone()).filter(|rpc_url| !rpc_url.is_empty())
+    }
+
+    // The pool's events whatever the symbol, whose base tells which token is priced,
+    // e.g. ethusdc prices WETH in USDC
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        if let (Some(pool), Some((base, _))) =
+            (self.pool.lock().unwrap().as_mut(), split_symbol(symbol))
+        {
+            let token1 = pool.token1.to_uppercase();
+            pool.invert = token1 == base || token1 == format!("W{}", base);
+        }
+        vec![format!(
+            r#"{{"jsonrpc": "2.0", "id": 1, "method": "eth_subscribe", "params": ["logs", {{"address": "{}", "topics": [["{}", "{}", "{}"]]}}]}}"#,
+            self.pool_address, SWAP_TOPIC, MINT_TOPIC, BURN_TOPIC
+        )]
+    }
+
+    // The subscription ends with the socket, its id isn't kept
+    fn unsubscribe_messages(&self, _symbol: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    // {"jsonrpc": "2.0", "id": 1, "result": "0xcd0c3e8af590364c09d0fa6a1210faf5"}
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["id"] == 1 && ack["result"].is_string()
+    }
+
+    // {"jsonrpc": "2.0", "id": 1, "error": {"code": -32602, "message": "invalid argument"}}
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let error = result.get("error")?;
+        Some(ExchangeError {
+            code: error_code(&error["code"]),
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+            action: ErrorAction::Disable,
+        })
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["method"] != "eth_subscription" {
+            return None;
+        }
+        let log = &result["params"]["result"];
+        // A log of a block dropped by a reorg, the liquidity has to be read again
+        if log["removed"] == true {
+            self.out_of_sync = true;
+            return None;
+        }
+        let topics = log["topics"].as_array()?;
+        let data = words(log["data"].as_str()?);
+        match topics.first()?.as_str()? {
+            // The data of a Swap ends with the new price, liquidity and tick
+            SWAP_TOPIC => {
+                let state = self.state.get_mut().unwrap().as_mut()?;
+                state.sqrt_price = word_f64(data.get(2)?) / 2f64.powi(96);
+                state.liquidity = word_f64(data.get(3)?);
+                state.tick = word_i32(data.get(4)?);
+            }
+            MINT_TOPIC => self.apply_position(topics, &data, 1.0)?,
+            BURN_TOPIC => self.apply_position(topics, &data, -1.0)?,
+            _ => return None,
+        }
+
+        let pool = self.pool.get_mut().unwrap().clone()?;
+        let state = self.state.get_mut().unwrap().as_ref()?;
+        // The ticks read cover the depth from the price until it moved by the depth
+        let margin = depth as i32 * pool.tick_spacing;
+        if state.tick < state.low_tick + margin || state.tick > state.high_tick - margin {
+            self.out_of_sync = true;
+            return None;
+        }
+        let (bids, asks) = synthetic_ladder(&pool, state, depth);
+        let spread = match (bids.first(), asks.first()) {
+            (Some(bid), Some(ask)) => decimal_to_f64(bid.price - ask.price),
+            _ => 0.0,
+        };
+        Some(OrderBook {
+            bids,
+            asks,
+            spread,
+            venues: Vec::new(),
+        })
+    }
+
+    fn needs_resync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    fn reset(&mut self) {
+        *self.state.get_mut().unwrap() = None;
+        self.out_of_sync = false;
+    }
+}
+
+crate::register_connector!("uniswap_v3", false, |depth| Box::new(
+    UniswapV3Connector::from_env(depth as usize)
+));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A pool without decimals at the price of 1, with a position of 1e6 from tick -20 to 10
+    fn pool_state() -> (UniswapV3Pool, PoolState) {
+        let pool = UniswapV3Pool {
+            token0: "USDC".to_string(),
+            token1: "WETH".to_string(),
+            decimals0: 0,
+            decimals1: 0,
+            tick_spacing: 10,
+            invert: false,
+        };
+        let state = PoolState {
+            sqrt_price: 1.0,
+            tick: 0,
+            liquidity: 1e6,
+            liquidity_net: BTreeMap::from([(-20, 1e6), (10, -1e6)]),
+            low_tick: -100,
+            high_tick: 100,
+        };
+        (pool, state)
+    }
+
+    // (price, amount) of the levels
+    fn doubles(levels: &[PriceAmountLevel]) -> Vec<(f64, f64)> {
+        levels
+            .iter()
+            .map(|level| (decimal_to_f64(level.price), decimal_to_f64(level.amount)))
+            .collect()
+    }
+
+    fn word(value: f64) -> String {
+        format!("{:064x}", value as u128)
+    }
+
+    fn log(topics: &[String], data: &[String]) -> String {
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"eth_subscription","params":{{"subscription":"0xcd0c3e8af590364c09d0fa6a1210faf5","result":{{"address":"0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640","topics":[{}],"data":"0x{}","blockNumber":"0x1234","removed":false}}}}}}"#,
+            topics
+                .iter()
+                .map(|topic| format!(r#""{}""#, topic))
+                .collect::<Vec<_>>()
+                .join(","),
+            data.concat()
+        )
+    }
+
+    #[test]
+    fn test_synthetic_ladder() {
+        let (mut pool, state) = pool_state();
+        let (bids, asks) = synthetic_ladder(&pool, &state, 10);
+        let (bids, asks) = (doubles(&bids), doubles(&asks));
+        // The position ends at tick 10 above and -20 below the price
+        assert_eq!(asks.len(), 1);
+        assert_eq!(bids.len(), 2);
+        assert!((asks[0].0 - 1.0001f64.powi(10)).abs() < 1e-12);
+        assert!((asks[0].1 - 1e6 * (1.0 - 1.0 / sqrt_price_at(10))).abs() < 1e-6);
+        assert!((bids[0].0 - 1.0001f64.powi(-10)).abs() < 1e-12);
+        assert!((bids[0].1 - 1e6 * (sqrt_price_at(10) - 1.0)).abs() < 1e-6);
+        assert!(bids[1].0 < bids[0].0);
+
+        // Priced in token1 the sides swap
+        pool.invert = true;
+        let (inverted_bids, inverted_asks) = synthetic_ladder(&pool, &state, 10);
+        let (inverted_bids, inverted_asks) = (doubles(&inverted_bids), doubles(&inverted_asks));
+        assert_eq!(inverted_bids.len(), 1);
+        assert!((inverted_bids[0].0 - 1.0 / asks[0].0).abs() < 1e-12);
+        assert!((inverted_asks[1].1 - bids[1].1 * bids[1].0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_uniswap_v3_events() {
+        let (pool, state) = pool_state();
+        let mut connector = UniswapV3Connector::with_pool("ws://localhost", pool, state);
+        assert!(connector.is_subscribe_ack(r#"{"jsonrpc":"2.0","id":1,"result":"0xcd0c3e8a"}"#));
+        assert!(connector.subscribe_messages("ethusdc")[0].contains(SWAP_TOPIC));
+        assert!(connector.pool.lock().unwrap().as_ref().unwrap().invert);
+        connector.pool.get_mut().unwrap().as_mut().unwrap().invert = false;
+
+        // A position from 10 to 30 adds asks above the first one
+        let tick = |tick: i64| format!("0x{}", encode_int(tick));
+        let mint = log(
+            &[MINT_TOPIC.to_string(), tick(0), tick(10), tick(30)],
+            &[word(0.0), word(5e5), word(1.0), word(1.0)],
+        );
+        let orderbook = connector.apply_message(&mint, 3).unwrap();
+        assert_eq!(orderbook.asks.len(), 3);
+        assert_eq!(orderbook.bids.len(), 2);
+
+        // A Swap to tick -5 moves the price, in the range of the first position
+        let sqrt_price = sqrt_price_at(-5);
+        let swap = log(
+            &[SWAP_TOPIC.to_string(), tick(0), tick(0)],
+            &[
+                word(0.0),
+                word(0.0),
+                word(sqrt_price * 2f64.powi(96)),
+                word(1e6),
+                encode_int(-5),
+            ],
+        );
+        let orderbook = connector.apply_message(&swap, 3).unwrap();
+        assert!((doubles(&orderbook.asks)[0].0 - 1.0).abs() < 1e-12);
+        assert!((doubles(&orderbook.bids)[0].0 - 1.0001f64.powi(-10)).abs() < 1e-12);
+
+        // Past the ticks read, the stream is resubscribed
+        let far_swap = log(
+            &[SWAP_TOPIC.to_string(), tick(0), tick(0)],
+            &[
+                word(0.0),
+                word(0.0),
+                word(2f64.powi(96)),
+                word(1e6),
+                encode_int(95),
+            ],
+        );
+        assert!(connector.apply_message(&far_swap, 3).is_none());
+        assert!(connector.needs_resync());
+    }
+}
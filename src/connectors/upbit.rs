@@ -0,0 +1,218 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::connectors::{ErrorAction, ExchangeConnector, ExchangeError};
+use crate::fx::{convert_quote, FxRateSource};
+use crate::number::{decimal_to_f64, json_decimal, json_f64};
+use crate::symbols::split_symbol;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::sync::Arc;
+
+// Market whose mid is the KRW rate when no other rate source is configured
+const RATE_MARKET: &str = "KRW-USDT";
+
+// Upbit's orderbook type pushes the top levels of a KRW market on every change, as
+// units pairing the nth bid with the nth ask. Upbit quotes in KRW, so the prices are
+// converted with the rate of the KRW source before they are merged, by default the
+// mid of Upbit's own KRW-USDT market read on the same socket. Upbit sends no
+// subscription ack and its JSON in binary frames
+pub struct UpbitConnector {
+    url: String,
+    fx_rate_source: Option<Arc<dyn FxRateSource>>,
+    // KRW per USDT, from the books of RATE_MARKET
+    market_rate: Option<f64>,
+}
+
+impl UpbitConnector {
+    pub fn new() -> UpbitConnector {
+        UpbitConnector::with_url("wss://api.upbit.com/websocket/v1")
+    }
+
+    pub fn with_url(url: &str) -> UpbitConnector {
+        UpbitConnector {
+            url: url.to_string(),
+            fx_rate_source: None,
+            market_rate: None,
+        }
+    }
+
+    fn rate(&self) -> Option<f64> {
+        match &self.fx_rate_source {
+            Some(source) => source.rate(),
+            None => self.market_rate,
+        }
+    }
+}
+
+impl Default for UpbitConnector {
+    fn default() -> Self {
+        UpbitConnector::new()
+    }
+}
+
+// Each unit holds the nth bid and the nth ask, a side can run out before the other
+fn parse_upbit_units(units: &[Value], side: &str) -> Vec<PriceAmountLevel> {
+    units
+        .iter()
+        .filter_map(|unit| {
+            Some(PriceAmountLevel {
+                exchange: "upbit".to_string(),
+                price: json_decimal(&unit[format!("{}_price", side)])?,
+                amount: json_decimal(&unit[format!("{}_size", side)])?,
+            })
+        })
+        .filter(|level| level.amount > Decimal::ZERO)
+        .collect()
+}
+
+impl ExchangeConnector for UpbitConnector {
+    fn name(&self) -> &str {
+        "Upbit"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    // KRW-BTC, the KRW market of the base asset whatever the served quote
+    fn normalize_symbol(&self, symbol: &str) -> String {
+        match split_symbol(symbol) {
+            Some((base, _)) => format!("KRW-{}", base),
+            None => symbol.to_uppercase(),
+        }
+    }
+
+    fn rest_url(&self, symbol: &str) -> Option<String> {
+        Some(format!(
+            "https://api.upbit.com/v1/orderbook?markets={}",
+            symbol
+        ))
+    }
+
+    fn subscribe_messages(&self, symbol: &str) -> Vec<String> {
+        let mut codes = vec![symbol];
+        if self.fx_rate_source.is_none() && symbol != RATE_MARKET {
+            codes.push(RATE_MARKET);
+        }
+        vec![format!(
+            r#"[{{"ticket": "orderbook-aggregator"}}, {{"type": "orderbook", "codes": ["{}"]}}]"#,
+            codes.join(r#"", ""#)
+        )]
+    }
+
+    // Upbit has no unsubscription, the stream ends with the socket
+    fn unsubscribe_messages(&self, _symbol: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    // The first book confirms the subscription
+    fn is_subscribe_ack(&self, message_text: &str) -> bool {
+        let ack = serde_json::from_str::<Value>(message_text).unwrap_or_default();
+        ack["type"] == "orderbook"
+    }
+
+    // {"error": {"name": "INVALID_PARAM", "message": "..."}}, e.g. an unknown market
+    fn parse_error(&self, message_text: &str) -> Option<ExchangeError> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        let error = result.get("error")?;
+        Some(ExchangeError {
+            code: error["name"].as_str().map(str::to_string),
+            message: error["message"]
+                .as_str()
+                .or(error.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            action: ErrorAction::Disable,
+        })
+    }
+
+    // The timestamp of the book, in ms
+    fn event_time(&self, message_text: &str) -> Option<u64> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        result["timestamp"].as_u64()
+    }
+
+    fn apply_message(&mut self, message_text: &str, depth: usize) -> Option<OrderBook> {
+        let result = serde_json::from_str::<Value>(message_text).ok()?;
+        if result["type"] != "orderbook" {
+            return None;
+        }
+        let units = result["orderbook_units"].as_array()?;
+        if result["code"] == RATE_MARKET && self.fx_rate_source.is_none() {
+            let unit = units.first()?;
+            self.market_rate =
+                Some((json_f64(&unit["bid_price"])? + json_f64(&unit["ask_price"])?) / 2.0);
+            return None;
+        }
+        // The KRW book can't be merged before a rate is known
+        let rate = self.rate()?;
+
+        let mut orderbook = OrderBook::new();
+        orderbook.bids = parse_upbit_units(units, "bid");
+        orderbook.asks = parse_upbit_units(units, "ask");
+        orderbook.bids.truncate(depth);
+        orderbook.asks.truncate(depth);
+        if let (Some(best_bid), Some(best_ask)) = (orderbook.bids.first(), orderbook.asks.first()) {
+            orderbook.spread = decimal_to_f64(best_bid.price - best_ask.price);
+        }
+        Some(convert_quote(&orderbook, rate))
+    }
+
+    fn set_fx_rate_source(&mut self, currency: &str, source: Arc<dyn FxRateSource>) {
+        if currency == "KRW" {
+            self.fx_rate_source = Some(source);
+        }
+    }
+}
+
+crate::register_connector!("upbit", false, |_| Box::new(UpbitConnector::new()));
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::{run_conformance, ConformanceFixture};
+    use crate::fx::FixedRate;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_upbit_market_rate() {
+        let mut connector = UpbitConnector::new();
+        assert_eq!(
+            connector.subscribe_messages("KRW-BTC"),
+            vec![
+                r#"[{"ticket": "orderbook-aggregator"}, {"type": "orderbook", "codes": ["KRW-BTC", "KRW-USDT"]}]"#
+            ]
+        );
+        let btc = r#"{"type":"orderbook","code":"KRW-BTC","timestamp":1746601573804,"orderbook_units":[{"ask_price":140140000,"bid_price":140000000,"ask_size":0.2,"bid_size":0.5}]}"#;
+        assert!(connector.apply_message(btc, 10).is_none());
+
+        let usdt = r#"{"type":"orderbook","code":"KRW-USDT","timestamp":1746601573805,"orderbook_units":[{"ask_price":1401,"bid_price":1399,"ask_size":100.0,"bid_size":200.0}]}"#;
+        assert!(connector.apply_message(usdt, 10).is_none());
+        let orderbook = connector.apply_message(btc, 10).unwrap();
+        assert_eq!(orderbook.bids[0].price, dec!(100_000.0));
+        assert_eq!(orderbook.asks[0].price, dec!(100_100.0));
+    }
+
+    #[test]
+    fn test_upbit_conformance() {
+        run_conformance(
+            |url| {
+                let mut connector = UpbitConnector::with_url(url);
+                connector.set_fx_rate_source("KRW", Arc::new(FixedRate(1_000.0)));
+                Box::new(connector)
+            },
+            ConformanceFixture {
+                symbol: "btcusdt",
+                ack: r#"{"type":"orderbook","code":"KRW-BTC","timestamp":1746601573804,"orderbook_units":[{"ask_price":11000,"bid_price":10000,"ask_size":0.8,"bid_size":1.0}]}"#.to_string(),
+                snapshot: r#"{"type":"orderbook","code":"KRW-BTC","timestamp":1746601573904,"total_ask_size":1.5,"total_bid_size":1.0,"orderbook_units":[{"ask_price":11000,"bid_price":10000,"ask_size":0.8,"bid_size":1.0},{"ask_price":11500,"bid_price":9900,"ask_size":0.7,"bid_size":0}],"stream_type":"REALTIME","level":0}"#.to_string(),
+                expected_snapshot: (vec![(10.0, 1.0)], vec![(11.0, 0.8), (11.5, 0.7)]),
+                // A later snapshot replaces the book
+                updates: vec![(
+                    r#"{"type":"orderbook","code":"KRW-BTC","timestamp":1746601574004,"total_ask_size":0.1,"total_bid_size":3.5,"orderbook_units":[{"ask_price":10900,"bid_price":10100,"ask_size":0.1,"bid_size":0.5},{"ask_price":11000,"bid_price":9800,"ask_size":0,"bid_size":3.0}],"stream_type":"REALTIME","level":0}"#.to_string(),
+                    (vec![(10.1, 0.5), (9.8, 3.0)], vec![(10.9, 0.1)]),
+                )],
+                gap: None,
+            },
+        );
+    }
+}
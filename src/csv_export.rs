@@ -0,0 +1,278 @@
+use crate::aggregator::OrderbookAggregatorService;
+use crate::orderbook_proto::orderbook_aggregator_server::OrderbookAggregator;
+use crate::orderbook_proto::{Level, PriceEncoding, Summary, SummaryRequest};
+use crate::tenant::API_KEY_HEADER;
+use futures::StreamExt;
+use std::error::Error;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Request};
+
+pub(crate) const CSV_HEADER: &str = "timestamp,sequence,side,level,exchange,price,amount\n";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CsvRequest {
+    // GET /book/stream instead of the snapshot of GET /book
+    pub stream: bool,
+    pub api_key: Option<String>,
+    // align_interval_ms of the subscription, 0 for every update
+    pub interval_ms: u32,
+}
+
+// GET /book?format=csv for a snapshot or GET /book/stream for every update, with the
+// API key as a parameter since spreadsheets can't set headers, e.g.
+// /book/stream?api_key=<key>&interval_ms=1000
+pub(crate) fn parse_csv_request(request_line: &str) -> Result<CsvRequest, String> {
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let stream = match path {
+        "/book" | "/book.csv" => false,
+        "/book/stream" => true,
+        _ => {
+            return Err(format!(
+                "Unknown path {}, use /book?format=csv or /book/stream",
+                path
+            ))
+        }
+    };
+    let mut request = CsvRequest {
+        stream,
+        api_key: None,
+        interval_ms: 0,
+    };
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "format" if value == "csv" => {}
+            "format" => return Err(format!("Unsupported format {}, only csv is", value)),
+            "api_key" => request.api_key = Some(value.to_string()),
+            "interval_ms" => {
+                request.interval_ms = value
+                    .parse()
+                    .map_err(|_| format!("Invalid interval_ms {}", value))?
+            }
+            _ => return Err(format!("Unknown parameter {}", key)),
+        }
+    }
+    Ok(request)
+}
+
+// One row per level of the summary, bids then asks, best first. The prices and amounts
+// are the exact decimals of the venues when the summary has them
+pub(crate) fn csv_rows(summary: &Summary) -> String {
+    let mut rows = String::new();
+    for (side, levels) in [("bid", &summary.bids), ("ask", &summary.asks)] {
+        for (index, level) in levels.iter().enumerate() {
+            let (price, amount) = level_values(level);
+            let _ = writeln!(
+                rows,
+                "{},{},{},{},{},{},{}",
+                summary.timestamp,
+                summary.sequence,
+                side,
+                index + 1,
+                level.exchange,
+                price,
+                amount
+            );
+        }
+    }
+    rows
+}
+
+fn level_values(level: &Level) -> (String, String) {
+    if level.price_decimal.is_empty() {
+        (level.price.to_string(), level.amount.to_string())
+    } else {
+        (level.price_decimal.clone(), level.amount_decimal.clone())
+    }
+}
+
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+fn http_chunk(data: &str) -> String {
+    format!("{:x}\r\n{}\r\n", data.len(), data)
+}
+
+fn error_status(code: Code) -> &'static str {
+    match code {
+        Code::Unauthenticated => "401 Unauthorized",
+        Code::PermissionDenied => "403 Forbidden",
+        Code::InvalidArgument => "400 Bad Request",
+        _ => "503 Service Unavailable",
+    }
+}
+
+// Serves the merged book of the server's symbol as CSV over plain HTTP, for Excel and
+// Sheets web queries. Every request is a BookSummary subscription of its own, with the
+// tenant limits of its API key, and a stream ends when its client goes away
+pub(crate) async fn serve_csv(
+    addr: SocketAddr,
+    service: OrderbookAggregatorService,
+) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(addr).await?;
+    loop {
+        // A failed accept, e.g. too many open files, only loses that connection
+        let (mut stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(err) => {
+                eprintln!("Failed to accept a CSV connection on {}: {}", addr, err);
+                continue;
+            }
+        };
+        let service = service.clone();
+        tokio::spawn(async move {
+            let mut request = [0; 1024];
+            let read = stream.read(&mut request).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..read]);
+            let request_line = request.lines().next().unwrap_or_default();
+            let csv_request = match parse_csv_request(request_line) {
+                Ok(csv_request) => csv_request,
+                Err(err) => {
+                    let response = http_response("400 Bad Request", "text/plain", &err);
+                    let _ = stream.write_all(response.as_bytes()).await;
+                    return;
+                }
+            };
+
+            let mut summary_request = Request::new(SummaryRequest {
+                align_interval_ms: csv_request.interval_ms,
+                price_encoding: PriceEncoding::String.into(),
+                ..SummaryRequest::default()
+            });
+            if let Some(api_key) = csv_request
+                .api_key
+                .as_deref()
+                .and_then(|api_key| MetadataValue::try_from(api_key).ok())
+            {
+                summary_request
+                    .metadata_mut()
+                    .insert(API_KEY_HEADER, api_key);
+            }
+            let mut summaries = match service.book_summary(summary_request).await {
+                Ok(response) => response.into_inner(),
+                Err(status) => {
+                    let response =
+                        http_response(error_status(status.code()), "text/plain", status.message());
+                    let _ = stream.write_all(response.as_bytes()).await;
+                    return;
+                }
+            };
+
+            if !csv_request.stream {
+                let response = match summaries.next().await {
+                    Some(Ok(summary)) => http_response(
+                        "200 OK",
+                        "text/csv",
+                        &format!("{}{}", CSV_HEADER, csv_rows(&summary)),
+                    ),
+                    _ => {
+                        http_response("503 Service Unavailable", "text/plain", "No book available")
+                    }
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+                return;
+            }
+
+            let head = "HTTP/1.1 200 OK\r\nContent-Type: text/csv\r\nCache-Control: no-cache\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n";
+            if stream.write_all(head.as_bytes()).await.is_err()
+                || stream
+                    .write_all(http_chunk(CSV_HEADER).as_bytes())
+                    .await
+                    .is_err()
+            {
+                return;
+            }
+            // Dropping the summaries when the client is gone ends the subscription
+            while let Some(Ok(summary)) = summaries.next().await {
+                let rows = csv_rows(&summary);
+                if rows.is_empty() {
+                    continue;
+                }
+                if stream
+                    .write_all(http_chunk(&rows).as_bytes())
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+            let _ = stream.write_all(b"0\r\n\r\n").await;
+        });
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_request() {
+        assert_eq!(
+            parse_csv_request("GET /book?format=csv HTTP/1.1"),
+            Ok(CsvRequest {
+                stream: false,
+                api_key: None,
+                interval_ms: 0,
+            })
+        );
+        assert_eq!(
+            parse_csv_request("GET /book/stream?api_key=secret&interval_ms=1000 HTTP/1.1"),
+            Ok(CsvRequest {
+                stream: true,
+                api_key: Some("secret".to_string()),
+                interval_ms: 1000,
+            })
+        );
+        assert_eq!(
+            parse_csv_request("GET /book?api_key=a%2Bb%3D%3D HTTP/1.1")
+                .unwrap()
+                .api_key,
+            Some("a+b==".to_string())
+        );
+        assert!(parse_csv_request("GET /book?format=json HTTP/1.1").is_err());
+        assert!(parse_csv_request("GET /metrics HTTP/1.1").is_err());
+    }
+
+    #[test]
+    fn test_csv_rows() {
+        let level = |exchange: &str, price: &str, amount: &str| Level {
+            exchange: exchange.to_string(),
+            price_decimal: price.to_string(),
+            amount_decimal: amount.to_string(),
+            ..Level::default()
+        };
+        let summary = Summary {
+            timestamp: 1700000000000,
+            sequence: 42,
+            bids: vec![
+                level("binance", "100.10", "1.5"),
+                level("kraken", "100.0", "2"),
+            ],
+            asks: vec![Level {
+                exchange: "okx".to_string(),
+                price: 100.5,
+                amount: 0.25,
+                ..Level::default()
+            }],
+            ..Summary::default()
+        };
+        assert_eq!(
+            csv_rows(&summary),
+            "1700000000000,42,bid,1,binance,100.10,1.5\n\
+             1700000000000,42,bid,2,kraken,100.0,2\n\
+             1700000000000,42,ask,1,okx,100.5,0.25\n"
+        );
+    }
+}
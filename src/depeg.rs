@@ -0,0 +1,129 @@
+// Quote assets that are merged as equivalent while the stablecoin holds its peg
+pub fn quote_asset(symbol: &str) -> Option<&'static str> {
+    let symbol = symbol.to_lowercase();
+    if symbol.ends_with("usdt") {
+        Some("usdt")
+    } else if symbol.ends_with("usd") {
+        Some("usd")
+    } else {
+        None
+    }
+}
+
+// Books quoted in USD and USDT only need the guard when the quotes actually differ
+pub fn needs_depeg_guard(symbol: &str, other_symbol: &str) -> bool {
+    matches!(
+        (quote_asset(symbol), quote_asset(other_symbol)),
+        (Some("usd"), Some("usdt")) | (Some("usdt"), Some("usd"))
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DepegEvent {
+    Depegged { rate: f64, deviation_bps: f64 },
+    Repegged { rate: f64, deviation_bps: f64 },
+}
+
+// Tracks the USDT/USD rate and decides whether USD and USDT books can be merged.
+// Until the first rate is received the quotes are not treated as equivalent
+#[derive(Debug, Clone)]
+pub struct DepegGuard {
+    pub threshold_bps: f64,
+    pub rate: Option<f64>,
+    equivalent: bool,
+}
+
+impl DepegGuard {
+    pub fn new(threshold_bps: f64) -> DepegGuard {
+        DepegGuard {
+            threshold_bps,
+            rate: None,
+            equivalent: false,
+        }
+    }
+
+    pub fn is_equivalent(&self) -> bool {
+        self.equivalent
+    }
+
+    // Returns an event only when the equivalence changes
+    pub fn update_rate(&mut self, rate: f64) -> Option<DepegEvent> {
+        let deviation_bps = (rate - 1.0).abs() * 10_000.0;
+        let was_equivalent = self.equivalent;
+        let first_rate = self.rate.is_none();
+
+        self.rate = Some(rate);
+        self.equivalent = deviation_bps <= self.threshold_bps;
+
+        match (was_equivalent, self.equivalent) {
+            (true, false) => Some(DepegEvent::Depegged {
+                rate,
+                deviation_bps,
+            }),
+            (false, false) if first_rate => Some(DepegEvent::Depegged {
+                rate,
+                deviation_bps,
+            }),
+            (false, true) => Some(DepegEvent::Repegged {
+                rate,
+                deviation_bps,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_asset() {
+        assert_eq!(quote_asset("btcusdt"), Some("usdt"));
+        assert_eq!(quote_asset("BTCUSD"), Some("usd"));
+        assert_eq!(quote_asset("btceur"), None);
+
+        assert!(needs_depeg_guard("btcusdt", "btcusd"));
+        assert!(!needs_depeg_guard("btcusdt", "btcusdt"));
+        assert!(!needs_depeg_guard("btcusdt", "btceur"));
+    }
+
+    #[test]
+    fn test_update_rate() {
+        let mut guard = DepegGuard::new(50.0);
+        assert!(!guard.is_equivalent());
+
+        assert!(matches!(
+            guard.update_rate(1.0002),
+            Some(DepegEvent::Repegged { .. })
+        ));
+        assert!(guard.is_equivalent());
+
+        // Small moves within the threshold don't change anything
+        assert_eq!(guard.update_rate(0.9996), None);
+
+        assert!(matches!(
+            guard.update_rate(0.99),
+            Some(DepegEvent::Depegged { .. })
+        ));
+        assert!(!guard.is_equivalent());
+        assert_eq!(guard.update_rate(0.98), None);
+
+        assert!(matches!(
+            guard.update_rate(1.0),
+            Some(DepegEvent::Repegged { .. })
+        ));
+    }
+
+    #[test]
+    fn test_update_rate_starts_depegged() {
+        let mut guard = DepegGuard::new(50.0);
+
+        assert!(matches!(
+            guard.update_rate(0.97),
+            Some(DepegEvent::Depegged { .. })
+        ));
+        assert!(!guard.is_equivalent());
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviationEvent {
+    Deviating {
+        exchange: String,
+        deviation_bps: f64,
+    },
+    Recovered {
+        exchange: String,
+        deviation_bps: f64,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+struct VenueState {
+    deviating_since: Option<Instant>,
+    alarmed: bool,
+}
+
+// Compares the mid of every venue with the consolidated fair value. A venue has to
+// deviate by more than threshold_bps for the whole sustain period before the alarm
+// goes off, so a single bad tick doesn't alarm. With quarantine set, alarmed venues
+// are kept out of the merged book until they recover
+#[derive(Debug, Clone)]
+pub struct DeviationMonitor {
+    pub threshold_bps: f64,
+    pub sustain: Duration,
+    pub quarantine: bool,
+    venues: HashMap<String, VenueState>,
+}
+
+impl DeviationMonitor {
+    pub fn new(threshold_bps: f64, sustain: Duration, quarantine: bool) -> DeviationMonitor {
+        DeviationMonitor {
+            threshold_bps,
+            sustain,
+            quarantine,
+            venues: HashMap::new(),
+        }
+    }
+
+    pub fn is_quarantined(&self, exchange: &str) -> bool {
+        self.quarantine && self.venues.get(exchange).is_some_and(|venue| venue.alarmed)
+    }
+
+    // Returns an event only when the alarm of the venue goes off or clears
+    pub fn update(
+        &mut self,
+        exchange: &str,
+        mid: f64,
+        fair_value: f64,
+        now: Instant,
+    ) -> Option<DeviationEvent> {
+        if fair_value <= 0.0 {
+            return None;
+        }
+        let deviation_bps = (mid - fair_value).abs() / fair_value * 10_000.0;
+        let venue = self.venues.entry(exchange.to_string()).or_default();
+
+        if deviation_bps <= self.threshold_bps {
+            venue.deviating_since = None;
+            if venue.alarmed {
+                venue.alarmed = false;
+                return Some(DeviationEvent::Recovered {
+                    exchange: exchange.to_string(),
+                    deviation_bps,
+                });
+            }
+            return None;
+        }
+
+        let deviating_since = *venue.deviating_since.get_or_insert(now);
+        if !venue.alarmed && now.duration_since(deviating_since) >= self.sustain {
+            venue.alarmed = true;
+            return Some(DeviationEvent::Deviating {
+                exchange: exchange.to_string(),
+                deviation_bps,
+            });
+        }
+        None
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deviation_monitor() {
+        let start = Instant::now();
+        let mut monitor = DeviationMonitor::new(50.0, Duration::from_secs(5), true);
+
+        // Within the threshold
+        assert_eq!(monitor.update("bitstamp", 100.2, 100.0, start), None);
+
+        // 100 bps away, but not for the whole sustain period yet
+        assert_eq!(monitor.update("bitstamp", 101.0, 100.0, start), None);
+        assert_eq!(
+            monitor.update("bitstamp", 101.0, 100.0, start + Duration::from_secs(4)),
+            None
+        );
+        assert!(!monitor.is_quarantined("bitstamp"));
+
+        match monitor.update("bitstamp", 101.0, 100.0, start + Duration::from_secs(5)) {
+            Some(DeviationEvent::Deviating {
+                exchange,
+                deviation_bps,
+            }) => {
+                assert_eq!(exchange, "bitstamp");
+                assert!((deviation_bps - 100.0).abs() < 1e-6);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(monitor.is_quarantined("bitstamp"));
+        assert!(!monitor.is_quarantined("binance"));
+        assert_eq!(
+            monitor.update("bitstamp", 101.0, 100.0, start + Duration::from_secs(6)),
+            None
+        );
+
+        assert!(matches!(
+            monitor.update("bitstamp", 100.1, 100.0, start + Duration::from_secs(7)),
+            Some(DeviationEvent::Recovered { .. })
+        ));
+        assert!(!monitor.is_quarantined("bitstamp"));
+    }
+
+    #[test]
+    fn test_deviation_monitor_interrupted() {
+        let start = Instant::now();
+        let mut monitor = DeviationMonitor::new(50.0, Duration::from_secs(5), false);
+
+        assert_eq!(monitor.update("bitstamp", 101.0, 100.0, start), None);
+        // Back within the threshold, the sustain period starts over
+        assert_eq!(
+            monitor.update("bitstamp", 100.0, 100.0, start + Duration::from_secs(3)),
+            None
+        );
+        assert_eq!(
+            monitor.update("bitstamp", 101.0, 100.0, start + Duration::from_secs(6)),
+            None
+        );
+        assert!(monitor
+            .update("bitstamp", 101.0, 100.0, start + Duration::from_secs(11))
+            .is_some());
+        // Alarmed, but without quarantine the venue keeps being merged
+        assert!(!monitor.is_quarantined("bitstamp"));
+    }
+}
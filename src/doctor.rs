@@ -0,0 +1,265 @@
+use crate::aggregator::configured_connector;
+use crate::compression::message_text;
+use crate::config::check_symbol_settings;
+use crate::config::ServerOptions;
+use crate::connectors::{apply_connector_message, connect_connector, ExchangeConnector};
+use crate::symbols::check_venue_symbol;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+use tungstenite::stream::Stream;
+use url::{Position, Url};
+
+// Longest wait for a connection, a response or the first book of a venue
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn new(name: String, result: Result<String, String>) -> CheckResult {
+        let (passed, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        CheckResult {
+            name,
+            passed,
+            detail,
+        }
+    }
+}
+
+// Subscribes to the symbol and waits for its first book, an unknown symbol is
+// acknowledged by some exchanges but never gets a book
+fn check_websocket(
+    mut connector: Box<dyn ExchangeConnector>,
+    symbol: &str,
+    depth: usize,
+) -> Result<String, String> {
+    let started = Instant::now();
+    let mut socket =
+        connect_connector(connector.as_ref(), symbol).map_err(|err| err.to_string())?;
+    let subscribed = started.elapsed();
+
+    let stream = match socket.get_mut() {
+        Stream::Plain(stream) => stream,
+        Stream::Tls(stream) => stream.get_mut(),
+    };
+    stream
+        .set_read_timeout(Some(CHECK_TIMEOUT))
+        .map_err(|err| err.to_string())?;
+    while started.elapsed() < CHECK_TIMEOUT {
+        let message = socket.read_message().map_err(|err| err.to_string())?;
+        if apply_connector_message(connector.as_mut(), &message_text(&message), depth).is_some() {
+            return Ok(format!(
+                "subscribed in {} ms, first book of {} after {} ms",
+                subscribed.as_millis(),
+                symbol,
+                started.elapsed().as_millis()
+            ));
+        }
+    }
+    Err(format!(
+        "subscribed in {} ms but no book of {} within {} s, is the symbol listed?",
+        subscribed.as_millis(),
+        symbol,
+        CHECK_TIMEOUT.as_secs()
+    ))
+}
+
+fn read_status(mut stream: impl Read + Write, url: &Url) -> Result<u16, Box<dyn Error>> {
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: orderbook-doctor\r\nConnection: close\r\n\r\n",
+        &url[Position::BeforePath..],
+        url.host_str().unwrap_or_default()
+    )?;
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    // e.g. HTTP/1.1 200 OK
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| format!("Unexpected response: {}", status_line.trim()))?;
+    Ok(status)
+}
+
+fn http_get_status(url: &Url) -> Result<u16, Box<dyn Error>> {
+    let host = url.host_str().ok_or("No host name in the url")?;
+    let addr = url
+        .socket_addrs(|| None)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("{} resolved to no address", host))?;
+    let stream = TcpStream::connect_timeout(&addr, CHECK_TIMEOUT)?;
+    stream.set_read_timeout(Some(CHECK_TIMEOUT))?;
+    if url.scheme() == "https" {
+        let connector = native_tls::TlsConnector::new()?;
+        let stream = connector
+            .connect(host, stream)
+            .map_err(|err| err.to_string())?;
+        read_status(stream, url)
+    } else {
+        read_status(stream, url)
+    }
+}
+
+// Exchanges answer a request for an unknown symbol with a client error
+fn check_rest(url: &str) -> Result<String, String> {
+    let url = Url::parse(url).map_err(|err| err.to_string())?;
+    let started = Instant::now();
+    let status = http_get_status(&url).map_err(|err| err.to_string())?;
+    let round_trip = started.elapsed().as_millis();
+    match status {
+        200..=299 => Ok(format!("HTTP {} in {} ms", status, round_trip)),
+        400..=499 => Err(format!(
+            "HTTP {} in {} ms, the symbol is likely not listed",
+            status, round_trip
+        )),
+        _ => Err(format!("HTTP {} in {} ms", status, round_trip)),
+    }
+}
+
+fn check_bind(addr: SocketAddr) -> Result<String, String> {
+    TcpListener::bind(addr)
+        .map(|_| format!("{} is free", addr))
+        .map_err(|err| format!("cannot bind {}: {}", addr, err))
+}
+
+// The venues the server would connect to with these options, as (name, symbol)
+pub(crate) fn configured_venues(options: &ServerOptions) -> Vec<(String, String)> {
+    options
+        .venues()
+        .into_iter()
+        .map(|name| {
+            let symbol = if name == "bitstamp" {
+                options.bitstamp_symbol.clone()
+            } else {
+                options.symbol.clone()
+            };
+            (name, symbol)
+        })
+        .collect()
+}
+
+// Runs every check of the deployment, without starting the server
+pub fn run_doctor(options: &ServerOptions) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    let depth = options.pipeline(&options.symbol).depth;
+    // The venues at the endpoints of the server
+    let connector_settings = options.connector_settings();
+    if !options.venue_params.is_empty() {
+        results.push(CheckResult::new(
+            "venue parameters".to_string(),
+            connector_settings
+                .check_venue_params(depth)
+                .map(|_| "taken by their connectors".to_string()),
+        ));
+    }
+    if !options.symbol_settings.is_empty() {
+        results.push(CheckResult::new(
+            "symbol settings".to_string(),
+            check_symbol_settings(&options.symbol_settings, &options.symbol)
+                .map(|_| "applied to their pipelines".to_string()),
+        ));
+    }
+    for (name, symbol) in configured_venues(options) {
+        let connector = match configured_connector(&name, false, depth, &connector_settings) {
+            Some(connector) => connector,
+            None => {
+                results.push(CheckResult::new(
+                    format!("{} connector", name),
+                    Err(format!("unknown connector {}", name)),
+                ));
+                continue;
+            }
+        };
+        if let Some(rest_url) = connector.rest_url(&connector.normalize_symbol(&symbol)) {
+            results.push(CheckResult::new(
+                format!("{} REST {}", name, symbol),
+                check_rest(&rest_url),
+            ));
+        }
+        if let Some(result) = check_venue_symbol(connector.as_ref(), &symbol) {
+            results.push(CheckResult::new(
+                format!("{} symbol {}", name, symbol),
+                result,
+            ));
+        }
+        results.push(CheckResult::new(
+            format!("{} WebSocket {}", name, symbol),
+            check_websocket(connector, &symbol, depth as usize),
+        ));
+    }
+    results.push(CheckResult::new(
+        "gRPC port".to_string(),
+        check_bind(options.addr),
+    ));
+    results
+}
+
+// Prints one line per check, returns true if all of them passed
+pub fn print_diagnosis(results: &[CheckResult]) -> bool {
+    for result in results {
+        println!(
+            "[{}] {}: {}",
+            if result.passed { "PASS" } else { "FAIL" },
+            result.name,
+            result.detail
+        );
+    }
+    let failed = results.iter().filter(|result| !result.passed).count();
+    if failed == 0 {
+        println!("All {} checks passed", results.len());
+    } else {
+        println!("{} of {} checks failed", failed, results.len());
+    }
+    failed == 0
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::BinanceConnector;
+    use crate::mock_exchange::{MockExchange, MockSession};
+
+    #[test]
+    fn test_check_websocket() {
+        let exchange = MockExchange::start(vec![MockSession {
+            ack: r#"{"result":null,"id":1}"#.to_string(),
+            frames: vec![
+                r#"{"lastUpdateId": 1, "bids": [["10.0", "1.0"]], "asks": [["11.0", "0.8"]]}"#
+                    .to_string(),
+            ],
+            close: true,
+        }]);
+        let connector = Box::new(BinanceConnector::with_url(&exchange.url, 10));
+        let result = check_websocket(connector, "btcusdt", 10);
+        assert!(result.unwrap().contains("first book of btcusdt"));
+        exchange.finish();
+    }
+
+    #[test]
+    fn test_check_bind() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert!(check_bind(addr).is_err());
+        drop(listener);
+        assert!(check_bind(addr).is_ok());
+
+        let results = [
+            CheckResult::new("gRPC port".to_string(), check_bind(addr)),
+            CheckResult::new("binance".to_string(), Err("timed out".to_string())),
+        ];
+        assert!(results[0].passed);
+        assert!(!print_diagnosis(&results));
+    }
+}
@@ -0,0 +1,406 @@
+use crate::aggregator::configured_connector;
+use crate::bitstamp_pool::plan_channels;
+use crate::config::ServerOptions;
+use crate::config::{check_symbol_settings, MergeCadence};
+use crate::connection_manager::{connection_manager, venue_limits};
+use crate::connectors::bitstamp::bitstamp_channel;
+use crate::depeg::needs_depeg_guard;
+use crate::doctor::configured_venues;
+use crate::scripting::ScriptHook;
+use crate::sink::{RedisSink, WebhookSink};
+use crate::tenant::TenantRegistry;
+use std::path::Path;
+use url::Url;
+
+// The RPCs served by OrderbookAggregatorServer
+const RPC_SERVICES: [&str; 9] = [
+    "BookSummary",
+    "BasisStream",
+    "IndexPriceStream",
+    "Alerts",
+    "QueryAuditLog",
+    "GetUsageReport",
+    "GetFeedStatus",
+    "RequestSnapshot",
+    "GetBboAttribution",
+];
+
+// Everything the server would run with these options, and what is wrong with them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pipeline {
+    pub lines: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+fn connection_limits(url: &str) -> String {
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default();
+    let limits = venue_limits(&host);
+    format!(
+        "{} connects in flight, {} connections per {} s",
+        limits.max_concurrent_connects,
+        limits.max_connects,
+        limits.window.as_secs()
+    )
+}
+
+// Subscription messages on a single line
+fn compact(message: &str) -> String {
+    message.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// A file the server appends to needs an existing directory
+fn check_output_file(name: &str, path: &Option<String>, pipeline: &mut Pipeline) {
+    let Some(path) = path else {
+        return;
+    };
+    pipeline.lines.push(format!("  {}: {}", name, path));
+    let parent = Path::new(path)
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty());
+    if parent.is_some_and(|parent| !parent.is_dir()) {
+        pipeline.problems.push(format!(
+            "The directory of the {} {} doesn't exist",
+            name, path
+        ));
+    }
+}
+
+// Resolves the options the way Aggregator::connect would, without connecting to
+// anything, so a misconfiguration shows up before touching the exchanges
+pub fn resolve_pipeline(options: &ServerOptions) -> Pipeline {
+    let mut pipeline = Pipeline::default();
+    let symbol_pipeline = options.pipeline(&options.symbol);
+    if let Err(err) = check_symbol_settings(&options.symbol_settings, &options.symbol) {
+        pipeline.problems.push(err);
+    }
+    let lines = &mut pipeline.lines;
+    lines.push(format!(
+        "Symbol: {}, depth {}",
+        options.symbol, symbol_pipeline.depth
+    ));
+
+    lines.push("Connectors:".to_string());
+    let connector_settings = options.connector_settings();
+    if let Err(err) = connector_settings.check_venue_params(symbol_pipeline.depth) {
+        pipeline.problems.push(err);
+    }
+    for (name, symbol) in configured_venues(options) {
+        if name == "bitstamp" {
+            // The server subscribes bitstamp through its pool of sockets
+            let mut channels = vec![connector_settings.bitstamp_book_channel(&symbol)];
+            if needs_depeg_guard(&options.symbol, &symbol) {
+                channels.push(bitstamp_channel("usdtusd"));
+            }
+            let bitstamp_url = options.bitstamp_url();
+            let per_socket = connection_manager()
+                .streams_per_connection(bitstamp_url, options.bitstamp_channels_per_socket);
+            let sockets = plan_channels(&channels, per_socket);
+            pipeline.lines.push(format!(
+                "  bitstamp {} at {}, channels {} over {} socket(s), {}",
+                symbol,
+                bitstamp_url,
+                channels.join(", "),
+                sockets.len(),
+                connection_limits(bitstamp_url)
+            ));
+            continue;
+        }
+        match configured_connector(&name, false, symbol_pipeline.depth, &connector_settings) {
+            Some(connector) => {
+                // The symbol as the venue lists it
+                let venue_symbol = connector.normalize_symbol(&symbol);
+                pipeline.lines.push(format!(
+                    "  {} {} at {}, {}",
+                    name,
+                    venue_symbol,
+                    connector.url(),
+                    connection_limits(connector.url())
+                ));
+                for message in connector.subscribe_messages(&venue_symbol) {
+                    pipeline
+                        .lines
+                        .push(format!("    subscribe: {}", compact(&message)));
+                }
+            }
+            None => pipeline
+                .problems
+                .push(format!("Unknown connector {}", name)),
+        }
+    }
+    if options.max_reconnects_per_minute.is_some()
+        || !options.max_venue_reconnects_per_minute.is_empty()
+    {
+        let mut caps: Vec<String> = options
+            .max_reconnects_per_minute
+            .map(|cap| format!("{} for all venues", cap))
+            .into_iter()
+            .collect();
+        let mut venue_caps: Vec<_> = options.max_venue_reconnects_per_minute.iter().collect();
+        venue_caps.sort();
+        caps.extend(
+            venue_caps
+                .into_iter()
+                .map(|(venue, cap)| format!("{} for {}", cap, venue)),
+        );
+        pipeline.lines.push(format!(
+            "  reconnects per minute: at most {}",
+            caps.join(", ")
+        ));
+    }
+
+    let lines = &mut pipeline.lines;
+    lines.push("Processing:".to_string());
+    if needs_depeg_guard(&options.symbol, &options.bitstamp_symbol) {
+        lines.push(format!(
+            "  depeg guard: bitstamp merged while USDT/USD is within {} bps",
+            options.depeg_threshold_bps
+        ));
+    }
+    if let Some(compaction) = &options.compaction {
+        lines.push(format!(
+            "  compaction: levels beyond {} bps (hysteresis {} bps)",
+            compaction.max_distance_bps, compaction.hysteresis_bps
+        ));
+    }
+    if let Some(conflation) = symbol_pipeline.conflation {
+        lines.push(format!(
+            "  conflation: at most one summary every {} ms",
+            conflation.as_millis()
+        ));
+    }
+    match symbol_pipeline.merge_cadence {
+        Some(MergeCadence::Slowest) => {
+            lines.push("  merge cadence: the slowest venue's update rate".to_string())
+        }
+        Some(MergeCadence::Tick(tick)) => {
+            lines.push(format!("  merge cadence: every {} ms", tick.as_millis()))
+        }
+        None => {}
+    }
+    if !symbol_pipeline.fair_value {
+        lines.push("  fair value: off for the symbol".to_string());
+    }
+    if !symbol_pipeline.toxicity {
+        lines.push("  toxicity: off for the symbol".to_string());
+    }
+    lines.push(format!(
+        "  fair value: {} weighted, venue weights {:?}",
+        if options.fair_value_model.volume_weighted {
+            "volume"
+        } else {
+            "equally"
+        },
+        options.fair_value_model.venue_weights
+    ));
+    if let Some(threshold_bps) = options.deviation_threshold_bps {
+        lines.push(format!(
+            "  deviation: {} bps for {} ms{}",
+            threshold_bps,
+            options.deviation_sustain.as_millis(),
+            if options.quarantine_deviating_venues {
+                ", quarantined"
+            } else {
+                ""
+            }
+        ));
+    }
+    if let Some(multiple) = options.wall_multiple {
+        lines.push(format!(
+            "  walls: levels {}x the average level size over {} s",
+            multiple,
+            options.wall_window.as_secs()
+        ));
+    }
+    if let Some(max_skew) = options.latency_compensation {
+        lines.push(format!(
+            "  latency compensation: faster venues delayed by up to {} ms",
+            max_skew.as_millis()
+        ));
+    }
+    for (exchange, max_distance_bps) in &options.max_touch_distance_bps {
+        lines.push(format!(
+            "  touch filter: {} levels beyond {} bps of its best price left out",
+            exchange, max_distance_bps
+        ));
+    }
+    lines.push(format!(
+        "  toxicity: buckets of {}, averaged over {}",
+        options.toxicity_bucket_volume, options.toxicity_buckets
+    ));
+    for (name, formula) in &options.indexes {
+        lines.push(format!(
+            "  index {}: venues {:?}, weights {:?}, max deviation {:?} bps, min {} venue(s)",
+            name, formula.venues, formula.weights, formula.max_deviation_bps, formula.min_venues
+        ));
+    }
+    if let Some((_, path)) = options
+        .scripts
+        .iter()
+        .find(|(symbol, _)| *symbol == options.symbol)
+    {
+        lines.push(format!("  script: {}", path));
+        if let Err(err) = ScriptHook::from_file(path) {
+            pipeline
+                .problems
+                .push(format!("The script {} doesn't load: {}", path, err));
+        }
+    }
+
+    pipeline.lines.push("Sinks:".to_string());
+    check_output_file("recording", &options.record_file, &mut pipeline);
+    check_output_file("audit log", &options.audit_log_file, &mut pipeline);
+    check_output_file("usage export", &options.usage_export_file, &mut pipeline);
+    if let Some(url) = &options.webhook_url {
+        pipeline.lines.push(format!("  webhook: POST {}", url));
+        if let Err(err) = WebhookSink::new(url) {
+            pipeline
+                .problems
+                .push(format!("The webhook url {} is invalid: {}", url, err));
+        }
+    }
+    if let Some(url) = &options.redis_url {
+        pipeline.lines.push(format!("  redis: PUBLISH to {}", url));
+        if let Err(err) = RedisSink::new(url) {
+            pipeline
+                .problems
+                .push(format!("The redis url {} is invalid: {}", url, err));
+        }
+    }
+    if let Some(capacity) = options.summary_history {
+        pipeline.lines.push(format!(
+            "  history: the latest {} summaries, for GetSummariesSince",
+            capacity
+        ));
+    }
+    for sink in &options.sinks {
+        pipeline.lines.push(format!("  {}", sink.name()));
+    }
+    if options.usage_export_file.is_some() {
+        pipeline.lines.push(format!(
+            "    every {} s",
+            options.usage_export_interval.as_secs()
+        ));
+    }
+    if options.retention.is_enabled() {
+        pipeline.lines.push(format!(
+            "  retention: max age {:?}, max {:?} bytes, every {} s",
+            options.retention.max_age,
+            options.retention.max_total_bytes,
+            options.retention_interval.as_secs()
+        ));
+    }
+
+    pipeline.lines.push(format!(
+        "RPC services on {}: {}",
+        options.addr,
+        RPC_SERVICES.join(", ")
+    ));
+    pipeline.lines.push("Limits:".to_string());
+    match &options.tenants_file {
+        Some(path) => match TenantRegistry::from_file(path) {
+            Ok(tenants) => {
+                for tenant in tenants.tenants() {
+                    let config = &tenant.config;
+                    pipeline.lines.push(format!(
+                        "  tenant {}: symbols {:?}, max depth {:?}, max {:?} updates/s{}",
+                        config.namespace,
+                        config.symbols,
+                        config.max_depth,
+                        config.max_updates_per_second,
+                        if config.admin { ", admin" } else { "" }
+                    ));
+                }
+            }
+            Err(err) => pipeline
+                .problems
+                .push(format!("The tenants {} don't load: {}", path, err)),
+        },
+        None => pipeline
+            .lines
+            .push("  no tenants, every client is served".to_string()),
+    }
+    pipeline
+}
+
+// Prints the pipeline and its problems, returns true if there is none
+pub fn print_pipeline(pipeline: &Pipeline) -> bool {
+    for line in &pipeline.lines {
+        println!("{}", line);
+    }
+    for problem in &pipeline.problems {
+        println!("Problem: {}", problem);
+    }
+    pipeline.problems.is_empty()
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SymbolSettings;
+
+    #[test]
+    fn test_resolve_pipeline() {
+        let args: Vec<String> =
+            "server btcusdt 20 --bitstamp-symbol btcusd --connector test_venue \
+             --connector nope --record /nonexistent/recording.jsonl \
+             --venue-param binance.update_speed_ms=1000 --venue-param kraken.depth=7 \
+             --max-reconnects-per-minute 30 --max-venue-reconnects-per-minute kraken=5"
+                .split_whitespace()
+                .map(str::to_string)
+                .collect();
+        let mut options = ServerOptions::from_args(&args).unwrap();
+        options.symbol_settings.insert(
+            "ethusdt".to_string(),
+            SymbolSettings {
+                depth: Some(10),
+                conflation_ms: Some(100),
+                ..SymbolSettings::default()
+            },
+        );
+        let pipeline = resolve_pipeline(&options);
+
+        assert_eq!(pipeline.lines[0], "Symbol: btcusdt, depth 20");
+        assert!(pipeline.lines.iter().any(|line| line.contains(
+            "channels detail_order_book_btcusd, detail_order_book_usdtusd over 1 socket(s)"
+        )));
+        assert!(pipeline
+            .lines
+            .iter()
+            .any(|line| line.starts_with("  test_venue btcusdt at ws://localhost")));
+        assert!(pipeline
+            .lines
+            .iter()
+            .any(|line| line.contains("depeg guard")));
+        // The 1000 ms stream, without the @100ms suffix
+        assert!(pipeline
+            .lines
+            .iter()
+            .any(|line| line.contains(r#""params": ["btcusdt@depth20"]"#)));
+        assert!(
+            pipeline
+                .lines
+                .iter()
+                .any(|line| line
+                    == "  reconnects per minute: at most 30 for all venues, 5 for kraken")
+        );
+        assert_eq!(
+            pipeline.problems,
+            vec![
+                "conflation_ms of [symbols.ethusdt] don't apply, the server only serves \
+                 btcusdt, other symbols only take a depth for their BasisStream legs"
+                    .to_string(),
+                "Invalid --venue-param kraken.depth=7: depth must be one of 10, 25, 100, 500, \
+                 1000, got 7"
+                    .to_string(),
+                "Unknown connector nope".to_string(),
+                "The directory of the recording /nonexistent/recording.jsonl doesn't exist"
+                    .to_string()
+            ]
+        );
+        assert!(!print_pipeline(&pipeline));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::basis::mid_price;
+use crate::book::OrderBook;
+use crate::number::decimal_to_f64;
+use crate::orderbook_proto::{EmissionTrigger, SummaryRequest};
+use rust_decimal::Decimal;
+
+// Decides which merged books of a subscription are sent, between the merge and the
+// client's channel. The candidates are the venue updates, or the wall-clock ticks of
+// align_interval_ms, and the trigger filters them
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EmissionPolicy {
+    trigger: EmissionTrigger,
+    spread_change_bps: f64,
+    // Best bid and ask as (price, amount) of the last book sent
+    last_bbo: Option<((Decimal, Decimal), (Decimal, Decimal))>,
+    last_spread_bps: Option<f64>,
+}
+
+fn best_levels(orderbook: &OrderBook) -> Option<((Decimal, Decimal), (Decimal, Decimal))> {
+    let best_bid = orderbook.bids.first()?;
+    let best_ask = orderbook.asks.first()?;
+    Some((
+        (best_bid.price, best_bid.amount),
+        (best_ask.price, best_ask.amount),
+    ))
+}
+
+fn spread_bps(orderbook: &OrderBook) -> Option<f64> {
+    let ((bid, _), (ask, _)) = best_levels(orderbook)?;
+    let mid = mid_price(orderbook).filter(|mid| *mid > 0.0)?;
+    Some(decimal_to_f64(ask - bid) / mid * 10_000.0)
+}
+
+impl EmissionPolicy {
+    pub fn new(trigger: EmissionTrigger, spread_change_bps: f64) -> EmissionPolicy {
+        EmissionPolicy {
+            trigger,
+            spread_change_bps,
+            last_bbo: None,
+            last_spread_bps: None,
+        }
+    }
+
+    // The policy of a BookSummary request, the timer trigger needs the ticks of
+    // align_interval_ms and the spread trigger a positive threshold
+    pub fn from_request(request: &SummaryRequest) -> Result<EmissionPolicy, String> {
+        let trigger = EmissionTrigger::from_i32(request.trigger)
+            .ok_or_else(|| format!("Unknown emission trigger {}", request.trigger))?;
+        match trigger {
+            EmissionTrigger::Timer if request.align_interval_ms == 0 => {
+                Err("The timer trigger needs align_interval_ms".to_string())
+            }
+            EmissionTrigger::SpreadChange if request.spread_change_bps <= 0.0 => {
+                Err("The spread change trigger needs a positive spread_change_bps".to_string())
+            }
+            _ => Ok(EmissionPolicy::new(trigger, request.spread_change_bps)),
+        }
+    }
+
+    // True when the merged book has to be sent, the book is then the reference of the
+    // next changes
+    pub fn should_emit(&mut self, orderbook: &OrderBook) -> bool {
+        let emit = match self.trigger {
+            EmissionTrigger::AnyChange | EmissionTrigger::Timer => true,
+            EmissionTrigger::BboChange => best_levels(orderbook) != self.last_bbo,
+            EmissionTrigger::SpreadChange => match (spread_bps(orderbook), self.last_spread_bps) {
+                (Some(spread_bps), Some(last_spread_bps)) => {
+                    (spread_bps - last_spread_bps).abs() >= self.spread_change_bps
+                }
+                (spread_bps, last_spread_bps) => spread_bps.is_some() != last_spread_bps.is_some(),
+            },
+        };
+        if emit {
+            self.last_bbo = best_levels(orderbook);
+            self.last_spread_bps = spread_bps(orderbook);
+        }
+        emit
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PriceAmountLevel;
+    use crate::number::decimal_from_f64;
+
+    fn orderbook(bid: f64, ask: f64, amount: f64) -> OrderBook {
+        let level = |price| PriceAmountLevel {
+            exchange: "exchange1".to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: decimal_from_f64(amount).unwrap(),
+        };
+        OrderBook {
+            bids: vec![level(bid), level(bid - 1.0)],
+            asks: vec![level(ask), level(ask + 1.0)],
+            spread: bid - ask,
+            venues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_emission_policy() {
+        let mut bbo_change = EmissionPolicy::new(EmissionTrigger::BboChange, 0.0);
+        assert!(bbo_change.should_emit(&orderbook(100.0, 101.0, 1.0)));
+        assert!(!bbo_change.should_emit(&orderbook(100.0, 101.0, 1.0)));
+        assert!(bbo_change.should_emit(&orderbook(100.0, 101.0, 2.0)));
+
+        // 99.5 bps, then 109 bps and 149 bps of spread
+        let mut spread_change = EmissionPolicy::new(EmissionTrigger::SpreadChange, 20.0);
+        assert!(spread_change.should_emit(&orderbook(100.0, 101.0, 1.0)));
+        assert!(!spread_change.should_emit(&orderbook(100.0, 101.1, 5.0)));
+        assert!(spread_change.should_emit(&orderbook(100.0, 101.5, 1.0)));
+        assert!(!spread_change.should_emit(&orderbook(100.0, 101.5, 1.0)));
+
+        let request = SummaryRequest {
+            trigger: EmissionTrigger::Timer as i32,
+            ..Default::default()
+        };
+        assert!(EmissionPolicy::from_request(&request).is_err());
+        let request = SummaryRequest {
+            align_interval_ms: 250,
+            ..request
+        };
+        let mut timer = EmissionPolicy::from_request(&request).unwrap();
+        assert!(timer.should_emit(&orderbook(100.0, 101.0, 1.0)));
+        assert!(timer.should_emit(&orderbook(100.0, 101.0, 1.0)));
+    }
+}
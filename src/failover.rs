@@ -0,0 +1,142 @@
+use std::time::{Duration, Instant};
+
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+struct ServerEndpoint {
+    addr: String,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+// Aggregator servers of a redundant deployment, in order of preference. A failing
+// server is backed off from 1s up to 60s, the client fails over to the next one and
+// fails back once a preferred server is out of its backoff and healthy again
+#[derive(Debug, Clone)]
+pub struct ServerEndpoints {
+    endpoints: Vec<ServerEndpoint>,
+    current: usize,
+}
+
+impl ServerEndpoints {
+    // None without any address
+    pub fn new(addrs: Vec<String>) -> Option<ServerEndpoints> {
+        if addrs.is_empty() {
+            return None;
+        }
+        Some(ServerEndpoints {
+            endpoints: addrs
+                .into_iter()
+                .map(|addr| ServerEndpoint {
+                    addr,
+                    failures: 0,
+                    retry_at: None,
+                })
+                .collect(),
+            current: 0,
+        })
+    }
+
+    // In order of preference
+    pub fn addrs(&self) -> impl Iterator<Item = &str> {
+        self.endpoints.iter().map(|endpoint| endpoint.addr.as_str())
+    }
+
+    pub fn addr(&self, index: usize) -> &str {
+        &self.endpoints[index].addr
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    fn is_ready(&self, index: usize, now: Instant) -> bool {
+        self.endpoints[index]
+            .retry_at
+            .is_none_or(|retry_at| retry_at <= now)
+    }
+
+    // The most preferred server out of its backoff, or the soonest to retry if they
+    // are all backing off
+    pub fn next_endpoint(&self, now: Instant) -> usize {
+        (0..self.endpoints.len())
+            .find(|index| self.is_ready(*index, now))
+            .unwrap_or_else(|| {
+                (0..self.endpoints.len())
+                    .min_by_key(|index| self.endpoints[*index].retry_at)
+                    .unwrap_or(0)
+            })
+    }
+
+    // Time to wait before connecting to the server
+    pub fn retry_delay(&self, index: usize, now: Instant) -> Duration {
+        self.endpoints[index]
+            .retry_at
+            .map_or(Duration::ZERO, |retry_at| {
+                retry_at.saturating_duration_since(now)
+            })
+    }
+
+    // A server preferred over the current one that can be probed to fail back to it
+    pub fn fail_back_candidate(&self, now: Instant) -> Option<usize> {
+        (0..self.current).find(|index| self.is_ready(*index, now))
+    }
+
+    pub fn record_failure(&mut self, index: usize, now: Instant) {
+        let endpoint = &mut self.endpoints[index];
+        endpoint.failures += 1;
+        let delay = BACKOFF_BASE
+            .saturating_mul(2u32.saturating_pow(endpoint.failures - 1))
+            .min(BACKOFF_MAX);
+        endpoint.retry_at = Some(now + delay);
+    }
+
+    // The server passed its health check and becomes the current one
+    pub fn record_success(&mut self, index: usize) {
+        self.endpoints[index].failures = 0;
+        self.endpoints[index].retry_at = None;
+        self.current = index;
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_endpoints() {
+        let mut endpoints = ServerEndpoints::new(vec![
+            "http://a:50051".to_string(),
+            "http://b:50051".to_string(),
+        ])
+        .unwrap();
+        let now = Instant::now();
+        assert_eq!(endpoints.next_endpoint(now), 0);
+
+        // Fails over to b
+        endpoints.record_failure(0, now);
+        assert_eq!(endpoints.next_endpoint(now), 1);
+        endpoints.record_success(1);
+        assert_eq!(endpoints.addr(endpoints.current()), "http://b:50051");
+        assert_eq!(endpoints.fail_back_candidate(now), None);
+
+        // a is probed again once its backoff is over
+        let later = now + Duration::from_secs(1);
+        assert_eq!(endpoints.fail_back_candidate(later), Some(0));
+        endpoints.record_failure(0, later);
+        assert_eq!(endpoints.retry_delay(0, later), Duration::from_secs(2));
+        endpoints.record_success(0);
+        assert_eq!(endpoints.current(), 0);
+        assert_eq!(endpoints.retry_delay(0, later), Duration::ZERO);
+
+        // Both down, the soonest to retry goes first
+        endpoints.record_failure(1, now);
+        endpoints.record_failure(1, now);
+        endpoints.record_failure(0, now);
+        assert_eq!(endpoints.next_endpoint(now), 0);
+
+        assert!(ServerEndpoints::new(Vec::new()).is_none());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::number::decimal_to_f64;
+use std::collections::HashMap;
+
+// Mid weighted by the opposite top of book volume, it leans towards the side with
+// less volume, where the price is more likely to move next
+pub fn weighted_mid(orderbook: &OrderBook) -> Option<f64> {
+    let best_bid = orderbook.bids.first()?;
+    let best_ask = orderbook.asks.first()?;
+    let (bid_price, bid_amount) = (
+        decimal_to_f64(best_bid.price),
+        decimal_to_f64(best_bid.amount),
+    );
+    let (ask_price, ask_amount) = (
+        decimal_to_f64(best_ask.price),
+        decimal_to_f64(best_ask.amount),
+    );
+    let volume = bid_amount + ask_amount;
+    if volume <= 0.0 {
+        return None;
+    }
+    Some((bid_price * ask_amount + ask_price * bid_amount) / volume)
+}
+
+// How the weighted mids of the venues are blended into a single reference price.
+// A venue's weight is its configured weight (1 by default), times its top of book
+// volume when volume_weighted is set
+#[derive(Debug, Clone, PartialEq)]
+pub struct FairValueModel {
+    pub volume_weighted: bool,
+    pub venue_weights: HashMap<String, f64>,
+}
+
+impl FairValueModel {
+    pub fn new() -> FairValueModel {
+        FairValueModel {
+            volume_weighted: true,
+            venue_weights: HashMap::new(),
+        }
+    }
+
+    fn venue_weight(&self, exchange: &str, orderbook: &OrderBook) -> f64 {
+        let weight = self.venue_weights.get(exchange).copied().unwrap_or(1.0);
+        if !self.volume_weighted {
+            return weight;
+        }
+        let amount = |level: &PriceAmountLevel| decimal_to_f64(level.amount);
+        let volume =
+            orderbook.bids.first().map_or(0.0, amount) + orderbook.asks.first().map_or(0.0, amount);
+        weight * volume
+    }
+
+    // Blends the books of the venues, given as (exchange, book). Venues without a
+    // top of book or with a zero weight are left out
+    pub fn fair_value(&self, venues: &[(&str, &OrderBook)]) -> Option<f64> {
+        let (weighted_sum, total_weight) = venues
+            .iter()
+            .filter_map(|(exchange, orderbook)| {
+                let mid = weighted_mid(orderbook)?;
+                let weight = self.venue_weight(exchange, orderbook);
+                Some((mid, weight)).filter(|(_, weight)| *weight > 0.0)
+            })
+            .fold((0.0, 0.0), |(weighted_sum, total_weight), (mid, weight)| {
+                (weighted_sum + mid * weight, total_weight + weight)
+            });
+
+        if total_weight > 0.0 {
+            Some(weighted_sum / total_weight)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for FairValueModel {
+    fn default() -> Self {
+        FairValueModel::new()
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PriceAmountLevel;
+    use crate::number::decimal_from_f64;
+
+    fn orderbook(bid: f64, bid_amount: f64, ask: f64, ask_amount: f64) -> OrderBook {
+        OrderBook {
+            bids: vec![PriceAmountLevel {
+                exchange: "exchange1".to_string(),
+                price: decimal_from_f64(bid).unwrap(),
+                amount: decimal_from_f64(bid_amount).unwrap(),
+            }],
+            asks: vec![PriceAmountLevel {
+                exchange: "exchange1".to_string(),
+                price: decimal_from_f64(ask).unwrap(),
+                amount: decimal_from_f64(ask_amount).unwrap(),
+            }],
+            spread: bid - ask,
+            venues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_weighted_mid() {
+        // 3 on the bid and 1 on the ask, the mid leans towards the ask
+        assert_eq!(
+            weighted_mid(&orderbook(100.0, 3.0, 102.0, 1.0)),
+            Some(101.5)
+        );
+        assert_eq!(
+            weighted_mid(&orderbook(100.0, 1.0, 102.0, 1.0)),
+            Some(101.0)
+        );
+        assert_eq!(weighted_mid(&orderbook(100.0, 0.0, 102.0, 0.0)), None);
+        assert_eq!(weighted_mid(&OrderBook::new()), None);
+    }
+
+    #[test]
+    fn test_fair_value() {
+        let binance = orderbook(100.0, 1.0, 102.0, 1.0);
+        let bitstamp = orderbook(104.0, 0.5, 106.0, 0.5);
+        let empty = OrderBook::new();
+        let venues = [
+            ("binance", &binance),
+            ("bitstamp", &bitstamp),
+            ("bybit", &empty),
+        ];
+
+        // binance has twice the top of book volume of bitstamp
+        let model = FairValueModel::new();
+        let fair_value = model.fair_value(&venues).unwrap();
+        assert!((fair_value - (101.0 * 2.0 + 105.0) / 3.0).abs() < 1e-9);
+
+        let equal = FairValueModel {
+            volume_weighted: false,
+            venue_weights: HashMap::new(),
+        };
+        assert_eq!(equal.fair_value(&venues), Some(103.0));
+
+        let bitstamp_only = FairValueModel {
+            volume_weighted: false,
+            venue_weights: HashMap::from([("binance".to_string(), 0.0)]),
+        };
+        assert_eq!(bitstamp_only.fair_value(&venues), Some(105.0));
+
+        assert_eq!(model.fair_value(&[("bybit", &empty)]), None);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::book::OrderBook;
+use crate::connectors::{ErrorAction, ExchangeError};
+use crate::orderbook_proto::FeedStatus;
+use std::collections::{HashMap, HashSet};
+
+// Venues whose feed ended, or without an update for stale_after_ms. A venue that never
+// sent an update is stale once the subscription is older than stale_after_ms
+pub(crate) fn stale_venues(
+    venues: &[String],
+    last_updates: &HashMap<String, u64>,
+    ended: &HashSet<String>,
+    started_at: u64,
+    now: u64,
+    stale_after_ms: u64,
+) -> Vec<String> {
+    venues
+        .iter()
+        .filter(|venue| {
+            let last_update = last_updates.get(*venue).copied().unwrap_or(started_at);
+            ended.contains(*venue) || now.saturating_sub(last_update) >= stale_after_ms
+        })
+        .cloned()
+        .collect()
+}
+
+// A venue book without bids or asks, as some exchanges send while they reset their
+// books for maintenance. Its spread of 0 isn't a price, the venue is unavailable and
+// left out of the merged book until both sides are back
+pub(crate) fn is_empty_book(orderbook: &OrderBook) -> bool {
+    orderbook.bids.is_empty() || orderbook.asks.is_empty()
+}
+
+// Venues either stale or with an empty book, for feed_status
+pub(crate) fn unavailable_venues(stale_venues: &[String], empty_book_venues: &[String]) -> usize {
+    let empty_only = empty_book_venues
+        .iter()
+        .filter(|venue| !stale_venues.contains(venue))
+        .count();
+    stale_venues.len() + empty_only
+}
+
+pub(crate) fn feed_status(venues: usize, stale_venues: usize) -> FeedStatus {
+    if stale_venues == 0 {
+        FeedStatus::Live
+    } else if stale_venues < venues {
+        FeedStatus::Degraded
+    } else {
+        FeedStatus::Down
+    }
+}
+
+// Feeds of the server across the subscriptions, for GetFeedStatus: the latest update
+// of each venue, the last error event its exchange sent, the venues disabled by one and
+// the ones whose latest book is empty, the repeated price levels and the sequence gaps
+// of each venue
+#[derive(Debug, Default)]
+pub(crate) struct FeedMonitor {
+    last_updates: HashMap<String, u64>,
+    empty_books: HashSet<String>,
+    duplicate_levels: HashMap<String, u64>,
+    resyncs: HashMap<String, u64>,
+    // With the time it was received
+    last_errors: HashMap<String, (ExchangeError, u64)>,
+    disabled: HashSet<String>,
+}
+
+impl FeedMonitor {
+    pub fn on_update(&mut self, exchange: &str, now: u64) {
+        self.last_updates.insert(exchange.to_string(), now);
+    }
+
+    // Returns true when the venue's book turned empty or came back with this one
+    pub fn on_book(&mut self, exchange: &str, orderbook: &OrderBook) -> bool {
+        if is_empty_book(orderbook) {
+            self.empty_books.insert(exchange.to_string())
+        } else {
+            self.empty_books.remove(exchange)
+        }
+    }
+
+    pub fn has_empty_book(&self, exchange: &str) -> bool {
+        self.empty_books.contains(exchange)
+    }
+
+    pub fn on_duplicate_levels(&mut self, exchange: &str, count: u64) {
+        *self
+            .duplicate_levels
+            .entry(exchange.to_string())
+            .or_default() += count;
+    }
+
+    pub fn duplicate_levels(&self, exchange: &str) -> u64 {
+        self.duplicate_levels.get(exchange).copied().unwrap_or(0)
+    }
+
+    pub fn on_resync(&mut self, exchange: &str) {
+        *self.resyncs.entry(exchange.to_string()).or_default() += 1;
+    }
+
+    pub fn resyncs(&self, exchange: &str) -> u64 {
+        self.resyncs.get(exchange).copied().unwrap_or(0)
+    }
+
+    // Returns what the reader loop does about the error
+    pub fn on_error(&mut self, exchange: &str, error: ExchangeError, now: u64) -> ErrorAction {
+        let action = error.action;
+        if action == ErrorAction::Disable {
+            self.disabled.insert(exchange.to_string());
+        }
+        self.last_errors.insert(exchange.to_string(), (error, now));
+        action
+    }
+
+    pub fn is_disabled(&self, exchange: &str) -> bool {
+        self.disabled.contains(exchange)
+    }
+
+    pub fn last_update(&self, exchange: &str) -> Option<u64> {
+        self.last_updates.get(exchange).copied()
+    }
+
+    pub fn last_error(&self, exchange: &str) -> Option<&(ExchangeError, u64)> {
+        self.last_errors.get(exchange)
+    }
+
+    // Disabled venues are stale like the ones whose feed ended
+    pub fn stale_venues(
+        &self,
+        venues: &[String],
+        started_at: u64,
+        now: u64,
+        stale_after_ms: u64,
+    ) -> Vec<String> {
+        stale_venues(
+            venues,
+            &self.last_updates,
+            &self.disabled,
+            started_at,
+            now,
+            stale_after_ms,
+        )
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PriceAmountLevel;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_stale_venues() {
+        let venues = vec!["binance".to_string(), "bitstamp".to_string()];
+        let mut last_updates = HashMap::new();
+        let mut ended = HashSet::new();
+        last_updates.insert("binance".to_string(), 9_000);
+
+        // bitstamp never sent anything, it goes stale with the subscription's age
+        let stale = stale_venues(&venues, &last_updates, &ended, 5_000, 10_000, 5_000);
+        assert_eq!(stale, vec!["bitstamp".to_string()]);
+        assert_eq!(feed_status(venues.len(), stale.len()), FeedStatus::Degraded);
+        assert!(stale_venues(&venues, &last_updates, &ended, 6_000, 10_000, 5_000).is_empty());
+
+        ended.insert("binance".to_string());
+        let stale = stale_venues(&venues, &last_updates, &ended, 5_000, 10_000, 5_000);
+        assert_eq!(feed_status(venues.len(), stale.len()), FeedStatus::Down);
+        assert_eq!(feed_status(venues.len(), 0), FeedStatus::Live);
+    }
+
+    #[test]
+    fn test_feed_monitor() {
+        let venues = vec!["binance".to_string(), "okx".to_string()];
+        let mut feed_monitor = FeedMonitor::default();
+        feed_monitor.on_update("binance", 9_000);
+        feed_monitor.on_update("okx", 9_000);
+        let error = ExchangeError {
+            code: Some("60012".to_string()),
+            message: "Invalid request".to_string(),
+            action: ErrorAction::Disable,
+        };
+
+        assert_eq!(
+            feed_monitor.on_error("okx", error.clone(), 9_500),
+            ErrorAction::Disable
+        );
+        assert!(feed_monitor.is_disabled("okx"));
+        assert_eq!(feed_monitor.last_error("okx"), Some(&(error, 9_500)));
+        assert_eq!(
+            feed_monitor.stale_venues(&venues, 0, 10_000, 5_000),
+            vec!["okx".to_string()]
+        );
+
+        feed_monitor.on_duplicate_levels("okx", 2);
+        feed_monitor.on_duplicate_levels("okx", 1);
+        assert_eq!(feed_monitor.duplicate_levels("okx"), 3);
+        assert_eq!(feed_monitor.duplicate_levels("binance"), 0);
+
+        feed_monitor.on_resync("okx");
+        assert_eq!(feed_monitor.resyncs("okx"), 1);
+        assert_eq!(feed_monitor.resyncs("binance"), 0);
+    }
+
+    #[test]
+    fn test_empty_books() {
+        let mut feed_monitor = FeedMonitor::default();
+        let mut orderbook = OrderBook::new();
+        orderbook.bids.push(PriceAmountLevel {
+            exchange: "okx".to_string(),
+            price: dec!(10.0),
+            amount: dec!(1.0),
+        });
+        orderbook.asks.push(PriceAmountLevel {
+            exchange: "okx".to_string(),
+            price: dec!(11.0),
+            amount: dec!(1.0),
+        });
+        assert!(!feed_monitor.on_book("okx", &orderbook));
+
+        // The maintenance frame without asks, then the book back to normal
+        let mut maintenance = orderbook.clone();
+        maintenance.asks.clear();
+        assert!(is_empty_book(&maintenance));
+        assert!(feed_monitor.on_book("okx", &maintenance));
+        assert!(!feed_monitor.on_book("okx", &OrderBook::new()));
+        assert!(feed_monitor.has_empty_book("okx"));
+        let empty_book_venues = vec!["okx".to_string()];
+        let unavailable = unavailable_venues(&[], &empty_book_venues);
+        assert_eq!(feed_status(2, unavailable), FeedStatus::Degraded);
+        // Stale and empty counts once
+        assert_eq!(
+            unavailable_venues(&empty_book_venues, &empty_book_venues),
+            1
+        );
+
+        assert!(feed_monitor.on_book("okx", &orderbook));
+        assert!(!feed_monitor.has_empty_book("okx"));
+        assert_eq!(
+            feed_status(2, unavailable_venues(&[], &[])),
+            FeedStatus::Live
+        );
+    }
+}
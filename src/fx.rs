@@ -0,0 +1,73 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::number::decimal_from_f64;
+use rust_decimal::Decimal;
+
+// Rate of a local currency in the quote of the served symbol, e.g. KRW per USDT, for
+// merging the books of venues that list the pair only in their local currency
+pub trait FxRateSource: Send + Sync {
+    fn rate(&self) -> Option<f64>;
+}
+
+// A rate set on the command line with --fx-rate <currency>=<rate>
+#[derive(Debug, Clone, Copy)]
+pub struct FixedRate(pub f64);
+
+impl FxRateSource for FixedRate {
+    fn rate(&self) -> Option<f64> {
+        (self.0 > 0.0).then_some(self.0)
+    }
+}
+
+fn convert_levels(levels: &[PriceAmountLevel], rate: Decimal) -> Vec<PriceAmountLevel> {
+    levels
+        .iter()
+        .map(|level| PriceAmountLevel {
+            exchange: level.exchange.clone(),
+            price: level.price / rate,
+            amount: level.amount,
+        })
+        .collect()
+}
+
+// Divides the prices of a book quoted in the local currency by the rate, the amounts
+// are in the base asset and stay as they are
+pub fn convert_quote(orderbook: &OrderBook, rate: f64) -> OrderBook {
+    let mut converted = orderbook.clone();
+    let Some(decimal_rate) = decimal_from_f64(rate).filter(|rate| !rate.is_zero()) else {
+        return converted;
+    };
+    converted.bids = convert_levels(&orderbook.bids, decimal_rate);
+    converted.asks = convert_levels(&orderbook.asks, decimal_rate);
+    converted.spread = orderbook.spread / rate;
+    converted
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_convert_quote() {
+        let mut orderbook = OrderBook::new();
+        orderbook.bids = vec![PriceAmountLevel {
+            exchange: "upbit".to_string(),
+            price: dec!(140_000_000),
+            amount: dec!(0.5),
+        }];
+        orderbook.asks = vec![PriceAmountLevel {
+            exchange: "upbit".to_string(),
+            price: dec!(140_140_000),
+            amount: dec!(0.2),
+        }];
+        orderbook.spread = -140_000.0;
+
+        let converted = convert_quote(&orderbook, FixedRate(1_400.0).rate().unwrap());
+        assert_eq!(converted.bids[0].price, dec!(100_000));
+        assert_eq!(converted.bids[0].amount, dec!(0.5));
+        assert_eq!(converted.asks[0].price, dec!(100_100));
+        assert_eq!(converted.spread, -100.0);
+        assert_eq!(FixedRate(0.0).rate(), None);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::book::PriceAmountLevel;
+use crate::number::decimal_from_f64;
+use rust_decimal::Decimal;
+
+// Price bucket sizes offered by the grouping selector, 0 shows the levels ungrouped
+pub const GROUPING_STEPS: [f64; 16] = [
+    0.0, 0.01, 0.02, 0.05, 0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0,
+];
+
+// Next bucket size of the selector, coarser or finer, staying at the ends
+pub fn next_grouping_step(step: f64, coarser: bool) -> f64 {
+    let index = GROUPING_STEPS
+        .iter()
+        .position(|grouping| *grouping >= step)
+        .unwrap_or(GROUPING_STEPS.len() - 1);
+    let index = if coarser {
+        (index + 1).min(GROUPING_STEPS.len() - 1)
+    } else {
+        index.saturating_sub(1)
+    };
+    GROUPING_STEPS[index]
+}
+
+// Groups the levels of one side into price buckets of step, like the grouping selector
+// of the exchange UIs. Bids are rounded down and asks up, so a bucket never crosses the
+// spread. The amounts of a bucket are summed and its exchange lists the venues in it
+pub fn group_levels(levels: &[PriceAmountLevel], step: f64, is_bid: bool) -> Vec<PriceAmountLevel> {
+    let step = match decimal_from_f64(step) {
+        Some(step) if step > Decimal::ZERO => step,
+        _ => return levels.to_vec(),
+    };
+
+    let mut grouped: Vec<PriceAmountLevel> = Vec::new();
+    for level in levels {
+        // Exact in decimals, a price on a bucket boundary stays in its bucket, e.g. 0.3 / 0.1
+        let bucket = if is_bid {
+            (level.price / step).floor()
+        } else {
+            (level.price / step).ceil()
+        };
+        let price = (bucket * step).normalize();
+
+        // The levels are sorted, so a bucket's levels are next to each other
+        match grouped.last_mut() {
+            Some(last) if last.price == price => {
+                last.amount += level.amount;
+                if !last
+                    .exchange
+                    .split('+')
+                    .any(|exchange| exchange == level.exchange)
+                {
+                    last.exchange = format!("{}+{}", last.exchange, level.exchange);
+                }
+            }
+            _ => grouped.push(PriceAmountLevel {
+                exchange: level.exchange.clone(),
+                price,
+                amount: level.amount,
+            }),
+        }
+    }
+    grouped
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn level(exchange: &str, price: f64, amount: f64) -> PriceAmountLevel {
+        PriceAmountLevel {
+            exchange: exchange.to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: decimal_from_f64(amount).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_group_levels() {
+        let bids = vec![
+            level("binance", 100.3, 1.0),
+            level("bitstamp", 100.1, 2.0),
+            level("binance", 100.0, 0.5),
+            level("binance", 99.9, 1.5),
+        ];
+        let grouped = group_levels(&bids, 0.5, true);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0].price, dec!(100));
+        assert_eq!(grouped[0].amount, dec!(3.5));
+        assert_eq!(grouped[0].exchange, "binance+bitstamp");
+        assert_eq!(grouped[1].price, dec!(99.5));
+        assert_eq!(grouped[1].exchange, "binance");
+
+        let asks = vec![level("binance", 0.3, 1.0), level("binance", 0.31, 1.0)];
+        let grouped = group_levels(&asks, 0.1, false);
+        assert_eq!(grouped[0].price, dec!(0.3));
+        assert_eq!(grouped[1].price, dec!(0.4));
+
+        assert_eq!(group_levels(&bids, 0.0, true).len(), 4);
+    }
+
+    #[test]
+    fn test_next_grouping_step() {
+        assert_eq!(next_grouping_step(0.0, true), 0.01);
+        assert_eq!(next_grouping_step(0.5, true), 1.0);
+        assert_eq!(next_grouping_step(0.5, false), 0.2);
+        assert_eq!(next_grouping_step(0.0, false), 0.0);
+        assert_eq!(next_grouping_step(500.0, true), 500.0);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::bbo_attribution::InsideQuote;
+use crate::book::{OrderBook, PriceAmountLevel, VenueMetadata};
+use crate::checksum::book_checksum;
+use crate::connectors::{ErrorAction, ExchangeError};
+use crate::grpc::SUMMARY_SCHEMA_VERSION;
+use crate::number::{decimal_from_f64, decimal_string, decimal_to_f64};
+use crate::orderbook_proto::{
+    self, AuditRecord, ExchangeErrorAction, FeedStatus, Level, SplitBook, Summary, UsageReport,
+    VenueError,
+};
+use crate::projection::SummaryFields;
+use crate::reference::ReferenceImprovement;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+// The doubles are always set, the checksum is computed over them. With decimal_prices
+// the strings hold the level's exact decimals, see encode_decimal_prices
+pub(crate) fn level_to_summary_level(level: &PriceAmountLevel, decimal_prices: bool) -> Level {
+    let decimal = |value: Decimal| {
+        if decimal_prices {
+            value.normalize().to_string()
+        } else {
+            String::new()
+        }
+    };
+    Level {
+        exchange: level.exchange.clone(),
+        price: decimal_to_f64(level.price),
+        amount: decimal_to_f64(level.amount),
+        price_decimal: decimal(level.price),
+        amount_decimal: decimal(level.amount),
+    }
+}
+
+pub(crate) fn venue_to_summary_venue(venue: &VenueMetadata) -> orderbook_proto::VenueMetadata {
+    orderbook_proto::VenueMetadata {
+        exchange: venue.exchange.clone(),
+        mark_price: venue.mark_price,
+        funding_rate: venue.funding_rate,
+        next_funding_time: venue.next_funding_time,
+    }
+}
+
+// Only the fields selected by the subscriber are built
+pub(crate) fn orderbook_to_summary(orderbook: &OrderBook, fields: &SummaryFields) -> Summary {
+    Summary {
+        spread: if fields.spread { orderbook.spread } else { 0.0 },
+        bids: fields
+            .bid_levels(&orderbook.bids)
+            .iter()
+            .map(|level| level_to_summary_level(level, fields.decimal_prices))
+            .collect(),
+        asks: fields
+            .ask_levels(&orderbook.asks)
+            .iter()
+            .map(|level| level_to_summary_level(level, fields.decimal_prices))
+            .collect(),
+        venues: if fields.venues {
+            orderbook
+                .venues
+                .iter()
+                .map(venue_to_summary_venue)
+                .collect()
+        } else {
+            Vec::new()
+        },
+        signals: HashMap::new(),
+        weighted_mid: None,
+        fair_value: None,
+        timestamp: 0,
+        venue_timestamps: HashMap::new(),
+        checksum: 0,
+        toxicity: None,
+        status: FeedStatus::Live as i32,
+        stale_venues: Vec::new(),
+        bbo_only_venues: Vec::new(),
+        index_prices: HashMap::new(),
+        sequence: 0,
+        symbol: String::new(),
+        subscription_id: 0,
+        snapshot: false,
+        spread_decimal: String::new(),
+        empty_book_venues: Vec::new(),
+        reference_improvement: None,
+        schema_version: SUMMARY_SCHEMA_VERSION,
+        split_books: Vec::new(),
+    }
+}
+
+// A book kept out of the merged book, with the levels of the fields like the summary's
+pub(crate) fn orderbook_to_split_book(
+    exchange: &str,
+    quote: &str,
+    orderbook: &OrderBook,
+    fields: &SummaryFields,
+) -> SplitBook {
+    let levels = |levels: &[PriceAmountLevel]| {
+        levels
+            .iter()
+            .map(|level| level_to_summary_level(level, fields.decimal_prices))
+            .collect()
+    };
+    SplitBook {
+        exchange: exchange.to_string(),
+        quote: quote.to_string(),
+        bids: levels(fields.bid_levels(&orderbook.bids)),
+        asks: levels(fields.ask_levels(&orderbook.asks)),
+    }
+}
+
+// The book of a summary built with all its fields, e.g. for the recording sink. The
+// levels are the decimal strings when set, else the shortest decimals of the doubles
+pub(crate) fn summary_to_orderbook(summary: &Summary) -> OrderBook {
+    let decimal = |text: &str, value: f64| text.parse().ok().or_else(|| decimal_from_f64(value));
+    let level = |level: &Level| {
+        Some(PriceAmountLevel {
+            exchange: level.exchange.clone(),
+            price: decimal(&level.price_decimal, level.price)?,
+            amount: decimal(&level.amount_decimal, level.amount)?,
+        })
+    };
+    OrderBook {
+        bids: summary.bids.iter().filter_map(level).collect(),
+        asks: summary.asks.iter().filter_map(level).collect(),
+        spread: summary.spread,
+        venues: summary
+            .venues
+            .iter()
+            .map(|venue| VenueMetadata {
+                exchange: venue.exchange.clone(),
+                mark_price: venue.mark_price,
+                funding_rate: venue.funding_rate,
+                next_funding_time: venue.next_funding_time,
+            })
+            .collect(),
+    }
+}
+
+// Checksum of the levels as they are sent, after the projection
+pub(crate) fn summary_checksum(summary: &Summary) -> u32 {
+    book_checksum(
+        summary.bids.iter().map(|level| (level.price, level.amount)),
+        summary.asks.iter().map(|level| (level.price, level.amount)),
+    )
+}
+
+fn fraction_digits(decimal: &str) -> usize {
+    decimal
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len())
+}
+
+// Leaves only the decimal strings of the prices and amounts, for the subscribers asking
+// for PRICE_ENCODING_STRING. Done after the checksum, the strings parse back to the
+// doubles it is computed over. Levels built without their strings get the shortest
+// decimals of their doubles
+pub(crate) fn encode_decimal_prices(summary: &mut Summary) {
+    let split_levels = summary
+        .split_books
+        .iter_mut()
+        .flat_map(|book| book.bids.iter_mut().chain(book.asks.iter_mut()));
+    for level in summary
+        .bids
+        .iter_mut()
+        .chain(summary.asks.iter_mut())
+        .chain(split_levels)
+    {
+        if level.price_decimal.is_empty() {
+            level.price_decimal = decimal_string(level.price);
+            level.amount_decimal = decimal_string(level.amount);
+        }
+        level.price = 0.0;
+        level.amount = 0.0;
+    }
+    // Rounded to the decimals of the best prices, the spread of 10.1 and 10.2 is 0.1
+    // and not 0.09999999999999964
+    let decimals = summary
+        .bids
+        .first()
+        .zip(summary.asks.first())
+        .map(|(bid, ask)| {
+            fraction_digits(&bid.price_decimal).max(fraction_digits(&ask.price_decimal))
+        });
+    summary.spread_decimal = match decimals {
+        Some(decimals) => format!("{:.*}", decimals, summary.spread),
+        None => decimal_string(summary.spread),
+    };
+    summary.spread = 0.0;
+}
+
+pub(crate) fn audit_record_to_proto(record: crate::audit::AuditRecord) -> AuditRecord {
+    AuditRecord {
+        subscription_id: record.subscription_id,
+        event: record.event,
+        rpc: record.rpc,
+        client: record.client,
+        params: record.params,
+        ip: record.ip,
+        timestamp: record.timestamp,
+        duration_ms: record.duration_ms,
+    }
+}
+
+pub(crate) fn usage_report_to_proto(report: crate::metering::UsageReport) -> UsageReport {
+    UsageReport {
+        since: report.since,
+        timestamp: report.timestamp,
+        usage: report
+            .usage
+            .into_iter()
+            .map(|line| orderbook_proto::Usage {
+                client: line.client,
+                symbol: line.symbol,
+                messages: line.messages,
+                bytes: line.bytes,
+            })
+            .collect(),
+    }
+}
+
+pub(crate) fn venue_error_to_proto(error: &ExchangeError, timestamp: u64) -> VenueError {
+    let action = match error.action {
+        ErrorAction::Resubscribe => ExchangeErrorAction::Resubscribe,
+        ErrorAction::BackOff => ExchangeErrorAction::BackOff,
+        ErrorAction::Disable => ExchangeErrorAction::Disable,
+    };
+    VenueError {
+        code: error.code.clone().unwrap_or_default(),
+        message: error.message.clone(),
+        action: action as i32,
+        timestamp,
+    }
+}
+
+pub(crate) fn inside_quote_to_proto(quote: &InsideQuote) -> orderbook_proto::InsideQuote {
+    orderbook_proto::InsideQuote {
+        price: quote.price,
+        venues: quote.venues.clone(),
+        set_by: quote.set_by.clone(),
+        since: quote.since,
+    }
+}
+
+pub(crate) fn reference_improvement_to_proto(
+    venue: &str,
+    band_bps: f64,
+    improvement: &ReferenceImprovement,
+) -> orderbook_proto::ReferenceImprovement {
+    orderbook_proto::ReferenceImprovement {
+        venue: venue.to_string(),
+        band_bps,
+        bid_improvement_bps: improvement.bid_improvement_bps,
+        ask_improvement_bps: improvement.ask_improvement_bps,
+        extra_bid_depth: improvement.extra_bid_depth,
+        extra_ask_depth: improvement.extra_ask_depth,
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orderbook_to_split_book() {
+        let level = |price: f64| PriceAmountLevel {
+            exchange: "bitstamp".to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: decimal_from_f64(1.0).unwrap(),
+        };
+        let orderbook = OrderBook {
+            bids: vec![level(10.1), level(10.0)],
+            asks: vec![level(10.2), level(10.3)],
+            spread: 10.2 - 10.1,
+            venues: Vec::new(),
+        };
+        let fields = SummaryFields::from_mask(&["bbo".to_string()]).unwrap();
+        let split_book = orderbook_to_split_book("bitstamp", "usd", &orderbook, &fields);
+        assert_eq!((split_book.bids.len(), split_book.asks.len()), (1, 1));
+        assert_eq!(split_book.quote, "usd");
+
+        let mut summary = orderbook_to_summary(&orderbook, &SummaryFields::all());
+        summary.split_books = vec![split_book];
+        encode_decimal_prices(&mut summary);
+        assert_eq!(summary.split_books[0].asks[0].price_decimal, "10.2");
+        assert_eq!(summary.split_books[0].asks[0].price, 0.0);
+    }
+
+    #[test]
+    fn test_encode_decimal_prices() {
+        let level = |price: f64, amount: f64| PriceAmountLevel {
+            exchange: "binance".to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: decimal_from_f64(amount).unwrap(),
+        };
+        let orderbook = OrderBook {
+            bids: vec![level(10.1, 0.3), level(10.0, 1.0)],
+            asks: vec![level(10.2, 0.1 + 0.2)],
+            spread: 10.2 - 10.1,
+            venues: Vec::new(),
+        };
+        let mut summary = orderbook_to_summary(&orderbook, &SummaryFields::all());
+        let checksum = summary_checksum(&summary);
+        encode_decimal_prices(&mut summary);
+
+        assert_eq!(summary.bids[0].price_decimal, "10.1");
+        assert_eq!(summary.asks[0].amount_decimal, "0.30000000000000004");
+        assert_eq!(summary.spread_decimal, "0.1");
+        assert_eq!((summary.bids[0].price, summary.spread), (0.0, 0.0));
+        // Parsed back, the levels match the checksum of the doubles
+        for level in summary.bids.iter_mut().chain(summary.asks.iter_mut()) {
+            level.price = level.price_decimal.parse().unwrap();
+            level.amount = level.amount_decimal.parse().unwrap();
+        }
+        assert_eq!(summary_checksum(&summary), checksum);
+
+        // From the decimals of the levels, with the digits a double can't hold
+        let mut fields = SummaryFields::all();
+        fields.decimal_prices = true;
+        let mut orderbook = orderbook;
+        orderbook.bids[0].price = "64123.123456789012345".parse().unwrap();
+        let mut summary = orderbook_to_summary(&orderbook, &fields);
+        encode_decimal_prices(&mut summary);
+        assert_eq!(summary.bids[0].price_decimal, "64123.123456789012345");
+        assert_eq!(summary.bids[1].amount_decimal, "1");
+    }
+}
@@ -0,0 +1,25 @@
+// The gRPC API of the server, the types generated from proto/orderbook.proto, the
+// conversions of the book types into them and the RPCs of the aggregator's service
+pub(crate) mod convert;
+mod service;
+
+// Version of the Summary's schema, bumped by the changes the clients have to understand
+// to read the summaries, e.g. deltas instead of full books or decimal prices only
+pub const SUMMARY_SCHEMA_VERSION: u32 = 1;
+
+// Whether this build reads the summaries of a server sending the schema version, the
+// servers from before the versions send 0
+pub fn check_summary_schema(version: u32) -> Result<(), String> {
+    if version > SUMMARY_SCHEMA_VERSION {
+        return Err(format!(
+            "the server sends summaries of schema version {}, this client reads up to \
+             version {}, upgrade the client",
+            version, SUMMARY_SCHEMA_VERSION
+        ));
+    }
+    Ok(())
+}
+
+pub mod orderbook_proto {
+    tonic::include_proto!("orderbook");
+}
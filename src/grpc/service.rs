@@ -0,0 +1,555 @@
+// The RPCs of the OrderbookAggregator service, on the state of the symbol's server
+use super::convert::{
+    audit_record_to_proto, inside_quote_to_proto, usage_report_to_proto, venue_error_to_proto,
+};
+use super::orderbook_proto::orderbook_aggregator_server::OrderbookAggregator;
+use super::orderbook_proto::{
+    Alert, AuditQuery, AuditRecords, Basis, BasisRequest, BboAttributionReport, Empty,
+    FeedStatusReport, IndexPrice, IndexRequest, ListSymbolsRequest, PriceEncoding, SnapshotRequest,
+    SummariesSince, SummariesSinceRequest, Summary, SummaryRequest, SymbolInfo, SymbolList,
+    UsageQuery, UsageReport, VenueBboAttribution, VenueFeed,
+};
+use crate::aggregator::{
+    client_name, forward_summaries, pipeline_align_interval, process_basis_messages,
+    process_index_messages, ClientSender, ClientSubscription, OrderbookAggregatorService,
+};
+use crate::audit::AuditFilter;
+use crate::bbo_attribution::time_share;
+use crate::connectors::connect_connector_async;
+use crate::emission::EmissionPolicy;
+use crate::feed_status::{feed_status, unavailable_venues};
+use crate::projection::SummaryFields;
+
+use futures::stream::{Stream, StreamExt};
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::mpsc::channel;
+use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
+use tonic::{Code, Request, Response, Status};
+
+#[tonic::async_trait]
+impl OrderbookAggregator for OrderbookAggregatorService {
+    type BookSummaryStream =
+        Pin<Box<dyn Stream<Item = Result<Summary, Status>> + Send + Sync + 'static>>;
+
+    type BasisStreamStream =
+        Pin<Box<dyn Stream<Item = Result<Basis, Status>> + Send + Sync + 'static>>;
+
+    type AlertsStream = Pin<Box<dyn Stream<Item = Result<Alert, Status>> + Send + Sync + 'static>>;
+
+    type IndexPriceStreamStream =
+        Pin<Box<dyn Stream<Item = Result<IndexPrice, Status>> + Send + Sync + 'static>>;
+
+    #[allow(clippy::result_large_err)]
+    async fn book_summary(
+        &self,
+        request: Request<SummaryRequest>,
+    ) -> Result<Response<Self::BookSummaryStream>, Status> {
+        let tenant = self.authorize(&request, &[&self.symbol])?;
+        let pipeline = self.pipeline(&self.symbol);
+        let depth = tenant
+            .as_ref()
+            .map_or(pipeline.depth, |tenant| tenant.depth(pipeline.depth));
+        let audit_guard = self.audit(
+            &request,
+            "BookSummary",
+            &tenant,
+            format!(
+                "symbol={} depth={} fields={} align_interval_ms={} trigger={:?} encoding={:?}",
+                self.symbol,
+                depth,
+                request.get_ref().fields.join(","),
+                request.get_ref().align_interval_ms,
+                request.get_ref().trigger(),
+                request.get_ref().price_encoding()
+            ),
+        );
+        let summary_request = request.into_inner();
+        let mut fields =
+            SummaryFields::from_mask(&summary_request.fields).map_err(Status::invalid_argument)?;
+        // Analytics turned off for the symbol aren't computed whatever the mask
+        fields.fair_value &= pipeline.fair_value;
+        fields.toxicity &= pipeline.toxicity;
+        fields.decimal_prices = summary_request.price_encoding() == PriceEncoding::String;
+        let emission_policy =
+            EmissionPolicy::from_request(&summary_request).map_err(Status::invalid_argument)?;
+        // The feed is already aligned on the symbol's conflation interval, the shortest
+        // one a subscription gets
+        let align_interval = Some(summary_request.align_interval_ms)
+            .filter(|interval_ms| *interval_ms > 0)
+            .map(|interval_ms| Duration::from_millis(interval_ms.into()))
+            .filter(|interval| Some(*interval) > pipeline_align_interval(&pipeline));
+        let (sender, receiver) = channel(100);
+        let sender = ClientSender::new(
+            sender,
+            tenant,
+            self.symbol.clone(),
+            Arc::clone(&self.usage_meter),
+            Arc::clone(&self.clock),
+        );
+        let subscription = Arc::new(ClientSubscription {
+            id: self.next_subscription_id.fetch_add(1, Ordering::Relaxed),
+            sender,
+            depth,
+            fields,
+            align_interval,
+            emission_policy: Mutex::new(emission_policy),
+            latest: Mutex::new(None),
+        });
+        {
+            let mut subscriptions = self.subscriptions.lock().unwrap();
+            // Subscriptions ended by a failed task are only dropped here
+            subscriptions.retain(|_, subscription| subscription.strong_count() > 0);
+            subscriptions.insert(subscription.id, Arc::downgrade(&subscription));
+        }
+        let feed_receiver = self.subscribe_feed();
+        self.runtime
+            .spawn(forward_summaries(subscription, feed_receiver, self.clone()));
+
+        let stream = ReceiverStream::new(receiver).map(move |result: Result<Summary, ()>| {
+            let _audit_guard = &audit_guard;
+            result.map_err(|_| Status::new(Code::Internal, "Unknown error occurred"))
+        });
+
+        let response_stream: Self::BookSummaryStream = Box::pin(stream);
+        Ok(Response::new(response_stream))
+    }
+
+    // Connects to the requested spot and perp venues for this subscription only,
+    // so any symbol pair can be monitored independently of the server's main symbol
+    #[allow(clippy::result_large_err)]
+    async fn basis_stream(
+        &self,
+        request: Request<BasisRequest>,
+    ) -> Result<Response<Self::BasisStreamStream>, Status> {
+        let tenant = {
+            let basis_request = request.get_ref();
+            self.authorize(
+                &request,
+                &[&basis_request.spot_symbol, &basis_request.perp_symbol],
+            )?
+        };
+        let pipeline_depth = self.pipeline(&request.get_ref().spot_symbol).depth;
+        let depth = tenant
+            .as_ref()
+            .map_or(pipeline_depth, |tenant| tenant.depth(pipeline_depth));
+        let audit_guard = {
+            let basis_request = request.get_ref();
+            self.audit(
+                &request,
+                "BasisStream",
+                &tenant,
+                format!(
+                    "spot={}:{} perp={}:{} depth={}",
+                    basis_request.spot_exchange,
+                    basis_request.spot_symbol,
+                    basis_request.perp_exchange,
+                    basis_request.perp_symbol,
+                    depth
+                ),
+            )
+        };
+        let basis_request = request.into_inner();
+
+        let spot_connector = self
+            .new_connector(&basis_request.spot_exchange, false, depth)
+            .ok_or_else(|| {
+                Status::invalid_argument(format!(
+                    "Unsupported spot exchange: {}",
+                    basis_request.spot_exchange
+                ))
+            })?;
+        let perp_connector = self
+            .new_connector(&basis_request.perp_exchange, true, depth)
+            .ok_or_else(|| {
+                Status::invalid_argument(format!(
+                    "Unsupported perp exchange: {}",
+                    basis_request.perp_exchange
+                ))
+            })?;
+
+        let (spot_connector, spot_socket) =
+            connect_connector_async(spot_connector, &basis_request.spot_symbol).await;
+        let spot_socket = spot_socket.map_err(|err| Status::unavailable(err.to_string()))?;
+        let (perp_connector, perp_socket) =
+            connect_connector_async(perp_connector, &basis_request.perp_symbol).await;
+        let perp_socket = perp_socket.map_err(|err| Status::unavailable(err.to_string()))?;
+
+        let (sender, receiver) = channel(100);
+        let basis_sender = Arc::new(ClientSender::new(
+            sender,
+            tenant,
+            format!(
+                "{}/{}",
+                basis_request.spot_symbol, basis_request.perp_symbol
+            ),
+            Arc::clone(&self.usage_meter),
+            Arc::clone(&self.clock),
+        ));
+        let reconnect_limiter = Arc::clone(&self.reconnect_limiter);
+
+        self.runtime.spawn(async move {
+            let subscription_result = process_basis_messages(
+                basis_sender,
+                basis_request,
+                depth,
+                reconnect_limiter,
+                spot_connector,
+                spot_socket,
+                perp_connector,
+                perp_socket,
+            )
+            .await;
+
+            if let Err(err) = subscription_result {
+                eprintln!("Error during basis subscription: {}", err);
+            }
+        });
+
+        let stream = ReceiverStream::new(receiver).map(move |result: Result<Basis, ()>| {
+            let _audit_guard = &audit_guard;
+            result.map_err(|_| Status::new(Code::Internal, "Unknown error occurred"))
+        });
+
+        let response_stream: Self::BasisStreamStream = Box::pin(stream);
+        Ok(Response::new(response_stream))
+    }
+
+    // Connects to the venues of the index for this subscription only, like basis_stream,
+    // the server's symbol is read on every venue
+    #[allow(clippy::result_large_err)]
+    async fn index_price_stream(
+        &self,
+        request: Request<IndexRequest>,
+    ) -> Result<Response<Self::IndexPriceStreamStream>, Status> {
+        let tenant = self.authorize(&request, &[&self.symbol])?;
+        let pipeline_depth = self.pipeline(&self.symbol).depth;
+        let depth = tenant
+            .as_ref()
+            .map_or(pipeline_depth, |tenant| tenant.depth(pipeline_depth));
+        let name = request.get_ref().name.clone();
+        let audit_guard = self.audit(
+            &request,
+            "IndexPriceStream",
+            &tenant,
+            format!("index={} symbol={} depth={}", name, self.symbol, depth),
+        );
+        let formula = self.indexes.get(&name).cloned().ok_or_else(|| {
+            Status::not_found(format!(
+                "Unknown index {}, expected one of {}",
+                name,
+                self.indexes.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))
+        })?;
+
+        let mut venues = Vec::new();
+        for exchange in &formula.venues {
+            let connector = self.new_connector(exchange, false, depth).ok_or_else(|| {
+                Status::invalid_argument(format!("Unsupported exchange: {}", exchange))
+            })?;
+            let symbol = if exchange == "bitstamp" {
+                self.bitstamp_symbol.clone()
+            } else {
+                self.symbol.clone()
+            };
+            let (connector, socket) = connect_connector_async(connector, &symbol).await;
+            let socket = socket.map_err(|err| Status::unavailable(err.to_string()))?;
+            venues.push((exchange.clone(), symbol, connector, socket));
+        }
+
+        let (sender, receiver) = channel(100);
+        let index_sender = Arc::new(ClientSender::new(
+            sender,
+            tenant,
+            self.symbol.clone(),
+            Arc::clone(&self.usage_meter),
+            Arc::clone(&self.clock),
+        ));
+        let reconnect_limiter = Arc::clone(&self.reconnect_limiter);
+
+        self.runtime.spawn(async move {
+            let subscription_result = process_index_messages(
+                index_sender,
+                name,
+                formula,
+                depth,
+                venues,
+                reconnect_limiter,
+            )
+            .await;
+
+            if let Err(err) = subscription_result {
+                eprintln!("Error during index subscription: {}", err);
+            }
+        });
+
+        let stream = ReceiverStream::new(receiver).map(move |result: Result<IndexPrice, ()>| {
+            let _audit_guard = &audit_guard;
+            result.map_err(|_| Status::new(Code::Internal, "Unknown error occurred"))
+        });
+
+        let response_stream: Self::IndexPriceStreamStream = Box::pin(stream);
+        Ok(Response::new(response_stream))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn alerts(
+        &self,
+        request: Request<Empty>,
+    ) -> Result<Response<Self::AlertsStream>, Status> {
+        let tenant = self.authorize(&request, &[])?;
+        let audit_guard = self.audit(&request, "Alerts", &tenant, String::new());
+
+        // Alerts missed by a lagging client are skipped rather than ending the stream
+        let stream = BroadcastStream::new(self.alert_sender.subscribe())
+            .filter_map(|alert| async move { alert.ok().map(Ok) })
+            .map(move |alert| {
+                let _audit_guard = &audit_guard;
+                alert
+            });
+
+        let response_stream: Self::AlertsStream = Box::pin(stream);
+        Ok(Response::new(response_stream))
+    }
+
+    // Admin RPC for compliance reviews, only admin tenants may query it. The records hold
+    // the IPs and parameters of every client, so without tenants nobody can
+    #[allow(clippy::result_large_err)]
+    async fn query_audit_log(
+        &self,
+        request: Request<AuditQuery>,
+    ) -> Result<Response<AuditRecords>, Status> {
+        let tenant = self.authorize(&request, &[])?.ok_or_else(|| {
+            Status::failed_precondition(
+                "The audit log is only queried by admin tenants, the server runs without tenants",
+            )
+        })?;
+        if !tenant.config.admin {
+            return Err(Status::permission_denied(format!(
+                "Namespace {} is not an admin",
+                tenant.config.namespace
+            )));
+        }
+        let audit_log = self
+            .audit_log
+            .as_ref()
+            .ok_or_else(|| Status::failed_precondition("The server runs without audit log"))?;
+
+        let query = request.into_inner();
+        let filter = AuditFilter {
+            client: query.client,
+            rpc: query.rpc,
+            since: query.since,
+            limit: query.limit as usize,
+        };
+        let records = audit_log
+            .query(&filter)
+            .map_err(|err| Status::internal(err.to_string()))?;
+
+        Ok(Response::new(AuditRecords {
+            records: records.into_iter().map(audit_record_to_proto).collect(),
+        }))
+    }
+
+    // Tenants see their own usage, admins (or everyone without tenants) any client's
+    #[allow(clippy::result_large_err)]
+    async fn get_usage_report(
+        &self,
+        request: Request<UsageQuery>,
+    ) -> Result<Response<UsageReport>, Status> {
+        let tenant = self.authorize(&request, &[])?;
+        let query = request.into_inner();
+        let client = match &tenant {
+            Some(tenant) if !tenant.config.admin => Some(tenant.config.namespace.as_str()),
+            _ if query.client.is_empty() => None,
+            _ => Some(query.client.as_str()),
+        };
+
+        let report = self.usage_meter.report(client);
+        Ok(Response::new(usage_report_to_proto(report)))
+    }
+
+    // The venues as read by the subscriptions, with the last error event of each exchange
+    #[allow(clippy::result_large_err)]
+    async fn get_feed_status(
+        &self,
+        request: Request<Empty>,
+    ) -> Result<Response<FeedStatusReport>, Status> {
+        self.authorize(&request, &[])?;
+        let feed_monitor = self.feed_monitor.lock().unwrap();
+        let latency_estimator = self.latency_estimator.lock().unwrap();
+        let max_skew = self.latency_compensation.unwrap_or_default().as_millis() as u64;
+        let stale_venues = feed_monitor.stale_venues(
+            &self.venues,
+            self.started_at,
+            self.clock.now_millis(),
+            self.stale_after.as_millis() as u64,
+        );
+        let venues = self
+            .venues
+            .iter()
+            .map(|exchange| VenueFeed {
+                exchange: exchange.clone(),
+                last_update: feed_monitor.last_update(exchange).unwrap_or(0),
+                stale: stale_venues.contains(exchange),
+                disabled: feed_monitor.is_disabled(exchange),
+                last_error: feed_monitor
+                    .last_error(exchange)
+                    .map(|(error, timestamp)| venue_error_to_proto(error, *timestamp)),
+                latency_ms: latency_estimator.latency_ms(exchange).unwrap_or(0.0),
+                compensation_delay_ms: latency_estimator.compensation_delay(exchange, max_skew),
+                empty_book: feed_monitor.has_empty_book(exchange),
+                duplicate_levels: feed_monitor.duplicate_levels(exchange),
+                resyncs: feed_monitor.resyncs(exchange),
+            })
+            .collect();
+        let empty_book_venues: Vec<String> = self
+            .venues
+            .iter()
+            .filter(|exchange| feed_monitor.has_empty_book(exchange))
+            .cloned()
+            .collect();
+        let mut report = FeedStatusReport {
+            venues,
+            ..Default::default()
+        };
+        let unavailable = unavailable_venues(&stale_venues, &empty_book_venues);
+        report.set_status(feed_status(self.venues.len(), unavailable));
+        Ok(Response::new(report))
+    }
+
+    // Sends the latest book of the feed again on a BookSummary stream of the same tenant,
+    // whatever the stream's emission policy. Streams of the other tenants are reported as
+    // not found
+    #[allow(clippy::result_large_err)]
+    async fn request_snapshot(
+        &self,
+        request: Request<SnapshotRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        let tenant = self.authorize(&request, &[])?;
+        let subscription_id = request.get_ref().subscription_id;
+        let subscription = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .get(&subscription_id)
+            .and_then(Weak::upgrade)
+            .filter(|subscription| client_name(&subscription.sender.tenant) == client_name(&tenant))
+            .ok_or_else(|| Status::not_found(format!("No subscription {}", subscription_id)))?;
+        if !subscription.send_snapshot() {
+            return Err(Status::unavailable("No venue book received yet"));
+        }
+        Ok(Response::new(Empty {}))
+    }
+
+    // Time each venue spent at the consolidated best bid and offer since the server
+    // started, with the current inside
+    #[allow(clippy::result_large_err)]
+    async fn get_bbo_attribution(
+        &self,
+        request: Request<Empty>,
+    ) -> Result<Response<BboAttributionReport>, Status> {
+        self.authorize(&request, &[])?;
+        let bbo_attribution = self.bbo_attribution.lock().unwrap();
+        let (tracked_ms, venues) = bbo_attribution.report(self.clock.now_millis());
+        let venues = venues
+            .into_iter()
+            .map(|(exchange, attribution)| VenueBboAttribution {
+                exchange,
+                bid_time_pct: time_share(attribution.bid_ms, tracked_ms),
+                ask_time_pct: time_share(attribution.ask_ms, tracked_ms),
+                bid_time_ms: attribution.bid_ms,
+                ask_time_ms: attribution.ask_ms,
+                bid_sets: attribution.bid_sets,
+                ask_sets: attribution.ask_sets,
+            })
+            .collect();
+        Ok(Response::new(BboAttributionReport {
+            tracked_ms,
+            best_bid: bbo_attribution.best_bid().map(inside_quote_to_proto),
+            best_ask: bbo_attribution.best_ask().map(inside_quote_to_proto),
+            venues,
+        }))
+    }
+
+    // The server's symbol and the ones with a depth in [symbols], the BasisStream legs
+    // (check_symbol_settings), for the pickers of the client UIs, without the symbols
+    // the client's tenant isn't allowed
+    #[allow(clippy::result_large_err)]
+    async fn list_symbols(
+        &self,
+        request: Request<ListSymbolsRequest>,
+    ) -> Result<Response<SymbolList>, Status> {
+        let tenant = self.authorize(&request, &[])?;
+        let include_venues = request.get_ref().include_venues;
+        let mut symbols = vec![self.symbol.clone()];
+        symbols.extend(
+            self.symbol_settings
+                .keys()
+                .filter(|symbol| **symbol != self.symbol)
+                .cloned(),
+        );
+        if let Some(tenant) = &tenant {
+            symbols.retain(|symbol| tenant.allows_symbol(symbol));
+        }
+
+        let symbols = symbols
+            .into_iter()
+            .map(|symbol| {
+                let book_summary = symbol == self.symbol;
+                let depth = self.pipeline(&symbol).depth;
+                let venues = if include_venues && book_summary {
+                    self.venue_symbols(depth)
+                } else {
+                    Vec::new()
+                };
+                SymbolInfo {
+                    symbol,
+                    book_summary,
+                    depth,
+                    venues,
+                }
+            })
+            .collect();
+        Ok(Response::new(SymbolList { symbols }))
+    }
+
+    // The summaries kept in memory after the client's sequence, for clients polling
+    // instead of streaming. The history is a sink, it is fed from the startup on
+    // whether or not a BookSummary stream is open
+    #[allow(clippy::result_large_err)]
+    async fn get_summaries_since(
+        &self,
+        request: Request<SummariesSinceRequest>,
+    ) -> Result<Response<SummariesSince>, Status> {
+        let symbol = match request.get_ref().symbol.as_str() {
+            "" => self.symbol.clone(),
+            symbol => symbol.to_string(),
+        };
+        let tenant = self.authorize(&request, &[&symbol])?;
+        if symbol != self.symbol {
+            return Err(Status::not_found(format!(
+                "Summaries are only kept for {}",
+                self.symbol
+            )));
+        }
+        let history = self.summary_history.as_ref().ok_or_else(|| {
+            Status::failed_precondition("The server doesn't keep summaries, see --summary-history")
+        })?;
+        let sequence = request.get_ref().sequence;
+        let _audit_guard = self.audit(
+            &request,
+            "GetSummariesSince",
+            &tenant,
+            format!("symbol={} sequence={}", symbol, sequence),
+        );
+        let (summaries, truncated) = history.since(sequence);
+        Ok(Response::new(SummariesSince {
+            summaries,
+            truncated,
+            latest_sequence: history.latest_sequence(),
+        }))
+    }
+}
@@ -0,0 +1,125 @@
+use crate::orderbook_proto::Summary;
+use crate::sink::{SinkError, SummarySink};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+// The latest summaries of the server's symbol kept in memory (--summary-history), for
+// GetSummariesSince. Fed as a sink, so with every field of the summaries
+#[derive(Debug)]
+pub struct SummaryHistory {
+    capacity: usize,
+    state: Mutex<HistoryState>,
+}
+
+#[derive(Debug, Default)]
+struct HistoryState {
+    summaries: VecDeque<Summary>,
+    // Sequence of the latest summary dropped to make room, 0 before the first one
+    evicted_through: u64,
+}
+
+impl SummaryHistory {
+    pub fn new(capacity: usize) -> SummaryHistory {
+        SummaryHistory {
+            capacity,
+            state: Mutex::new(HistoryState::default()),
+        }
+    }
+
+    pub fn push(&self, summary: Summary) {
+        let mut state = self.state.lock().unwrap();
+        // Every subscription sends the book of a sequence, and a tick or a status change
+        // sends it again, the latest of a sequence replaces the one kept
+        if let Some(last) = state.summaries.back_mut() {
+            if last.sequence == summary.sequence {
+                *last = summary;
+                return;
+            }
+            if last.sequence > summary.sequence {
+                return;
+            }
+        }
+        state.summaries.push_back(summary);
+        while state.summaries.len() > self.capacity {
+            if let Some(evicted) = state.summaries.pop_front() {
+                state.evicted_through = evicted.sequence;
+            }
+        }
+    }
+
+    // The summaries with a sequence above the given one, oldest first, and whether some
+    // of them were already dropped
+    pub fn since(&self, sequence: u64) -> (Vec<Summary>, bool) {
+        let state = self.state.lock().unwrap();
+        let summaries = state
+            .summaries
+            .iter()
+            .filter(|summary| summary.sequence > sequence)
+            .cloned()
+            .collect();
+        (summaries, sequence < state.evicted_through)
+    }
+
+    pub fn latest_sequence(&self) -> u64 {
+        let state = self.state.lock().unwrap();
+        state.summaries.back().map_or(0, |summary| summary.sequence)
+    }
+}
+
+impl SummarySink for SummaryHistory {
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    fn send(&self, summary: &Summary) -> Result<(), SinkError> {
+        self.push(summary.clone());
+        Ok(())
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(sequence: u64, spread: f64) -> Summary {
+        Summary {
+            sequence,
+            spread,
+            ..Default::default()
+        }
+    }
+
+    fn sequences(summaries: &[Summary]) -> Vec<u64> {
+        summaries.iter().map(|summary| summary.sequence).collect()
+    }
+
+    #[test]
+    fn test_summaries_since() {
+        let history = SummaryHistory::new(3);
+        assert_eq!(history.since(0), (Vec::new(), false));
+        assert_eq!(history.latest_sequence(), 0);
+
+        for sequence in 1..=3 {
+            history.push(summary(sequence, 1.0));
+        }
+        // A second subscription's summary of the same sequence replaces the first, an
+        // older one is ignored
+        history.push(summary(3, 2.0));
+        history.push(summary(2, 1.0));
+        let (summaries, truncated) = history.since(1);
+        assert_eq!(sequences(&summaries), vec![2, 3]);
+        assert_eq!(summaries[1].spread, 2.0);
+        assert!(!truncated);
+
+        history.push(summary(5, 1.0));
+        history.push(summary(6, 1.0));
+        assert_eq!(history.latest_sequence(), 6);
+        let (summaries, truncated) = history.since(1);
+        assert_eq!(sequences(&summaries), vec![3, 5, 6]);
+        assert!(truncated);
+        // Nothing was missed from sequence 2 on
+        assert!(!history.since(2).1);
+        assert!(history.since(6).0.is_empty());
+    }
+}
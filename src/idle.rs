@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdleTransition {
+    Suspend,
+    Resume,
+}
+
+// Tracks how long the symbol went without a subscriber, in ms of the server's clock.
+// Its exchange connections are suspended once it was idle for idle_after, and resumed
+// by the next subscription
+#[derive(Debug)]
+pub struct IdleTracker {
+    idle_after: u64,
+    idle_since: Option<u64>,
+    suspended: bool,
+}
+
+impl IdleTracker {
+    pub fn new(idle_after: Duration) -> IdleTracker {
+        IdleTracker {
+            idle_after: idle_after.as_millis() as u64,
+            idle_since: None,
+            suspended: false,
+        }
+    }
+
+    pub fn update(&mut self, in_use: bool, now: u64) -> Option<IdleTransition> {
+        if in_use {
+            self.idle_since = None;
+            if self.suspended {
+                self.suspended = false;
+                return Some(IdleTransition::Resume);
+            }
+            return None;
+        }
+        let idle_since = *self.idle_since.get_or_insert(now);
+        if !self.suspended && now.saturating_sub(idle_since) >= self.idle_after {
+            self.suspended = true;
+            return Some(IdleTransition::Suspend);
+        }
+        None
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_tracker() {
+        let mut tracker = IdleTracker::new(Duration::from_secs(60));
+        assert_eq!(tracker.update(false, 1_000), None);
+        assert_eq!(tracker.update(false, 60_000), None);
+
+        // A subscriber in between starts the idle period over
+        assert_eq!(tracker.update(true, 61_000), None);
+        assert_eq!(tracker.update(false, 70_000), None);
+        assert_eq!(tracker.update(false, 129_999), None);
+        assert_eq!(
+            tracker.update(false, 130_000),
+            Some(IdleTransition::Suspend)
+        );
+        // Suspended once
+        assert_eq!(tracker.update(false, 200_000), None);
+
+        assert_eq!(tracker.update(true, 210_000), Some(IdleTransition::Resume));
+        assert_eq!(tracker.update(true, 211_000), None);
+    }
+}
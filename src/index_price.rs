@@ -0,0 +1,177 @@
+use crate::basis::mid_price;
+use crate::book::OrderBook;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+fn default_min_venues() -> usize {
+    1
+}
+
+// An index price over the mids of selected venues, defined in the config file like
+// exchange index prices, e.g.
+// [indexes.btc]
+// venues = ["binance", "bitstamp", "kraken", "coinbase"]
+// weights = { binance = 2.0 }
+// max_deviation_bps = 150
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IndexFormula {
+    pub venues: Vec<String>,
+    // 1 for the venues without a weight
+    #[serde(default)]
+    pub weights: BTreeMap<String, f64>,
+    // Venues whose mid is further than this from the median mid are left out
+    pub max_deviation_bps: Option<f64>,
+    // There is no index with fewer constituents left
+    #[serde(default = "default_min_venues")]
+    pub min_venues: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexConstituent {
+    pub exchange: String,
+    pub mid: f64,
+    // Share of the index, the weights of the constituents add up to 1
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexPrice {
+    pub price: f64,
+    pub constituents: Vec<IndexConstituent>,
+    // Venues of the formula without a top of book or trimmed as outliers
+    pub excluded: Vec<String>,
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[middle - 1] + values[middle]) / 2.0)
+    } else {
+        Some(values[middle])
+    }
+}
+
+impl IndexFormula {
+    // The index of the venues' books, given as (exchange, book). Venues that aren't in
+    // the formula are ignored
+    pub fn compute(&self, venues: &[(&str, &OrderBook)]) -> Option<IndexPrice> {
+        let mut mids = Vec::new();
+        let mut excluded = Vec::new();
+        for exchange in &self.venues {
+            let weight = self.weights.get(exchange).copied().unwrap_or(1.0);
+            let mid = venues
+                .iter()
+                .find(|(venue, _)| venue == exchange)
+                .and_then(|(_, orderbook)| mid_price(orderbook));
+            match mid {
+                Some(mid) if weight > 0.0 && mid.is_finite() => {
+                    mids.push((exchange.clone(), mid, weight))
+                }
+                _ => excluded.push(exchange.clone()),
+            }
+        }
+
+        // Outliers are trimmed around the median, which a single bad venue can't move
+        if let Some(max_deviation_bps) = self.max_deviation_bps {
+            let median_mid = median(&mut mids.iter().map(|(_, mid, _)| *mid).collect::<Vec<_>>())?;
+            mids.retain(|(exchange, mid, _)| {
+                let within = (mid - median_mid).abs() / median_mid * 10_000.0 <= max_deviation_bps;
+                if !within {
+                    excluded.push(exchange.clone());
+                }
+                within
+            });
+        }
+        if mids.is_empty() || mids.len() < self.min_venues {
+            return None;
+        }
+
+        let total_weight: f64 = mids.iter().map(|(_, _, weight)| weight).sum();
+        let constituents: Vec<IndexConstituent> = mids
+            .into_iter()
+            .map(|(exchange, mid, weight)| IndexConstituent {
+                exchange,
+                mid,
+                weight: weight / total_weight,
+            })
+            .collect();
+        Some(IndexPrice {
+            price: constituents
+                .iter()
+                .map(|constituent| constituent.mid * constituent.weight)
+                .sum(),
+            constituents,
+            excluded,
+        })
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PriceAmountLevel;
+    use crate::number::decimal_from_f64;
+    use rust_decimal_macros::dec;
+
+    fn orderbook(bid: f64, ask: f64) -> OrderBook {
+        let level = |price| PriceAmountLevel {
+            exchange: "exchange1".to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: dec!(1.0),
+        };
+        OrderBook {
+            bids: vec![level(bid)],
+            asks: vec![level(ask)],
+            spread: bid - ask,
+            venues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_compute_index() {
+        let formula: IndexFormula = toml::from_str(
+            r#"
+            venues = ["binance", "bitstamp", "kraken", "coinbase"]
+            weights = { binance = 2.0 }
+            max_deviation_bps = 150
+            min_venues = 2
+            "#,
+        )
+        .unwrap();
+        let binance = orderbook(99.0, 101.0);
+        let bitstamp = orderbook(100.0, 102.0);
+        let kraken = orderbook(109.0, 111.0);
+        let venues = [
+            ("binance", &binance),
+            ("bitstamp", &bitstamp),
+            ("kraken", &kraken),
+            ("bybit", &binance),
+        ];
+
+        let index = formula.compute(&venues).unwrap();
+
+        // kraken is 10% away from the median, coinbase has no book
+        assert_eq!(
+            index.excluded,
+            vec!["coinbase".to_string(), "kraken".to_string()]
+        );
+        assert_eq!(index.constituents.len(), 2);
+        assert!((index.constituents[0].weight - 2.0 / 3.0).abs() < 1e-9);
+        assert!((index.price - (100.0 * 2.0 + 101.0) / 3.0).abs() < 1e-9);
+
+        assert!(formula.compute(&venues[..1]).is_none());
+    }
+
+    #[test]
+    fn test_median_nan() {
+        assert_eq!(median(&mut [3.0, f64::NAN, 1.0, 2.0]), Some(2.5));
+        assert_eq!(median(&mut [2.0, 1.0, 3.0]), Some(2.0));
+        assert_eq!(median(&mut []), None);
+    }
+}
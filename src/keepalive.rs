@@ -0,0 +1,122 @@
+use crate::connectors::ExchangeConnector;
+use std::time::{Duration, Instant};
+use tungstenite::Message;
+
+// A socket quiet for this long gets a keepalive, so neither the exchange nor a proxy
+// in between closes it as idle
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+// A socket without any frame for this long, the answers to the keepalives included,
+// is dead without having been closed and is reconnected
+pub const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(90);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeepaliveAction {
+    Send,
+    Reconnect,
+}
+
+// When to send the keepalives of a socket and when to give up on it
+#[derive(Debug)]
+pub struct Keepalive {
+    interval: Duration,
+    timeout: Duration,
+    last_received: Instant,
+    last_sent: Instant,
+}
+
+impl Keepalive {
+    pub fn new(interval: Duration, timeout: Duration) -> Keepalive {
+        let now = Instant::now();
+        Keepalive {
+            interval,
+            timeout,
+            last_received: now,
+            last_sent: now,
+        }
+    }
+
+    // Any frame counts, a pong as much as a book
+    pub fn received(&mut self, now: Instant) {
+        self.last_received = now;
+    }
+
+    // A new socket starts quiet
+    pub fn reset(&mut self, now: Instant) {
+        self.last_received = now;
+        self.last_sent = now;
+    }
+
+    fn next_check(&self) -> Instant {
+        let send_at = self.last_received.max(self.last_sent) + self.interval;
+        send_at.min(self.last_received + self.timeout)
+    }
+
+    pub fn due(&mut self, now: Instant) -> Option<KeepaliveAction> {
+        if now >= self.last_received + self.timeout {
+            return Some(KeepaliveAction::Reconnect);
+        }
+        if now >= self.last_received.max(self.last_sent) + self.interval {
+            self.last_sent = now;
+            return Some(KeepaliveAction::Send);
+        }
+        None
+    }
+
+    // Completes with the next action, for a select! with the socket's reads
+    pub async fn tick(&mut self) -> KeepaliveAction {
+        loop {
+            tokio::time::sleep_until(self.next_check().into()).await;
+            if let Some(action) = self.due(Instant::now()) {
+                return action;
+            }
+        }
+    }
+}
+
+// The exchange's own heartbeat where it has one, a websocket ping otherwise
+pub(crate) fn keepalive_frame(connector: &dyn ExchangeConnector) -> Message {
+    match connector.keepalive_message() {
+        Some(message) => Message::Text(message),
+        None => Message::Ping(Vec::new()),
+    }
+}
+
+// Pings are answered by tungstenite on the next read, pongs and the close handshake
+// carry nothing for the parsers
+pub(crate) fn is_control_frame(message: &Message) -> bool {
+    message.is_ping() || message.is_pong() || message.is_close()
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keepalive_due() {
+        let mut keepalive = Keepalive::new(Duration::from_secs(30), Duration::from_secs(90));
+        let start = keepalive.last_received;
+        assert_eq!(keepalive.due(start + Duration::from_secs(10)), None);
+
+        // Books keep the socket alive without keepalives
+        keepalive.received(start + Duration::from_secs(20));
+        assert_eq!(keepalive.due(start + Duration::from_secs(40)), None);
+
+        // Quiet for 30 s, then once per interval
+        let quiet = start + Duration::from_secs(50);
+        assert_eq!(keepalive.due(quiet), Some(KeepaliveAction::Send));
+        assert_eq!(keepalive.due(quiet + Duration::from_secs(1)), None);
+        assert_eq!(
+            keepalive.due(quiet + Duration::from_secs(30)),
+            Some(KeepaliveAction::Send)
+        );
+
+        // Nothing came back
+        assert_eq!(
+            keepalive.due(start + Duration::from_secs(110)),
+            Some(KeepaliveAction::Reconnect)
+        );
+        keepalive.reset(start + Duration::from_secs(110));
+        assert_eq!(keepalive.due(start + Duration::from_secs(120)), None);
+    }
+}
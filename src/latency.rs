@@ -0,0 +1,134 @@
+use std::collections::{HashMap, VecDeque};
+
+// Weight of a new sample in the moving average of a venue's latency
+const LATENCY_SMOOTHING: f64 = 0.1;
+
+// Moving average of each venue's latency, the time from the exchange's timestamp of a
+// message to its receipt by the server. The clock offset of the exchange is part of it,
+// which is fine for comparing the venues as long as the exchanges keep their clocks in
+// sync. Venues whose messages carry no timestamp have no latency
+#[derive(Debug, Clone, Default)]
+pub struct LatencyEstimator {
+    latencies: HashMap<String, f64>,
+}
+
+impl LatencyEstimator {
+    pub fn new() -> LatencyEstimator {
+        LatencyEstimator::default()
+    }
+
+    pub fn observe(&mut self, exchange: &str, event_time: u64, received_at: u64) {
+        let sample = received_at as f64 - event_time as f64;
+        self.latencies
+            .entry(exchange.to_string())
+            .and_modify(|latency| *latency += LATENCY_SMOOTHING * (sample - *latency))
+            .or_insert(sample);
+    }
+
+    pub fn latency_ms(&self, exchange: &str) -> Option<f64> {
+        self.latencies.get(exchange).copied()
+    }
+
+    // How long the venue's books are held back so they line up with the books of the
+    // slowest venue, at most max_skew_ms. The slowest venue isn't delayed
+    pub fn compensation_delay(&self, exchange: &str, max_skew_ms: u64) -> u64 {
+        let latency = match self.latency_ms(exchange) {
+            Some(latency) => latency,
+            None => return 0,
+        };
+        let slowest = self.latencies.values().copied().fold(latency, f64::max);
+        ((slowest - latency).round() as u64).min(max_skew_ms)
+    }
+}
+
+// Values held back until their release time, in arrival order per venue
+#[derive(Debug)]
+pub struct DelayLine<T> {
+    queues: HashMap<String, VecDeque<(u64, T)>>,
+}
+
+impl<T> Default for DelayLine<T> {
+    fn default() -> Self {
+        DelayLine {
+            queues: HashMap::new(),
+        }
+    }
+}
+
+impl<T> DelayLine<T> {
+    pub fn new() -> DelayLine<T> {
+        DelayLine::default()
+    }
+
+    // A value isn't released before the ones the venue sent earlier, even when the
+    // venue's delay shrank since
+    pub fn push(&mut self, exchange: &str, release_at: u64, value: T) {
+        let queue = self.queues.entry(exchange.to_string()).or_default();
+        let release_at = queue
+            .back()
+            .map_or(release_at, |(last, _)| release_at.max(*last));
+        queue.push_back((release_at, value));
+    }
+
+    // The latest value of each venue due at now, the earlier ones are superseded by it
+    pub fn pop_due(&mut self, now: u64) -> Vec<(String, T)> {
+        let mut due = Vec::new();
+        for (exchange, queue) in self.queues.iter_mut() {
+            let mut latest = None;
+            while queue
+                .front()
+                .is_some_and(|(release_at, _)| *release_at <= now)
+            {
+                latest = queue.pop_front().map(|(_, value)| value);
+            }
+            if let Some(value) = latest {
+                due.push((exchange.clone(), value));
+            }
+        }
+        due
+    }
+
+    pub fn next_release(&self) -> Option<u64> {
+        self.queues
+            .values()
+            .filter_map(|queue| queue.front().map(|(release_at, _)| *release_at))
+            .min()
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compensation_delay() {
+        let mut estimator = LatencyEstimator::new();
+        estimator.observe("okx", 1_000, 1_020);
+        estimator.observe("bitstamp", 1_000, 1_080);
+        estimator.observe("bitstamp", 2_000, 2_180);
+        // 80 ms moved 10% of the way to 180 ms
+        assert_eq!(estimator.latency_ms("bitstamp"), Some(90.0));
+        assert_eq!(estimator.compensation_delay("okx", 100), 70);
+        assert_eq!(estimator.compensation_delay("okx", 50), 50);
+        assert_eq!(estimator.compensation_delay("bitstamp", 100), 0);
+        assert_eq!(estimator.compensation_delay("binance", 100), 0);
+    }
+
+    #[test]
+    fn test_delay_line() {
+        let mut delay_line = DelayLine::new();
+        delay_line.push("okx", 100, 1);
+        delay_line.push("okx", 120, 2);
+        // Not released before the previous one
+        delay_line.push("okx", 110, 3);
+        delay_line.push("kraken", 105, 4);
+        assert_eq!(delay_line.next_release(), Some(100));
+        assert_eq!(delay_line.pop_due(99), Vec::new());
+        let mut due = delay_line.pop_due(115);
+        due.sort();
+        assert_eq!(due, vec![("kraken".to_string(), 4), ("okx".to_string(), 1)]);
+        assert_eq!(delay_line.pop_due(120), vec![("okx".to_string(), 3)]);
+        assert_eq!(delay_line.next_release(), None);
+    }
+}
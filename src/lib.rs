@@ -0,0 +1,80 @@
+// Stable public API, changes to these modules follow semver (see README):
+// the aggregator (Aggregator, run_server), the book model, merge and rendering, the
+// streams of the built-in venues, the gRPC types, the helpers of the first versions, the
+// connector trait, registry and venue connectors, the server options (ServerOptions),
+// config presets and indexes, the client failover and cache, the clock, the summary checksum,
+// the basis/BBO attribution/depeg/deviation/fair value/index price/reference
+// improvement/toxicity/wall analytics, the latency compensation, the FX conversion, the
+// price grouping, the JSON number parsing, the recordings, reports and book diffs, the
+// summary sinks, the SLA monitor, the symbol normalization, the trade tape, the doctor,
+// the dry run and the runtime of the tasks, and with the conformance feature the
+// connector conformance suite and its mock exchange
+pub mod aggregator;
+pub mod basis;
+pub mod bbo_attribution;
+pub mod book;
+pub mod book_diff;
+pub mod checksum;
+pub mod client_cache;
+pub mod clock;
+pub mod compaction;
+pub mod config;
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
+pub mod connectors;
+pub mod depeg;
+pub mod deviation;
+pub mod doctor;
+pub mod dry_run;
+pub mod failover;
+pub mod fair_value;
+pub mod fx;
+pub mod grouping;
+pub mod grpc;
+pub mod index_price;
+pub mod latency;
+pub mod merge;
+#[cfg(any(test, feature = "conformance"))]
+pub mod mock_exchange;
+pub mod number;
+pub mod orderbook_helper;
+pub mod recording;
+pub mod reference;
+pub mod registry;
+pub mod render;
+pub mod report;
+pub mod runtime;
+pub mod sink;
+pub mod sla;
+pub mod symbols;
+pub mod tape;
+pub mod toxicity;
+pub mod walls;
+
+pub use grpc::orderbook_proto;
+
+// Internals of the server, they may change in any release
+pub(crate) mod audit;
+pub(crate) mod bitstamp_pool;
+pub(crate) mod cadence;
+pub(crate) mod compression;
+pub(crate) mod connection_manager;
+pub(crate) mod csv_export;
+pub(crate) mod emission;
+pub(crate) mod feed_status;
+pub(crate) mod history;
+pub(crate) mod idle;
+pub(crate) mod keepalive;
+pub(crate) mod metering;
+pub(crate) mod metrics;
+pub(crate) mod profiling;
+pub(crate) mod projection;
+pub(crate) mod reconnect;
+pub(crate) mod resolver;
+pub(crate) mod retention;
+pub(crate) mod scripting;
+pub(crate) mod tenant;
+pub(crate) mod touch_filter;
+
+// Re-exported for register_connector!, so connector crates don't need their own dependency
+pub use inventory;
@@ -0,0 +1,222 @@
+// Merge of the venues' books into one ladder, and the top of book refresh of a book
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::number::decimal_to_f64;
+
+pub(crate) fn sort_and_trim_levels(
+    levels: &[PriceAmountLevel],
+    depth: usize,
+    ascending: bool,
+) -> Vec<PriceAmountLevel> {
+    let mut sorted_levels = levels.to_vec();
+
+    sorted_levels.sort_by(|a, b| {
+        if a.price == b.price {
+            // If prices are the same, sort by descending order of amount
+            b.amount.cmp(&a.amount)
+        } else if ascending {
+            // Sort by ascending order of price
+            a.price.cmp(&b.price)
+        } else {
+            // Sort by descending order of price
+            b.price.cmp(&a.price)
+        }
+    });
+
+    if depth <= sorted_levels.len() {
+        sorted_levels[..depth].to_vec()
+    } else {
+        sorted_levels
+    }
+}
+
+// The book with its best bid and ask replaced by a fresher top of book, the levels the
+// new best prices cross are dropped
+pub fn with_best_levels(
+    orderbook: &OrderBook,
+    best_bid: PriceAmountLevel,
+    best_ask: PriceAmountLevel,
+    depth: usize,
+) -> OrderBook {
+    let mut bids = vec![best_bid.clone()];
+    bids.extend(
+        orderbook
+            .bids
+            .iter()
+            .filter(|level| level.price < best_bid.price)
+            .cloned(),
+    );
+    let mut asks = vec![best_ask.clone()];
+    asks.extend(
+        orderbook
+            .asks
+            .iter()
+            .filter(|level| level.price > best_ask.price)
+            .cloned(),
+    );
+    bids.truncate(depth);
+    asks.truncate(depth);
+    OrderBook {
+        bids,
+        asks,
+        spread: decimal_to_f64(best_bid.price - best_ask.price),
+        venues: orderbook.venues.clone(),
+    }
+}
+
+pub fn merge_orderbooks(
+    binance_orderbook: &OrderBook,
+    bitstamp_orderbook: &OrderBook,
+    depth: usize,
+) -> OrderBook {
+    let mut merged_bids = binance_orderbook.bids.clone();
+    merged_bids.extend(bitstamp_orderbook.bids.iter().cloned());
+
+    let mut merged_asks = binance_orderbook.asks.clone();
+    merged_asks.extend(bitstamp_orderbook.asks.iter().cloned());
+
+    let sorted_bids = sort_and_trim_levels(&merged_bids, depth, false);
+    let sorted_asks = sort_and_trim_levels(&merged_asks, depth, true);
+
+    let spread = match (sorted_bids.first(), sorted_asks.first()) {
+        (Some(first_bid), Some(first_ask)) => decimal_to_f64(first_bid.price - first_ask.price),
+        _ => 0.0,
+    };
+
+    let mut venues = binance_orderbook.venues.clone();
+    venues.extend(bitstamp_orderbook.venues.iter().cloned());
+
+    OrderBook {
+        bids: sorted_bids,
+        asks: sorted_asks,
+        spread,
+        venues,
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_sort_and_trim_levels() {
+        let levels = vec![
+            PriceAmountLevel {
+                exchange: "exchange1".to_string(),
+                price: dec!(10.0),
+                amount: dec!(1.0),
+            },
+            PriceAmountLevel {
+                exchange: "exchange2".to_string(),
+                price: dec!(9.5),
+                amount: dec!(2.0),
+            },
+            PriceAmountLevel {
+                exchange: "exchange3".to_string(),
+                price: dec!(11.0),
+                amount: dec!(0.8),
+            },
+        ];
+
+        let sorted_levels = sort_and_trim_levels(&levels, 2, true);
+
+        assert_eq!(sorted_levels.len(), 2);
+        assert_eq!(sorted_levels[0].price, dec!(9.5));
+        assert_eq!(sorted_levels[1].price, dec!(10.0));
+    }
+
+    #[test]
+    fn test_merge_orderbooks() {
+        let binance_orderbook = OrderBook {
+            bids: vec![
+                PriceAmountLevel {
+                    exchange: "binance".to_string(),
+                    price: dec!(10.0),
+                    amount: dec!(1.0),
+                },
+                PriceAmountLevel {
+                    exchange: "binance".to_string(),
+                    price: dec!(9.5),
+                    amount: dec!(2.0),
+                },
+            ],
+            asks: vec![
+                PriceAmountLevel {
+                    exchange: "binance".to_string(),
+                    price: dec!(11.0),
+                    amount: dec!(0.8),
+                },
+                PriceAmountLevel {
+                    exchange: "binance".to_string(),
+                    price: dec!(11.5),
+                    amount: dec!(0.7),
+                },
+            ],
+            spread: 0.5,
+            venues: Vec::new(),
+        };
+
+        let bitstamp_orderbook = OrderBook {
+            bids: vec![
+                PriceAmountLevel {
+                    exchange: "bitstamp".to_string(),
+                    price: dec!(10.2),
+                    amount: dec!(0.9),
+                },
+                PriceAmountLevel {
+                    exchange: "bitstamp".to_string(),
+                    price: dec!(9.8),
+                    amount: dec!(1.5),
+                },
+            ],
+            asks: vec![
+                PriceAmountLevel {
+                    exchange: "bitstamp".to_string(),
+                    price: dec!(11.2),
+                    amount: dec!(0.6),
+                },
+                PriceAmountLevel {
+                    exchange: "bitstamp".to_string(),
+                    price: dec!(11.8),
+                    amount: dec!(0.4),
+                },
+            ],
+            spread: 0.6,
+            venues: Vec::new(),
+        };
+
+        let depth = 3;
+
+        let merged_orderbook = merge_orderbooks(&binance_orderbook, &bitstamp_orderbook, depth);
+
+        assert_eq!(merged_orderbook.bids.len(), 3);
+        assert_eq!(merged_orderbook.asks.len(), 3);
+
+        // Assert the bid levels
+        assert_eq!(merged_orderbook.bids[0].exchange, "bitstamp");
+        assert_eq!(merged_orderbook.bids[0].price, dec!(10.2));
+        assert_eq!(merged_orderbook.bids[0].amount, dec!(0.9));
+
+        assert_eq!(merged_orderbook.bids[1].exchange, "binance");
+        assert_eq!(merged_orderbook.bids[1].price, dec!(10.0));
+        assert_eq!(merged_orderbook.bids[1].amount, dec!(1.0));
+
+        assert_eq!(merged_orderbook.bids[2].exchange, "bitstamp");
+        assert_eq!(merged_orderbook.bids[2].price, dec!(9.8));
+        assert_eq!(merged_orderbook.bids[2].amount, dec!(1.5));
+
+        // Assert the ask levels
+        assert_eq!(merged_orderbook.asks[0].exchange, "binance");
+        assert_eq!(merged_orderbook.asks[0].price, dec!(11.0));
+        assert_eq!(merged_orderbook.asks[0].amount, dec!(0.8));
+
+        assert_eq!(merged_orderbook.asks[1].exchange, "bitstamp");
+        assert_eq!(merged_orderbook.asks[1].price, dec!(11.2));
+        assert_eq!(merged_orderbook.asks[1].amount, dec!(0.6));
+
+        assert_eq!(merged_orderbook.asks[2].exchange, "binance");
+        assert_eq!(merged_orderbook.asks[2].price, dec!(11.5));
+        assert_eq!(merged_orderbook.asks[2].amount, dec!(0.7));
+    }
+}
@@ -0,0 +1,163 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Messages and encoded bytes sent to one client for one symbol
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Usage {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageLine {
+    pub client: String,
+    pub symbol: String,
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+// Cumulative usage since the server started, for internal chargeback
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageReport {
+    pub since: u64,
+    pub timestamp: u64,
+    pub usage: Vec<UsageLine>,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// Usage by client (the tenant namespace of the API key) and symbol. A BTreeMap keeps
+// the reports sorted
+#[derive(Debug)]
+pub struct UsageMeter {
+    since: u64,
+    usage: Mutex<BTreeMap<(String, String), Usage>>,
+    // Held by the export while it appends to the file
+    export: Mutex<()>,
+}
+
+impl UsageMeter {
+    pub fn new() -> UsageMeter {
+        UsageMeter {
+            since: now_millis(),
+            usage: Mutex::new(BTreeMap::new()),
+            export: Mutex::new(()),
+        }
+    }
+
+    pub fn record(&self, client: &str, symbol: &str, bytes: usize) {
+        let mut usage = self.usage.lock().unwrap();
+        let usage = usage
+            .entry((client.to_string(), symbol.to_string()))
+            .or_default();
+        usage.messages += 1;
+        usage.bytes += bytes as u64;
+    }
+
+    // Usage of one client, or of every client when client is None
+    pub fn report(&self, client: Option<&str>) -> UsageReport {
+        let usage = self
+            .usage
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((usage_client, _), _)| client.is_none_or(|client| client == usage_client))
+            .map(|((client, symbol), usage)| UsageLine {
+                client: client.clone(),
+                symbol: symbol.clone(),
+                messages: usage.messages,
+                bytes: usage.bytes,
+            })
+            .collect();
+
+        UsageReport {
+            since: self.since,
+            timestamp: now_millis(),
+            usage,
+        }
+    }
+
+    // Appends the report of every client as a JSON line
+    pub fn export(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let line = serde_json::to_string(&self.report(None))?;
+        let _export = self.pause_exports();
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+
+    // Holds back the exports while the retention cleanup rewrites the file, they open it
+    // again for every export
+    pub(crate) fn pause_exports(&self) -> MutexGuard<'_, ()> {
+        self.export.lock().unwrap()
+    }
+}
+
+impl Default for UsageMeter {
+    fn default() -> Self {
+        UsageMeter::new()
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_meter() {
+        let meter = UsageMeter::new();
+        meter.record("research", "btcusdt", 100);
+        meter.record("research", "btcusdt", 50);
+        meter.record("research", "ethusdt", 10);
+        meter.record("risk", "btcusdt", 20);
+
+        let report = meter.report(None);
+        assert_eq!(report.usage.len(), 3);
+        assert_eq!(
+            report.usage[0],
+            UsageLine {
+                client: "research".to_string(),
+                symbol: "btcusdt".to_string(),
+                messages: 2,
+                bytes: 150,
+            }
+        );
+
+        let risk = meter.report(Some("risk"));
+        assert_eq!(risk.usage.len(), 1);
+        assert_eq!(risk.usage[0].bytes, 20);
+        assert!(meter.report(Some("unknown")).usage.is_empty());
+    }
+
+    #[test]
+    fn test_usage_export() {
+        let path = std::env::temp_dir().join(format!("orderbook-usage-{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let meter = UsageMeter::new();
+        meter.record("research", "btcusdt", 100);
+        meter.export(path).unwrap();
+        meter.export(path).unwrap();
+
+        let exported = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = exported.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let report: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(report["usage"][0]["client"], "research");
+        assert_eq!(report["usage"][0]["bytes"], 100);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
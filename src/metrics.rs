@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+type Collector = Box<dyn Fn(&MetricsRegistry) + Send + Sync>;
+
+// Gauges of the server scraped by Prometheus, by name and then by their labels as
+// written in the exposition format, e.g. symbol="btcusdt",reference="binance"
+#[derive(Default)]
+pub(crate) struct MetricsRegistry {
+    gauges: Mutex<BTreeMap<String, BTreeMap<String, f64>>>,
+    // Set the gauges of counters kept elsewhere, e.g. the tenants', on every scrape
+    collectors: Mutex<Vec<Collector>>,
+}
+
+impl MetricsRegistry {
+    pub fn set_gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let labels = labels
+            .iter()
+            .map(|(label, value)| format!("{}=\"{}\"", label, value.replace('"', "\\\"")))
+            .collect::<Vec<_>>()
+            .join(",");
+        self.gauges
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .insert(labels, value);
+    }
+
+    pub fn add_collector(&self, collector: impl Fn(&MetricsRegistry) + Send + Sync + 'static) {
+        self.collectors.lock().unwrap().push(Box::new(collector));
+    }
+
+    // The Prometheus text exposition format
+    pub fn render(&self) -> String {
+        for collector in self.collectors.lock().unwrap().iter() {
+            collector(self);
+        }
+        let mut text = String::new();
+        for (name, series) in self.gauges.lock().unwrap().iter() {
+            let _ = writeln!(text, "# TYPE {} gauge", name);
+            for (labels, value) in series {
+                let _ = writeln!(text, "{}{{{}}} {}", name, labels, value);
+            }
+        }
+        text
+    }
+}
+
+// Answers every HTTP request on the address with the metrics, e.g. GET /metrics
+pub(crate) async fn serve_metrics(
+    addr: SocketAddr,
+    metrics: Arc<MetricsRegistry>,
+) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(addr).await?;
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let metrics = Arc::clone(&metrics);
+        tokio::spawn(async move {
+            // The request itself doesn't matter
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await;
+            let body = metrics.render();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = MetricsRegistry::default();
+        let labels = [("symbol", "btcusdt"), ("reference", "binance")];
+        metrics.set_gauge("orderbook_bid_improvement_bps", &labels, 1.5);
+        metrics.set_gauge("orderbook_bid_improvement_bps", &labels, 2.0);
+        metrics.set_gauge("orderbook_ask_improvement_bps", &labels, 0.0);
+        assert_eq!(
+            metrics.render(),
+            "# TYPE orderbook_ask_improvement_bps gauge\n\
+             orderbook_ask_improvement_bps{symbol=\"btcusdt\",reference=\"binance\"} 0\n\
+             # TYPE orderbook_bid_improvement_bps gauge\n\
+             orderbook_bid_improvement_bps{symbol=\"btcusdt\",reference=\"binance\"} 2\n"
+        );
+    }
+}
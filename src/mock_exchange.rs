@@ -0,0 +1,90 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread::{spawn, JoinHandle};
+use tungstenite::{accept, Message};
+
+// What the mock exchange does for one client connection
+pub struct MockSession {
+    pub ack: String,
+    pub frames: Vec<String>,
+    // Close the connection after the frames, to test reconnects
+    pub close: bool,
+}
+
+// Scripted WebSocket exchange on localhost, serves one session per connection
+// and records every text frame the client sends
+pub struct MockExchange {
+    pub url: String,
+    received: Arc<Mutex<Vec<String>>>,
+    handle: JoinHandle<()>,
+}
+
+impl MockExchange {
+    pub fn start(sessions: Vec<MockSession>) -> MockExchange {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let handle = spawn({
+            let received = Arc::clone(&received);
+            move || {
+                for session in sessions {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut socket = accept(stream).unwrap();
+
+                    // Acknowledge the subscription message
+                    if let Ok(Message::Text(subscribe_message)) = socket.read_message() {
+                        received.lock().unwrap().push(subscribe_message);
+                    }
+                    socket.write_message(Message::Text(session.ack)).unwrap();
+
+                    for frame in session.frames {
+                        socket.write_message(Message::Text(frame)).unwrap();
+                    }
+
+                    if session.close {
+                        let _ = socket.close(None);
+                        let _ = socket.write_pending();
+                    }
+
+                    // Record everything else until the client goes away
+                    while let Ok(message) = socket.read_message() {
+                        if let Message::Text(message_text) = message {
+                            received.lock().unwrap().push(message_text);
+                        }
+                    }
+                }
+            }
+        });
+
+        MockExchange {
+            url,
+            received,
+            handle,
+        }
+    }
+
+    // Waits for all sessions to end and returns the messages sent by the client
+    pub fn finish(self) -> Vec<String> {
+        self.handle.join().unwrap();
+        let received = self.received.lock().unwrap();
+        received.clone()
+    }
+}
+
+// Answers every HTTP request with the body, e.g. the REST snapshot of a diff book, for
+// as long as the test runs. Returns the url of the server
+pub fn serve_snapshot(body: &str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let body = body.to_string();
+    spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let _ = write!(stream, "HTTP/1.0 200 OK\r\n\r\n{}", body);
+        }
+    });
+    url
+}
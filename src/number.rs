@@ -0,0 +1,225 @@
+use rust_decimal::Decimal;
+use serde::de::{self, Deserializer, Visitor};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+
+// A price or amount sent either as a JSON string ("10.5") or as a JSON number (10.5).
+// Strings are parsed with str::parse and numbers by serde_json with float_roundtrip,
+// both round to the nearest f64, so the two representations give the same value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlexibleF64(pub f64);
+
+struct FlexibleF64Visitor;
+
+impl<'de> Visitor<'de> for FlexibleF64Visitor {
+    type Value = FlexibleF64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number or a string holding a number")
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<FlexibleF64, E> {
+        Ok(FlexibleF64(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<FlexibleF64, E> {
+        Ok(FlexibleF64(value as f64))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<FlexibleF64, E> {
+        Ok(FlexibleF64(value as f64))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<FlexibleF64, E> {
+        value
+            .trim()
+            .parse()
+            .map(FlexibleF64)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+}
+
+impl<'de> Deserialize<'de> for FlexibleF64 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FlexibleF64, D::Error> {
+        deserializer.deserialize_any(FlexibleF64Visitor)
+    }
+}
+
+// For #[serde(deserialize_with = "flexible_f64")] fields
+pub fn flexible_f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    FlexibleF64::deserialize(deserializer).map(|number| number.0)
+}
+
+// A price or amount of a parsed message, None when it is missing or not a number
+pub fn json_f64(value: &Value) -> Option<f64> {
+    FlexibleF64::deserialize(value).ok().map(|number| number.0)
+}
+
+// The shortest decimal string parsing back to the same f64, 64123.45 rather than the
+// 64123.4500000000043655745685100555419921875 the double holds, and never an exponent
+pub fn decimal_string(value: f64) -> String {
+    format!("{}", value)
+}
+
+// The decimal a double stands for, the shortest one parsing back to it, so 0.1 is 0.1
+// and not the 0.1000000000000000055511151231257827 the double holds. None for NaN, the
+// infinities and values beyond the range of Decimal
+pub fn decimal_from_f64(value: f64) -> Option<Decimal> {
+    if !value.is_finite() {
+        return None;
+    }
+    decimal_string(value).parse().ok()
+}
+
+// The nearest double of a price or amount, for the analytics and the doubles of the
+// summaries
+pub fn decimal_to_f64(value: Decimal) -> f64 {
+    // Decimal::to_f64 may be off by one ulp. Below 2^53 and 10^22 the mantissa and the
+    // power of ten are exact doubles and the one division rounds to the nearest double,
+    // like parsing the decimal's string does for the others
+    let (mantissa, scale) = (value.mantissa(), value.scale());
+    if mantissa.unsigned_abs() < 1 << 53 && scale <= 22 {
+        return mantissa as f64 / 10f64.powi(scale as i32);
+    }
+    value.to_string().parse().unwrap_or(f64::NAN)
+}
+
+// A price or amount kept as the decimal the exchange sent. Strings are parsed exactly,
+// "1e-8" too, numbers are read as doubles by serde_json and taken as their shortest
+// decimal (decimal_from_f64), which is exact for up to 15 significant digits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlexibleDecimal(pub Decimal);
+
+struct FlexibleDecimalVisitor;
+
+impl<'de> Visitor<'de> for FlexibleDecimalVisitor {
+    type Value = FlexibleDecimal;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a decimal number or a string holding one")
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<FlexibleDecimal, E> {
+        decimal_from_f64(value)
+            .map(FlexibleDecimal)
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Float(value), &self))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<FlexibleDecimal, E> {
+        Ok(FlexibleDecimal(Decimal::from(value)))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<FlexibleDecimal, E> {
+        Ok(FlexibleDecimal(Decimal::from(value)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<FlexibleDecimal, E> {
+        let trimmed = value.trim();
+        trimmed
+            .parse()
+            .or_else(|_| Decimal::from_scientific(trimmed))
+            .map(FlexibleDecimal)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+}
+
+impl<'de> Deserialize<'de> for FlexibleDecimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FlexibleDecimal, D::Error> {
+        deserializer.deserialize_any(FlexibleDecimalVisitor)
+    }
+}
+
+// A price or amount of a parsed message as the exchange sent it, None when it is
+// missing or not a number
+pub fn json_decimal(value: &Value) -> Option<Decimal> {
+    FlexibleDecimal::deserialize(value)
+        .ok()
+        .map(|number| number.0)
+}
+
+// For #[serde(with = "decimal_number")] fields: written as a JSON number, like the
+// doubles of the recordings written before the levels were decimals, and read back
+// as FlexibleDecimal
+pub mod decimal_number {
+    use super::{decimal_to_f64, FlexibleDecimal};
+    use rust_decimal::Decimal;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(decimal_to_f64(*value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+        FlexibleDecimal::deserialize(deserializer).map(|number| number.0)
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number::{decimal_from_f64, decimal_to_f64};
+
+    #[test]
+    fn test_json_f64() {
+        let level: Value = serde_json::from_str(r#"["0.1", 0.1, 3, "1e-8", "abc", null]"#).unwrap();
+        assert_eq!(json_f64(&level[0]), Some(0.1));
+        assert_eq!(json_f64(&level[1]), Some(0.1));
+        assert_eq!(json_f64(&level[2]), Some(3.0));
+        assert_eq!(json_f64(&level[3]), Some(1e-8));
+        assert_eq!(json_f64(&level[4]), None);
+        assert_eq!(json_f64(&level[5]), None);
+
+        #[derive(Deserialize)]
+        struct Level {
+            #[serde(deserialize_with = "flexible_f64")]
+            price: f64,
+        }
+        let level: Level = serde_json::from_str(r#"{"price": 64123.45000001}"#).unwrap();
+        assert_eq!(level.price, "64123.45000001".parse::<f64>().unwrap());
+    }
+
+    #[test]
+    fn test_decimal_string() {
+        assert_eq!(decimal_string(64123.45), "64123.45");
+        assert_eq!(decimal_string(1e-8), "0.00000001");
+        assert_eq!(decimal_string(3.0), "3");
+        let value = 0.1 + 0.2;
+        assert_eq!(decimal_string(value).parse::<f64>().unwrap(), value);
+    }
+
+    #[test]
+    fn test_json_decimal() {
+        let level: Value =
+            serde_json::from_str(r#"["0.00000001", 1e-8, 3, "1e-8", " 0.1 ", "abc", null]"#)
+                .unwrap();
+        let satoshi = Decimal::new(1, 8);
+        assert_eq!(json_decimal(&level[0]), Some(satoshi));
+        assert_eq!(json_decimal(&level[1]), Some(satoshi));
+        assert_eq!(json_decimal(&level[2]), Some(Decimal::from(3)));
+        assert_eq!(json_decimal(&level[3]), Some(satoshi));
+        assert_eq!(json_decimal(&level[4]), Some(Decimal::new(1, 1)));
+        assert_eq!(json_decimal(&level[5]), None);
+        assert_eq!(json_decimal(&level[6]), None);
+
+        // No rounding where doubles round
+        let sum = json_decimal(&level[4]).unwrap() + Decimal::new(2, 1);
+        assert_eq!(sum.to_string(), "0.3");
+        assert_eq!(decimal_from_f64(f64::NAN), None);
+        assert_eq!(decimal_to_f64(sum), 0.3);
+        assert_eq!(decimal_to_f64("-64123.45".parse().unwrap()), -64123.45);
+        let long = "0.1234567890123456789012345678";
+        let expected: f64 = long.parse().unwrap();
+        assert_eq!(decimal_to_f64(long.parse().unwrap()), expected);
+
+        #[derive(serde::Serialize, Deserialize)]
+        struct Level {
+            #[serde(with = "decimal_number")]
+            price: Decimal,
+        }
+        let level: Level = serde_json::from_str(r#"{"price": 0.00000001}"#).unwrap();
+        assert_eq!(level.price, satoshi);
+        assert_eq!(serde_json::to_string(&level).unwrap(), r#"{"price":1e-8}"#);
+    }
+}
@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::spawn_blocking;
+
+pub const DEFAULT_PROFILE_DURATION: Duration = Duration::from_secs(30);
+pub const MAX_PROFILE_DURATION: Duration = Duration::from_secs(300);
+
+// Samples per second, off the multiples of the timers running the server's tasks
+#[cfg(feature = "profiling")]
+const PROFILE_FREQUENCY: i32 = 99;
+
+// The duration of GET /profile?seconds=<n>, the default without the parameter
+fn profile_duration(request_line: &str) -> Result<Duration, String> {
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path != "/profile" {
+        return Err(format!("Unknown path {}, use /profile?seconds=<n>", path));
+    }
+    match query
+        .split('&')
+        .find_map(|param| param.strip_prefix("seconds="))
+    {
+        None => Ok(DEFAULT_PROFILE_DURATION),
+        Some(seconds) => match seconds.parse::<u64>() {
+            Ok(seconds) if seconds > 0 => {
+                Ok(Duration::from_secs(seconds).min(MAX_PROFILE_DURATION))
+            }
+            _ => Err(format!("Invalid seconds {}", seconds)),
+        },
+    }
+}
+
+// Samples the stacks of every thread of the process for the duration, as an SVG
+// flamegraph
+#[cfg(feature = "profiling")]
+fn capture_flamegraph(duration: Duration) -> Result<Vec<u8>, String> {
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(PROFILE_FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .map_err(|err| err.to_string())?;
+    std::thread::sleep(duration);
+    let report = guard.report().build().map_err(|err| err.to_string())?;
+    let mut svg = Vec::new();
+    report.flamegraph(&mut svg).map_err(|err| err.to_string())?;
+    Ok(svg)
+}
+
+#[cfg(not(feature = "profiling"))]
+fn capture_flamegraph(_duration: Duration) -> Result<Vec<u8>, String> {
+    Err("Built without the profiling feature, rebuild with --features profiling".to_string())
+}
+
+fn http_response(status: &str, content_type: &str, body: &[u8]) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(body);
+    response
+}
+
+// Answers GET /profile?seconds=<n> with a flamegraph of the running server over the
+// next n seconds, one profile at a time
+pub(crate) async fn serve_profiles(addr: SocketAddr) -> Result<(), Box<dyn Error>> {
+    static CAPTURING: AtomicBool = AtomicBool::new(false);
+    let listener = TcpListener::bind(addr).await?;
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            let mut request = [0; 1024];
+            let read = stream.read(&mut request).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..read]);
+            let request_line = request.lines().next().unwrap_or_default();
+            let response = match profile_duration(request_line) {
+                Err(err) => http_response("400 Bad Request", "text/plain", err.as_bytes()),
+                Ok(_) if CAPTURING.swap(true, Ordering::AcqRel) => http_response(
+                    "409 Conflict",
+                    "text/plain",
+                    b"A profile is already being captured",
+                ),
+                Ok(duration) => {
+                    eprintln!("Capturing a CPU profile for {} s", duration.as_secs());
+                    let svg = spawn_blocking(move || capture_flamegraph(duration)).await;
+                    CAPTURING.store(false, Ordering::Release);
+                    match svg.map_err(|err| err.to_string()).and_then(|svg| svg) {
+                        Ok(svg) => http_response("200 OK", "image/svg+xml", &svg),
+                        Err(err) => {
+                            http_response("500 Internal Server Error", "text/plain", err.as_bytes())
+                        }
+                    }
+                }
+            };
+            let _ = stream.write_all(&response).await;
+        });
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_duration() {
+        assert_eq!(
+            profile_duration("GET /profile?seconds=10 HTTP/1.1"),
+            Ok(Duration::from_secs(10))
+        );
+        assert_eq!(
+            profile_duration("GET /profile HTTP/1.1"),
+            Ok(DEFAULT_PROFILE_DURATION)
+        );
+        assert_eq!(
+            profile_duration("GET /profile?seconds=86400 HTTP/1.1"),
+            Ok(MAX_PROFILE_DURATION)
+        );
+        assert!(profile_duration("GET /profile?seconds=0 HTTP/1.1").is_err());
+        assert!(profile_duration("GET /metrics HTTP/1.1").is_err());
+    }
+}
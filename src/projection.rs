@@ -0,0 +1,154 @@
+use crate::book::PriceAmountLevel;
+
+// Fields of the summary a subscriber asked for. Lightweight consumers can skip the
+// ladders, the server then doesn't build or send them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SummaryFields {
+    pub spread: bool,
+    // Only the best bid and ask, ignored when the full ladders are selected
+    pub bbo: bool,
+    pub bids: bool,
+    pub asks: bool,
+    pub venues: bool,
+    pub signals: bool,
+    // Weighted mid, fair value and index prices
+    pub fair_value: bool,
+    // Flow toxicity of the merged book
+    pub toxicity: bool,
+    // Improvement of the merged book over the reference venue
+    pub reference: bool,
+    // Prices and amounts sent as decimal strings, see grpc::convert::encode_decimal_prices
+    pub decimal_prices: bool,
+}
+
+pub const FIELD_NAMES: [&str; 9] = [
+    "spread",
+    "bbo",
+    "bids",
+    "asks",
+    "venues",
+    "signals",
+    "fair_value",
+    "toxicity",
+    "reference",
+];
+
+impl SummaryFields {
+    pub fn all() -> SummaryFields {
+        SummaryFields {
+            spread: true,
+            bbo: false,
+            bids: true,
+            asks: true,
+            venues: true,
+            signals: true,
+            fair_value: true,
+            toxicity: true,
+            reference: true,
+            decimal_prices: false,
+        }
+    }
+
+    // An empty mask selects every field, so existing subscribers keep the full summary
+    pub fn from_mask(fields: &[String]) -> Result<SummaryFields, String> {
+        if fields.is_empty() {
+            return Ok(SummaryFields::all());
+        }
+
+        let mut selected = SummaryFields {
+            spread: false,
+            bbo: false,
+            bids: false,
+            asks: false,
+            venues: false,
+            signals: false,
+            fair_value: false,
+            toxicity: false,
+            reference: false,
+            decimal_prices: false,
+        };
+        for field in fields {
+            match field.as_str() {
+                "spread" => selected.spread = true,
+                "bbo" => selected.bbo = true,
+                "bids" => selected.bids = true,
+                "asks" => selected.asks = true,
+                "venues" => selected.venues = true,
+                "signals" => selected.signals = true,
+                "fair_value" => selected.fair_value = true,
+                "toxicity" => selected.toxicity = true,
+                "reference" => selected.reference = true,
+                _ => {
+                    return Err(format!(
+                        "Unknown field {}, expected one of {}",
+                        field,
+                        FIELD_NAMES.join(", ")
+                    ))
+                }
+            }
+        }
+        Ok(selected)
+    }
+
+    // Levels of one side to send, the full ladder, only the top level or nothing
+    pub fn bid_levels<'a>(&self, levels: &'a [PriceAmountLevel]) -> &'a [PriceAmountLevel] {
+        project_levels(levels, self.bids, self.bbo)
+    }
+
+    pub fn ask_levels<'a>(&self, levels: &'a [PriceAmountLevel]) -> &'a [PriceAmountLevel] {
+        project_levels(levels, self.asks, self.bbo)
+    }
+}
+
+fn project_levels(levels: &[PriceAmountLevel], ladder: bool, bbo: bool) -> &[PriceAmountLevel] {
+    if ladder {
+        levels
+    } else if bbo {
+        &levels[..levels.len().min(1)]
+    } else {
+        &[]
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number::decimal_from_f64;
+    use rust_decimal_macros::dec;
+
+    fn level(price: f64) -> PriceAmountLevel {
+        PriceAmountLevel {
+            exchange: "binance".to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: dec!(1.0),
+        }
+    }
+
+    #[test]
+    fn test_from_mask() {
+        assert_eq!(SummaryFields::from_mask(&[]), Ok(SummaryFields::all()));
+
+        let fields = SummaryFields::from_mask(&["spread".to_string(), "bbo".to_string()]).unwrap();
+        assert!(fields.spread && fields.bbo);
+        assert!(!fields.bids && !fields.asks && !fields.venues && !fields.signals);
+        assert!(!fields.fair_value && !fields.toxicity && !fields.reference);
+
+        assert!(SummaryFields::from_mask(&["ladder".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_project_levels() {
+        let levels = vec![level(10.0), level(9.5)];
+
+        let bbo = SummaryFields::from_mask(&["bbo".to_string()]).unwrap();
+        assert_eq!(bbo.bid_levels(&levels).len(), 1);
+        assert_eq!(bbo.bid_levels(&levels)[0].price, dec!(10));
+        assert!(bbo.bid_levels(&[]).is_empty());
+
+        let spread_only = SummaryFields::from_mask(&["spread".to_string()]).unwrap();
+        assert!(spread_only.ask_levels(&levels).is_empty());
+
+        assert_eq!(SummaryFields::all().ask_levels(&levels).len(), 2);
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const RECONNECT_BASE: Duration = Duration::from_millis(500);
+pub const RECONNECT_MAX: Duration = Duration::from_secs(30);
+// Window of the reconnect caps, they count the attempts of the last minute
+pub const RECONNECT_CAP_WINDOW: Duration = Duration::from_secs(60);
+
+// Longest delay before the attempt: none for the first one, then base doubling with
+// every failed attempt, up to max
+pub fn backoff_cap(base: Duration, max: Duration, attempt: u32) -> Duration {
+    match attempt {
+        0 => Duration::ZERO,
+        attempt => base
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(max),
+    }
+}
+
+// Delays between the attempts to reconnect an exchange's socket. Every delay is
+// between half and all of its cap, so the sockets dropped together don't reconnect
+// together. The attempts start over once the new socket delivers messages again
+#[derive(Debug)]
+pub struct ReconnectBackoff {
+    base: Duration,
+    max: Duration,
+    attempts: u32,
+    // xorshift64 state of the jitter
+    state: u64,
+    limiter: Option<Arc<ReconnectLimiter>>,
+}
+
+impl ReconnectBackoff {
+    pub fn new(base: Duration, max: Duration) -> ReconnectBackoff {
+        // Backoffs created in the same nanosecond still jitter differently
+        static CREATED: AtomicU64 = AtomicU64::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        let created = CREATED.fetch_add(1, Ordering::Relaxed);
+        ReconnectBackoff {
+            base,
+            max,
+            attempts: 0,
+            state: (nanos ^ created.wrapping_mul(0x9E37_79B9_7F4A_7C15)) | 1,
+            limiter: None,
+        }
+    }
+
+    // The attempts also wait for the reconnect caps of the limiter, shared by the
+    // backoffs of every socket
+    pub fn with_limiter(mut self, limiter: Arc<ReconnectLimiter>) -> ReconnectBackoff {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    // Uniform in [0, 1)
+    fn next_jitter(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let cap = backoff_cap(self.base, self.max, self.attempts);
+        self.attempts += 1;
+        cap.mul_f64(0.5 + self.next_jitter() / 2.0)
+    }
+
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+
+    // Sleeps the delay of the next attempt to reconnect the venue, then until the
+    // reconnect caps let it through, false when the client went away in the meantime
+    pub async fn wait(&mut self, venue: &str, client_closed: impl Future<Output = ()>) -> bool {
+        tokio::pin!(client_closed);
+        let delay = self.next_delay();
+        if !delay.is_zero() {
+            eprintln!(
+                "Reconnecting {} in {} ms (attempt {})",
+                venue,
+                delay.as_millis(),
+                self.attempts()
+            );
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = &mut client_closed => return false,
+        }
+        let limiter = match &self.limiter {
+            Some(limiter) => limiter,
+            None => return true,
+        };
+        loop {
+            let wait = match limiter.admit(venue, Instant::now()) {
+                Admission::Admitted => return true,
+                Admission::Held { wait } => wait,
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = &mut client_closed => return false,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconnectCap {
+    // Attempts of all the venues together
+    Global(u32),
+    Venue(u32),
+}
+
+impl fmt::Display for ReconnectCap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReconnectCap::Global(cap) => write!(f, "global cap of {} reconnects per minute", cap),
+            ReconnectCap::Venue(cap) => write!(f, "cap of {} reconnects per minute", cap),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Admitted,
+    // Ask again after the wait, when the oldest attempt counted leaves the window
+    Held { wait: Duration },
+}
+
+// A venue whose attempts are held back by a cap, until one of them goes through
+#[derive(Debug, Clone)]
+struct HeldVenue {
+    since: Instant,
+    attempts: u32,
+}
+
+// Called with the venue and the cap reached, e.g. to raise an alert
+type CapHitHook = Box<dyn Fn(&str, ReconnectCap) + Send + Sync>;
+
+#[derive(Debug, Default)]
+struct LimiterState {
+    // Time and venue of the attempts let through within the window, oldest first
+    attempts: VecDeque<(Instant, String)>,
+    held: HashMap<String, HeldVenue>,
+}
+
+// Rate caps on the reconnects of the flapping exchanges, on top of the backoff of each
+// socket: at most global_cap attempts a minute for all the venues together and at most
+// the venue's cap for each. The attempts over a cap queue until the oldest attempt
+// counted leaves the window. The attempts held back for a venue are coalesced, its
+// first one calls on_cap_hit and logs once, and the one going through logs how many
+// were held, so a flapping exchange doesn't flood the logs and the alerts either
+pub struct ReconnectLimiter {
+    global_cap: Option<u32>,
+    venue_caps: HashMap<String, u32>,
+    on_cap_hit: CapHitHook,
+    state: Mutex<LimiterState>,
+}
+
+impl fmt::Debug for ReconnectLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReconnectLimiter")
+            .field("global_cap", &self.global_cap)
+            .field("venue_caps", &self.venue_caps)
+            .finish()
+    }
+}
+
+impl ReconnectLimiter {
+    pub fn new(
+        global_cap: Option<u32>,
+        venue_caps: HashMap<String, u32>,
+        on_cap_hit: impl Fn(&str, ReconnectCap) + Send + Sync + 'static,
+    ) -> ReconnectLimiter {
+        ReconnectLimiter {
+            global_cap,
+            venue_caps,
+            on_cap_hit: Box::new(on_cap_hit),
+            state: Mutex::new(LimiterState::default()),
+        }
+    }
+
+    // Counts the attempt to reconnect the venue if the caps allow it now
+    pub fn admit(&self, venue: &str, now: Instant) -> Admission {
+        let mut state = self.state.lock().unwrap();
+        while state
+            .attempts
+            .front()
+            .is_some_and(|(time, _)| now.saturating_duration_since(*time) >= RECONNECT_CAP_WINDOW)
+        {
+            state.attempts.pop_front();
+        }
+        // When the oldest attempt counted by a cap reached leaves the window
+        let wait_for = |cap: u32, attempts: Vec<Instant>| {
+            (attempts.len() >= cap as usize).then(|| {
+                let oldest = attempts[attempts.len() - cap as usize];
+                (oldest + RECONNECT_CAP_WINDOW).saturating_duration_since(now)
+            })
+        };
+        let global = self.global_cap.and_then(|cap| {
+            let attempts = state.attempts.iter().map(|(time, _)| *time).collect();
+            wait_for(cap, attempts).map(|wait| (ReconnectCap::Global(cap), wait))
+        });
+        let venue_cap = self.venue_caps.get(venue).and_then(|cap| {
+            let attempts = state
+                .attempts
+                .iter()
+                .filter(|(_, attempt_venue)| attempt_venue == venue)
+                .map(|(time, _)| *time)
+                .collect();
+            wait_for(*cap, attempts).map(|wait| (ReconnectCap::Venue(*cap), wait))
+        });
+
+        match global
+            .into_iter()
+            .chain(venue_cap)
+            .max_by_key(|(_, wait)| *wait)
+        {
+            Some((cap, wait)) => {
+                let first = !state.held.contains_key(venue);
+                let held = state.held.entry(venue.to_string()).or_insert(HeldVenue {
+                    since: now,
+                    attempts: 0,
+                });
+                held.attempts += 1;
+                drop(state);
+                if first {
+                    eprintln!(
+                        "Reconnects of {} held back by the {}, retrying in {} ms",
+                        venue,
+                        cap,
+                        wait.as_millis()
+                    );
+                    (self.on_cap_hit)(venue, cap);
+                }
+                Admission::Held { wait }
+            }
+            None => {
+                state.attempts.push_back((now, venue.to_string()));
+                if let Some(held) = state.held.remove(venue) {
+                    eprintln!(
+                        "Reconnecting {} after {} ms held back, {} attempts coalesced",
+                        venue,
+                        now.saturating_duration_since(held.since).as_millis(),
+                        held.attempts
+                    );
+                }
+                Admission::Admitted
+            }
+        }
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delays() {
+        let (base, max) = (Duration::from_millis(500), Duration::from_secs(5));
+        assert_eq!(backoff_cap(base, max, 0), Duration::ZERO);
+        assert_eq!(backoff_cap(base, max, 1), base);
+        assert_eq!(backoff_cap(base, max, 3), Duration::from_secs(2));
+        assert_eq!(backoff_cap(base, max, 40), max);
+
+        let mut backoff = ReconnectBackoff::new(base, max);
+        assert_eq!(backoff.next_delay(), Duration::ZERO);
+        for attempt in 1..10 {
+            let cap = backoff_cap(base, max, attempt);
+            let delay = backoff.next_delay();
+            assert!(delay >= cap / 2 && delay <= cap, "{:?} of {:?}", delay, cap);
+        }
+        backoff.reset();
+        assert_eq!(backoff.attempts(), 0);
+        assert_eq!(backoff.next_delay(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_reconnect_limiter() {
+        let hits = Arc::new(Mutex::new(Vec::new()));
+        let limiter = {
+            let hits = Arc::clone(&hits);
+            let venue_caps = HashMap::from([("kraken".to_string(), 2)]);
+            ReconnectLimiter::new(Some(3), venue_caps, move |venue, cap| {
+                hits.lock().unwrap().push((venue.to_string(), cap))
+            })
+        };
+        let start = Instant::now();
+        let second = |secs: u64| start + Duration::from_secs(secs);
+        assert_eq!(limiter.admit("kraken", second(0)), Admission::Admitted);
+        assert_eq!(limiter.admit("kraken", second(10)), Admission::Admitted);
+        // Kraken's own cap, the first attempt of its first minute leaves at 60 s
+        assert_eq!(
+            limiter.admit("kraken", second(20)),
+            Admission::Held {
+                wait: Duration::from_secs(40)
+            }
+        );
+        assert_eq!(
+            limiter.admit("kraken", second(30)),
+            Admission::Held {
+                wait: Duration::from_secs(30)
+            }
+        );
+        // Then the global cap for every venue
+        assert_eq!(limiter.admit("binance", second(30)), Admission::Admitted);
+        assert_eq!(
+            limiter.admit("okx", second(30)),
+            Admission::Held {
+                wait: Duration::from_secs(30)
+            }
+        );
+        assert_eq!(limiter.admit("kraken", second(60)), Admission::Admitted);
+        // The held attempts of a venue are one hit until one goes through
+        assert_eq!(
+            *hits.lock().unwrap(),
+            vec![
+                ("kraken".to_string(), ReconnectCap::Venue(2)),
+                ("okx".to_string(), ReconnectCap::Global(3)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use crate::book::OrderBook;
+use crate::clock::{Clock, SimulatedClock, SystemClock};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::{oneshot, Mutex, MutexGuard};
+
+// Books waiting for the writer task, beyond that the books are dropped instead of
+// holding up the market data
+const RECORDING_QUEUE: usize = 10_000;
+
+// Most books written and flushed at once
+const RECORDING_BATCH: usize = 256;
+
+// Version of the recording files, bumped by the changes the readers have to understand,
+// e.g. deltas instead of full books. Written in the header line of a new file
+pub const RECORDING_FORMAT: &str = "orderbook-recording";
+pub const RECORDING_FORMAT_VERSION: u32 = 1;
+
+// The first line of a recording, before the books
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub format: String,
+    pub version: u32,
+}
+
+impl RecordingHeader {
+    pub fn current() -> RecordingHeader {
+        RecordingHeader {
+            format: RECORDING_FORMAT.to_string(),
+            version: RECORDING_FORMAT_VERSION,
+        }
+    }
+
+    // The header of a line, None for a book
+    pub fn parse(line: &str) -> Option<RecordingHeader> {
+        serde_json::from_str(line).ok()
+    }
+
+    fn check(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        if self.format != RECORDING_FORMAT {
+            return Err(format!("{} is a {} file, not a recording", path, self.format).into());
+        }
+        if self.version > RECORDING_FORMAT_VERSION {
+            return Err(format!(
+                "{} is a recording of format version {}, this build reads up to version {}",
+                path, self.version, RECORDING_FORMAT_VERSION
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+// One line of a recording, the merged book at the time it was sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedBook {
+    pub timestamp: u64,
+    pub orderbook: OrderBook,
+}
+
+pub(crate) struct RecorderFile {
+    file: tokio::fs::File,
+    last_line: String,
+}
+
+enum RecorderCommand {
+    Book(u64, OrderBook),
+    Flush(oneshot::Sender<()>),
+}
+
+// Appends the merged books to a JSON lines file, so a session can be replayed and
+// reported on offline. A book equal to the previous one isn't recorded again. The
+// books are queued to a writer task, so a slow disk never stalls the reader loops,
+// and when the queue is full the books are dropped and counted
+pub struct Recorder {
+    path: String,
+    sender: Sender<RecorderCommand>,
+    file: Arc<Mutex<RecorderFile>>,
+    dropped_books: AtomicU64,
+    dropping: AtomicBool,
+}
+
+impl Recorder {
+    // Starts the writer task, so it has to be called within the tokio runtime
+    pub fn open(path: &str) -> Result<Recorder, Box<dyn Error>> {
+        Recorder::open_with_queue(path, RECORDING_QUEUE)
+    }
+
+    fn open_with_queue(path: &str, queue: usize) -> Result<Recorder, Box<dyn Error>> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        // Appended to, an existing recording keeps its header
+        if file.metadata()?.len() == 0 {
+            let header = serde_json::to_string(&RecordingHeader::current())?;
+            writeln!(file, "{}", header)?;
+        }
+        let file = Arc::new(Mutex::new(RecorderFile {
+            file: tokio::fs::File::from_std(file),
+            last_line: String::new(),
+        }));
+        let (sender, receiver) = channel(queue);
+        tokio::spawn(write_recording(
+            path.to_string(),
+            receiver,
+            Arc::clone(&file),
+        ));
+        Ok(Recorder {
+            path: path.to_string(),
+            sender,
+            file,
+            dropped_books: AtomicU64::new(0),
+            dropping: AtomicBool::new(false),
+        })
+    }
+
+    // Holds back the writes while the retention cleanup rewrites the file, called from
+    // a blocking thread
+    pub(crate) fn pause_writes(&self) -> MutexGuard<'_, RecorderFile> {
+        self.file.blocking_lock()
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+
+    // Called with the writes paused once the retention cleanup renamed the rewritten
+    // recording over the file the writer holds open
+    pub(crate) fn reopen(&self, file: &mut RecorderFile) -> Result<(), Box<dyn Error>> {
+        let reopened = OpenOptions::new().append(true).open(&self.path)?;
+        file.file = tokio::fs::File::from_std(reopened);
+        Ok(())
+    }
+
+    pub fn record(&self, orderbook: &OrderBook) {
+        self.record_at(SystemClock.now_millis(), orderbook)
+    }
+
+    pub fn record_at(&self, timestamp: u64, orderbook: &OrderBook) {
+        match self
+            .sender
+            .try_send(RecorderCommand::Book(timestamp, orderbook.clone()))
+        {
+            Ok(()) => self.dropping.store(false, Ordering::Relaxed),
+            Err(TrySendError::Full(_)) => {
+                self.dropped_books.fetch_add(1, Ordering::Relaxed);
+                if !self.dropping.swap(true, Ordering::Relaxed) {
+                    eprintln!(
+                        "Recording to {} can't keep up, dropping books ({} so far)",
+                        self.path,
+                        self.dropped_books()
+                    );
+                }
+            }
+            Err(TrySendError::Closed(_)) => {
+                eprintln!("The writer of the recording to {} stopped", self.path)
+            }
+        }
+    }
+
+    // Books left out of the recording because the writer couldn't keep up
+    pub fn dropped_books(&self) -> u64 {
+        self.dropped_books.load(Ordering::Relaxed)
+    }
+
+    // Waits until the books recorded so far are written
+    pub async fn flush(&self) {
+        let (done, written) = oneshot::channel();
+        if self.sender.send(RecorderCommand::Flush(done)).await.is_ok() {
+            let _ = written.await;
+        }
+    }
+}
+
+// Writes the queued books in batches, a write and a flush for all the books queued
+// while the previous batch was written
+async fn write_recording(
+    path: String,
+    mut receiver: Receiver<RecorderCommand>,
+    file: Arc<Mutex<RecorderFile>>,
+) {
+    while let Some(command) = receiver.recv().await {
+        let mut commands = vec![command];
+        while commands.len() < RECORDING_BATCH {
+            match receiver.try_recv() {
+                Ok(command) => commands.push(command),
+                Err(_) => break,
+            }
+        }
+
+        let mut file = file.lock().await;
+        let mut lines = String::new();
+        let mut flushed = Vec::new();
+        for command in commands {
+            let (timestamp, orderbook) = match command {
+                RecorderCommand::Book(timestamp, orderbook) => (timestamp, orderbook),
+                RecorderCommand::Flush(done) => {
+                    flushed.push(done);
+                    continue;
+                }
+            };
+            let book = match serde_json::to_string(&orderbook) {
+                Ok(book) => book,
+                Err(err) => {
+                    eprintln!("Failed to record the orderbook to {}: {}", path, err);
+                    continue;
+                }
+            };
+            if file.last_line == book {
+                continue;
+            }
+            lines.push_str(&format!(
+                "{{\"timestamp\":{},\"orderbook\":{}}}\n",
+                timestamp, book
+            ));
+            file.last_line = book;
+        }
+        // A failing recording must not stop the market data
+        if !lines.is_empty() {
+            let written = match file.file.write_all(lines.as_bytes()).await {
+                Ok(()) => file.file.flush().await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = written {
+                eprintln!("Failed to record the orderbooks to {}: {}", path, err);
+            }
+        }
+        drop(file);
+        for done in flushed {
+            let _ = done.send(());
+        }
+    }
+}
+
+// Replays a recording, in the order the books were recorded. The recordings from
+// before the header are read as version 1
+pub fn read_recording(path: &str) -> Result<Vec<RecordedBook>, Box<dyn Error>> {
+    let mut books = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(header) = RecordingHeader::parse(&line) {
+            header.check(path)?;
+            continue;
+        }
+        books.push(serde_json::from_str(&line)?);
+    }
+    Ok(books)
+}
+
+// Replays a recording on a simulated clock, set to the time of each book before it is
+// handed over, so the time based logic sees the session at the pace it was recorded
+pub fn replay_recording(
+    path: &str,
+    clock: &SimulatedClock,
+    mut on_book: impl FnMut(&RecordedBook),
+) -> Result<(), Box<dyn Error>> {
+    for book in read_recording(path)? {
+        clock.set_millis(book.timestamp);
+        on_book(&book);
+    }
+    Ok(())
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PriceAmountLevel;
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn test_recording() {
+        let path =
+            std::env::temp_dir().join(format!("orderbook-recording-{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let mut orderbook = OrderBook::new();
+        orderbook.bids.push(PriceAmountLevel {
+            exchange: "binance".to_string(),
+            price: dec!(100.0),
+            amount: dec!(1.5),
+        });
+        let recorder = Recorder::open(path).unwrap();
+        recorder.record_at(1, &orderbook);
+        // Unchanged, not recorded again
+        recorder.record_at(2, &orderbook);
+        orderbook.bids[0].amount = dec!(2);
+        recorder.record_at(3, &orderbook);
+        recorder.flush().await;
+
+        let books = read_recording(path).unwrap();
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[0].timestamp, 1);
+        assert_eq!(books[0].orderbook.bids[0].amount, dec!(1.5));
+        assert_eq!(books[1].timestamp, 3);
+        assert_eq!(books[1].orderbook.bids[0].exchange, "binance");
+
+        let clock = SimulatedClock::new(0);
+        let mut replayed = Vec::new();
+        replay_recording(path, &clock, |book| {
+            replayed.push((book.timestamp, clock.now_millis()))
+        })
+        .unwrap();
+        assert_eq!(replayed, vec![(1, 1), (3, 3)]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_recording_header() {
+        let path = std::env::temp_dir().join(format!(
+            "orderbook-recording-header-{}.log",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let book = r#"{"timestamp":1,"orderbook":{"bids":[],"asks":[],"spread":0.0,"venues":[]}}"#;
+
+        // Recorded before the header
+        std::fs::write(path, format!("{}\n", book)).unwrap();
+        assert_eq!(read_recording(path).unwrap().len(), 1);
+
+        let header = r#"{"format":"orderbook-recording","version":1}"#;
+        assert_eq!(
+            RecordingHeader::parse(header),
+            Some(RecordingHeader::current())
+        );
+        assert_eq!(RecordingHeader::parse(book), None);
+        std::fs::write(path, format!("{}\n{}\n", header, book)).unwrap();
+        assert_eq!(read_recording(path).unwrap().len(), 1);
+
+        let newer = r#"{"format":"orderbook-recording","version":2}"#;
+        std::fs::write(path, format!("{}\n{}\n", newer, book)).unwrap();
+        let err = read_recording(path).unwrap_err().to_string();
+        assert!(err.contains("format version 2, this build reads up to version 1"));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_recording_backpressure() {
+        let path = std::env::temp_dir().join(format!(
+            "orderbook-recording-backpressure-{}.log",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        // The writer task only runs once the test yields, so the queue fills up
+        let recorder = Recorder::open_with_queue(path, 2).unwrap();
+        let mut orderbook = OrderBook::new();
+        for timestamp in 1..=5 {
+            orderbook.spread = timestamp as f64;
+            recorder.record_at(timestamp, &orderbook);
+        }
+        assert_eq!(recorder.dropped_books(), 3);
+
+        recorder.flush().await;
+        let books = read_recording(path).unwrap();
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[1].timestamp, 2);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_recording_reopen() {
+        let path = std::env::temp_dir().join(format!(
+            "orderbook-recording-reopen-{}.log",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(&path);
+
+        let recorder = Arc::new(Recorder::open(&path).unwrap());
+        let mut orderbook = OrderBook::new();
+        recorder.record_at(1, &orderbook);
+        recorder.flush().await;
+
+        // The retention cleanup renames the rewritten recording over the file
+        let paused = Arc::clone(&recorder);
+        tokio::task::spawn_blocking(move || {
+            let mut file = paused.pause_writes();
+            let rewritten = format!("{}.retention", paused.path());
+            std::fs::copy(paused.path(), &rewritten).unwrap();
+            std::fs::rename(&rewritten, paused.path()).unwrap();
+            paused.reopen(&mut file).unwrap();
+        })
+        .await
+        .unwrap();
+        orderbook.spread = 1.0;
+        recorder.record_at(2, &orderbook);
+        recorder.flush().await;
+
+        let books = read_recording(&path).unwrap();
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[1].timestamp, 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::number::decimal_to_f64;
+
+// How much better the merged book is than the book of one reference venue alone, the
+// headline figure of the aggregation. The improvements are in bps of the reference's
+// mid, 0 when the reference quotes the best price itself. The extra depth is the amount
+// the merged book has on a side within band_bps of the reference's mid beyond the one
+// the reference has there
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferenceImprovement {
+    pub bid_improvement_bps: f64,
+    pub ask_improvement_bps: f64,
+    pub extra_bid_depth: f64,
+    pub extra_ask_depth: f64,
+}
+
+fn depth_within(levels: &[PriceAmountLevel], within: impl Fn(f64) -> bool) -> f64 {
+    levels
+        .iter()
+        .take_while(|level| within(decimal_to_f64(level.price)))
+        .map(|level| decimal_to_f64(level.amount))
+        .sum()
+}
+
+// None while either book lacks a side
+pub fn reference_improvement(
+    merged: &OrderBook,
+    reference: &OrderBook,
+    band_bps: f64,
+) -> Option<ReferenceImprovement> {
+    let (merged_bid, merged_ask) = (merged.bids.first()?, merged.asks.first()?);
+    let (reference_bid, reference_ask) = (reference.bids.first()?, reference.asks.first()?);
+    let mid = decimal_to_f64(reference_bid.price + reference_ask.price) / 2.0;
+    let bps = |difference: f64| (difference / mid * 10_000.0).max(0.0);
+    let (floor, ceiling) = (
+        mid * (1.0 - band_bps / 10_000.0),
+        mid * (1.0 + band_bps / 10_000.0),
+    );
+    let bid_depth = |levels: &[PriceAmountLevel]| depth_within(levels, |price| price >= floor);
+    let ask_depth = |levels: &[PriceAmountLevel]| depth_within(levels, |price| price <= ceiling);
+    Some(ReferenceImprovement {
+        bid_improvement_bps: bps(decimal_to_f64(merged_bid.price - reference_bid.price)),
+        ask_improvement_bps: bps(decimal_to_f64(reference_ask.price - merged_ask.price)),
+        extra_bid_depth: bid_depth(&merged.bids) - bid_depth(&reference.bids),
+        extra_ask_depth: ask_depth(&merged.asks) - ask_depth(&reference.asks),
+    })
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number::decimal_from_f64;
+
+    fn orderbook(bids: &[(f64, f64)], asks: &[(f64, f64)]) -> OrderBook {
+        let levels = |levels: &[(f64, f64)]| {
+            levels
+                .iter()
+                .map(|(price, amount)| PriceAmountLevel {
+                    exchange: "binance".to_string(),
+                    price: decimal_from_f64(*price).unwrap(),
+                    amount: decimal_from_f64(*amount).unwrap(),
+                })
+                .collect()
+        };
+        OrderBook {
+            bids: levels(bids),
+            asks: levels(asks),
+            ..OrderBook::new()
+        }
+    }
+
+    #[test]
+    fn test_reference_improvement() {
+        let reference = orderbook(&[(99.0, 1.0), (98.0, 5.0)], &[(101.0, 1.0), (102.0, 5.0)]);
+        // Another venue bids higher and adds an ask inside the band of 150 bps
+        let merged = orderbook(
+            &[(99.5, 2.0), (99.0, 1.0), (98.0, 5.0)],
+            &[(101.0, 1.0), (101.0, 0.5), (102.0, 5.0)],
+        );
+        let improvement = reference_improvement(&merged, &reference, 150.0).unwrap();
+        assert_eq!(improvement.bid_improvement_bps, 50.0);
+        assert_eq!(improvement.ask_improvement_bps, 0.0);
+        assert_eq!(improvement.extra_bid_depth, 2.0);
+        assert_eq!(improvement.extra_ask_depth, 0.5);
+
+        assert!(reference_improvement(&merged, &orderbook(&[], &[]), 10.0).is_none());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::connectors::ExchangeConnector;
+
+pub type ConnectorFactory = fn(depth: u32) -> Box<dyn ExchangeConnector>;
+
+// A connector registered with register_connector!, perp connectors can only be used
+// as the perpetual leg of the basis stream
+pub struct ConnectorRegistration {
+    pub name: &'static str,
+    pub perp: bool,
+    pub factory: ConnectorFactory,
+}
+
+inventory::collect!(ConnectorRegistration);
+
+// Registers a connector under a name at compile time. Connectors living in other
+// crates use the same macro, and are available once that crate is linked in:
+//
+//     orderbook::register_connector!("myvenue", false, |depth| Box::new(MyVenueConnector::new(depth)));
+#[macro_export]
+macro_rules! register_connector {
+    ($name:expr, $perp:expr, $factory:expr) => {
+        $crate::inventory::submit! {
+            $crate::registry::ConnectorRegistration {
+                name: $name,
+                perp: $perp,
+                factory: $factory,
+            }
+        }
+    };
+}
+
+pub fn find_connector(name: &str, perp: bool, depth: u32) -> Option<Box<dyn ExchangeConnector>> {
+    inventory::iter::<ConnectorRegistration>
+        .into_iter()
+        .find(|registration| registration.name == name && registration.perp == perp)
+        .map(|registration| (registration.factory)(depth))
+}
+
+pub fn connector_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = inventory::iter::<ConnectorRegistration>
+        .into_iter()
+        .map(|registration| registration.name)
+        .collect();
+    names.sort();
+    names
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::BinanceConnector;
+
+    crate::register_connector!("test_venue", false, |depth| Box::new(
+        BinanceConnector::with_url("ws://localhost", depth)
+    ));
+
+    #[test]
+    fn test_find_connector() {
+        assert_eq!(
+            find_connector("binance", false, 10).unwrap().name(),
+            "Binance"
+        );
+        assert_eq!(find_connector("bybit", true, 10).unwrap().name(), "Bybit");
+        // bybit's spot market is merged, its perpetuals are the basis stream's perp leg
+        let bybit_spot = find_connector("bybit", false, 10).unwrap();
+        assert_eq!(bybit_spot.url(), "wss://stream.bybit.com/v5/public/spot");
+        assert!(find_connector("nope", false, 10).is_none());
+
+        let connector = find_connector("test_venue", false, 10).unwrap();
+        assert_eq!(connector.url(), "ws://localhost");
+    }
+
+    #[test]
+    fn test_connector_names() {
+        let names = connector_names();
+
+        assert!(names.contains(&"binance"));
+        assert!(names.contains(&"bitstamp"));
+        assert!(names.contains(&"test_venue"));
+    }
+}
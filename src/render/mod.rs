@@ -0,0 +1,80 @@
+// Console output of the books
+use crate::book::OrderBook;
+
+pub fn print_orderbook(orderbook: &OrderBook) {
+    println!("Spread: {:#?}", orderbook.spread);
+    println!(
+        "{:<6} {:<12} {:<16} {:<12} | {:<12} {:<16} {:<12}",
+        "Depth", "BidExchange", "BidVolume", "BidPrice", "AskPrice", "AskVolume", "AskExchange"
+    );
+
+    let max_levels = orderbook.bids.len().max(orderbook.asks.len());
+
+    for i in 0..max_levels {
+        let bid = orderbook.bids.get(i);
+        let ask = orderbook.asks.get(i);
+
+        let bid_exchange = bid.map(|b| b.exchange.clone()).unwrap_or("".to_string());
+        let bid_amount = bid.map(|b| b.amount.to_string()).unwrap_or("".to_string());
+        let bid_price = bid.map(|b| b.price.to_string()).unwrap_or("".to_string());
+
+        let ask_price = ask.map(|a| a.price.to_string()).unwrap_or("".to_string());
+        let ask_amount = ask.map(|a| a.amount.to_string()).unwrap_or("".to_string());
+        let ask_exchange = ask.map(|a| a.exchange.clone()).unwrap_or("".to_string());
+
+        println!(
+            "{:<6} {:<12} {:<16} {:<12} | {:<12} {:<16} {:<12}",
+            format!("[{}]", i + 1),
+            bid_exchange,
+            bid_amount,
+            bid_price,
+            ask_price,
+            ask_amount,
+            ask_exchange
+        );
+    }
+
+    println!();
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PriceAmountLevel;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_print_orderbook() {
+        let orderbook = OrderBook {
+            bids: vec![
+                PriceAmountLevel {
+                    exchange: "exchange1".to_string(),
+                    price: dec!(10.0),
+                    amount: dec!(1.0),
+                },
+                PriceAmountLevel {
+                    exchange: "exchange2".to_string(),
+                    price: dec!(9.5),
+                    amount: dec!(2.0),
+                },
+            ],
+            asks: vec![
+                PriceAmountLevel {
+                    exchange: "exchange3".to_string(),
+                    price: dec!(11.0),
+                    amount: dec!(0.8),
+                },
+                PriceAmountLevel {
+                    exchange: "exchange4".to_string(),
+                    price: dec!(11.5),
+                    amount: dec!(0.7),
+                },
+            ],
+            spread: 0.5,
+            venues: Vec::new(),
+        };
+
+        print_orderbook(&orderbook);
+    }
+}
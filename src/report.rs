@@ -0,0 +1,423 @@
+use crate::basis::mid_price;
+use crate::number::decimal_to_f64;
+use crate::recording::{read_recording, RecordedBook};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+
+// Spread and liquidity of one recorded book. The spread is in bps of the mid,
+// ask - bid, so it is negative while the book is crossed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub timestamp: u64,
+    pub mid: f64,
+    pub spread_bps: f64,
+    pub bid_depth: f64,
+    pub ask_depth: f64,
+    pub best_bid_exchange: String,
+    pub best_ask_exchange: String,
+}
+
+// How much of the merged book an exchange provided over the recording
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Contribution {
+    pub levels: u64,
+    pub volume: f64,
+    pub best_bid: u64,
+    pub best_ask: u64,
+}
+
+// Consecutive books where the best bid of one exchange was above the best ask of another
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArbitrageEpisode {
+    pub start: u64,
+    pub end: u64,
+    pub buy_exchange: String,
+    pub sell_exchange: String,
+    pub max_profit_bps: f64,
+    pub books: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub snapshots: Vec<Snapshot>,
+    pub contributions: BTreeMap<String, Contribution>,
+    pub episodes: Vec<ArbitrageEpisode>,
+}
+
+fn snapshot(recorded: &RecordedBook) -> Option<Snapshot> {
+    let orderbook = &recorded.orderbook;
+    let best_bid = orderbook.bids.first()?;
+    let best_ask = orderbook.asks.first()?;
+    let mid = mid_price(orderbook)?;
+    Some(Snapshot {
+        timestamp: recorded.timestamp,
+        mid,
+        spread_bps: decimal_to_f64(best_ask.price - best_bid.price) / mid * 10_000.0,
+        bid_depth: orderbook
+            .bids
+            .iter()
+            .map(|level| decimal_to_f64(level.amount))
+            .sum(),
+        ask_depth: orderbook
+            .asks
+            .iter()
+            .map(|level| decimal_to_f64(level.amount))
+            .sum(),
+        best_bid_exchange: best_bid.exchange.clone(),
+        best_ask_exchange: best_ask.exchange.clone(),
+    })
+}
+
+pub fn build_report(books: &[RecordedBook]) -> Report {
+    let mut report = Report::default();
+    let mut episode: Option<ArbitrageEpisode> = None;
+
+    for recorded in books {
+        for level in recorded
+            .orderbook
+            .bids
+            .iter()
+            .chain(recorded.orderbook.asks.iter())
+        {
+            let contribution = report
+                .contributions
+                .entry(level.exchange.clone())
+                .or_default();
+            contribution.levels += 1;
+            contribution.volume += decimal_to_f64(level.amount);
+        }
+
+        let snapshot = match snapshot(recorded) {
+            Some(snapshot) => snapshot,
+            None => continue,
+        };
+        for (exchange, bid) in [
+            (&snapshot.best_bid_exchange, true),
+            (&snapshot.best_ask_exchange, false),
+        ] {
+            let contribution = report.contributions.entry(exchange.clone()).or_default();
+            if bid {
+                contribution.best_bid += 1;
+            } else {
+                contribution.best_ask += 1;
+            }
+        }
+
+        // Buying on the ask exchange and selling on the bid exchange makes a profit
+        let crossed =
+            snapshot.spread_bps < 0.0 && snapshot.best_bid_exchange != snapshot.best_ask_exchange;
+        if crossed {
+            let profit_bps = -snapshot.spread_bps;
+            let current = episode.get_or_insert_with(|| ArbitrageEpisode {
+                start: snapshot.timestamp,
+                end: snapshot.timestamp,
+                buy_exchange: snapshot.best_ask_exchange.clone(),
+                sell_exchange: snapshot.best_bid_exchange.clone(),
+                max_profit_bps: profit_bps,
+                books: 0,
+            });
+            current.end = snapshot.timestamp;
+            current.max_profit_bps = current.max_profit_bps.max(profit_bps);
+            current.books += 1;
+        } else if let Some(finished) = episode.take() {
+            report.episodes.push(finished);
+        }
+        report.snapshots.push(snapshot);
+    }
+    report.episodes.extend(episode);
+
+    report
+}
+
+fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    let index = ((sorted.len() - 1) as f64 * percentile / 100.0).round() as usize;
+    sorted[index]
+}
+
+impl Report {
+    // (percentile, spread in bps) of the 0th, 50th, 90th, 99th and 100th percentile
+    pub fn spread_percentiles(&self) -> Vec<(f64, f64)> {
+        let mut spreads: Vec<f64> = self.snapshots.iter().map(|s| s.spread_bps).collect();
+        if spreads.is_empty() {
+            return Vec::new();
+        }
+        spreads.sort_by(|a, b| a.total_cmp(b));
+        [0.0, 50.0, 90.0, 99.0, 100.0]
+            .iter()
+            .map(|p| (*p, percentile(&spreads, *p)))
+            .collect()
+    }
+
+    // Counts of the spreads in equal width buckets, as (bucket start, bucket end, count)
+    pub fn spread_histogram(&self, buckets: usize) -> Vec<(f64, f64, u64)> {
+        let percentiles = self.spread_percentiles();
+        let (min, max) = match (percentiles.first(), percentiles.last()) {
+            (Some((_, min)), Some((_, max))) => (*min, *max),
+            _ => return Vec::new(),
+        };
+        let width = ((max - min) / buckets as f64).max(f64::EPSILON);
+        let mut counts = vec![0; buckets];
+        for snapshot in &self.snapshots {
+            let bucket = ((snapshot.spread_bps - min) / width) as usize;
+            counts[bucket.min(buckets - 1)] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| {
+                let start = min + width * i as f64;
+                (start, start + width, count)
+            })
+            .collect()
+    }
+
+    // One line per recorded book
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "timestamp,mid,spread_bps,bid_depth,ask_depth,best_bid_exchange,best_ask_exchange\n",
+        );
+        for s in &self.snapshots {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{}",
+                s.timestamp,
+                s.mid,
+                s.spread_bps,
+                s.bid_depth,
+                s.ask_depth,
+                s.best_bid_exchange,
+                s.best_ask_exchange
+            );
+        }
+        csv
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Orderbook report</title>\n\
+             <style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}\
+             td,th{border:1px solid #ccc;padding:4px 8px;text-align:right}</style>\n</head>\n<body>\n",
+        );
+        let _ = writeln!(html, "<h1>Orderbook report</h1>");
+        let (start, end) = match (self.snapshots.first(), self.snapshots.last()) {
+            (Some(first), Some(last)) => (first.timestamp, last.timestamp),
+            _ => (0, 0),
+        };
+        let _ = writeln!(
+            html,
+            "<p>{} books from {} to {} (ms since epoch)</p>",
+            self.snapshots.len(),
+            start,
+            end
+        );
+
+        let _ = writeln!(html, "<h2>Spread distribution (bps)</h2>\n<table>");
+        let _ = writeln!(html, "<tr><th>Percentile</th><th>Spread</th></tr>");
+        for (percentile, spread) in self.spread_percentiles() {
+            let _ = writeln!(
+                html,
+                "<tr><td>p{}</td><td>{:.2}</td></tr>",
+                percentile, spread
+            );
+        }
+        let _ = writeln!(html, "</table>");
+        html.push_str(&histogram_svg(&self.spread_histogram(20)));
+
+        let _ = writeln!(html, "<h2>Depth over time</h2>");
+        let bid_depth: Vec<f64> = self.snapshots.iter().map(|s| s.bid_depth).collect();
+        let ask_depth: Vec<f64> = self.snapshots.iter().map(|s| s.ask_depth).collect();
+        html.push_str(&line_chart_svg(&[
+            ("bid depth", "green", &bid_depth),
+            ("ask depth", "red", &ask_depth),
+        ]));
+
+        let _ = writeln!(html, "<h2>Per exchange contribution</h2>\n<table>");
+        let _ = writeln!(
+            html,
+            "<tr><th>Exchange</th><th>Levels</th><th>Volume</th><th>Best bid</th><th>Best ask</th></tr>"
+        );
+        let books = self.snapshots.len().max(1) as f64;
+        for (exchange, contribution) in &self.contributions {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{:.4}</td><td>{:.1}%</td><td>{:.1}%</td></tr>",
+                exchange,
+                contribution.levels,
+                contribution.volume,
+                contribution.best_bid as f64 / books * 100.0,
+                contribution.best_ask as f64 / books * 100.0
+            );
+        }
+        let _ = writeln!(html, "</table>");
+
+        let _ = writeln!(html, "<h2>Arbitrage episodes</h2>\n<table>");
+        let _ = writeln!(
+            html,
+            "<tr><th>Start</th><th>End</th><th>Duration (ms)</th><th>Buy on</th><th>Sell on</th><th>Max profit (bps)</th><th>Books</th></tr>"
+        );
+        for episode in &self.episodes {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td></tr>",
+                episode.start,
+                episode.end,
+                episode.end - episode.start,
+                episode.buy_exchange,
+                episode.sell_exchange,
+                episode.max_profit_bps,
+                episode.books
+            );
+        }
+        let _ = writeln!(html, "</table>\n</body>\n</html>");
+        html
+    }
+}
+
+const CHART_WIDTH: f64 = 800.0;
+const CHART_HEIGHT: f64 = 200.0;
+
+fn histogram_svg(histogram: &[(f64, f64, u64)]) -> String {
+    let max_count = histogram
+        .iter()
+        .map(|(_, _, count)| *count)
+        .max()
+        .unwrap_or(0);
+    let mut svg = format!(
+        "<svg width=\"{}\" height=\"{}\">\n",
+        CHART_WIDTH, CHART_HEIGHT
+    );
+    if max_count == 0 {
+        svg.push_str("</svg>\n");
+        return svg;
+    }
+    let bar_width = CHART_WIDTH / histogram.len() as f64;
+    for (i, (start, end, count)) in histogram.iter().enumerate() {
+        let height = *count as f64 / max_count as f64 * CHART_HEIGHT;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"steelblue\"><title>{:.2} to {:.2} bps: {}</title></rect>",
+            i as f64 * bar_width,
+            CHART_HEIGHT - height,
+            bar_width - 1.0,
+            height,
+            start,
+            end,
+            count
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Long recordings are downsampled to at most CHART_WIDTH points per line
+fn line_chart_svg(lines: &[(&str, &str, &[f64])]) -> String {
+    let max_value = lines
+        .iter()
+        .flat_map(|(_, _, values)| values.iter().copied())
+        .fold(0.0, f64::max);
+    let mut svg = format!(
+        "<svg width=\"{}\" height=\"{}\">\n",
+        CHART_WIDTH, CHART_HEIGHT
+    );
+    for (name, color, values) in lines {
+        if values.is_empty() || max_value <= 0.0 {
+            continue;
+        }
+        let step = (values.len() as f64 / CHART_WIDTH).ceil().max(1.0) as usize;
+        let last = (values.len() - 1).max(1) as f64;
+        let points: Vec<String> = values
+            .iter()
+            .enumerate()
+            .step_by(step)
+            .map(|(i, value)| {
+                format!(
+                    "{:.1},{:.1}",
+                    i as f64 / last * CHART_WIDTH,
+                    CHART_HEIGHT - value / max_value * CHART_HEIGHT
+                )
+            })
+            .collect();
+        let _ = writeln!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" points=\"{}\"><title>{}</title></polyline>",
+            color,
+            points.join(" "),
+            name
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Reads a recording and writes the report, as CSV if out ends with .csv and as HTML otherwise
+pub fn generate_report(file: &str, out: &str) -> Result<Report, Box<dyn Error>> {
+    let report = build_report(&read_recording(file)?);
+    let contents = if out.ends_with(".csv") {
+        report.to_csv()
+    } else {
+        report.to_html()
+    };
+    std::fs::write(out, contents)?;
+    Ok(report)
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::{OrderBook, PriceAmountLevel};
+    use crate::number::decimal_from_f64;
+    use rust_decimal_macros::dec;
+
+    fn recorded(timestamp: u64, bid: (&str, f64), ask: (&str, f64)) -> RecordedBook {
+        let level = |(exchange, price): (&str, f64)| PriceAmountLevel {
+            exchange: exchange.to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: dec!(1.0),
+        };
+        RecordedBook {
+            timestamp,
+            orderbook: OrderBook {
+                bids: vec![level(bid)],
+                asks: vec![level(ask)],
+                spread: bid.1 - ask.1,
+                venues: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_build_report() {
+        let books = [
+            recorded(1, ("binance", 99.0), ("bitstamp", 101.0)),
+            recorded(2, ("binance", 101.0), ("bitstamp", 100.0)),
+            recorded(3, ("binance", 102.0), ("bitstamp", 100.0)),
+            recorded(4, ("binance", 99.0), ("binance", 101.0)),
+            recorded(5, ("bitstamp", 101.0), ("binance", 100.0)),
+        ];
+        let report = build_report(&books);
+
+        assert_eq!(report.snapshots.len(), 5);
+        assert!((report.snapshots[0].spread_bps - 200.0).abs() < 1e-9);
+        assert_eq!(report.episodes.len(), 2);
+        assert_eq!(report.episodes[0].start, 2);
+        assert_eq!(report.episodes[0].end, 3);
+        assert_eq!(report.episodes[0].books, 2);
+        assert_eq!(report.episodes[0].buy_exchange, "bitstamp");
+        assert_eq!(report.episodes[0].sell_exchange, "binance");
+        assert!((report.episodes[0].max_profit_bps - 200.0 / 101.0 * 100.0).abs() < 1e-9);
+        // The last episode is still open when the recording ends
+        assert_eq!(report.episodes[1].buy_exchange, "binance");
+
+        let binance = &report.contributions["binance"];
+        assert_eq!(binance.levels, 6);
+        assert_eq!(binance.best_bid, 4);
+        assert_eq!(binance.best_ask, 2);
+
+        let histogram = report.spread_histogram(4);
+        assert_eq!(histogram.iter().map(|(_, _, count)| count).sum::<u64>(), 5);
+        assert_eq!(report.to_csv().lines().count(), 6);
+        assert!(report.to_html().contains("<td>bitstamp</td>"));
+    }
+}
@@ -0,0 +1,58 @@
+use orderbook::book_diff::generate_diff;
+use orderbook::report::generate_report;
+use std::io::IsTerminal;
+
+const USAGE: &str =
+    "Usage: cargo run --bin orderbook-report -- --file <recording> --out <report.html|report.csv>\n       \
+     cargo run --bin orderbook-report -- viz-diff --file <recording> --at <ts> --vs <ts> [--out <diff.html>]";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The recording is written by the server with --record <path>
+    let args: Vec<String> = std::env::args().collect();
+    let flag_value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|index| args.get(index + 1))
+            .cloned()
+    };
+
+    // viz-diff compares the books recorded at two moments, e.g. before and after
+    // liquidity was withdrawn
+    if args.get(1).map(String::as_str) == Some("viz-diff") {
+        let moment = |flag: &str| flag_value(flag).and_then(|ts| ts.parse::<u64>().ok());
+        let (file, at, vs) = match (flag_value("--file"), moment("--at"), moment("--vs")) {
+            (Some(file), Some(at), Some(vs)) => (file, at, vs),
+            _ => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+        };
+        let out = flag_value("--out");
+        let diff = generate_diff(&file, at, vs, out.as_deref())?;
+        match out {
+            Some(out) => println!(
+                "Diff of the books at {} and {} written to {}",
+                diff.at, diff.vs, out
+            ),
+            None => print!("{}", diff.to_terminal(std::io::stdout().is_terminal())),
+        }
+        return Ok(());
+    }
+
+    let (file, out) = match (flag_value("--file"), flag_value("--out")) {
+        (Some(file), Some(out)) => (file, out),
+        _ => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+    };
+
+    let report = generate_report(&file, &out)?;
+    println!(
+        "Report of {} books with {} arbitrage episodes written to {}",
+        report.snapshots.len(),
+        report.episodes.len(),
+        out
+    );
+    Ok(())
+}
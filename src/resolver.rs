@@ -0,0 +1,343 @@
+use crate::connectors::AsyncSocket;
+use native_tls::TlsConnector;
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::task::spawn_blocking;
+use tokio_tungstenite::{client_async_tls_with_config, Connector};
+use tungstenite::client::AutoStream;
+use tungstenite::handshake::client::Response;
+use tungstenite::handshake::HandshakeError;
+use tungstenite::stream::Stream;
+use tungstenite::{client, WebSocket};
+use url::Url;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+struct ResolvedHost {
+    addrs: Vec<SocketAddr>,
+    resolved_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AddrBackoff {
+    failures: u32,
+    retry_at: Instant,
+}
+
+struct ResolverState {
+    refresh_interval: Duration,
+    hosts: HashMap<(String, u16), ResolvedHost>,
+    backoffs: HashMap<SocketAddr, AddrBackoff>,
+}
+
+// Exchange hostnames resolve to several edge nodes, over IPv4 and IPv6. The resolver
+// keeps every address, tries them in turn on connect errors, and backs off from the
+// failing ones, so one bad edge node doesn't keep the feed down. Resolutions are
+// refreshed every refresh_interval
+pub(crate) struct EndpointResolver {
+    state: Mutex<ResolverState>,
+}
+
+// Alternates the address families, keeping the resolver's order within each family,
+// so a broken IPv6 (or IPv4) route costs a single attempt
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (mut first, mut second): (Vec<SocketAddr>, Vec<SocketAddr>) = match addrs.first() {
+        Some(addr) => {
+            let ipv6 = addr.is_ipv6();
+            addrs.into_iter().partition(|addr| addr.is_ipv6() == ipv6)
+        }
+        None => return addrs,
+    };
+    let mut interleaved = Vec::with_capacity(first.len() + second.len());
+    first.reverse();
+    second.reverse();
+    while !first.is_empty() || !second.is_empty() {
+        interleaved.extend(first.pop());
+        interleaved.extend(second.pop());
+    }
+    interleaved
+}
+
+impl EndpointResolver {
+    pub(crate) fn new(refresh_interval: Duration) -> EndpointResolver {
+        EndpointResolver {
+            state: Mutex::new(ResolverState {
+                refresh_interval,
+                hosts: HashMap::new(),
+                backoffs: HashMap::new(),
+            }),
+        }
+    }
+
+    pub(crate) fn set_refresh_interval(&self, refresh_interval: Duration) {
+        self.state.lock().unwrap().refresh_interval = refresh_interval;
+    }
+
+    // Addresses of the url, resolved again once the last resolution is older than the
+    // refresh interval. The previous addresses are kept if the resolution fails
+    fn resolve(&self, url: &Url, now: Instant) -> Result<Vec<SocketAddr>, Box<dyn Error>> {
+        let host = url.host_str().ok_or("No host name in the url")?.to_string();
+        let port = url.port_or_known_default().ok_or("No port in the url")?;
+        let key = (host, port);
+
+        {
+            let state = self.state.lock().unwrap();
+            if let Some(resolved) = state.hosts.get(&key) {
+                if now.duration_since(resolved.resolved_at) < state.refresh_interval {
+                    return Ok(resolved.addrs.clone());
+                }
+            }
+        }
+
+        // Resolved without holding the lock, the other connections keep their addresses
+        let resolution = url.socket_addrs(|| None);
+        let mut state = self.state.lock().unwrap();
+        match resolution {
+            Ok(addrs) if !addrs.is_empty() => {
+                let addrs = interleave_families(addrs);
+                state.hosts.insert(
+                    key,
+                    ResolvedHost {
+                        addrs: addrs.clone(),
+                        resolved_at: now,
+                    },
+                );
+                Ok(addrs)
+            }
+            result => match state.hosts.get(&key) {
+                Some(resolved) => {
+                    eprintln!("Failed to resolve {}, using the previous addresses", key.0);
+                    Ok(resolved.addrs.clone())
+                }
+                None => Err(match result {
+                    Err(err) => err.into(),
+                    Ok(_) => format!("{} resolved to no address", key.0).into(),
+                }),
+            },
+        }
+    }
+
+    // Addresses in the order they should be tried, the ones backing off go last,
+    // the soonest to retry first
+    fn candidates(&self, addrs: &[SocketAddr], now: Instant) -> Vec<SocketAddr> {
+        let state = self.state.lock().unwrap();
+        let (mut ready, mut backing_off): (Vec<SocketAddr>, Vec<SocketAddr>) =
+            addrs.iter().partition(|addr| {
+                state
+                    .backoffs
+                    .get(addr)
+                    .is_none_or(|backoff| backoff.retry_at <= now)
+            });
+        backing_off.sort_by_key(|addr| state.backoffs[addr].retry_at);
+        ready.extend(backing_off);
+        ready
+    }
+
+    fn record_failure(&self, addr: SocketAddr, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        let failures = state
+            .backoffs
+            .get(&addr)
+            .map_or(0, |backoff| backoff.failures)
+            + 1;
+        let delay = BACKOFF_BASE
+            .saturating_mul(2u32.saturating_pow(failures - 1))
+            .min(BACKOFF_MAX);
+        state.backoffs.insert(
+            addr,
+            AddrBackoff {
+                failures,
+                retry_at: now + delay,
+            },
+        );
+    }
+
+    fn record_success(&self, addr: SocketAddr) {
+        self.state.lock().unwrap().backoffs.remove(&addr);
+    }
+
+    // Connects the websocket of the url, failing over across the addresses of its host
+    pub(crate) fn connect(
+        &self,
+        url: &Url,
+        tls_connector: &TlsConnector,
+    ) -> Result<(WebSocket<AutoStream>, Response), Box<dyn Error>> {
+        let addrs = self.resolve(url, Instant::now())?;
+        let mut last_err: Box<dyn Error> = format!("No address to connect to {}", url).into();
+        for addr in self.candidates(&addrs, Instant::now()) {
+            match connect_addr(url, addr, tls_connector) {
+                Ok(connected) => {
+                    self.record_success(addr);
+                    return Ok(connected);
+                }
+                Err(err) => {
+                    eprintln!("Failed to connect to {} at {}: {}", url, addr, err);
+                    self.record_failure(addr, Instant::now());
+                    last_err = err;
+                }
+            }
+        }
+        Err(last_err)
+    }
+
+    // Same as connect, for the sockets read by the server's async tasks
+    pub(crate) async fn connect_async(
+        &'static self,
+        url: &Url,
+        tls_connector: &TlsConnector,
+    ) -> Result<(AsyncSocket, Response), Box<dyn Error>> {
+        // Resolving blocks once the cached addresses are stale
+        let addrs = {
+            let url = url.clone();
+            spawn_blocking(move || {
+                self.resolve(&url, Instant::now())
+                    .map_err(|err| err.to_string())
+            })
+            .await??
+        };
+        let mut last_err = format!("No address to connect to {}", url);
+        for addr in self.candidates(&addrs, Instant::now()) {
+            match connect_addr_async(url, addr, tls_connector).await {
+                Ok(connected) => {
+                    self.record_success(addr);
+                    return Ok(connected);
+                }
+                Err(err) => {
+                    eprintln!("Failed to connect to {} at {}: {}", url, addr, err);
+                    self.record_failure(addr, Instant::now());
+                    last_err = err;
+                }
+            }
+        }
+        Err(last_err.into())
+    }
+}
+
+fn connect_addr(
+    url: &Url,
+    addr: SocketAddr,
+    tls_connector: &TlsConnector,
+) -> Result<(WebSocket<AutoStream>, Response), Box<dyn Error>> {
+    let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+    stream.set_nodelay(true)?;
+    let stream = match url.scheme() {
+        "wss" => {
+            let domain = url.host_str().ok_or("No host name in the url")?;
+            Stream::Tls(
+                tls_connector
+                    .connect(domain, stream)
+                    .map_err(|err| err.to_string())?,
+            )
+        }
+        _ => Stream::Plain(stream),
+    };
+    client(url.as_str(), stream).map_err(|err| match err {
+        HandshakeError::Failure(err) => err.into(),
+        HandshakeError::Interrupted(_) => "Websocket handshake interrupted".into(),
+    })
+}
+
+async fn connect_addr_async(
+    url: &Url,
+    addr: SocketAddr,
+    tls_connector: &TlsConnector,
+) -> Result<(AsyncSocket, Response), String> {
+    let stream = tokio::time::timeout(CONNECT_TIMEOUT, tokio::net::TcpStream::connect(addr))
+        .await
+        .map_err(|_| "Connection timed out".to_string())?
+        .map_err(|err| err.to_string())?;
+    stream.set_nodelay(true).map_err(|err| err.to_string())?;
+    let connector = match url.scheme() {
+        "wss" => Connector::NativeTls(tls_connector.clone()),
+        _ => Connector::Plain,
+    };
+    client_async_tls_with_config(url.as_str(), stream, None, Some(connector))
+        .await
+        .map_err(|err| err.to_string())
+}
+
+// Shared by every exchange connection of the process
+pub(crate) fn endpoint_resolver() -> &'static EndpointResolver {
+    static RESOLVER: OnceLock<EndpointResolver> = OnceLock::new();
+    RESOLVER.get_or_init(|| EndpointResolver::new(Duration::from_secs(60)))
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn test_interleave_families() {
+        let addrs = vec![
+            addr("[2001:db8::1]:443"),
+            addr("[2001:db8::2]:443"),
+            addr("[2001:db8::3]:443"),
+            addr("192.0.2.1:443"),
+        ];
+        assert_eq!(
+            interleave_families(addrs),
+            vec![
+                addr("[2001:db8::1]:443"),
+                addr("192.0.2.1:443"),
+                addr("[2001:db8::2]:443"),
+                addr("[2001:db8::3]:443"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failover_backoff() {
+        let resolver = EndpointResolver::new(Duration::from_secs(60));
+        let addrs = [addr("192.0.2.1:443"), addr("192.0.2.2:443")];
+        let now = Instant::now();
+
+        resolver.record_failure(addrs[0], now);
+        assert_eq!(resolver.candidates(&addrs, now), vec![addrs[1], addrs[0]]);
+        // Backed off for a second after the first failure, two after the second
+        assert_eq!(
+            resolver.candidates(&addrs, now + Duration::from_secs(1)),
+            addrs.to_vec()
+        );
+        resolver.record_failure(addrs[0], now);
+        assert_eq!(
+            resolver.candidates(&addrs, now + Duration::from_secs(1)),
+            vec![addrs[1], addrs[0]]
+        );
+
+        resolver.record_failure(addrs[1], now + Duration::from_millis(1500));
+        // Both backing off, the soonest to retry goes first
+        assert_eq!(
+            resolver.candidates(&addrs, now + Duration::from_millis(1500)),
+            vec![addrs[0], addrs[1]]
+        );
+
+        resolver.record_success(addrs[0]);
+        assert_eq!(resolver.candidates(&addrs, now), addrs.to_vec());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let resolver = EndpointResolver::new(Duration::from_secs(60));
+        let url = Url::parse("ws://127.0.0.1:9443/ws").unwrap();
+        let now = Instant::now();
+        assert_eq!(
+            resolver.resolve(&url, now).unwrap(),
+            vec![addr("127.0.0.1:9443")]
+        );
+        let url = Url::parse("wss://[::1]/ws").unwrap();
+        assert_eq!(
+            resolver.resolve(&url, now).unwrap(),
+            vec![addr("[::1]:443")]
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use crate::metrics::MetricsRegistry;
+use crate::recording::RecordingHeader;
+use serde_json::Value;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::time::Duration;
+
+// How much of the JSON lines files (recording, audit log, usage export) is kept.
+// Records older than max_age are removed, then the oldest records of all the files
+// until they fit in max_total_bytes together
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RetentionPolicy {
+    pub max_age: Option<Duration>,
+    pub max_total_bytes: Option<u64>,
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_total_bytes.is_some()
+    }
+}
+
+// Disk usage of one file after the cleanup
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileUsage {
+    pub path: String,
+    pub bytes: u64,
+    pub records: u64,
+    pub removed_records: u64,
+}
+
+impl FileUsage {
+    // The usage as the orderbook_retention_* gauges, labelled with the file's path
+    pub(crate) fn record_metrics(&self, metrics: &MetricsRegistry) {
+        let labels = [("path", self.path.as_str())];
+        for (name, value) in [
+            ("bytes", self.bytes),
+            ("records", self.records),
+            ("removed_records", self.removed_records),
+        ] {
+            let name = format!("orderbook_retention_{}", name);
+            metrics.set_gauge(&name, &labels, value as f64);
+        }
+    }
+}
+
+// The date and size of a line, the lines themselves are streamed from the files
+struct Record {
+    file: usize,
+    timestamp: u64,
+    bytes: u64,
+    kept: bool,
+    // The header of a recording, kept with the file whatever its records
+    header: bool,
+}
+
+// Records are dated by their timestamp field in ms, lines without one go first
+fn record_timestamp(line: &str) -> u64 {
+    serde_json::from_str::<Value>(line)
+        .ok()
+        .and_then(|record| record["timestamp"].as_u64())
+        .unwrap_or(0)
+}
+
+fn write_kept_lines(path: &str, temp_path: &str, kept: &[bool]) -> Result<(), Box<dyn Error>> {
+    let mut temp = BufWriter::new(File::create(temp_path)?);
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        // Lines appended since the records were read are kept
+        if kept.get(index).copied().unwrap_or(true) {
+            writeln!(temp, "{}", line)?;
+        }
+    }
+    temp.into_inner()?.sync_all()?;
+    Ok(())
+}
+
+// The kept lines are streamed into a file next to the original, renamed over it once
+// complete, so a crash midway leaves the original whole
+fn rewrite_file(path: &str, kept: &[bool]) -> Result<(), Box<dyn Error>> {
+    let temp_path = format!("{}.retention", path);
+    if let Err(err) = write_kept_lines(path, &temp_path, kept) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+// Files with removed records are rewritten to new files renamed over them. The caller
+// holds the writers' locks while the files are cleaned up, and reopens the rewritten
+// ones before releasing them
+pub(crate) fn apply_retention(
+    paths: &[String],
+    policy: &RetentionPolicy,
+    now: u64,
+) -> Result<Vec<FileUsage>, Box<dyn Error>> {
+    let mut records = Vec::new();
+    for (file, path) in paths.iter().enumerate() {
+        let reader = match File::open(path) {
+            Ok(reader) => BufReader::new(reader),
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        for line in reader.lines() {
+            let line = line?;
+            records.push(Record {
+                file,
+                timestamp: record_timestamp(&line),
+                bytes: line.len() as u64 + 1,
+                kept: true,
+                header: RecordingHeader::parse(&line).is_some(),
+            });
+        }
+    }
+
+    if let Some(max_age) = policy.max_age {
+        let oldest = now.saturating_sub(max_age.as_millis() as u64);
+        for record in records.iter_mut() {
+            record.kept = record.header || record.timestamp >= oldest;
+        }
+    }
+    if let Some(max_total_bytes) = policy.max_total_bytes {
+        let mut total_bytes: u64 = records
+            .iter()
+            .filter(|record| record.kept)
+            .map(|record| record.bytes)
+            .sum();
+        let mut by_age: Vec<&mut Record> = records
+            .iter_mut()
+            .filter(|record| record.kept && !record.header)
+            .collect();
+        by_age.sort_by_key(|record| record.timestamp);
+        for record in by_age {
+            if total_bytes <= max_total_bytes {
+                break;
+            }
+            record.kept = false;
+            total_bytes -= record.bytes;
+        }
+    }
+
+    let mut usages = Vec::new();
+    for (file, path) in paths.iter().enumerate() {
+        let mut usage = FileUsage {
+            path: path.clone(),
+            bytes: 0,
+            records: 0,
+            removed_records: 0,
+        };
+        let mut kept = Vec::new();
+        for record in records.iter().filter(|record| record.file == file) {
+            if record.kept {
+                usage.bytes += record.bytes;
+                usage.records += u64::from(!record.header);
+            } else {
+                usage.removed_records += 1;
+            }
+            kept.push(record.kept);
+        }
+        if usage.removed_records > 0 {
+            rewrite_file(path, &kept)?;
+        }
+        usages.push(usage);
+    }
+    Ok(usages)
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_retention() {
+        let dir = std::env::temp_dir();
+        let recording = dir.join(format!("orderbook-retention-{}.log", std::process::id()));
+        let audit_log = dir.join(format!(
+            "orderbook-retention-audit-{}.log",
+            std::process::id()
+        ));
+        let paths = [
+            recording.to_str().unwrap().to_string(),
+            audit_log.to_str().unwrap().to_string(),
+        ];
+        fs::write(
+            &paths[0],
+            "{\"timestamp\":1000}\n{\"timestamp\":5000}\n{\"timestamp\":9000}\n",
+        )
+        .unwrap();
+        fs::write(&paths[1], "{\"timestamp\":2000}\n{\"timestamp\":8000}\n").unwrap();
+
+        // Records older than 5 seconds are removed
+        let policy = RetentionPolicy {
+            max_age: Some(Duration::from_secs(5)),
+            max_total_bytes: None,
+        };
+        let usages = apply_retention(&paths, &policy, 10_000).unwrap();
+        assert_eq!(usages[0].records, 2);
+        assert_eq!(usages[0].removed_records, 1);
+        assert_eq!(usages[1].records, 1);
+        assert_eq!(
+            fs::read_to_string(&paths[1]).unwrap(),
+            "{\"timestamp\":8000}\n"
+        );
+
+        // 19 bytes per record, the oldest record of both files goes
+        let policy = RetentionPolicy {
+            max_age: None,
+            max_total_bytes: Some(40),
+        };
+        let usages = apply_retention(&paths, &policy, 10_000).unwrap();
+        assert_eq!(usages[0].records, 1);
+        assert_eq!(usages[0].bytes, 19);
+        assert_eq!(
+            fs::read_to_string(&paths[0]).unwrap(),
+            "{\"timestamp\":9000}\n"
+        );
+        assert_eq!(usages[1].records, 1);
+        let metrics = MetricsRegistry::default();
+        usages[0].record_metrics(&metrics);
+        assert!(metrics.render().contains(&format!(
+            "orderbook_retention_bytes{{path=\"{}\"}} 19\n",
+            paths[0]
+        )));
+        // The rewritten files replaced the originals
+        assert!(!std::path::Path::new(&format!("{}.retention", paths[0])).exists());
+
+        for path in &paths {
+            fs::remove_file(path).unwrap();
+        }
+        // Missing files are skipped
+        assert_eq!(
+            apply_retention(&paths, &policy, 10_000).unwrap()[0].bytes,
+            0
+        );
+
+        // The header of a recording outlives its records
+        let header = "{\"format\":\"orderbook-recording\",\"version\":1}\n";
+        fs::write(&paths[0], format!("{}{{\"timestamp\":1000}}\n", header)).unwrap();
+        let policy = RetentionPolicy {
+            max_age: Some(Duration::from_secs(5)),
+            max_total_bytes: None,
+        };
+        let usages = apply_retention(&paths[..1], &policy, 10_000).unwrap();
+        assert_eq!((usages[0].records, usages[0].removed_records), (0, 1));
+        assert_eq!(fs::read_to_string(&paths[0]).unwrap(), header);
+        fs::remove_file(&paths[0]).unwrap();
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::OnceLock;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+// Started on first use by applications without a tokio runtime, and never shut down
+static OWN_RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+// The runtime the aggregator runs its tasks on when ServerOptions::runtime isn't set:
+// the current one when called from tokio, else a multi-threaded runtime of the library's
+// own, so the aggregator can be embedded in applications on async-std, a plain
+// futures executor or threads without any runtime
+pub fn default_runtime() -> Handle {
+    Handle::try_current().unwrap_or_else(|_| {
+        OWN_RUNTIME
+            .get_or_init(|| {
+                Builder::new_multi_thread()
+                    .enable_all()
+                    .thread_name("orderbook-runtime")
+                    .build()
+                    .expect("Failed to start the tokio runtime")
+            })
+            .handle()
+            .clone()
+    })
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_runtime() {
+        // No runtime on the test's thread, the tasks run on the library's own
+        let runtime = default_runtime();
+        let task = runtime.spawn(async {
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            7
+        });
+        assert_eq!(futures::executor::block_on(task).unwrap(), 7);
+        assert!(Handle::try_current().is_err());
+
+        // Within a runtime, its own handle
+        let current = Builder::new_current_thread().enable_all().build().unwrap();
+        let on_current = current.block_on(async {
+            default_runtime()
+                .spawn(async { std::thread::current().name().map(str::to_string) })
+                .await
+                .unwrap()
+        });
+        assert_ne!(on_current.as_deref(), Some("orderbook-runtime"));
+    }
+}
@@ -0,0 +1,279 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::number::{decimal_from_f64, decimal_to_f64};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, NativeCallContext, Scope, AST};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+// Scripts can't run forever on the hot path
+const MAX_OPERATIONS: u64 = 100_000;
+
+// What a script produced for one merged update
+#[derive(Debug, Default, Clone)]
+pub struct ScriptOutput {
+    // Set when the script returned a transformed summary
+    pub orderbook: Option<OrderBook>,
+    pub alerts: Vec<(String, String)>,
+    pub signals: HashMap<String, f64>,
+}
+
+// Runs a user provided Rhai script on every merged update. The script defines
+// `fn on_update(summary)` and may call `alert(kind, message)` and `signal(name, value)`.
+// Returning a summary map replaces the summary, returning () keeps it unchanged
+pub struct ScriptHook {
+    engine: Engine,
+    ast: AST,
+}
+
+// The output of one call of the script, passed as the call's tag since the reader
+// tasks of the venues run the hook at the same time
+type CallOutput = Arc<Mutex<ScriptOutput>>;
+
+fn call_output(context: &NativeCallContext) -> Option<CallOutput> {
+    context.tag()?.clone().try_cast::<CallOutput>()
+}
+
+fn level_to_map(level: &PriceAmountLevel) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("exchange".into(), level.exchange.clone().into());
+    // Scripts get doubles, their levels are read back as the shortest decimals
+    map.insert("price".into(), decimal_to_f64(level.price).into());
+    map.insert("amount".into(), decimal_to_f64(level.amount).into());
+    Dynamic::from_map(map)
+}
+
+fn orderbook_to_map(orderbook: &OrderBook) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("spread".into(), orderbook.spread.into());
+    let bids: Array = orderbook.bids.iter().map(level_to_map).collect();
+    let asks: Array = orderbook.asks.iter().map(level_to_map).collect();
+    map.insert("bids".into(), Dynamic::from_array(bids));
+    map.insert("asks".into(), Dynamic::from_array(asks));
+    Dynamic::from_map(map)
+}
+
+// Scripts may write whole numbers, e.g. `level.price = 100`
+fn as_number(value: Option<&Dynamic>) -> Option<f64> {
+    let value = value?;
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|int| int as f64))
+}
+
+fn map_to_levels(levels: Option<&Dynamic>) -> Option<Vec<PriceAmountLevel>> {
+    levels?
+        .clone()
+        .try_cast::<Array>()?
+        .into_iter()
+        .map(|level| {
+            let level = level.try_cast::<Map>()?;
+            Some(PriceAmountLevel {
+                exchange: level.get("exchange")?.clone().into_string().ok()?,
+                price: decimal_from_f64(as_number(level.get("price"))?)?,
+                amount: decimal_from_f64(as_number(level.get("amount"))?)?,
+            })
+        })
+        .collect()
+}
+
+fn map_to_orderbook(map: Map, venues_from: &OrderBook) -> Option<OrderBook> {
+    Some(OrderBook {
+        bids: map_to_levels(map.get("bids"))?,
+        asks: map_to_levels(map.get("asks"))?,
+        spread: as_number(map.get("spread"))?,
+        venues: venues_from.venues.clone(),
+    })
+}
+
+impl ScriptHook {
+    pub fn new(script: &str) -> Result<ScriptHook, Box<dyn Error>> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        engine.register_fn(
+            "alert",
+            |context: NativeCallContext, kind: &str, message: &str| {
+                if let Some(output) = call_output(&context) {
+                    output
+                        .lock()
+                        .unwrap()
+                        .alerts
+                        .push((kind.to_string(), message.to_string()));
+                }
+            },
+        );
+        engine.register_fn(
+            "signal",
+            |context: NativeCallContext, name: &str, value: f64| {
+                if let Some(output) = call_output(&context) {
+                    output
+                        .lock()
+                        .unwrap()
+                        .signals
+                        .insert(name.to_string(), value);
+                }
+            },
+        );
+
+        let ast = engine.compile(script)?;
+        Ok(ScriptHook { engine, ast })
+    }
+
+    pub fn from_file(path: &str) -> Result<ScriptHook, Box<dyn Error>> {
+        ScriptHook::new(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn on_update(&self, orderbook: &OrderBook) -> Result<ScriptOutput, Box<dyn Error>> {
+        let call_output = CallOutput::default();
+        let options = CallFnOptions::new().with_tag(Arc::clone(&call_output));
+        let result: Dynamic = self.engine.call_fn_with_options(
+            options,
+            &mut Scope::new(),
+            &self.ast,
+            "on_update",
+            (orderbook_to_map(orderbook),),
+        )?;
+
+        let mut output = call_output.lock().unwrap().clone();
+        if let Some(map) = result.try_cast::<Map>() {
+            output.orderbook = Some(
+                map_to_orderbook(map, orderbook).ok_or("on_update returned an invalid summary")?,
+            );
+        }
+        Ok(output)
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn orderbook() -> OrderBook {
+        OrderBook {
+            bids: vec![PriceAmountLevel {
+                exchange: "binance".to_string(),
+                price: dec!(10.0),
+                amount: dec!(1.0),
+            }],
+            asks: vec![PriceAmountLevel {
+                exchange: "bitstamp".to_string(),
+                price: dec!(11.0),
+                amount: dec!(3.0),
+            }],
+            spread: -1.0,
+            venues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_on_update_signals_and_alerts() {
+        let hook = ScriptHook::new(
+            r#"
+            fn on_update(summary) {
+                let bid = summary.bids[0];
+                let ask = summary.asks[0];
+                signal("imbalance", bid.amount / (bid.amount + ask.amount));
+                if summary.spread < -0.5 {
+                    alert("wide_spread", "spread is " + summary.spread);
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let output = hook.on_update(&orderbook()).unwrap();
+
+        assert!(output.orderbook.is_none());
+        assert_eq!(output.signals["imbalance"], 0.25);
+        assert_eq!(output.alerts.len(), 1);
+        assert_eq!(output.alerts[0].0, "wide_spread");
+
+        // Outputs don't leak into the next update
+        let output = hook.on_update(&orderbook()).unwrap();
+        assert_eq!(output.alerts.len(), 1);
+    }
+
+    #[test]
+    fn test_on_update_transform() {
+        let hook = ScriptHook::new(
+            r#"
+            fn on_update(summary) {
+                summary.asks = [];
+                summary.spread = 0;
+                summary
+            }
+            "#,
+        )
+        .unwrap();
+
+        let transformed = hook.on_update(&orderbook()).unwrap().orderbook.unwrap();
+
+        assert_eq!(transformed.bids.len(), 1);
+        assert!(transformed.asks.is_empty());
+        assert_eq!(transformed.spread, 0.0);
+    }
+
+    #[test]
+    fn test_example_script() {
+        let hook = ScriptHook::from_file("scripts/imbalance.rhai").unwrap();
+
+        let output = hook.on_update(&orderbook()).unwrap();
+
+        assert_eq!(output.signals["imbalance"], -0.5);
+        assert!(output.alerts.is_empty());
+    }
+
+    #[test]
+    fn test_on_update_concurrent_calls() {
+        // Each call is slow enough for the other thread's calls to run meanwhile
+        let hook = Arc::new(
+            ScriptHook::new(
+                r#"
+                fn on_update(summary) {
+                    let price = summary.bids[0].price;
+                    signal("bid", price);
+                    let i = 0;
+                    while i < 1000 { i += 1; }
+                    alert("bid", "bid at " + price);
+                }
+                "#,
+            )
+            .unwrap(),
+        );
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+        let threads: Vec<_> = [dec!(10.0), dec!(20.0)]
+            .into_iter()
+            .map(|price| {
+                let (hook, barrier) = (Arc::clone(&hook), Arc::clone(&barrier));
+                std::thread::spawn(move || {
+                    let mut orderbook = orderbook();
+                    orderbook.bids[0].price = price;
+                    barrier.wait();
+                    for _ in 0..50 {
+                        let output = hook.on_update(&orderbook).unwrap();
+                        let price = decimal_to_f64(price);
+                        assert_eq!(output.signals["bid"], price);
+                        assert_eq!(
+                            output.alerts,
+                            [("bid".to_string(), format!("bid at {:?}", price))]
+                        );
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_on_update_errors() {
+        assert!(ScriptHook::new("fn on_update(summary) {").is_err());
+
+        let hook = ScriptHook::new("fn on_update(summary) { loop {} }").unwrap();
+        assert!(hook.on_update(&orderbook()).is_err());
+    }
+}
@@ -0,0 +1,539 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::connectors::http_request_with_body;
+use crate::grpc::convert::summary_to_orderbook;
+use crate::metrics::MetricsRegistry;
+use crate::number::decimal_to_f64;
+use crate::orderbook_proto::Summary;
+use crate::recording::Recorder;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use std::time::Duration;
+use url::Url;
+
+// Summaries waiting for a sink, beyond that they are dropped for that sink only
+const SINK_QUEUE: usize = 1_000;
+
+const REDIS_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type SinkError = Box<dyn Error + Send + Sync>;
+
+// An output of the merged summaries, e.g. the recording, a webhook or a Redis channel.
+// Every sink gets the summaries with all their fields, whatever the clients subscribed
+// to. Sinks are called from a thread of their own, so they can block
+pub trait SummarySink: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn send(&self, summary: &Summary) -> Result<(), SinkError>;
+
+    // Waits until the summaries sent so far are written, for sinks buffering them
+    fn flush(&self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+impl fmt::Debug for dyn SummarySink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SummarySink").field(&self.name()).finish()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SinkMetrics {
+    pub sent: AtomicU64,
+    pub failed: AtomicU64,
+    // Summaries dropped because the sink's queue was full
+    pub dropped: AtomicU64,
+    pub last_error: Mutex<Option<String>>,
+}
+
+enum SinkCommand {
+    Summary(Arc<Summary>),
+    Flush(mpsc::Sender<()>),
+}
+
+struct SinkWorker {
+    name: String,
+    sender: SyncSender<SinkCommand>,
+    metrics: Arc<SinkMetrics>,
+}
+
+// Drives every configured sink, each from its own thread and queue, so a slow or
+// failing sink neither holds up the server loop nor the other sinks. Errors are
+// logged and counted per sink, the summary is not retried
+pub struct SinkFanOut {
+    workers: Vec<SinkWorker>,
+}
+
+impl SinkFanOut {
+    pub fn new(sinks: Vec<Arc<dyn SummarySink>>) -> SinkFanOut {
+        let workers = sinks
+            .into_iter()
+            .map(|sink| {
+                let (sender, receiver) = sync_channel(SINK_QUEUE);
+                let metrics = Arc::new(SinkMetrics::default());
+                let name = sink.name().to_string();
+                spawn({
+                    let metrics = Arc::clone(&metrics);
+                    move || drive_sink(sink, receiver, metrics)
+                });
+                SinkWorker {
+                    name,
+                    sender,
+                    metrics,
+                }
+            })
+            .collect();
+        SinkFanOut { workers }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.workers
+            .iter()
+            .map(|worker| worker.name.as_str())
+            .collect()
+    }
+
+    pub fn send(&self, summary: Summary) {
+        let summary = Arc::new(summary);
+        for worker in &self.workers {
+            match worker
+                .sender
+                .try_send(SinkCommand::Summary(Arc::clone(&summary)))
+            {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    // Logged once per thousand, a stuck sink would flood the log
+                    let dropped = worker.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+                    if dropped % 1_000 == 0 {
+                        eprintln!("Sink {} can't keep up, dropping summaries", worker.name);
+                    }
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    eprintln!("The thread of sink {} stopped", worker.name)
+                }
+            }
+        }
+    }
+
+    // Waits until every sink wrote the summaries sent so far
+    pub fn flush(&self) {
+        let acks: Vec<Receiver<()>> = self
+            .workers
+            .iter()
+            .filter_map(|worker| {
+                let (done, flushed) = mpsc::channel();
+                worker.sender.send(SinkCommand::Flush(done)).ok()?;
+                Some(flushed)
+            })
+            .collect();
+        for flushed in acks {
+            let _ = flushed.recv();
+        }
+    }
+
+    pub fn metrics(&self) -> Vec<(&str, &SinkMetrics)> {
+        self.workers
+            .iter()
+            .map(|worker| (worker.name.as_str(), worker.metrics.as_ref()))
+            .collect()
+    }
+
+    // The counters of every sink as the orderbook_sink_* gauges, labelled with the sink,
+    // its last error is logged when it changes
+    pub(crate) fn record_metrics(&self, registry: &MetricsRegistry) {
+        for (name, metrics) in self.metrics() {
+            for (gauge, counter) in [
+                ("sent", &metrics.sent),
+                ("failed", &metrics.failed),
+                ("dropped", &metrics.dropped),
+            ] {
+                let gauge = format!("orderbook_sink_{}", gauge);
+                let value = counter.load(Ordering::Relaxed) as f64;
+                registry.set_gauge(&gauge, &[("sink", name)], value);
+            }
+        }
+    }
+}
+
+fn drive_sink(
+    sink: Arc<dyn SummarySink>,
+    receiver: Receiver<SinkCommand>,
+    metrics: Arc<SinkMetrics>,
+) {
+    let record = |result: Result<(), SinkError>| match result {
+        Ok(()) => {}
+        Err(err) => {
+            metrics.failed.fetch_add(1, Ordering::Relaxed);
+            let err = err.to_string();
+            let mut last_error = metrics.last_error.lock().unwrap();
+            // Only a new error is logged, a sink that is down fails every summary
+            if last_error.as_deref() != Some(err.as_str()) {
+                eprintln!("Sink {} failed: {}", sink.name(), err);
+            }
+            *last_error = Some(err);
+        }
+    };
+    for command in receiver {
+        match command {
+            SinkCommand::Summary(summary) => {
+                let result = sink.send(&summary);
+                if result.is_ok() {
+                    metrics.sent.fetch_add(1, Ordering::Relaxed);
+                }
+                record(result);
+            }
+            SinkCommand::Flush(done) => {
+                record(sink.flush());
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+// Field names of the JSON the sinks send. Legacy is the compact book of the early
+// versions, {"b": [...], "a": [...], "s": spread} with {"exchange", "p", "q"} levels,
+// still read by older consumers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonFormat {
+    #[default]
+    Canonical,
+    Legacy,
+}
+
+impl JsonFormat {
+    pub fn from_name(name: &str) -> Option<JsonFormat> {
+        match name {
+            "canonical" => Some(JsonFormat::Canonical),
+            "legacy" => Some(JsonFormat::Legacy),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LegacyLevel<'a> {
+    exchange: &'a str,
+    #[serde(rename = "p")]
+    price: f64,
+    #[serde(rename = "q")]
+    amount: f64,
+}
+
+#[derive(Serialize)]
+struct LegacyOrderBook<'a> {
+    #[serde(rename = "b")]
+    bids: Vec<LegacyLevel<'a>>,
+    #[serde(rename = "a")]
+    asks: Vec<LegacyLevel<'a>>,
+    #[serde(rename = "s")]
+    spread: f64,
+}
+
+fn legacy_levels(levels: &[PriceAmountLevel]) -> Vec<LegacyLevel<'_>> {
+    levels
+        .iter()
+        .map(|level| LegacyLevel {
+            exchange: &level.exchange,
+            price: decimal_to_f64(level.price),
+            amount: decimal_to_f64(level.amount),
+        })
+        .collect()
+}
+
+fn legacy_orderbook(orderbook: &OrderBook) -> LegacyOrderBook<'_> {
+    LegacyOrderBook {
+        bids: legacy_levels(&orderbook.bids),
+        asks: legacy_levels(&orderbook.asks),
+        spread: orderbook.spread,
+    }
+}
+
+// The summary as JSON, for the sinks sending it to other systems
+pub fn summary_json(summary: &Summary) -> String {
+    summary_json_as(summary, JsonFormat::Canonical)
+}
+
+// The summary as JSON with the field names of the format, the envelope is the same
+pub fn summary_json_as(summary: &Summary, format: JsonFormat) -> String {
+    let orderbook = summary_to_orderbook(summary);
+    let orderbook = match format {
+        JsonFormat::Canonical => serde_json::json!(orderbook),
+        JsonFormat::Legacy => serde_json::json!(legacy_orderbook(&orderbook)),
+    };
+    serde_json::json!({
+        "symbol": summary.symbol,
+        "timestamp": summary.timestamp,
+        "sequence": summary.sequence,
+        "status": summary.status().as_str_name(),
+        "orderbook": orderbook,
+    })
+    .to_string()
+}
+
+impl SummarySink for Recorder {
+    fn name(&self) -> &str {
+        "recording"
+    }
+
+    fn send(&self, summary: &Summary) -> Result<(), SinkError> {
+        self.record_at(summary.timestamp, &summary_to_orderbook(summary));
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), SinkError> {
+        futures::executor::block_on(Recorder::flush(self));
+        Ok(())
+    }
+}
+
+// POSTs every summary as JSON to a url, a response other than 2xx is an error
+pub struct WebhookSink {
+    url: Url,
+    format: JsonFormat,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str) -> Result<WebhookSink, Box<dyn Error>> {
+        Ok(WebhookSink {
+            url: Url::parse(url)?,
+            format: JsonFormat::Canonical,
+        })
+    }
+
+    pub fn with_format(self, format: JsonFormat) -> WebhookSink {
+        WebhookSink { format, ..self }
+    }
+}
+
+impl SummarySink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn send(&self, summary: &Summary) -> Result<(), SinkError> {
+        let body = summary_json_as(summary, self.format);
+        let (status, _) =
+            http_request_with_body("POST", &self.url, &body).map_err(|err| err.to_string())?;
+        match status {
+            200..=299 => Ok(()),
+            _ => Err(format!("{} answered HTTP {}", self.url, status).into()),
+        }
+    }
+}
+
+// PUBLISHes every summary as JSON on a Redis channel, from a redis://host:port/channel
+// url (orderbook when there is no channel). The connection is opened again after an
+// error
+pub struct RedisSink {
+    addr: String,
+    channel: String,
+    format: JsonFormat,
+    stream: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+impl RedisSink {
+    pub fn new(url: &str) -> Result<RedisSink, Box<dyn Error>> {
+        let url = Url::parse(url)?;
+        if url.scheme() != "redis" {
+            return Err(format!("Not a redis:// url: {}", url).into());
+        }
+        let host = url.host_str().ok_or("No host name in the url")?;
+        let channel = url.path().trim_start_matches('/');
+        Ok(RedisSink {
+            addr: format!("{}:{}", host, url.port().unwrap_or(6379)),
+            channel: match channel {
+                "" => "orderbook".to_string(),
+                channel => channel.to_string(),
+            },
+            format: JsonFormat::Canonical,
+            stream: Mutex::new(None),
+        })
+    }
+
+    pub fn with_format(self, format: JsonFormat) -> RedisSink {
+        RedisSink { format, ..self }
+    }
+
+    fn publish(&self, stream: &mut BufReader<TcpStream>, payload: &str) -> Result<(), SinkError> {
+        stream
+            .get_mut()
+            .write_all(&redis_command(&["PUBLISH", &self.channel, payload]))?;
+        let mut reply = String::new();
+        stream.read_line(&mut reply)?;
+        match reply.strip_prefix('-') {
+            Some(error) => Err(error.trim().to_string().into()),
+            None if reply.starts_with(':') => Ok(()),
+            None => Err(format!("Unexpected reply from Redis: {}", reply.trim()).into()),
+        }
+    }
+}
+
+// A command in the RESP protocol, an array of bulk strings
+fn redis_command(args: &[&str]) -> Vec<u8> {
+    let mut command = format!("*{}\r\n", args.len());
+    for arg in args {
+        command.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    command.into_bytes()
+}
+
+impl SummarySink for RedisSink {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    fn send(&self, summary: &Summary) -> Result<(), SinkError> {
+        let mut stream = self.stream.lock().unwrap();
+        if stream.is_none() {
+            let connection = TcpStream::connect(&self.addr)?;
+            connection.set_read_timeout(Some(REDIS_TIMEOUT))?;
+            *stream = Some(BufReader::new(connection));
+        }
+        let payload = summary_json_as(summary, self.format);
+        let result = self.publish(stream.as_mut().unwrap(), &payload);
+        if result.is_err() {
+            *stream = None;
+        }
+        result
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook_proto::Level;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    struct CountingSink {
+        name: &'static str,
+        fail: bool,
+        summaries: Mutex<Vec<u64>>,
+    }
+
+    impl SummarySink for CountingSink {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn send(&self, summary: &Summary) -> Result<(), SinkError> {
+            if self.fail {
+                return Err("unreachable".into());
+            }
+            self.summaries.lock().unwrap().push(summary.sequence);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let sink = |name, fail| {
+            Arc::new(CountingSink {
+                name,
+                fail,
+                summaries: Mutex::new(Vec::new()),
+            })
+        };
+        let (healthy, failing) = (sink("healthy", false), sink("failing", true));
+        let fan_out = SinkFanOut::new(vec![healthy.clone(), failing.clone()]);
+        assert_eq!(fan_out.names(), vec!["healthy", "failing"]);
+        for sequence in 1..=3 {
+            fan_out.send(Summary {
+                sequence,
+                ..Default::default()
+            });
+        }
+        fan_out.flush();
+
+        // The failing sink doesn't keep the summaries from the other one
+        assert_eq!(*healthy.summaries.lock().unwrap(), vec![1, 2, 3]);
+        let metrics = fan_out.metrics();
+        assert_eq!(metrics[0].1.sent.load(Ordering::Relaxed), 3);
+        assert_eq!(metrics[1].1.failed.load(Ordering::Relaxed), 3);
+        assert_eq!(
+            metrics[1].1.last_error.lock().unwrap().as_deref(),
+            Some("unreachable")
+        );
+        let registry = MetricsRegistry::default();
+        fan_out.record_metrics(&registry);
+        let text = registry.render();
+        assert!(text.contains("orderbook_sink_sent{sink=\"healthy\"} 3\n"));
+        assert!(text.contains("orderbook_sink_failed{sink=\"failing\"} 3\n"));
+        assert!(text.contains("orderbook_sink_dropped{sink=\"failing\"} 0\n"));
+    }
+
+    #[test]
+    fn test_legacy_json() {
+        let summary = Summary {
+            symbol: "btcusdt".to_string(),
+            spread: 1.5,
+            bids: vec![Level {
+                exchange: "binance".to_string(),
+                price: 10.0,
+                amount: 2.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let json = |format| {
+            serde_json::from_str::<serde_json::Value>(&summary_json_as(&summary, format)).unwrap()
+        };
+        let canonical = json(JsonFormat::Canonical);
+        assert_eq!(canonical["orderbook"]["bids"][0]["price"], 10.0);
+        assert_eq!(canonical["orderbook"]["spread"], 1.5);
+
+        let legacy = json(JsonFormat::Legacy);
+        assert_eq!(legacy["symbol"], "btcusdt");
+        assert_eq!(
+            legacy["orderbook"],
+            serde_json::json!({
+                "b": [{"exchange": "binance", "p": 10.0, "q": 2.0}],
+                "a": [],
+                "s": 1.5,
+            })
+        );
+        assert_eq!(JsonFormat::from_name("legacy"), Some(JsonFormat::Legacy));
+        assert_eq!(JsonFormat::from_name("compact"), None);
+    }
+
+    #[test]
+    fn test_redis_publish() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("redis://{}/books", listener.local_addr().unwrap());
+        let server = spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut command = vec![0; 4096];
+            let read = stream.read(&mut command).unwrap();
+            stream.write_all(b":1\r\n").unwrap();
+            String::from_utf8_lossy(&command[..read]).to_string()
+        });
+
+        let sink = RedisSink::new(&url).unwrap();
+        let summary = Summary {
+            symbol: "btcusdt".to_string(),
+            ..Default::default()
+        };
+        sink.send(&summary).unwrap();
+        let payload = summary_json(&summary);
+        assert_eq!(
+            server.join().unwrap(),
+            format!(
+                "*3\r\n$7\r\nPUBLISH\r\n$5\r\nbooks\r\n${}\r\n{}\r\n",
+                payload.len(),
+                payload
+            )
+        );
+    }
+}
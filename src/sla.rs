@@ -0,0 +1,200 @@
+use std::fmt;
+use std::time::Duration;
+
+// Liquidity conditions every merged book of the window has to meet, unchecked when None
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SlaConditions {
+    pub max_spread_bps: Option<f64>,
+    // Amount on each side of the book, in units of the base asset
+    pub min_depth: Option<f64>,
+}
+
+// Outcome of the window: the books seen, how many broke each condition and the worst
+// values reached
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SlaVerdict {
+    pub conditions: SlaConditions,
+    pub books: u64,
+    pub spread_violations: u64,
+    pub depth_violations: u64,
+    pub worst_spread_bps: Option<f64>,
+    pub min_depth: Option<f64>,
+}
+
+impl SlaVerdict {
+    pub fn passed(&self) -> bool {
+        self.books > 0 && self.spread_violations == 0 && self.depth_violations == 0
+    }
+
+    // For shell scripts: 0 when the conditions held, 1 on a violation, 2 when no book
+    // was received to judge them
+    pub fn exit_code(&self) -> i32 {
+        match (self.books, self.passed()) {
+            (0, _) => 2,
+            (_, true) => 0,
+            (_, false) => 1,
+        }
+    }
+}
+
+impl fmt::Display for SlaVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.books == 0 {
+            return write!(f, "NO DATA: no book received");
+        }
+        let verdict = if self.passed() { "PASS" } else { "FAIL" };
+        write!(f, "{}: {} books", verdict, self.books)?;
+        if let Some(max_spread_bps) = self.conditions.max_spread_bps {
+            write!(
+                f,
+                ", spread above {} bps in {} (worst {:.2} bps)",
+                max_spread_bps,
+                self.spread_violations,
+                self.worst_spread_bps.unwrap_or(f64::INFINITY)
+            )?;
+        }
+        if let Some(min_depth) = self.conditions.min_depth {
+            write!(
+                f,
+                ", depth below {} in {} (min {})",
+                min_depth,
+                self.depth_violations,
+                self.min_depth.unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Infinite while a side is missing
+fn spread_bps(best_bid: Option<f64>, best_ask: Option<f64>) -> f64 {
+    match (best_bid, best_ask) {
+        (Some(bid), Some(ask)) => (ask - bid) / ((bid + ask) / 2.0) * 10_000.0,
+        _ => f64::INFINITY,
+    }
+}
+
+// Checks the conditions on every merged book of a window
+#[derive(Debug, Clone, Default)]
+pub struct SlaMonitor {
+    verdict: SlaVerdict,
+}
+
+impl SlaMonitor {
+    pub fn new(conditions: SlaConditions) -> SlaMonitor {
+        SlaMonitor {
+            verdict: SlaVerdict {
+                conditions,
+                ..SlaVerdict::default()
+            },
+        }
+    }
+
+    // The (price, amount) levels of a merged book, best first
+    pub fn observe(
+        &mut self,
+        bids: impl IntoIterator<Item = (f64, f64)>,
+        asks: impl IntoIterator<Item = (f64, f64)>,
+    ) {
+        let (mut best_bid, mut bid_depth) = (None, 0.0);
+        for (price, amount) in bids {
+            best_bid.get_or_insert(price);
+            bid_depth += amount;
+        }
+        let (mut best_ask, mut ask_depth) = (None, 0.0);
+        for (price, amount) in asks {
+            best_ask.get_or_insert(price);
+            ask_depth += amount;
+        }
+
+        let verdict = &mut self.verdict;
+        verdict.books += 1;
+        let spread_bps = spread_bps(best_bid, best_ask);
+        verdict.worst_spread_bps = Some(
+            verdict
+                .worst_spread_bps
+                .map_or(spread_bps, |worst| worst.max(spread_bps)),
+        );
+        if verdict
+            .conditions
+            .max_spread_bps
+            .is_some_and(|max_spread_bps| spread_bps > max_spread_bps)
+        {
+            verdict.spread_violations += 1;
+        }
+        let depth = f64::min(bid_depth, ask_depth);
+        verdict.min_depth = Some(verdict.min_depth.map_or(depth, |min| min.min(depth)));
+        if verdict
+            .conditions
+            .min_depth
+            .is_some_and(|min_depth| depth < min_depth)
+        {
+            verdict.depth_violations += 1;
+        }
+    }
+
+    pub fn verdict(&self) -> &SlaVerdict {
+        &self.verdict
+    }
+}
+
+// A duration of the command line, 500ms, 60s, 5m or 1h, in seconds without a unit
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let (number, unit_secs) = if let Some(ms) = text.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(secs) = text.strip_suffix('s') {
+        (secs, 1.0)
+    } else if let Some(minutes) = text.strip_suffix('m') {
+        (minutes, 60.0)
+    } else if let Some(hours) = text.strip_suffix('h') {
+        (hours, 3_600.0)
+    } else {
+        (text, 1.0)
+    };
+    let secs = number.parse::<f64>().ok()? * unit_secs;
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sla_monitor() {
+        let conditions = SlaConditions {
+            max_spread_bps: Some(5.0),
+            min_depth: Some(10.0),
+        };
+        let mut monitor = SlaMonitor::new(conditions);
+        assert_eq!(monitor.verdict().exit_code(), 2);
+
+        monitor.observe([(9_999.0, 12.0)], [(10_001.0, 12.0), (10_002.0, 1.0)]);
+        assert_eq!(monitor.verdict().exit_code(), 0);
+        assert_eq!(
+            monitor.verdict().to_string(),
+            "PASS: 1 books, spread above 5 bps in 0 (worst 2.00 bps), depth below 10 in 0 (min 12)"
+        );
+
+        // 10 bps wide, and thin on one side
+        monitor.observe([(9_995.0, 4.0)], [(10_005.0, 20.0)]);
+        let verdict = monitor.verdict();
+        assert_eq!(
+            (verdict.spread_violations, verdict.depth_violations),
+            (1, 1)
+        );
+        assert_eq!(verdict.min_depth, Some(4.0));
+        assert_eq!(verdict.exit_code(), 1);
+        assert!(verdict.to_string().starts_with("FAIL: 2 books"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("60s"), Some(Duration::from_secs(60)));
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("5m"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(parse_duration("-1s"), None);
+    }
+}
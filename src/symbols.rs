@@ -0,0 +1,168 @@
+use crate::connectors::{http_request, ExchangeConnector};
+use std::error::Error;
+use std::fmt;
+use url::Url;
+
+// Quote assets recognized when splitting a symbol, the longer ones first so btcusdt
+// is BTC/USDT and not BTCU/SDT
+const QUOTE_ASSETS: [&str; 15] = [
+    "usdt", "usdc", "busd", "usd", "eur", "gbp", "jpy", "krw", "try", "aud", "cad", "chf", "nzd",
+    "btc", "eth",
+];
+
+// Most symbols suggested for a symbol a venue doesn't list
+const MAX_SUGGESTIONS: usize = 5;
+
+// Splits a symbol like btcusdt, BTC-USDT or BTC/USDT into its uppercase base and quote
+// assets, so every connector can write it the way its exchange lists it
+pub fn split_symbol(symbol: &str) -> Option<(String, String)> {
+    let symbol: String = symbol
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    QUOTE_ASSETS.iter().find_map(|quote| {
+        let base = symbol.strip_suffix(quote)?;
+        (!base.is_empty()).then(|| (base.to_uppercase(), quote.to_uppercase()))
+    })
+}
+
+// The assets of the symbol written back to back in uppercase, e.g. BTCUSDT for
+// BTC-USDT, the way most venues list their pairs. Symbols without a known quote asset
+// are only uppercased
+pub fn joined_symbol(symbol: &str) -> String {
+    match split_symbol(symbol) {
+        Some((base, quote)) => format!("{}{}", base, quote),
+        None => symbol.to_uppercase(),
+    }
+}
+
+// A pair written the same way whatever the venue, e.g. BTC-USDT. Each connector maps it
+// to its exchange's own format with normalize_symbol, e.g. btcusdt for binance, BTC/USDT
+// for Kraken or tBTCUST for Bitfinex
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CanonicalSymbol {
+    pub base: String,
+    pub quote: String,
+}
+
+impl CanonicalSymbol {
+    // Any of btcusdt, BTCUSDT, BTC-USDT or BTC/USDT
+    pub fn parse(symbol: &str) -> Option<CanonicalSymbol> {
+        let (base, quote) = split_symbol(symbol)?;
+        Some(CanonicalSymbol { base, quote })
+    }
+
+    // The symbol as the venue lists it
+    pub fn venue_symbol(&self, connector: &dyn ExchangeConnector) -> String {
+        connector.normalize_symbol(&self.to_string())
+    }
+}
+
+impl fmt::Display for CanonicalSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.base, self.quote)
+    }
+}
+
+// Checks a venue's symbol against the symbols the venue lists, the error suggests the
+// listed symbols of the same base asset
+pub fn check_listed(venue: &str, symbol: &str, listed: &[String]) -> Result<(), String> {
+    if listed.iter().any(|listed_symbol| listed_symbol == symbol) {
+        return Ok(());
+    }
+    let base = split_symbol(symbol).map_or_else(|| symbol.to_uppercase(), |(base, _)| base);
+    let mut similar: Vec<&str> = listed
+        .iter()
+        .filter(|listed_symbol| {
+            split_symbol(listed_symbol).is_some_and(|(listed_base, _)| listed_base == base)
+        })
+        .map(String::as_str)
+        .collect();
+    similar.sort();
+    similar.truncate(MAX_SUGGESTIONS);
+    if similar.is_empty() {
+        Err(format!("{} doesn't list {}", venue, symbol))
+    } else {
+        Err(format!(
+            "{} doesn't list {}, it lists {}",
+            venue,
+            symbol,
+            similar.join(", ")
+        ))
+    }
+}
+
+// Fetches the venue's instrument list and checks its symbol is in it, None for the
+// venues publishing no instrument list
+pub fn check_venue_symbol(
+    connector: &dyn ExchangeConnector,
+    symbol: &str,
+) -> Option<Result<String, String>> {
+    let instruments_url = connector.instruments_url()?;
+    let venue_symbol = connector.normalize_symbol(symbol);
+    let listed = match fetch_instruments(&instruments_url) {
+        Ok(body) => connector.listed_symbols(&body),
+        Err(err) => {
+            return Some(Err(format!(
+                "Cannot read the instruments of {} at {}: {}",
+                connector.name(),
+                instruments_url,
+                err
+            )))
+        }
+    };
+    Some(
+        check_listed(connector.name(), &venue_symbol, &listed)
+            .map(|_| format!("{} lists {}", connector.name(), venue_symbol)),
+    )
+}
+
+fn fetch_instruments(url: &str) -> Result<String, Box<dyn Error>> {
+    http_request("GET", &Url::parse(url)?)
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::kraken::KrakenConnector;
+    use crate::connectors::BinanceConnector;
+
+    #[test]
+    fn test_split_symbol() {
+        let pair = |base: &str, quote: &str| Some((base.to_string(), quote.to_string()));
+        assert_eq!(split_symbol("btcusdt"), pair("BTC", "USDT"));
+        assert_eq!(split_symbol("BTC/USD"), pair("BTC", "USD"));
+        assert_eq!(split_symbol("eth-btc"), pair("ETH", "BTC"));
+        assert_eq!(split_symbol("usdchf"), pair("USD", "CHF"));
+        assert_eq!(split_symbol("usdt"), None);
+        assert_eq!(split_symbol("btcxyz"), None);
+    }
+
+    #[test]
+    fn test_canonical_symbol() {
+        let symbol = CanonicalSymbol::parse("btcusdt").unwrap();
+        assert_eq!(symbol.to_string(), "BTC-USDT");
+        assert_eq!(CanonicalSymbol::parse("BTC/USDT"), Some(symbol.clone()));
+        assert_eq!(symbol.venue_symbol(&BinanceConnector::new(10)), "btcusdt");
+        assert_eq!(symbol.venue_symbol(&KrakenConnector::new(10)), "BTC/USDT");
+
+        let asset_pairs = r#"{"error":[],"result":{
+            "XXBTZUSD":{"altname":"XBTUSD","wsname":"XBT/USD","status":"online"},
+            "XXBTZEUR":{"altname":"XBTEUR","wsname":"XBT/EUR","status":"online"},
+            "ETHUSDT":{"altname":"ETHUSDT","wsname":"ETH/USDT","status":"online"},
+            "LUNAUSD":{"altname":"LUNAUSD","wsname":"LUNA/USD","status":"delisted"}}}"#;
+        let listed = KrakenConnector::new(10).listed_symbols(asset_pairs);
+        assert_eq!(listed.len(), 3);
+        assert_eq!(check_listed("Kraken", "BTC/USD", &listed), Ok(()));
+        assert_eq!(
+            check_listed("Kraken", "BTC/USDT", &listed),
+            Err("Kraken doesn't list BTC/USDT, it lists BTC/EUR, BTC/USD".to_string())
+        );
+        assert_eq!(
+            check_listed("Kraken", "SOL/USDT", &listed),
+            Err("Kraken doesn't list SOL/USDT".to_string())
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::fmt;
+
+// Side of the taker of a trade, a buy lifted an ask and a sell hit a bid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TakerSide {
+    Buy,
+    Sell,
+}
+
+impl TakerSide {
+    // The side as the venue reports it, buy or sell in any case. Some venues report the
+    // side of the resting (maker) order rather than the taker's, their side is inverted
+    pub fn from_venue(side: &str, maker_side: bool) -> Option<TakerSide> {
+        let side = match side.to_lowercase().as_str() {
+            "buy" => TakerSide::Buy,
+            "sell" => TakerSide::Sell,
+            _ => return None,
+        };
+        Some(if maker_side { side.opposite() } else { side })
+    }
+
+    pub fn opposite(self) -> TakerSide {
+        match self {
+            TakerSide::Buy => TakerSide::Sell,
+            TakerSide::Sell => TakerSide::Buy,
+        }
+    }
+}
+
+// A trade of a venue's trade channel, on the consolidated tape of the venues
+#[derive(Debug, Clone, PartialEq)]
+pub struct TapeTrade {
+    pub exchange: String,
+    pub price: f64,
+    pub amount: f64,
+    pub side: TakerSide,
+}
+
+impl fmt::Display for TapeTrade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let side = match self.side {
+            TakerSide::Buy => "buy",
+            TakerSide::Sell => "sell",
+        };
+        write!(
+            f,
+            "{} {} {} at {}",
+            self.exchange, side, self.amount, self.price
+        )
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_taker_side() {
+        assert_eq!(TakerSide::from_venue("buy", false), Some(TakerSide::Buy));
+        assert_eq!(TakerSide::from_venue("SELL", false), Some(TakerSide::Sell));
+        // A maker's sell was lifted by a taker's buy
+        assert_eq!(TakerSide::from_venue("SELL", true), Some(TakerSide::Buy));
+        assert_eq!(TakerSide::from_venue("unknown", false), None);
+
+        let trade = TapeTrade {
+            exchange: "kraken".to_string(),
+            price: 10.5,
+            amount: 0.2,
+            side: TakerSide::Sell,
+        };
+        assert_eq!(trade.to_string(), "kraken sell 0.2 at 10.5");
+    }
+}
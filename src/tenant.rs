@@ -0,0 +1,239 @@
+use crate::metrics::MetricsRegistry;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Metadata key clients send their API key in
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+// One internal team using the aggregator, identified by its API key
+#[derive(Debug, Clone, Deserialize)]
+pub struct TenantConfig {
+    pub api_key: String,
+    pub namespace: String,
+    // Symbols the tenant may subscribe to, empty allows every symbol
+    #[serde(default)]
+    pub symbols: Vec<String>,
+    pub max_depth: Option<u32>,
+    pub max_updates_per_second: Option<f64>,
+    // Admins may query the audit log
+    #[serde(default)]
+    pub admin: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct TenantsFile {
+    tenants: Vec<TenantConfig>,
+}
+
+// Counters of a single tenant, reported with the tenant namespace as label
+#[derive(Debug, Default)]
+pub struct TenantMetrics {
+    pub subscriptions: AtomicU64,
+    pub rejected_subscriptions: AtomicU64,
+    pub updates_sent: AtomicU64,
+    pub updates_throttled: AtomicU64,
+}
+
+#[derive(Debug)]
+pub struct Tenant {
+    pub config: TenantConfig,
+    pub metrics: TenantMetrics,
+}
+
+impl Tenant {
+    pub fn new(config: TenantConfig) -> Tenant {
+        Tenant {
+            config,
+            metrics: TenantMetrics::default(),
+        }
+    }
+
+    pub fn allows_symbol(&self, symbol: &str) -> bool {
+        self.config.symbols.is_empty()
+            || self
+                .config
+                .symbols
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(symbol))
+    }
+
+    // The depth served to the tenant, never more than the server depth
+    pub fn depth(&self, depth: u32) -> u32 {
+        self.config
+            .max_depth
+            .map_or(depth, |max_depth| max_depth.min(depth))
+    }
+
+    pub fn min_update_interval(&self) -> Option<Duration> {
+        self.config
+            .max_updates_per_second
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| Duration::from_secs_f64(1.0 / rate))
+    }
+
+    // The counters as the orderbook_tenant_* gauges, labelled with the namespace
+    pub(crate) fn record_metrics(&self, metrics: &MetricsRegistry) {
+        let labels = [("namespace", self.config.namespace.as_str())];
+        for (name, counter) in [
+            ("subscriptions", &self.metrics.subscriptions),
+            (
+                "rejected_subscriptions",
+                &self.metrics.rejected_subscriptions,
+            ),
+            ("updates_sent", &self.metrics.updates_sent),
+            ("updates_throttled", &self.metrics.updates_throttled),
+        ] {
+            let name = format!("orderbook_tenant_{}", name);
+            metrics.set_gauge(&name, &labels, counter.load(Ordering::Relaxed) as f64);
+        }
+    }
+}
+
+// Tenants by API key
+#[derive(Debug)]
+pub struct TenantRegistry {
+    tenants: HashMap<String, Arc<Tenant>>,
+}
+
+impl TenantRegistry {
+    // Parses {"tenants": [{"api_key": ..., "namespace": ..., ...}]}
+    pub fn from_json(text: &str) -> Result<TenantRegistry, Box<dyn Error>> {
+        let file: TenantsFile = serde_json::from_str(text)?;
+
+        let mut namespaces = HashSet::new();
+        let mut tenants = HashMap::new();
+        for config in file.tenants {
+            if !namespaces.insert(config.namespace.clone()) {
+                return Err(format!("Duplicate tenant namespace: {}", config.namespace).into());
+            }
+            if tenants.contains_key(&config.api_key) {
+                return Err(format!("Duplicate API key for namespace {}", config.namespace).into());
+            }
+            tenants.insert(config.api_key.clone(), Arc::new(Tenant::new(config)));
+        }
+
+        Ok(TenantRegistry { tenants })
+    }
+
+    pub fn from_file(path: &str) -> Result<TenantRegistry, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read tenants file {}: {}", path, err))?;
+        TenantRegistry::from_json(&text)
+    }
+
+    pub fn authenticate(&self, api_key: Option<&str>) -> Option<Arc<Tenant>> {
+        self.tenants.get(api_key?).cloned()
+    }
+
+    pub fn tenants(&self) -> impl Iterator<Item = &Arc<Tenant>> {
+        self.tenants.values()
+    }
+}
+
+// Drops updates sent faster than the tenant's max update rate, clients get the
+// latest book on the next allowed update
+#[derive(Debug)]
+pub struct UpdateThrottle {
+    interval: Option<Duration>,
+    last_sent: Option<Instant>,
+}
+
+impl UpdateThrottle {
+    pub fn new(interval: Option<Duration>) -> UpdateThrottle {
+        UpdateThrottle {
+            interval,
+            last_sent: None,
+        }
+    }
+
+    pub fn allow(&mut self, now: Instant) -> bool {
+        let allowed = match (self.interval, self.last_sent) {
+            (Some(interval), Some(last_sent)) => now.duration_since(last_sent) >= interval,
+            _ => true,
+        };
+        if allowed {
+            self.last_sent = Some(now);
+        }
+        allowed
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TENANTS: &str = r#"{"tenants": [
+        {"api_key": "key-research", "namespace": "research", "symbols": ["btcusdt"], "max_depth": 5, "max_updates_per_second": 2},
+        {"api_key": "key-risk", "namespace": "risk"}
+    ]}"#;
+
+    #[test]
+    fn test_tenant_registry() {
+        let registry = TenantRegistry::from_json(TENANTS).unwrap();
+
+        let research = registry.authenticate(Some("key-research")).unwrap();
+        assert_eq!(research.config.namespace, "research");
+        assert!(research.allows_symbol("BTCUSDT"));
+        assert!(!research.allows_symbol("ethusdt"));
+        assert_eq!(research.depth(10), 5);
+        assert_eq!(
+            research.min_update_interval(),
+            Some(Duration::from_millis(500))
+        );
+
+        let risk = registry.authenticate(Some("key-risk")).unwrap();
+        assert!(risk.allows_symbol("ethusdt"));
+        assert_eq!(risk.depth(10), 10);
+        assert_eq!(risk.min_update_interval(), None);
+
+        assert!(registry.authenticate(Some("unknown")).is_none());
+        assert!(registry.authenticate(None).is_none());
+
+        let duplicate = r#"{"tenants": [
+            {"api_key": "a", "namespace": "research"},
+            {"api_key": "b", "namespace": "research"}
+        ]}"#;
+        assert!(TenantRegistry::from_json(duplicate).is_err());
+    }
+
+    #[test]
+    fn test_record_metrics() {
+        let registry = TenantRegistry::from_json(TENANTS).unwrap();
+        let research = registry.authenticate(Some("key-research")).unwrap();
+        research
+            .metrics
+            .subscriptions
+            .fetch_add(2, Ordering::Relaxed);
+        research
+            .metrics
+            .updates_sent
+            .fetch_add(5, Ordering::Relaxed);
+
+        let metrics = MetricsRegistry::default();
+        research.record_metrics(&metrics);
+        let text = metrics.render();
+        assert!(text.contains("orderbook_tenant_subscriptions{namespace=\"research\"} 2\n"));
+        assert!(text.contains("orderbook_tenant_updates_sent{namespace=\"research\"} 5\n"));
+        assert!(text.contains("orderbook_tenant_updates_throttled{namespace=\"research\"} 0\n"));
+    }
+
+    #[test]
+    fn test_update_throttle() {
+        let start = Instant::now();
+        let mut throttle = UpdateThrottle::new(Some(Duration::from_millis(500)));
+
+        assert!(throttle.allow(start));
+        assert!(!throttle.allow(start + Duration::from_millis(200)));
+        assert!(throttle.allow(start + Duration::from_millis(500)));
+        assert!(!throttle.allow(start + Duration::from_millis(900)));
+
+        let mut unlimited = UpdateThrottle::new(None);
+        assert!(unlimited.allow(start));
+        assert!(unlimited.allow(start));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::book::{OrderBook, PriceAmountLevel};
+use crate::number::decimal_to_f64;
+
+fn within_touch(levels: &[PriceAmountLevel], max_distance_bps: f64) -> Vec<PriceAmountLevel> {
+    let Some(best_price) = levels.first().map(|level| decimal_to_f64(level.price)) else {
+        return Vec::new();
+    };
+    levels
+        .iter()
+        .filter(|level| {
+            let price = decimal_to_f64(level.price);
+            (price - best_price).abs() / best_price * 10_000.0 <= max_distance_bps
+        })
+        .cloned()
+        .collect()
+}
+
+// Some venues pad their books with phantom liquidity far from the touch. Their levels
+// beyond max_distance_bps of the venue's own best bid (for bids) or best ask (for asks)
+// are left out of the merged book, the fair value and indexes only use the top of book
+pub(crate) fn trim_beyond_touch(orderbook: &OrderBook, max_distance_bps: f64) -> OrderBook {
+    OrderBook {
+        bids: within_touch(&orderbook.bids, max_distance_bps),
+        asks: within_touch(&orderbook.asks, max_distance_bps),
+        ..orderbook.clone()
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number::decimal_from_f64;
+    use rust_decimal_macros::dec;
+
+    fn level(price: f64) -> PriceAmountLevel {
+        PriceAmountLevel {
+            exchange: "exchange1".to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: dec!(1.0),
+        }
+    }
+
+    #[test]
+    fn test_trim_beyond_touch() {
+        let orderbook = OrderBook {
+            bids: vec![level(100.0), level(99.6), level(90.0)],
+            asks: vec![level(101.0), level(101.4), level(120.0)],
+            spread: -1.0,
+            venues: Vec::new(),
+        };
+
+        let trimmed = trim_beyond_touch(&orderbook, 50.0);
+
+        let prices = |levels: &[PriceAmountLevel]| -> Vec<f64> {
+            levels
+                .iter()
+                .map(|level| decimal_to_f64(level.price))
+                .collect()
+        };
+        assert_eq!(prices(&trimmed.bids), vec![100.0, 99.6]);
+        assert_eq!(prices(&trimmed.asks), vec![101.0, 101.4]);
+        assert_eq!(trimmed.spread, orderbook.spread);
+    }
+}
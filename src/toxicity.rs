@@ -0,0 +1,174 @@
+use crate::book::OrderBook;
+use crate::number::decimal_to_f64;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TopOfBook {
+    bid_price: f64,
+    bid_amount: f64,
+    ask_price: f64,
+    ask_amount: f64,
+}
+
+fn top_of_book(orderbook: &OrderBook) -> Option<TopOfBook> {
+    let bid = orderbook.bids.first()?;
+    let ask = orderbook.asks.first()?;
+    Some(TopOfBook {
+        bid_price: decimal_to_f64(bid.price),
+        bid_amount: decimal_to_f64(bid.amount),
+        ask_price: decimal_to_f64(ask.price),
+        ask_amount: decimal_to_f64(ask.amount),
+    })
+}
+
+// Order flow imbalance between two books: volume joining the bid or leaving the ask is
+// buying pressure, volume leaving the bid or joining the ask is selling pressure
+fn order_flow_imbalance(previous: &TopOfBook, current: &TopOfBook) -> f64 {
+    let mut flow = 0.0;
+    if current.bid_price >= previous.bid_price {
+        flow += current.bid_amount;
+    }
+    if current.bid_price <= previous.bid_price {
+        flow -= previous.bid_amount;
+    }
+    if current.ask_price <= previous.ask_price {
+        flow -= current.ask_amount;
+    }
+    if current.ask_price >= previous.ask_price {
+        flow += previous.ask_amount;
+    }
+    flow
+}
+
+// VPIN-like flow toxicity. Without a trade tape the signed volume is the order flow
+// imbalance of the top of book, filled into buckets of bucket_volume. The toxicity is
+// the mean |buy - sell| / bucket_volume of the last buckets, from 0 (balanced flow)
+// to 1 (one-sided flow)
+#[derive(Debug, Clone)]
+pub struct ToxicityMeter {
+    pub bucket_volume: f64,
+    pub buckets: usize,
+    previous: Option<TopOfBook>,
+    buy_volume: f64,
+    sell_volume: f64,
+    imbalances: VecDeque<f64>,
+}
+
+impl ToxicityMeter {
+    pub fn new(bucket_volume: f64, buckets: usize) -> ToxicityMeter {
+        ToxicityMeter {
+            bucket_volume,
+            buckets: buckets.max(1),
+            previous: None,
+            buy_volume: 0.0,
+            sell_volume: 0.0,
+            imbalances: VecDeque::new(),
+        }
+    }
+
+    // None until the first bucket is filled
+    pub fn toxicity(&self) -> Option<f64> {
+        if self.imbalances.is_empty() {
+            return None;
+        }
+        Some(self.imbalances.iter().sum::<f64>() / self.imbalances.len() as f64)
+    }
+
+    // Flow larger than the room left in the bucket spills over into the next buckets
+    fn add_flow(&mut self, flow: f64) {
+        let mut remaining = flow.abs();
+        while remaining > 0.0 {
+            let room = self.bucket_volume - self.buy_volume - self.sell_volume;
+            let filled = remaining.min(room);
+            if flow > 0.0 {
+                self.buy_volume += filled;
+            } else {
+                self.sell_volume += filled;
+            }
+            remaining -= filled;
+
+            if self.buy_volume + self.sell_volume >= self.bucket_volume {
+                let imbalance = (self.buy_volume - self.sell_volume).abs() / self.bucket_volume;
+                self.imbalances.push_back(imbalance);
+                if self.imbalances.len() > self.buckets {
+                    self.imbalances.pop_front();
+                }
+                self.buy_volume = 0.0;
+                self.sell_volume = 0.0;
+            }
+        }
+    }
+
+    pub fn update(&mut self, orderbook: &OrderBook) -> Option<f64> {
+        if self.bucket_volume <= 0.0 {
+            return None;
+        }
+        if let Some(current) = top_of_book(orderbook) {
+            if let Some(previous) = self.previous {
+                self.add_flow(order_flow_imbalance(&previous, &current));
+            }
+            self.previous = Some(current);
+        }
+        self.toxicity()
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PriceAmountLevel;
+    use crate::number::decimal_from_f64;
+
+    fn orderbook(bid: (f64, f64), ask: (f64, f64)) -> OrderBook {
+        let level = |(price, amount): (f64, f64)| PriceAmountLevel {
+            exchange: "binance".to_string(),
+            price: decimal_from_f64(price).unwrap(),
+            amount: decimal_from_f64(amount).unwrap(),
+        };
+        OrderBook {
+            bids: vec![level(bid)],
+            asks: vec![level(ask)],
+            spread: bid.0 - ask.0,
+            venues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_order_flow_imbalance() {
+        let previous = top_of_book(&orderbook((100.0, 1.0), (101.0, 1.0))).unwrap();
+        // 2 more on the bid
+        let current = top_of_book(&orderbook((100.0, 3.0), (101.0, 1.0))).unwrap();
+        assert_eq!(order_flow_imbalance(&previous, &current), 2.0);
+        // The ask is lifted, the next level is at 102
+        let current = top_of_book(&orderbook((100.0, 1.0), (102.0, 4.0))).unwrap();
+        assert_eq!(order_flow_imbalance(&previous, &current), 1.0);
+        // The bid is hit, the next level is at 99
+        let current = top_of_book(&orderbook((99.0, 4.0), (101.0, 1.0))).unwrap();
+        assert_eq!(order_flow_imbalance(&previous, &current), -1.0);
+    }
+
+    #[test]
+    fn test_toxicity_meter() {
+        let mut meter = ToxicityMeter::new(4.0, 2);
+        assert_eq!(meter.update(&orderbook((100.0, 1.0), (101.0, 1.0))), None);
+        // 3 bought, the bucket isn't full yet
+        assert_eq!(meter.update(&orderbook((100.0, 4.0), (101.0, 1.0))), None);
+        // 1 more bought fills a one-sided bucket
+        assert_eq!(
+            meter.update(&orderbook((100.0, 5.0), (101.0, 1.0))),
+            Some(1.0)
+        );
+        // 2 bought and 2 sold, a balanced bucket
+        meter.update(&orderbook((100.0, 7.0), (101.0, 1.0)));
+        assert_eq!(
+            meter.update(&orderbook((100.0, 5.0), (101.0, 1.0))),
+            Some(0.5)
+        );
+        // 8 sold fill two more buckets, only the last 2 are kept
+        assert_eq!(
+            meter.update(&orderbook((100.0, 5.0), (101.0, 9.0))),
+            Some(1.0)
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use crate::book::OrderBook;
+use crate::number::decimal_to_f64;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WallSide {
+    Bid,
+    Ask,
+}
+
+impl fmt::Display for WallSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WallSide::Bid => write!(f, "bid"),
+            WallSide::Ask => write!(f, "ask"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WallEvent {
+    // A level at least multiple times the venue's average level size
+    Appeared {
+        exchange: String,
+        side: WallSide,
+        price: Decimal,
+        amount: Decimal,
+        average_amount: f64,
+    },
+    // The level was pulled, filled or shrank below the threshold, amount is the last
+    // one of the wall
+    Removed {
+        exchange: String,
+        side: WallSide,
+        price: Decimal,
+        amount: Decimal,
+        lifetime: Duration,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Wall {
+    amount: Decimal,
+    since: Instant,
+}
+
+#[derive(Debug, Clone, Default)]
+struct VenueState {
+    // Mean level size of the venue's books within the window, with their time
+    level_sizes: VecDeque<(Instant, f64)>,
+    walls: HashMap<(WallSide, Decimal), Wall>,
+}
+
+impl VenueState {
+    fn average_level_size(&self) -> Option<f64> {
+        if self.level_sizes.is_empty() {
+            return None;
+        }
+        let total: f64 = self.level_sizes.iter().map(|(_, size)| size).sum();
+        Some(total / self.level_sizes.len() as f64)
+    }
+}
+
+// Finds the levels of every venue resting at least multiple times the venue's average
+// level size over the window, the "walls". The average is the one of the books before
+// the current one, without their walls, so a wall doesn't raise the bar it is measured
+// against. Only the appearance and the removal of a wall are events
+#[derive(Debug, Clone)]
+pub struct WallDetector {
+    pub multiple: f64,
+    pub window: Duration,
+    venues: HashMap<String, VenueState>,
+}
+
+impl WallDetector {
+    pub fn new(multiple: f64, window: Duration) -> WallDetector {
+        WallDetector {
+            multiple,
+            window,
+            venues: HashMap::new(),
+        }
+    }
+
+    // The walls of a venue that appeared or were removed since its previous book
+    pub fn update(
+        &mut self,
+        exchange: &str,
+        orderbook: &OrderBook,
+        now: Instant,
+    ) -> Vec<WallEvent> {
+        let venue = self.venues.entry(exchange.to_string()).or_default();
+        let average_amount = venue.average_level_size().filter(|average| *average > 0.0);
+        let threshold = average_amount.map_or(f64::INFINITY, |average| average * self.multiple);
+        let mut current = HashMap::new();
+        // Sum and count of the levels that aren't walls
+        let (mut total, mut count) = (0.0, 0);
+        for (side, levels) in [
+            (WallSide::Bid, &orderbook.bids),
+            (WallSide::Ask, &orderbook.asks),
+        ] {
+            for level in levels {
+                let amount = decimal_to_f64(level.amount);
+                if amount >= threshold {
+                    current.insert((side, level.price), level.amount);
+                } else {
+                    total += amount;
+                    count += 1;
+                }
+            }
+        }
+        let mut events = Vec::new();
+
+        if let Some(average_amount) = average_amount {
+            venue
+                .walls
+                .retain(|(side, price), wall| match current.get(&(*side, *price)) {
+                    Some(amount) => {
+                        wall.amount = *amount;
+                        true
+                    }
+                    None => {
+                        events.push(WallEvent::Removed {
+                            exchange: exchange.to_string(),
+                            side: *side,
+                            price: *price,
+                            amount: wall.amount,
+                            lifetime: now.saturating_duration_since(wall.since),
+                        });
+                        false
+                    }
+                });
+            let mut appeared: Vec<((WallSide, Decimal), Decimal)> = current
+                .into_iter()
+                .filter(|(key, _)| !venue.walls.contains_key(key))
+                .collect();
+            appeared.sort_by_key(|((side, price), _)| (*side == WallSide::Ask, *price));
+            for ((side, price), amount) in appeared {
+                venue
+                    .walls
+                    .insert((side, price), Wall { amount, since: now });
+                events.push(WallEvent::Appeared {
+                    exchange: exchange.to_string(),
+                    side,
+                    price,
+                    amount,
+                    average_amount,
+                });
+            }
+        }
+
+        if count > 0 {
+            venue.level_sizes.push_back((now, total / count as f64));
+        }
+        while venue
+            .level_sizes
+            .front()
+            .is_some_and(|(time, _)| now.saturating_duration_since(*time) > self.window)
+        {
+            venue.level_sizes.pop_front();
+        }
+        events
+    }
+}
+
+// Unit test cases
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PriceAmountLevel;
+    use rust_decimal_macros::dec;
+
+    fn book(bids: &[(Decimal, Decimal)], asks: &[(Decimal, Decimal)]) -> OrderBook {
+        let levels = |levels: &[(Decimal, Decimal)]| {
+            levels
+                .iter()
+                .map(|(price, amount)| PriceAmountLevel {
+                    exchange: "binance".to_string(),
+                    price: *price,
+                    amount: *amount,
+                })
+                .collect()
+        };
+        OrderBook {
+            bids: levels(bids),
+            asks: levels(asks),
+            ..OrderBook::new()
+        }
+    }
+
+    #[test]
+    fn test_wall_detector() {
+        let start = Instant::now();
+        let mut detector = WallDetector::new(5.0, Duration::from_secs(60));
+        let calm = book(
+            &[(dec!(100), dec!(1)), (dec!(99), dec!(1))],
+            &[(dec!(101), dec!(1))],
+        );
+        // Nothing to compare the first book with
+        assert!(detector.update("binance", &calm, start).is_empty());
+        assert!(detector.update("binance", &calm, start).is_empty());
+
+        let walled = book(
+            &[(dec!(100), dec!(1)), (dec!(99), dec!(8))],
+            &[(dec!(101), dec!(1))],
+        );
+        assert_eq!(
+            detector.update("binance", &walled, start + Duration::from_secs(1)),
+            vec![WallEvent::Appeared {
+                exchange: "binance".to_string(),
+                side: WallSide::Bid,
+                price: dec!(99),
+                amount: dec!(8),
+                average_amount: 1.0,
+            }]
+        );
+        // Still there, and other venues have their own averages
+        assert!(detector
+            .update("binance", &walled, start + Duration::from_secs(2))
+            .is_empty());
+        assert!(detector.update("okx", &walled, start).is_empty());
+
+        assert_eq!(
+            detector.update("binance", &calm, start + Duration::from_secs(5)),
+            vec![WallEvent::Removed {
+                exchange: "binance".to_string(),
+                side: WallSide::Bid,
+                price: dec!(99),
+                amount: dec!(8),
+                lifetime: Duration::from_secs(4),
+            }]
+        );
+    }
+}